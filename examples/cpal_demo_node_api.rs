@@ -2,6 +2,7 @@ use hexodsp::*;
 
 use anyhow;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use ringbuf::{Producer, RingBuffer};
 
 fn main() {
     let (mut node_conf, node_exec) = new_node_engine();
@@ -14,6 +15,7 @@ fn main() {
         //
         // This defines all supported nodes and their
         // parameters/inputs ports and their outputs.
+        let midiin = NodeId::MidiIn(0);
         let sin = NodeId::Sin(0);
         let amp = NodeId::Amp(0);
         let out = NodeId::Out(0);
@@ -31,6 +33,7 @@ fn main() {
         // which deletes all nodes.
         //
         // You can't delete only one specific node.
+        node_conf.create_node(midiin);
         node_conf.create_node(sin);
         node_conf.create_node(amp);
         node_conf.create_node(out);
@@ -45,11 +48,20 @@ fn main() {
         // the order they will be executed by the audio thread.
         // You will have to take care that all nodes get their
         // data in the right order here.
+        node_conf.add_prog_node(&mut prog, &NodeId::MidiIn(0));
         node_conf.add_prog_node(&mut prog, &NodeId::Sin(0));
         node_conf.add_prog_node(&mut prog, &NodeId::Amp(0));
         node_conf.add_prog_node(&mut prog, &NodeId::Out(0));
 
         // Define the connections between the nodes in the NodeProg:
+        // The notes received from the first MIDI input device transpose
+        // the sine relative to A4:
+        node_conf.set_prog_node_exec_connection(
+            &mut prog,
+            (sin, sin.inp("det").unwrap()),
+            (midiin, midiin.out("freq").unwrap()),
+        );
+
         node_conf.set_prog_node_exec_connection(
             &mut prog,
             // first the input:
@@ -84,8 +96,8 @@ fn main() {
             //
             // Please note, that for sample accurate modulation you should
             // use the built in tracker or receive MIDI data from
-            // different application (MIDI processing has not been
-            // implemented yet though).
+            // different application. MIDI events received from the
+            // first MIDI input device are passed to the `MidiIn` node.
 
             let new_gain = match amp_counter {
                 0 => 0.2,
@@ -146,6 +158,14 @@ where
     let input_bufs = [[0.0; hexodsp::dsp::MAX_BLOCK_SIZE]; 2];
     let mut outputbufs = [[0.0; hexodsp::dsp::MAX_BLOCK_SIZE]; 2];
 
+    // The MIDI messages are received on a midir thread and passed
+    // to the audio thread via a ring buffer:
+    let (midi_prod, mut midi_cons) = RingBuffer::<[u8; 3]>::new(1024).split();
+    let _midi_conn = connect_midi_input(midi_prod);
+
+    // Preallocate the event buffer, so we don't allocate in the audio thread:
+    let mut period_events: Vec<MidiEvent> = Vec::with_capacity(1024);
+
     let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
     let stream = device.build_output_stream(
         config,
@@ -156,6 +176,17 @@ where
 
             node_exec.process_graph_updates();
 
+            // midir does not provide timestamps in the audio clock,
+            // so all events are passed at the start of the period:
+            period_events.clear();
+            while let Some(msg) = midi_cons.pop() {
+                if period_events.len() < period_events.capacity() {
+                    if let Some(ev) = MidiEvent::from_bytes(0, &msg[..]) {
+                        period_events.push(ev);
+                    }
+                }
+            }
+
             while frames_left > 0 {
                 let cur_nframes = if frames_left >= hexodsp::dsp::MAX_BLOCK_SIZE {
                     hexodsp::dsp::MAX_BLOCK_SIZE
@@ -170,7 +201,13 @@ where
                 let mut output =
                     [&mut ((split.0[0])[0..cur_nframes]), &mut ((split.1[0])[0..cur_nframes])];
 
-                let mut context = Context { nframes: cur_nframes, output: &mut output[..], input };
+                let mut context = Context {
+                    nframes: cur_nframes,
+                    output: &mut output[..],
+                    input,
+                    events: &period_events,
+                    transport: None,
+                };
 
                 context.output[0].fill(0.0);
                 context.output[1].fill(0.0);
@@ -197,6 +234,9 @@ where
                     }
                 }
 
+                // The events were passed with the first sub-block:
+                period_events.clear();
+
                 frames_left -= cur_nframes;
             }
         },
@@ -209,6 +249,29 @@ where
     Ok(())
 }
 
+// Connects to the first MIDI input device and pushes the received
+// MIDI messages into the ring buffer.
+fn connect_midi_input(mut midi_prod: Producer<[u8; 3]>) -> Option<midir::MidiInputConnection<()>> {
+    let midi_in = midir::MidiInput::new("HexoDSPCpalDemo").ok()?;
+    let port = midi_in.ports().into_iter().next()?;
+    println!("MIDI input: {}", midi_in.port_name(&port).unwrap_or_default());
+
+    midi_in
+        .connect(
+            &port,
+            "hexodsp_midi_in",
+            move |_stamp, bytes, _| {
+                let mut msg = [0; 3];
+                for (m, b) in msg.iter_mut().zip(bytes.iter()) {
+                    *m = *b;
+                }
+                let _ = midi_prod.push(msg);
+            },
+            (),
+        )
+        .ok()
+}
+
 // This function starts the CPAL backend and
 // runs the audio loop with the NodeExecutor.
 fn start_backend<F: FnMut()>(node_exec: NodeExecutor, frontend_loop: F) {
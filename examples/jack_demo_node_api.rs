@@ -14,6 +14,7 @@ fn main() {
         //
         // This defines all supported nodes and their
         // parameters/inputs ports and their outputs.
+        let midiin = NodeId::MidiIn(0);
         let sin = NodeId::Sin(0);
         let amp = NodeId::Amp(0);
         let out = NodeId::Out(0);
@@ -31,6 +32,7 @@ fn main() {
         // which deletes all nodes.
         //
         // You can't delete only one specific node.
+        node_conf.create_node(midiin);
         node_conf.create_node(sin);
         node_conf.create_node(amp);
         node_conf.create_node(out);
@@ -45,11 +47,20 @@ fn main() {
         // the order they will be executed by the audio thread.
         // You will have to take care that all nodes get their
         // data in the right order here.
+        node_conf.add_prog_node(&mut prog, &NodeId::MidiIn(0));
         node_conf.add_prog_node(&mut prog, &NodeId::Sin(0));
         node_conf.add_prog_node(&mut prog, &NodeId::Amp(0));
         node_conf.add_prog_node(&mut prog, &NodeId::Out(0));
 
         // Define the connections between the nodes in the NodeProg:
+        // The notes received on the MIDI input port transpose the sine
+        // relative to A4:
+        node_conf.set_prog_node_exec_connection(
+            &mut prog,
+            (sin, sin.inp("det").unwrap()),
+            (midiin, midiin.out("freq").unwrap()),
+        );
+
         node_conf.set_prog_node_exec_connection(
             &mut prog,
             // first the input:
@@ -84,9 +95,8 @@ fn main() {
             //
             // Please note, that for sample accurate modulation you should
             // use the built in tracker or receive MIDI data from
-            // different application (MIDI processing has not been
-            // implemented yet (2021-05-18) and is not implemented
-            // in this jack interface).
+            // different application. MIDI events received on the
+            // "hexodsp_midi_in" port are passed to the `MidiIn` node.
 
             let new_gain = match amp_counter {
                 0 => 0.2,
@@ -220,12 +230,16 @@ fn start_backend<F: FnMut()>(node_exec: NodeExecutor, mut frontend_loop: F) {
 
     let in_a = client.register_port("hexodsp_in1", jack::AudioIn::default()).unwrap();
     let in_b = client.register_port("hexodsp_in2", jack::AudioIn::default()).unwrap();
+    let midi_in = client.register_port("hexodsp_midi_in", jack::MidiIn::default()).unwrap();
     let mut out_a = client.register_port("hexodsp_out1", jack::AudioOut::default()).unwrap();
     let mut out_b = client.register_port("hexodsp_out2", jack::AudioOut::default()).unwrap();
 
     let ne = Arc::new(Mutex::new(node_exec));
     let ne2 = ne.clone();
 
+    // Preallocate the event buffer, so we don't allocate in the audio thread:
+    let mut period_events: Vec<MidiEvent> = Vec::with_capacity(1024);
+
     let mut first = true;
     let process_callback = move |client: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
         let out_a_p = out_a.as_mut_slice(ps);
@@ -245,6 +259,15 @@ fn start_backend<F: FnMut()>(node_exec: NodeExecutor, mut frontend_loop: F) {
 
         let nframes = out_a_p.len();
 
+        period_events.clear();
+        for raw in midi_in.iter(ps) {
+            if period_events.len() < period_events.capacity() {
+                if let Some(ev) = MidiEvent::from_bytes(raw.time as usize, raw.bytes) {
+                    period_events.push(ev);
+                }
+            }
+        }
+
         // Please note, locking the NodeExecutor is wrong and broken
         // and should not be done on a real time thread.
         //
@@ -258,36 +281,21 @@ fn start_backend<F: FnMut()>(node_exec: NodeExecutor, mut frontend_loop: F) {
         // updates that were sent by the frontend thread:
         node_exec.process_graph_updates();
 
-        let mut frames_left = nframes;
-        let mut offs = 0;
+        let output = &mut [&mut out_a_p[..], &mut out_b_p[..]];
+        let input = &[&in_a_p[..], &in_b_p[..]];
 
-        while frames_left > 0 {
-            let cur_nframes = if frames_left >= hexodsp::dsp::MAX_BLOCK_SIZE {
-                hexodsp::dsp::MAX_BLOCK_SIZE
-            } else {
-                frames_left
-            };
-
-            frames_left -= cur_nframes;
-
-            let output = &mut [
-                &mut out_a_p[offs..(offs + cur_nframes)],
-                &mut out_b_p[offs..(offs + cur_nframes)],
-            ];
-            let input = &[&in_a_p[offs..(offs + cur_nframes)], &in_b_p[offs..(offs + cur_nframes)]];
-
-            let mut context = Context { nframes: cur_nframes, output, input };
+        let mut context =
+            Context { nframes, output, input, events: &period_events, transport: None };
 
-            for i in 0..context.nframes {
-                context.output[0][i] = 0.0;
-                context.output[1][i] = 0.0;
-            }
-
-            node_exec.process(&mut context);
-
-            offs += cur_nframes;
+        for i in 0..context.nframes {
+            context.output[0][i] = 0.0;
+            context.output[1][i] = 0.0;
         }
 
+        // The NodeExecutor splits up the buffer period into blocks of
+        // hexodsp::dsp::MAX_BLOCK_SIZE by itself:
+        node_exec.process(&mut context);
+
         jack::Control::Continue
     };
 
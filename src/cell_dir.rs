@@ -39,6 +39,27 @@ impl CellDir {
         }
     }
 
+    /// Rotates the direction clockwise by `steps` of 60°,
+    /// negative `steps` rotate counter clockwise.
+    pub fn rotate(&self, steps: i32) -> Self {
+        if *self == CellDir::C {
+            return CellDir::C;
+        }
+
+        Self::from((self.as_edge() as i32 + steps).rem_euclid(6) as u8)
+    }
+
+    /// Mirrors the direction at the vertical axis.
+    pub fn mirror(&self) -> Self {
+        match self {
+            CellDir::TR => CellDir::TL,
+            CellDir::BR => CellDir::BL,
+            CellDir::BL => CellDir::BR,
+            CellDir::TL => CellDir::TR,
+            dir => *dir,
+        }
+    }
+
     #[inline]
     pub fn is_output(&self) -> bool {
         let e = self.as_edge();
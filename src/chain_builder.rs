@@ -230,6 +230,9 @@ impl MatrixCellChain {
             return Err(err);
         }
 
+        // The whole chain is one undo step:
+        matrix.begin_transaction();
+
         let mut last_unused = HashMap::new();
 
         let mut pos = (at_x, at_y);
@@ -266,6 +269,8 @@ impl MatrixCellChain {
             }
         }
 
+        matrix.end_transaction();
+
         Ok(())
     }
 }
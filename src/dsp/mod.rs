@@ -109,6 +109,16 @@ are all macros that are defined in the HexoDSP crate. You can create your own
 normalization/denormalization, rounding, step and formatting function macros if
 the existing ones don't suit the DSP node's needs.
 
+Parameter changes are smoothed with a 10ms linear ramp by default. An input can
+declare it's own smoothing policy after the default value. Trigger and gate inputs
+usually don't want any smoothing, while filter cutoff frequencies sound better with
+a longer exponential smoothing:
+
+```ignore
+               (1 trig  n_id  d_id  r_id  f_def stp_d -1.0, 1.0, 0.0; Smoothing::None)
+               (2 freq  n_pit d_pit r_fq  f_freq stp_d -1.0, 0.5647131, 1000.0; Smoothing::Exp(50.0))
+```
+
 ### Signal Ranges in HexoDSP
 
 The HexoDSP graph, or rather the nodes, operate with the raw normalized (audio)
@@ -497,6 +507,10 @@ mod node_bosc;
 #[allow(non_upper_case_globals)]
 mod node_bowstri;
 #[allow(non_upper_case_globals)]
+mod node_bus;
+#[allow(non_upper_case_globals)]
+mod node_clock;
+#[allow(non_upper_case_globals)]
 mod node_code;
 #[allow(non_upper_case_globals)]
 mod node_comb;
@@ -507,8 +521,12 @@ mod node_delay;
 #[allow(non_upper_case_globals)]
 mod node_fbwr_fbrd;
 #[allow(non_upper_case_globals)]
+mod node_inp;
+#[allow(non_upper_case_globals)]
 mod node_map;
 #[allow(non_upper_case_globals)]
+mod node_midiin;
+#[allow(non_upper_case_globals)]
 mod node_mix3;
 #[allow(non_upper_case_globals)]
 mod node_mux9;
@@ -539,6 +557,8 @@ mod node_tseq;
 #[allow(non_upper_case_globals)]
 mod node_tslfo;
 #[allow(non_upper_case_globals)]
+mod node_voice;
+#[allow(non_upper_case_globals)]
 mod node_vosc;
 
 mod satom;
@@ -559,12 +579,16 @@ use crate::fa_amp_neg_att;
 use crate::fa_biqfilt_ord;
 use crate::fa_biqfilt_type;
 use crate::fa_bosc_wtype;
+use crate::fa_clock_res;
 use crate::fa_comb_mode;
 use crate::fa_cqnt;
 use crate::fa_cqnt_omax;
 use crate::fa_cqnt_omin;
 use crate::fa_delay_mode;
+use crate::fa_inp_chans;
 use crate::fa_map_clip;
+use crate::fa_midiin_cc;
+use crate::fa_midiin_chan;
 use crate::fa_mux9_in_cnt;
 use crate::fa_noise_mode;
 use crate::fa_out_mono;
@@ -587,13 +611,18 @@ use node_amp::Amp;
 use node_biqfilt::BiqFilt;
 use node_bosc::BOsc;
 use node_bowstri::BowStri;
+use node_bus::BusRecv;
+use node_bus::BusSend;
+use node_clock::Clock;
 use node_code::Code;
 use node_comb::Comb;
 use node_cqnt::CQnt;
 use node_delay::Delay;
 use node_fbwr_fbrd::FbRd;
 use node_fbwr_fbrd::FbWr;
+use node_inp::Inp;
 use node_map::Map;
+use node_midiin::MidiIn;
 use node_mix3::Mix3;
 use node_mux9::Mux9;
 use node_noise::Noise;
@@ -609,12 +638,31 @@ use node_smap::SMap;
 use node_test::Test;
 use node_tseq::TSeq;
 use node_tslfo::TsLFO;
+use node_voice::Voice;
 use node_vosc::VOsc;
 
 pub const MIDI_MAX_FREQ: f32 = 13289.75;
 
 pub const MAX_BLOCK_SIZE: usize = 128;
 
+/// How the [crate::nodes::NodeExecutor] smoothes the changes of an input
+/// parameter. The default is declared per parameter in the node list,
+/// see [ParamId::smoothing], and can be overridden at runtime with
+/// [crate::nodes::NodeConfigurator::set_param_smoothing].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Changes are applied immediately, for instance for trigger and gate inputs.
+    None,
+    /// Linear ramp to the new value over the given time in milliseconds.
+    Linear(f32),
+    /// Exponential approach of the new value over the given time in milliseconds.
+    /// This sounds smoother for filter cutoff frequencies for instance.
+    Exp(f32),
+}
+
+/// The smoothing of all parameters that don't declare their own.
+pub const DEFAULT_SMOOTHING: Smoothing = Smoothing::Linear(10.0);
+
 /// A context structure that holds temporary information about the
 /// currently executed node.
 /// This structure is created by the [crate::nodes::NodeExecutor] on the fly.
@@ -1049,6 +1097,17 @@ macro_rules! r_tmz {
     };
 }
 
+/// The rounding function for the feedback delay time knob (n_fbtm / d_fbtm)
+macro_rules! r_fbtm {
+    ($x: expr, $coarse: expr) => {
+        if $coarse {
+            n_fbtm!((d_fbtm!($x) * 10.0).round() / 10.0)
+        } else {
+            n_fbtm!((d_fbtm!($x) * 100.0).round() / 100.0)
+        }
+    };
+}
+
 /// The rounding function for freq knobs (n_pit / d_pit)
 macro_rules! r_fq {
     ($x: expr, $coarse: expr) => {
@@ -1246,6 +1305,24 @@ macro_rules! f_det {
     }};
 }
 
+macro_rules! f_note_div {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {
+        write!($formatter, "{}", crate::nodes::note_div_label($v))
+    };
+}
+
+#[macro_export]
+macro_rules! fa_tsync {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = match ($v.round() as usize) {
+            0 => "Free",
+            1 => "Tempo",
+            _ => "?",
+        };
+        write!($formatter, "{}", s)
+    }};
+}
+
 //          norm-fun      denorm-min
 //                 denorm-fun  denorm-max
 define_exp! {n_gain d_gain 0.0, 2.0}
@@ -1260,6 +1337,7 @@ define_exp6! {n_lfot d_lfot 0.1,300000.0}
 define_exp! {n_time d_time 0.5,  5000.0}
 define_exp! {n_ftme d_ftme 0.1,  1000.0}
 define_exp! {n_timz d_timz 0.0,  5000.0}
+define_exp! {n_fbtm d_fbtm 0.0,  20.0}
 
 // Special linear gain factor for the Out node, to be able
 // to reach more exact "1.0".
@@ -1269,6 +1347,17 @@ define_lin! {n_pgin d_pgin 1.0, 10.0}
 
 define_lin! {n_vps d_vps 0.0, 20.0}
 
+/// Expands to the name of the optional bypass input of a node
+/// in the `node_list!` macro.
+macro_rules! bypass_inp_name {
+    () => {
+        None
+    };
+    ($byp: ident) => {
+        Some(stringify!($byp))
+    };
+}
+
 // A note about the input-indicies:
 //
 // Atoms and Input parameters share the same global ID space
@@ -1283,11 +1372,19 @@ define_lin! {n_vps d_vps 0.0, 20.0}
 // It is used by the [Matrix] to get the global ParamId for the atom data
 // when iterating through the atoms of a Node and initializes the default data
 // for new nodes.
+//
+// Input parameters can optionally declare their smoothing policy after
+// the default value, for instance `; Smoothing::None`. All others
+// use the DEFAULT_SMOOTHING.
+//
+// Nodes can optionally declare the input that is passed through to
+// their first output while they are bypassed, with `bypass <input>`
+// after the UI category. See also [NodeId::bypass_inp].
 macro_rules! node_list {
     ($inmacro: ident) => {
         $inmacro! {
             nop => Nop,
-            amp => Amp UIType::Generic UICategory::Signal
+            amp => Amp UIType::Generic UICategory::Signal bypass inp
              // node_param_idx
              //   name             denorm round format steps norm norm denorm
              //         norm_fun   fun    fun   fun    def   min  max  default
@@ -1352,9 +1449,24 @@ macro_rules! node_list {
                {4 2 omax setting(0) mode fa_cqnt_omax 0 4}
                [0 sig]
                [1 t],
+            midiin => MidiIn UIType::Generic UICategory::Ctrl
+               (0 det   n_det      d_det  r_det f_det  stp_f -0.2, 0.2, 0.0)
+               {1 0 chan setting(0) mode fa_midiin_chan 0 16}
+               {2 1 cc   setting(1) mode fa_midiin_cc   0 127}
+               [0 freq]
+               [1 gate]
+               [2 vel]
+               [3 bend]
+               [4 ccv]
+               [5 after],
+            voice => Voice UIType::Generic UICategory::Ctrl
+               (0 det   n_det      d_det  r_det f_det  stp_f -0.2, 0.2, 0.0)
+               [0 freq]
+               [1 gate]
+               [2 vel],
             tseq => TSeq UIType::Generic UICategory::Mod
-               (0 clock n_id       d_id   r_id  f_def  stp_d  0.0, 1.0, 0.0)
-               (1 trig  n_id       n_id   r_id  f_def  stp_d -1.0, 1.0, 0.0)
+               (0 clock n_id       d_id   r_id  f_def  stp_d  0.0, 1.0, 0.0; Smoothing::None)
+               (1 trig  n_id       n_id   r_id  f_def  stp_d -1.0, 1.0, 0.0; Smoothing::None)
                {2 0 cmode setting(1) mode fa_tseq_cmode 0  2}
                [0 trk1]
                [1 trk2]
@@ -1368,6 +1480,14 @@ macro_rules! node_list {
                [9  gat4]
                [10 gat5]
                [11 gat6],
+            clock => Clock UIType::Generic UICategory::Mod
+               (0 ofs   n_id       d_id   r_id  f_def  stp_d  0.0, 1.0, 0.0)
+               {1 0 res   setting(2) mode fa_clock_res 0  8}
+               [0 beat]
+               [1 bar]
+               [2 phase]
+               [3 bphase]
+               [4 play],
             code => Code UIType::Generic UICategory::Signal
                (0 in1   n_id       d_id   r_id  f_def  stp_d -1.0, 1.0, 0.0)
                (1 in2   n_id       d_id   r_id  f_def  stp_d -1.0, 1.0, 0.0)
@@ -1380,7 +1500,7 @@ macro_rules! node_list {
                [2 sig2],
             sampl => Sampl UIType::Generic UICategory::Osc
                (0 freq  n_pit      d_pit  r_fq  f_def    stp_d -1.0, 0.564713133, 440.0)
-               (1 trig  n_id       d_id   r_id  f_def    stp_d -1.0, 1.0, 0.0)
+               (1 trig  n_id       d_id   r_id  f_def    stp_d -1.0, 1.0, 0.0; Smoothing::None)
                (2 offs  n_id       d_id   r_id  f_def    stp_d  0.0, 1.0, 0.0)
                (3 len   n_id       d_id   r_id  f_def    stp_d  0.0, 1.0, 1.0)
                (4 dcms  n_declick  d_declick r_dc_ms f_ms   stp_m  0.0, 1.0, 3.0)
@@ -1420,6 +1540,11 @@ macro_rules! node_list {
                (3 force n_id       n_id  r_id  f_def   stp_d  0.0, 1.0, 0.5)
                (4 pos   n_id       n_id  r_id  f_def   stp_d  0.0, 1.0, 0.5)
                [0 sig],
+            inp => Inp UIType::Generic UICategory::IOUtil
+               (0  gain  n_ogin    d_ogin r_id  f_def  stp_d  0.0, 2.0, 1.0)
+               {1 0 chans setting(0) mode fa_inp_chans 0  7}
+               [0 sig1]
+               [1 sig2],
             out => Out UIType::Generic UICategory::IOUtil
                (0  ch1   n_id      d_id  r_id   f_def  stp_d -1.0, 1.0, 0.0)
                (1  ch2   n_id      d_id  r_id   f_def  stp_d -1.0, 1.0, 0.0)
@@ -1434,13 +1559,24 @@ macro_rules! node_list {
                (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0),
             fbrd => FbRd UIType::Generic UICategory::IOUtil
                (0  atv   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 1.0)
+               (1  time  n_fbtm   d_fbtm r_fbtm f_ms  stp_f  0.0, 1.0, 3.14)
+               {2 0 mode setting(0) mode fa_fbrd_mode 0 1}
+               [0 sig],
+            bussend => BusSend UIType::Generic UICategory::IOUtil
+               (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
+               {1 0 bus  setting(0) mode fa_bus 0 15}
+               {2 1 name str("")    text f_def  0 0},
+            busrecv => BusRecv UIType::Generic UICategory::IOUtil
+               (0  atv   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 1.0)
+               {1 0 bus  setting(0) mode fa_bus 0 15}
+               {2 1 name str("")    text f_def  0 0}
                [0 sig],
             scope => Scope UIType::Generic UICategory::IOUtil
                (0  in1   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (1  in2   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (2  in3   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (3  time  n_lfot  d_lfot r_lfot f_lfoms stp_f 0.0, 1.0, 1000.0)
-               (4  trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
+               (4  trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0; Smoothing::None)
                (5  thrsh n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (6  off1  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (7  off2  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
@@ -1451,41 +1587,44 @@ macro_rules! node_list {
                {12 0 tsrc  setting(0) mode fa_scope_tsrc 0 2},
             ad   => Ad   UIType::Generic UICategory::Mod
                (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 1.0)
-               (1  trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
+               (1  trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0; Smoothing::None)
                (2  atk   n_env     d_env r_ems  f_ms  stp_m  0.0, 1.0, 3.0)
                (3  dcy   n_env     d_env r_ems  f_ms  stp_m  0.0, 1.0, 10.0)
                (4  ashp  n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 0.5)
                (5  dshp  n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 0.5)
                {6 0 mult setting(0) mode fa_ad_mult  0 2}
+               {7 1 sync setting(0) mode fa_tsync    0 1}
                [0 sig]
                [1 eoet],
             tslfo => TsLFO UIType::Generic UICategory::Mod
                 (0 time  n_lfot   d_lfot r_lfot f_lfot stp_f 0.0, 1.0, 1000.0)
-                (1 trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
+                (1 trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0; Smoothing::None)
                 (2 rev   n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 0.5)
+                {3 0 sync setting(0) mode fa_tsync 0 1}
                 [0 sig],
             rndwk => RndWk UIType::Generic UICategory::Mod
-                (0 trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
+                (0 trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0; Smoothing::None)
                 (1 step  n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 0.2)
                 (2 offs  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                 (3 min   n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 0.0)
                 (4 max   n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 1.0)
                 (5 slew  n_timz   d_timz r_tmz  f_ms  stp_m  0.0, 1.0, 75.0)
                 [0 sig],
-            delay => Delay UIType::Generic UICategory::Signal
+            delay => Delay UIType::Generic UICategory::Signal bypass inp
                (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
-               (1  trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
+               (1  trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0; Smoothing::None)
                (2  time  n_time   d_time r_tms  f_ms  stp_m  0.0, 1.0, 250.0)
                (3  fb    n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (4  mix   n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 0.5)
                {5 0 mode setting(0) mode fa_delay_mode 0 1}
+               {6 1 sync setting(0) mode fa_tsync      0 1}
                [0 sig],
-            allp  => AllP UIType::Generic UICategory::Signal
+            allp  => AllP UIType::Generic UICategory::Signal bypass inp
                (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (1  time  n_ftme   d_ftme r_fms  f_ms  stp_m  0.0, 1.0, 25.0)
                (2  g     n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.7)
                [0 sig],
-            comb  => Comb UIType::Generic UICategory::Signal
+            comb  => Comb UIType::Generic UICategory::Signal bypass inp
                (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (1  time  n_ftme   d_ftme r_fms  f_ms  stp_m  0.0, 1.0, 25.0)
                (2  g     n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.7)
@@ -1496,21 +1635,21 @@ macro_rules! node_list {
                (1  offs  n_id      d_id  r_s    f_def stp_d -1.0, 1.0, 0.0)
                {2 0 mode setting(0) mode fa_noise_mode 0 1}
                [0 sig],
-            sfilter => SFilter UIType::Generic UICategory::Signal
+            sfilter => SFilter UIType::Generic UICategory::Signal bypass inp
                (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (1 freq  n_pit      d_pit r_fq  f_freq  stp_d -1.0, 0.5647131, 1000.0)
                (2  res   n_id      d_id  r_id   f_def stp_d 0.0, 1.0, 0.5)
                {3 0 ftype setting(8) mode fa_sfilter_type 0 13}
                [0 sig],
-            biqfilt => BiqFilt UIType::Generic UICategory::Signal
+            biqfilt => BiqFilt UIType::Generic UICategory::Signal bypass inp
                (0 inp    n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
-               (1 freq   n_pit     d_pit r_fq  f_freq stp_d -1.0, 0.5647131, 1000.0)
+               (1 freq   n_pit     d_pit r_fq  f_freq stp_d -1.0, 0.5647131, 1000.0; Smoothing::Exp(50.0))
                (2 q      n_id      d_id  r_id   f_def stp_d 0.0, 1.0, 0.5)
                (3 gain   n_ogin   d_ogin r_id   f_def stp_d 0.0, 1.0, 1.0)
                {4 0 ftype setting(0) mode fa_biqfilt_type 0 1}
                {5 1 order setting(0) mode fa_biqfilt_ord  0 3}
                [0 sig],
-            pverb => PVerb UIType::Generic UICategory::Signal
+            pverb => PVerb UIType::Generic UICategory::Signal bypass in_l
                ( 0 in_l   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                ( 1 in_r   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                ( 2 predly n_timz   d_timz r_tmz  f_ms  stp_m  0.0, 1.0, 0.0)
@@ -1526,6 +1665,7 @@ macro_rules! node_list {
                (12 rlpf  n_pit      d_pit r_fq  f_freq stp_d -1.0, 0.5647131, 22050.0)
                (13 rhpf  n_pit      d_pit r_fq  f_freq stp_d -1.0, 0.5647131, 0.0)
                (14 mix   n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 0.5)
+               {15 0 sync setting(0) mode fa_tsync 0 1}
                [0 sig_l]
                [1 sig_r],
             test => Test UIType::Generic UICategory::IOUtil
@@ -1550,9 +1690,10 @@ impl UICategory {
                 $($str: ident => $variant: ident
                     UIType:: $gui_type: ident
                     UICategory:: $ui_cat: ident
+                    $(bypass $byp: ident)?
                     $(($in_idx: literal $para: ident
                        $n_fun: ident $d_fun: ident $r_fun: ident $f_fun: ident
-                       $steps: ident $min: expr, $max: expr, $def: expr))*
+                       $steps: ident $min: expr, $max: expr, $def: expr $(; $smooth: expr)?))*
                     $({$in_at_idx: literal $at_idx: literal $atom: ident
                        $at_fun: ident ($at_init: expr) $at_ui: ident $fa_fun: ident
                        $amin: literal $amax: literal})*
@@ -1590,6 +1731,7 @@ fn rand_node_satisfies_spec(nid: NodeId, sel: RandNodeSelector) -> bool {
             NodeId::Nop => false,
             NodeId::Out(_) => false,
             NodeId::FbRd(_) => false,
+            NodeId::BusRecv(_) => false,
             NodeId::Test(_) => false,
             _ => true,
         },
@@ -1652,9 +1794,10 @@ macro_rules! make_node_info_enum {
         $($str: ident => $variant: ident
             UIType:: $gui_type: ident
             UICategory:: $ui_cat: ident
+            $(bypass $byp: ident)?
             $(($in_idx: literal $para: ident
                $n_fun: ident $d_fun: ident $r_fun: ident $f_fun: ident
-               $steps: ident $min: expr, $max: expr, $def: expr))*
+               $steps: ident $min: expr, $max: expr, $def: expr $(; $smooth: expr)?))*
             $({$in_at_idx: literal $at_idx: literal $atom: ident
                $at_fun: ident ($at_init: expr) $at_ui: ident $fa_fun: ident
                $amin: literal $amax: literal})*
@@ -1828,6 +1971,24 @@ macro_rules! make_node_info_enum {
                 }
             }
 
+            /// Returns the smoothing policy that is declared for this
+            /// input parameter. Atoms are never smoothed.
+            pub fn smoothing(&self) -> Smoothing {
+                match self.node {
+                    NodeId::$v1           => Smoothing::None,
+                    $(NodeId::$variant(_) => {
+                        match self.idx {
+                            $($in_idx => {
+                                let smoothing = DEFAULT_SMOOTHING;
+                                $(let smoothing = $smooth;)?
+                                smoothing
+                            },)*
+                            _ => Smoothing::None,
+                        }
+                    }),+
+                }
+            }
+
             pub fn norm_def(&self) -> f32 {
                 match self.node {
                     NodeId::$v1           => 0.0,
@@ -1964,6 +2125,17 @@ macro_rules! make_node_info_enum {
                 }
             }
 
+            /// Returns the input that is passed through to the first output
+            /// while the node is bypassed, see [crate::nodes::NodeState::Bypass].
+            pub fn bypass_inp(&self) -> Option<u8> {
+                match self {
+                    NodeId::$v1           => None,
+                    $(NodeId::$variant(_) => {
+                        bypass_inp_name!($($byp)?).and_then(|name| self.inp(name))
+                    }),+
+                }
+            }
+
             /// Consistently initialize the phase for oscillators.
             /// This does some fixed phase offset for the first 3
             /// instances, which is usually relied on by the automated
@@ -2357,9 +2529,10 @@ macro_rules! make_node_enum {
         $($str: ident => $variant: ident
             UIType:: $gui_type: ident
             UICategory:: $ui_cat: ident
+            $(bypass $byp: ident)?
             $(($in_idx: literal $para: ident
                $n_fun: ident $d_fun: ident $r_fun: ident $f_fun: ident
-               $steps: ident $min: expr, $max: expr, $def: expr))*
+               $steps: ident $min: expr, $max: expr, $def: expr $(; $smooth: expr)?))*
             $({$in_at_idx: literal $at_idx: literal $atom: ident
                $at_fun: ident ($at_init: expr) $at_ui: ident $fa_fun: ident
                $amin: literal $amax: literal})*
@@ -2444,9 +2617,10 @@ pub fn node_factory(node_id: NodeId) -> Option<(Node, NodeInfo)> {
             $($str: ident => $variant: ident
                 UIType:: $gui_type: ident
                 UICategory:: $ui_cat: ident
+                $(bypass $byp: ident)?
                 $(($in_idx: literal $para: ident
                    $n_fun: ident $d_fun: ident $r_fun: ident $f_fun: ident
-                   $steps: ident $min: expr, $max: expr, $def: expr))*
+                   $steps: ident $min: expr, $max: expr, $def: expr $(; $smooth: expr)?))*
                 $({$in_at_idx: literal $at_idx: literal $atom: ident
                    $at_fun: ident ($at_init: expr) $at_ui: ident $fa_fun: ident
                    $amin: literal $amax: literal})*
@@ -2466,6 +2640,74 @@ pub fn node_factory(node_id: NodeId) -> Option<(Node, NodeInfo)> {
     node_list! {make_node_factory_match}
 }
 
+impl ParamId {
+    /// Returns the `sync` setting of the node, if this is a time parameter
+    /// that can be synchronized to the tempo of the [crate::nodes::Transport].
+    /// If that setting is enabled, the parameter selects one of the
+    /// [crate::nodes::NOTE_DIVISIONS] instead of a time in milliseconds.
+    pub fn sync_atom(&self) -> Option<ParamId> {
+        match (self.node_id(), self.name()) {
+            (NodeId::Delay(_), "time")
+            | (NodeId::TsLFO(_), "time")
+            | (NodeId::Ad(_), "atk")
+            | (NodeId::Ad(_), "dcy")
+            | (NodeId::PVerb(_), "predly") => self.node_id().inp_param("sync"),
+            _ => None,
+        }
+    }
+
+    /// Formats the normalized value `v` of a tempo synced time parameter
+    /// as note division, like "1/8." or "1/4T". Returns `None` if this
+    /// parameter can't be synced, see also [ParamId::sync_atom].
+    pub fn format_note_div(
+        &self,
+        f: &mut dyn std::io::Write,
+        v: f32,
+    ) -> Option<std::io::Result<()>> {
+        self.sync_atom()?;
+        Some(f_note_div!(f, v, v))
+    }
+}
+
+impl NodeId {
+    /// Returns true if the node may be executed on a worker thread of the
+    /// [crate::nodes::NodeExecutor], see also [crate::NodeConfigurator::set_worker_threads].
+    /// Nodes that access the audio driver, the MIDI events or the shared state
+    /// in the [crate::nodes::NodeExecContext] (like the feedback buffers, the
+    /// buses or the voice allocator) are always executed on the audio thread.
+    pub fn is_parallel_safe(&self) -> bool {
+        !matches!(
+            self,
+            NodeId::Nop
+                | NodeId::Inp(_)
+                | NodeId::Out(_)
+                | NodeId::MidiIn(_)
+                | NodeId::Voice(_)
+                | NodeId::FbWr(_)
+                | NodeId::FbRd(_)
+                | NodeId::BusSend(_)
+                | NodeId::BusRecv(_)
+        )
+    }
+
+    /// Returns true if a second instance of the node can run alongside
+    /// the first one, while the [crate::nodes::NodeExecutor] crossfades
+    /// between two programs, see [crate::NodeConfigurator::set_prog_crossfade].
+    /// Nodes that are connected to a single backend in the frontend or that
+    /// share the feedback buffers or the buses can't be duplicated.
+    pub fn is_crossfade_safe(&self) -> bool {
+        !matches!(
+            self,
+            NodeId::TSeq(_)
+                | NodeId::Code(_)
+                | NodeId::FbWr(_)
+                | NodeId::FbRd(_)
+                | NodeId::BusSend(_)
+                | NodeId::BusRecv(_)
+        )
+    }
+}
+
 impl Node {
     /// This function is the heart of any DSP.
     /// It dispatches this call to the corresponding [Node] implementation.
@@ -2516,9 +2758,10 @@ impl Node {
                 $($str: ident => $variant: ident
                     UIType:: $gui_type: ident
                     UICategory:: $ui_cat: ident
+                    $(bypass $byp: ident)?
                     $(($in_idx: literal $para: ident
                        $n_fun: ident $d_fun: ident $r_fun: ident $f_fun: ident
-                       $steps: ident $min: expr, $max: expr, $def: expr))*
+                       $steps: ident $min: expr, $max: expr, $def: expr $(; $smooth: expr)?))*
                     $({$in_at_idx: literal $at_idx: literal $atom: ident
                        $at_fun: ident ($at_init: expr) $at_ui: ident $fa_fun: ident
                        $amin: literal $amax: literal})*
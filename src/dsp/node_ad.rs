@@ -64,6 +64,10 @@ impl Ad {
         exponential shape.\nRange: (0..1)\n";
     pub const mult: &'static str = "Ad mult\nAttack and Decay time range multiplier. \
         This will extend the maximum range of the 'atk' and 'dcy' parameters.";
+    pub const sync: &'static str =
+        "Ad sync\nIf set to 'Tempo', the 'atk' and 'dcy' parameters select a note \
+        length (1/64 to 1/1, dotted or triplets) relative to the tempo of the \
+        transport. The 'mult' setting is ignored then.";
     pub const sig: &'static str =
         "Ad sig\nEnvelope signal output. If a signal is sent to the 'inp' port, \
         you will receive an attenuated signal here. If you set 'inp' to a \
@@ -90,6 +94,9 @@ other inputs.
 
 With the 'eoet' output you can either trigger other envelopes or via
 'FbWr'/'FbRd' retrigger the envelope.
+
+With the 'sync' setting the attack and decay times follow the tempo of
+the transport and are selected as note lengths.
 "#;
 }
 
@@ -116,7 +123,7 @@ impl DspNode for Ad {
     fn process<T: NodeAudioContext>(
         &mut self,
         ctx: &mut T,
-        _ectx: &mut NodeExecContext,
+        ectx: &mut NodeExecContext,
         _nctx: &NodeContext,
         atoms: &[SAtom],
         inputs: &[ProcBuf],
@@ -132,6 +139,7 @@ impl DspNode for Ad {
         let atk_shape = inp::Ad::ashp(inputs);
         let dcy_shape = inp::Ad::dshp(inputs);
         let mult = at::Ad::mult(atoms);
+        let sync = at::Ad::sync(atoms).i() != 0;
 
         // block start:
         let (mut shape_src, mut inc_time_src, mut target, mut delta) = match self.stage {
@@ -140,6 +148,7 @@ impl DspNode for Ad {
             _ => (atk_shape, atk, 0.0, 0.0),
         };
         let mult: f64 = match mult.i() {
+            _ if sync => 1.0,
             1 => 10.0,
             2 => 100.0,
             _ => 1.0,
@@ -158,7 +167,11 @@ impl DspNode for Ad {
                 inc_time_src = atk;
             }
 
-            let cur_time = denorm::Ad::atk(inc_time_src, frame);
+            let cur_time = if sync {
+                ectx.transport.note_div_ms(inc_time_src.read(frame))
+            } else {
+                denorm::Ad::atk(inc_time_src, frame)
+            };
             if self.last_time != cur_time {
                 self.inc = if cur_time <= 0.0001 {
                     delta
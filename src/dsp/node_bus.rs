@@ -0,0 +1,167 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{DspNode, LedPhaseVals, NodeContext, NodeId, ProcBuf, SAtom};
+use crate::nodes::{NodeAudioContext, NodeExecContext};
+
+#[macro_export]
+macro_rules! fa_bus {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        write!($formatter, "Bus {}", ($v.round() as usize) + 1)
+    }};
+}
+
+/// Sums a signal into a bus
+#[derive(Debug, Clone)]
+pub struct BusSend {}
+
+impl BusSend {
+    pub fn new(_nid: &NodeId) -> Self {
+        Self {}
+    }
+    pub const inp: &'static str = "BusSend inp\nSignal input\nRange: (-1..1)\n";
+    pub const bus: &'static str = "BusSend bus\nThe bus the signal is summed into.\n";
+    pub const name: &'static str = "BusSend name\nThe name of the bus. \
+        If set, the 'bus' is chosen on sync from the name.\n";
+
+    pub const DESC: &'static str = "Bus Send\n\n\
+This node sums the input signal into a bus. Use 'BusRecv' nodes \
+anywhere on the grid to read the sum of all 'BusSend' nodes of the bus \
+without any delay.";
+    pub const HELP: &'static str = r#"Bus Send
+
+The hexagonal grid only allows connections between adjacent cells.
+The 'BusSend' and 'BusRecv' nodes connect parts of the graph that are
+far apart, for instance to build an effect return chain.
+
+The signals of all 'BusSend' nodes that send to the same bus are summed
+up. The 'BusRecv' nodes of the bus read that sum in the same block,
+without any delay. For this the 'BusSend' nodes are always executed before
+the 'BusRecv' nodes of their bus. So a bus can't be used for feedback,
+a 'BusSend' that depends on a 'BusRecv' of the same bus is a cycle.
+Please use 'FbWr'/'FbRd' for feedback.
+
+Instead of a bus number you can give the bus a 'name'. All 'BusSend'
+and 'BusRecv' nodes with the same name share a bus, which is picked from
+the buses that no unnamed node uses. The 'bus' setting then shows it.
+
+Changing the 'bus' setting or the 'name' changes the order of execution,
+so it only takes effect after the next sync of the matrix.
+"#;
+}
+
+impl DspNode for BusSend {
+    fn outputs() -> usize {
+        0
+    }
+
+    fn set_sample_rate(&mut self, _srate: f32) {}
+    fn reset(&mut self) {}
+
+    #[inline]
+    fn process<T: NodeAudioContext>(
+        &mut self,
+        ctx: &mut T,
+        ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        _outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        use crate::dsp::{at, inp};
+
+        let inp = inp::BusSend::inp(inputs);
+        let bus = at::BusSend::bus(atoms);
+
+        if let Some(buf) = ectx.bus_buffers.get_mut(bus.i() as usize) {
+            for (frame, s) in buf.iter_mut().enumerate().take(ctx.nframes()) {
+                *s += inp.read(frame);
+            }
+        }
+
+        ctx_vals[0].set(inp.read(ctx.nframes() - 1));
+    }
+}
+
+/// Reads the sum of a bus
+#[derive(Debug, Clone)]
+pub struct BusRecv {}
+
+impl BusRecv {
+    pub fn new(_nid: &NodeId) -> Self {
+        Self {}
+    }
+    pub const atv: &'static str =
+        "BusRecv atv\nAttenuate or invert the bus signal.\nRange: (-1..1)\n";
+    pub const bus: &'static str = "BusRecv bus\nThe bus that is read.\n";
+    pub const name: &'static str = "BusRecv name\nThe name of the bus. \
+        If set, the 'bus' is chosen on sync from the name.\n";
+    pub const sig: &'static str = "BusRecv sig\nThe sum of the bus.\nRange: (-1..1)\n";
+
+    pub const DESC: &'static str = "Bus Receive\n\n\
+This node reads the sum of all 'BusSend' nodes of a bus, \
+without any delay.";
+    pub const HELP: &'static str = r#"Bus Receive
+
+The hexagonal grid only allows connections between adjacent cells.
+The 'BusSend' and 'BusRecv' nodes connect parts of the graph that are
+far apart, for instance to build an effect return chain.
+
+This node outputs the sum of the signals of all 'BusSend' nodes that
+send to the same bus, without any delay. You can read a bus with
+multiple 'BusRecv' nodes.
+
+The 'atv' parameter is a convenience parameter to allow attenuating or
+even inverting the signal.
+
+Instead of a bus number you can give the bus a 'name'. All 'BusSend'
+and 'BusRecv' nodes with the same name share a bus, which is picked from
+the buses that no unnamed node uses. The 'bus' setting then shows it.
+
+Changing the 'bus' setting or the 'name' changes the order of execution,
+so it only takes effect after the next sync of the matrix.
+"#;
+}
+
+impl DspNode for BusRecv {
+    fn outputs() -> usize {
+        1
+    }
+
+    fn set_sample_rate(&mut self, _srate: f32) {}
+    fn reset(&mut self) {}
+
+    #[inline]
+    fn process<T: NodeAudioContext>(
+        &mut self,
+        ctx: &mut T,
+        ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        use crate::dsp::{at, denorm, inp, out};
+
+        let atv = inp::BusRecv::atv(inputs);
+        let bus = at::BusRecv::bus(atoms);
+        let sig = out::BusRecv::sig(outputs);
+
+        let mut last_val = 0.0;
+        if let Some(buf) = ectx.bus_buffers.get(bus.i() as usize) {
+            for (frame, s) in buf.iter().enumerate().take(ctx.nframes()) {
+                last_val = *s * denorm::BusRecv::atv(atv, frame);
+                sig.write(frame, last_val);
+            }
+        } else {
+            for frame in 0..ctx.nframes() {
+                sig.write(frame, 0.0);
+            }
+        }
+
+        ctx_vals[0].set(last_val);
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{DspNode, LedPhaseVals, NodeContext, NodeId, ProcBuf, SAtom};
+use crate::nodes::{NodeAudioContext, NodeExecContext};
+use synfx_dsp::TrigSignal;
+
+#[macro_export]
+macro_rules! fa_clock_res {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = match ($v.round() as usize) {
+            0 => "1/1",
+            1 => "1/2",
+            2 => "1/4",
+            3 => "1/8",
+            4 => "1/16",
+            5 => "1/32",
+            6 => "1/4T",
+            7 => "1/8T",
+            8 => "1/16T",
+            _ => "?",
+        };
+        write!($formatter, "{}", s)
+    }};
+}
+
+/// Length of the 'res' setting values in quarter notes.
+const RES_QUARTERS: [f64; 9] = [4.0, 2.0, 1.0, 0.5, 0.25, 0.125, 2.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0];
+
+/// Provides triggers and phases in sync with the global transport
+#[derive(Debug, Clone)]
+pub struct Clock {
+    srate: f32,
+    last_div: Option<i64>,
+    last_bar: Option<i64>,
+    beat_trig: TrigSignal,
+    bar_trig: TrigSignal,
+}
+
+impl Clock {
+    pub fn new(_nid: &NodeId) -> Self {
+        Self {
+            srate: 44100.0,
+            last_div: None,
+            last_bar: None,
+            beat_trig: TrigSignal::new(),
+            bar_trig: TrigSignal::new(),
+        }
+    }
+    pub const ofs: &'static str =
+        "Clock ofs\nShifts the 'beat' trigger and 'phase' output by a fraction \
+        of the note length selected with 'res'.\nRange: (0..1)\n";
+    pub const res: &'static str =
+        "Clock res\nThe note length of the 'beat' trigger and 'phase' output. \
+        'T' denotes triplets.";
+    pub const beat: &'static str =
+        "Clock beat\nSends a trigger for each note length selected with 'res' \
+        while the transport is playing.\nRange: (0..1)\n";
+    pub const bar: &'static str = "Clock bar\nSends a trigger at the start of each bar \
+        while the transport is playing.\nRange: (0..1)\n";
+    pub const phase: &'static str =
+        "Clock phase\nThe phase of the current note length selected with 'res', \
+        rising from 0.0 to 1.0.\nRange: (0..1)\n";
+    pub const bphase: &'static str =
+        "Clock bphase\nThe phase of the current bar, rising from 0.0 to 1.0.\nRange: (0..1)\n";
+    pub const play: &'static str =
+        "Clock play\nIs high while the transport is playing.\nRange: (0..1)\n";
+    pub const DESC: &'static str = r#"Transport Clock
+
+Provides beat and bar triggers and phases that follow the global transport, which is either controlled by the host (your DAW) or the frontend.
+"#;
+    pub const HELP: &'static str = r#"Clock - Transport Clock
+
+This node follows the song position, tempo and time signature of the
+global transport. The transport is either provided by the host (for
+instance your DAW) or controlled by the frontend.
+
+The 'beat' output sends a trigger for each note length that is selected
+with the 'res' setting, the 'bar' output sends a trigger at the start of
+each bar. The triggers are only sent while the transport is playing,
+which is signalled by the 'play' output.
+
+The 'phase' output provides the position inside the current note length
+and 'bphase' the position inside the current bar. You can use these
+to drive sequencers or to modulate parameters in sync with the song.
+
+With 'ofs' the 'beat' trigger and 'phase' can be shifted, for instance
+to create offbeats.
+"#;
+}
+
+impl DspNode for Clock {
+    fn outputs() -> usize {
+        5
+    }
+
+    fn set_sample_rate(&mut self, srate: f32) {
+        self.srate = srate;
+        self.beat_trig.set_sample_rate(srate);
+        self.bar_trig.set_sample_rate(srate);
+    }
+
+    fn reset(&mut self) {
+        self.last_div = None;
+        self.last_bar = None;
+        self.beat_trig.reset();
+        self.bar_trig.reset();
+    }
+
+    #[inline]
+    fn process<T: NodeAudioContext>(
+        &mut self,
+        ctx: &mut T,
+        ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        use crate::dsp::{at, denorm, inp, out_buf};
+
+        let ofs = inp::Clock::ofs(inputs);
+        let res = at::Clock::res(atoms).i().clamp(0, RES_QUARTERS.len() as i64 - 1);
+        let res_len = RES_QUARTERS[res as usize];
+
+        let mut beat = out_buf::Clock::beat(outputs);
+        let mut bar = out_buf::Clock::bar(outputs);
+        let mut phase = out_buf::Clock::phase(outputs);
+        let mut bphase = out_buf::Clock::bphase(outputs);
+        let mut play = out_buf::Clock::play(outputs);
+
+        let transport = ectx.transport;
+        let bar_len = transport.bar_len();
+        let playing = transport.playing;
+
+        let mut last_phase = 0.0;
+        for frame in 0..ctx.nframes() {
+            let pos = transport.beat_pos_at(frame, self.srate);
+
+            let div_pos = pos / res_len + denorm::Clock::ofs(ofs, frame) as f64;
+            let div = div_pos.floor() as i64;
+            let div_phase = div_pos - div_pos.floor();
+
+            let bar_pos = pos / bar_len;
+            let bar_idx = bar_pos.floor() as i64;
+            let bar_phase = bar_pos - bar_pos.floor();
+
+            if playing {
+                if self.last_div != Some(div) {
+                    self.beat_trig.trigger();
+                }
+                if self.last_bar != Some(bar_idx) {
+                    self.bar_trig.trigger();
+                }
+
+                self.last_div = Some(div);
+                self.last_bar = Some(bar_idx);
+            } else {
+                // A transport that is stopped right at the start of a note or bar
+                // sends the trigger as soon as it starts playing:
+                self.last_div = if div_phase > 0.0 { Some(div) } else { None };
+                self.last_bar = if bar_phase > 0.0 { Some(bar_idx) } else { None };
+            }
+
+            beat.write(frame, self.beat_trig.next());
+            bar.write(frame, self.bar_trig.next());
+            phase.write(frame, div_phase as f32);
+            bphase.write(frame, bar_phase as f32);
+            play.write(frame, if playing { 1.0 } else { 0.0 });
+
+            last_phase = div_phase as f32;
+        }
+
+        ctx_vals[0].set(last_phase);
+    }
+}
@@ -46,6 +46,10 @@ impl Delay {
         'Time' is the default, and means that the 'time' input \
         specifies the delay time. 'Sync' will synchronize the delay time \
         with the trigger signals on the 'trig' input.";
+    pub const sync: &'static str =
+        "Delay sync\nIf set to 'Tempo', the 'time' parameter selects a note \
+        length (1/64 to 1/1, dotted or triplets) relative to the tempo of the \
+        transport, instead of a time in milliseconds.";
     pub const sig: &'static str = "Delay sig\nThe output of the dry/wet mix.\nRange: (-1..1)";
 
     pub const DESC: &'static str = r#"Simple Delay Line
@@ -61,6 +65,10 @@ and a feedback 'fb' parameter.
 
 Via the 'mix' parameter you can mix in the input signal to the output.
 
+With the 'sync' setting the delay 'time' follows the tempo of the
+transport and is selected as note length, for instance '1/8.' for
+a dotted eighth note.
+
 You can use this node to delay any kind of signal, from a simple control
 signal to an audio signal.
 
@@ -86,7 +94,7 @@ impl DspNode for Delay {
     fn process<T: NodeAudioContext>(
         &mut self,
         ctx: &mut T,
-        _ectx: &mut NodeExecContext,
+        ectx: &mut NodeExecContext,
         _nctx: &NodeContext,
         atoms: &[SAtom],
         inputs: &[ProcBuf],
@@ -98,6 +106,7 @@ impl DspNode for Delay {
         let buffer = &mut *self.buffer;
 
         let mode = at::Delay::mode(atoms);
+        let sync = at::Delay::sync(atoms).i() != 0;
         let inp = inp::Delay::inp(inputs);
         let trig = inp::Delay::trig(inputs);
         let time = inp::Delay::time(inputs);
@@ -109,7 +118,13 @@ impl DspNode for Delay {
             for frame in 0..ctx.nframes() {
                 let dry = inp.read(frame);
 
-                let out_sample = buffer.cubic_interpolate_at(denorm::Delay::time(time, frame));
+                let time_ms = if sync {
+                    ectx.transport.note_div_ms(time.read(frame)).min(5000.0)
+                } else {
+                    denorm::Delay::time(time, frame)
+                };
+
+                let out_sample = buffer.cubic_interpolate_at(time_ms);
 
                 buffer.feed(dry + out_sample * denorm::Delay::fb(fb, frame));
 
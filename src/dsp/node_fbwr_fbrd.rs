@@ -3,7 +3,28 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{DspNode, LedPhaseVals, NodeContext, NodeId, ProcBuf, SAtom};
-use crate::nodes::{NodeAudioContext, NodeExecContext};
+use crate::nodes::{
+    FeedbackBuffer, NodeAudioContext, NodeExecContext, FB_DELAY_TIME_US, MAX_FB_DELAY_SIZE,
+};
+
+#[macro_export]
+macro_rules! fa_fbrd_mode {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = match ($v.round() as usize) {
+            0 => "Fixed",
+            1 => "Time",
+            _ => "?",
+        };
+        write!($formatter, "{}", s)
+    }};
+}
+
+/// The sample count of the fixed 3.14ms delay, as the feedback
+/// nodes always had it. Above [crate::nodes::MAX_FB_DELAY_SRATE]
+/// it wraps around the ring of the [FeedbackBuffer].
+fn fixed_delay_samples(srate: f32) -> f32 {
+    (((srate as usize * FB_DELAY_TIME_US) / 1000000) % MAX_FB_DELAY_SIZE) as f32
+}
 
 /// A simple amplifier
 #[derive(Debug, Clone)]
@@ -22,7 +43,7 @@ HexoSynth does not allow direct feedback cycles in it's graph.\n\
 To make feedback possible anyways the 'FbWr' and 'FbRd' nodes are provided.\n\
 This node allows you to write a signal into the corresponsing signal delay buffer.\n\
 Use 'FbRd' for using the signal.\n\
-The delay is 3.14ms.";
+The delay is set by the 'FbRd' node, by default it is 3.14ms.";
     pub const HELP: &'static str = r#"Feedback Delay Writer
 
 HexoSynth does not allow direct feedback cycles in it's graph.
@@ -35,8 +56,8 @@ That means 'FbRd 0' is connected to the corresponding 'FbWr 0'. You can use
 the signal multiple times by connecting the 'FbRd 0' 'sig' port to multiple
 inputs.
 
-The delay is always 3.14ms, regardless of the sampling rate the synthesizer
-is running at.
+The delay is set by the 'FbRd' node. By default it is 3.14ms, regardless
+of the sampling rate the synthesizer is running at.
 "#;
 }
 
@@ -63,8 +84,11 @@ impl DspNode for FbWr {
 
         let inp = inp::FbWr::inp(inputs);
 
-        for frame in 0..ctx.nframes() {
-            ectx.feedback_delay_buffers[self.fb_index as usize].write(inp.read(frame));
+        // The buffers are allocated on demand, see NodeEngineBuilder::feedback_delays:
+        if let Some(buf) = ectx.feedback_delay_buffers.get_mut(self.fb_index as usize) {
+            for frame in 0..ctx.nframes() {
+                buf.write(frame, inp.read(frame));
+            }
         }
 
         ctx_vals[0].set(inp.read(ctx.nframes() - 1));
@@ -75,14 +99,26 @@ impl DspNode for FbWr {
 #[derive(Debug, Clone)]
 pub struct FbRd {
     fb_index: u8,
+    srate_ms: f32,
+    fixed_delay: f32,
 }
 
 impl FbRd {
     pub fn new(nid: &NodeId) -> Self {
-        Self { fb_index: nid.instance() as u8 }
+        Self {
+            fb_index: nid.instance() as u8,
+            srate_ms: 44.1,
+            fixed_delay: fixed_delay_samples(44100.0),
+        }
     }
     pub const atv: &'static str = "FbRd atv\nAttenuate or invert input.\n\
          Use this to adjust the feedback amount.\nRange: (-1..1)\n";
+    pub const time: &'static str = "FbRd time\nThe delay time in milliseconds, \
+         used if 'mode' is set to 'Time'. It can't be shorter than the current \
+         audio block, times below that give the shortest possible delay.\n\
+         Range: (0..1)\n";
+    pub const mode: &'static str = "FbRd mode\n'Fixed' is the fixed delay of 3.14ms, \
+         'Time' uses the 'time' parameter.\n";
     pub const sig: &'static str = "FbRd sig\nFeedback signal output.\nRange: (-1..1)\n";
 
     pub const DESC: &'static str = "Feedback Delay Reader\n\n\
@@ -90,7 +126,7 @@ HexoSynth does not allow direct feedback cycles in it's graph.\n\
 To make feedback possible anyways the 'FbWr' and 'FbRd' nodes are provided.\n\
 This node allows you to tap into the corresponsing 'FbWr' signal delay \
 for feedback.\n\
-The delay is 3.14ms.";
+The delay is 3.14ms or set by the 'time' parameter.";
     pub const HELP: &'static str = r#"Feedback Delay Reader
 
 HexoSynth does not allow direct feedback cycles in it's graph.
@@ -103,8 +139,13 @@ That means 'FbRd 0' is connected to the corresponding 'FbWr 0'. You can use
 the signal multiple times by connecting the 'FbRd 0' 'sig' port to multiple
 inputs.
 
-The delay is always 3.14ms, regardless of the sampling rate the synthesizer
-is running at.
+In the 'Fixed' mode the delay is always 3.14ms, regardless of the sampling
+rate the synthesizer is running at. In the 'Time' mode the 'time' parameter
+sets the delay, up to 20ms (a bit less at sample rates above 48kHz,
+where the longest delay shrinks with the rate). The shortest delay is the length of one
+audio block, so you can build tight feedback loops like a Karplus-Strong
+string. Fractional delays are interpolated, so the 'time' can be
+modulated smoothly.
 
 The 'atv' parameter is a convenience parameter to allow attenuating or
 even inverting the signal.
@@ -116,7 +157,10 @@ impl DspNode for FbRd {
         1
     }
 
-    fn set_sample_rate(&mut self, _srate: f32) {}
+    fn set_sample_rate(&mut self, srate: f32) {
+        self.srate_ms = srate / 1000.0;
+        self.fixed_delay = fixed_delay_samples(srate);
+    }
     fn reset(&mut self) {}
 
     #[inline]
@@ -125,19 +169,44 @@ impl DspNode for FbRd {
         ctx: &mut T,
         ectx: &mut NodeExecContext,
         _nctx: &NodeContext,
-        _atoms: &[SAtom],
+        atoms: &[SAtom],
         inputs: &[ProcBuf],
         outputs: &mut [ProcBuf],
         ctx_vals: LedPhaseVals,
     ) {
-        use crate::dsp::{denorm, inp, out};
+        use crate::dsp::{at, denorm, inp, out};
 
         let atv = inp::FbRd::atv(inputs);
+        let time = inp::FbRd::time(inputs);
+        let mode = at::FbRd::mode(atoms);
         let sig = out::FbRd::sig(outputs);
 
+        let buf = if let Some(buf) = ectx.feedback_delay_buffers.get(self.fb_index as usize) {
+            buf
+        } else {
+            for frame in 0..ctx.nframes() {
+                sig.write(frame, 0.0);
+            }
+            ctx_vals[0].set(0.0);
+            return;
+        };
+
+        // The FbWr might be executed after this node, so the delay
+        // has to cover at least the current block. The fixed delay is
+        // left as it always was, so that old patches sound the same:
+        let min_delay = ctx.nframes() as f32;
+        let max_delay = FeedbackBuffer::max_delay() as f32;
+        let fixed = mode.i() == 0;
+
         let mut last_val = 0.0;
         for frame in 0..ctx.nframes() {
-            last_val = ectx.feedback_delay_buffers[self.fb_index as usize].read();
+            let delay = if fixed {
+                self.fixed_delay
+            } else {
+                (denorm::FbRd::time(time, frame) * self.srate_ms).clamp(min_delay, max_delay)
+            };
+
+            last_val = buf.read(frame, delay);
             last_val *= denorm::FbRd::atv(atv, frame);
             sig.write(frame, last_val);
         }
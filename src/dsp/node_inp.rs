@@ -0,0 +1,83 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{
+    at, denorm, inp, out_buf, DspNode, LedPhaseVals, NodeContext, NodeId, ProcBuf, SAtom,
+};
+use crate::nodes::{NodeAudioContext, NodeExecContext};
+
+#[macro_export]
+macro_rules! fa_inp_chans {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let pair = $v.round() as usize;
+        write!($formatter, "{}-{}", pair * 2 + 1, pair * 2 + 2)
+    }};
+}
+
+/// The (stereo) input port of the plugin
+#[derive(Debug, Clone)]
+pub struct Inp {}
+
+impl Inp {
+    pub fn new(_nid: &NodeId) -> Self {
+        Self {}
+    }
+
+    pub const gain: &'static str =
+        "Inp gain\nThe gain of the input signals, applied to both channels. \
+        Please note that this is a linear control, to prevent inaccuracies for 1.0. \
+        \nRange: (0..2)";
+    pub const chans: &'static str =
+        "Inp chans\nThe pair of audio input channels that is read by this node.";
+    pub const sig1: &'static str = "Inp sig1\nAudio input channel 1 (left)\nRange: (-1..1)";
+    pub const sig2: &'static str = "Inp sig2\nAudio input channel 2 (right)\nRange: (-1..1)";
+
+    pub const DESC: &'static str = "Audio Input Port\n\n\
+        This input port node allows you to receive audio signals \
+        from audio devices or tracks in your DAW.";
+    pub const HELP: &'static str = r#"Audio Input Port
+
+This input port node allows you to receive audio signals from audio devices
+or tracks in your DAW. For instance you can process a guitar or vocals
+with the effects in your patch.
+
+With the 'chans' setting you select which pair of input channels is
+read. Channels that are not provided by the audio device are silent.
+"#;
+}
+
+impl DspNode for Inp {
+    fn outputs() -> usize {
+        2
+    }
+
+    fn set_sample_rate(&mut self, _srate: f32) {}
+    fn reset(&mut self) {}
+
+    #[inline]
+    fn process<T: NodeAudioContext>(
+        &mut self,
+        ctx: &mut T,
+        _ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        let gain = inp::Inp::gain(inputs);
+        let chan = (at::Inp::chans(atoms).i().max(0) as usize) * 2;
+
+        let mut sig1 = out_buf::Inp::sig1(outputs);
+        let mut sig2 = out_buf::Inp::sig2(outputs);
+
+        for frame in 0..ctx.nframes() {
+            let gain = denorm::Inp::gain(gain, frame);
+            sig1.write(frame, gain * ctx.input(chan, frame));
+            sig2.write(frame, gain * ctx.input(chan + 1, frame));
+        }
+
+        ctx_vals[0].set(sig1.read(ctx.nframes() - 1));
+    }
+}
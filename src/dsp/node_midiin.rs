@@ -0,0 +1,223 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{DspNode, LedPhaseVals, NodeContext, NodeId, ProcBuf, SAtom};
+use crate::nodes::{MidiEventKind, NodeAudioContext, NodeExecContext};
+
+#[macro_export]
+macro_rules! fa_midiin_chan {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let chan = $v.round() as i64;
+        if chan <= 0 {
+            write!($formatter, "Omni")
+        } else {
+            write!($formatter, "Ch {}", chan)
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! fa_midiin_cc {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        write!($formatter, "CC {}", $v.round() as i64)
+    }};
+}
+
+const MAX_HELD_NOTES: usize = 16;
+
+/// Converts MIDI events of the audio driver to control signals
+#[derive(Debug, Clone)]
+pub struct MidiIn {
+    held: [u8; MAX_HELD_NOTES],
+    held_cnt: usize,
+    note: u8,
+    gate: bool,
+    vel: f32,
+    bend: f32,
+    cc: f32,
+    at: f32,
+}
+
+impl MidiIn {
+    pub fn new(_nid: &NodeId) -> Self {
+        Self {
+            held: [0; MAX_HELD_NOTES],
+            held_cnt: 0,
+            note: 69,
+            gate: false,
+            vel: 0.0,
+            bend: 0.0,
+            cc: 0.0,
+            at: 0.0,
+        }
+    }
+    pub const det: &'static str = "MidiIn det\nDetune the 'freq' output in semitones and cents.\n\
+        Range: (-1..1)\n";
+    pub const chan: &'static str =
+        "MidiIn chan\nThe MIDI channel to receive events from. 'Omni' receives \
+        the events of all channels.";
+    pub const cc: &'static str =
+        "MidiIn cc\nThe number of the control change that is sent to the 'ccv' output.";
+    pub const freq: &'static str =
+        "MidiIn freq\nThe pitch of the most recently pressed note that is still held.\n\
+        Connect this to the 'freq' input of an oscillator.\nRange: (-1..1)\n";
+    pub const gate: &'static str =
+        "MidiIn gate\nIs high as long as any note is held. A new note while \
+        another one is still held sends a short low gate to retrigger envelopes.\n\
+        Range: (0..1)\n";
+    pub const vel: &'static str =
+        "MidiIn vel\nThe velocity of the most recent note on event.\nRange: (0..1)\n";
+    pub const bend: &'static str = "MidiIn bend\nThe pitch bend wheel.\nRange: (-1..1)\n";
+    pub const ccv: &'static str =
+        "MidiIn ccv\nThe value of the control change selected with the 'cc' setting.\n\
+        Range: (0..1)\n";
+    pub const after: &'static str =
+        "MidiIn after\nThe aftertouch (channel pressure or the key pressure of the current note).\n\
+        Range: (0..1)\n";
+    pub const DESC: &'static str = r#"MIDI Input
+
+Converts the MIDI events that are received from the audio driver (for instance from a keyboard or your DAW) into pitch, gate, velocity and controller signals.
+"#;
+    pub const HELP: &'static str = r#"MidiIn - MIDI Input
+
+This node receives the MIDI events of the audio driver and turns them
+into control signals. The 'freq' output provides the pitch of the most
+recently pressed note, in the same range as the 'freq' inputs of the
+oscillators. If a note is released while other notes are still held,
+the pitch returns to the previously pressed note.
+
+The 'gate' output is high as long as any note is held. 'vel' provides
+the velocity of the note, 'bend' the pitch bend wheel and 'after' the
+aftertouch.
+
+With the 'cc' setting you select which control change is sent to the
+'ccv' output. With 'chan' you can restrict the node to one MIDI channel.
+
+This node is monophonic. For playing chords see the voice region and
+the 'Voice' node.
+"#;
+
+    fn push_note(&mut self, note: u8) {
+        self.remove_note(note);
+
+        if self.held_cnt == MAX_HELD_NOTES {
+            self.held.copy_within(1.., 0);
+            self.held_cnt -= 1;
+        }
+
+        self.held[self.held_cnt] = note;
+        self.held_cnt += 1;
+    }
+
+    fn remove_note(&mut self, note: u8) {
+        if let Some(pos) = self.held[0..self.held_cnt].iter().position(|n| *n == note) {
+            self.held.copy_within((pos + 1)..self.held_cnt, pos);
+            self.held_cnt -= 1;
+        }
+    }
+}
+
+impl DspNode for MidiIn {
+    fn outputs() -> usize {
+        6
+    }
+
+    fn set_sample_rate(&mut self, _srate: f32) {}
+    fn reset(&mut self) {
+        self.held_cnt = 0;
+        self.note = 69;
+        self.gate = false;
+        self.vel = 0.0;
+        self.bend = 0.0;
+        self.cc = 0.0;
+        self.at = 0.0;
+    }
+
+    #[inline]
+    fn process<T: NodeAudioContext>(
+        &mut self,
+        ctx: &mut T,
+        _ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        use crate::dsp::{at, inp, out_buf};
+
+        let det = inp::MidiIn::det(inputs);
+        let chan = at::MidiIn::chan(atoms).i();
+        let cc_num = at::MidiIn::cc(atoms).i();
+
+        let mut freq = out_buf::MidiIn::freq(outputs);
+        let mut gate = out_buf::MidiIn::gate(outputs);
+        let mut vel = out_buf::MidiIn::vel(outputs);
+        let mut bend = out_buf::MidiIn::bend(outputs);
+        let mut ccv = out_buf::MidiIn::ccv(outputs);
+        let mut after = out_buf::MidiIn::after(outputs);
+
+        let events = ctx.events();
+        let mut ev_idx = 0;
+
+        for frame in 0..ctx.nframes() {
+            let mut retrigger = false;
+
+            while ev_idx < events.len() && events[ev_idx].frame <= frame {
+                let ev = events[ev_idx];
+                ev_idx += 1;
+
+                if chan > 0 && (ev.channel as i64) != (chan - 1) {
+                    continue;
+                }
+
+                match ev.kind {
+                    MidiEventKind::NoteOn { note, vel } => {
+                        self.push_note(note);
+                        retrigger = self.gate;
+                        self.note = note;
+                        self.gate = true;
+                        self.vel = vel;
+                    }
+                    MidiEventKind::NoteOff { note } => {
+                        self.remove_note(note);
+
+                        if self.held_cnt > 0 {
+                            self.note = self.held[self.held_cnt - 1];
+                        } else {
+                            self.gate = false;
+                        }
+                    }
+                    MidiEventKind::PitchBend { value } => {
+                        self.bend = value;
+                    }
+                    MidiEventKind::CC { cc, value } => {
+                        if (cc as i64) == cc_num {
+                            self.cc = value;
+                        }
+                    }
+                    MidiEventKind::Aftertouch { value } => {
+                        self.at = value;
+                    }
+                    MidiEventKind::PolyAftertouch { note, value } => {
+                        if note == self.note {
+                            self.at = value;
+                        }
+                    }
+                }
+            }
+
+            let gate_v = if self.gate && !retrigger { 1.0 } else { 0.0 };
+
+            freq.write(frame, (self.note as f32 - 69.0) / 120.0 + det.read(frame));
+            gate.write(frame, gate_v);
+            vel.write(frame, self.vel);
+            bend.write(frame, self.bend);
+            ccv.write(frame, self.cc);
+            after.write(frame, self.at);
+        }
+
+        ctx_vals[0].set(if self.gate { 1.0 } else { 0.0 });
+    }
+}
@@ -4,10 +4,12 @@
 
 use synfx_dsp::{DattorroReverb, DattorroReverbParams, crossfade};
 use crate::dsp::{denorm, DspNode, LedPhaseVals, NodeContext, NodeId, ProcBuf, SAtom};
-use crate::nodes::{NodeAudioContext, NodeExecContext};
+use crate::nodes::{NodeAudioContext, NodeExecContext, Transport};
 
 pub struct DatParams {
     frame: usize,
+    /// The transport, if the pre-delay is tempo synced.
+    sync: Option<Transport>,
     predly: ProcBuf,
     size: ProcBuf,
     dcy: ProcBuf,
@@ -31,7 +33,11 @@ impl DatParams {
 
 impl DattorroReverbParams for DatParams {
     fn pre_delay_time_ms(&self) -> f64 {
-        denorm::PVerb::predly(&self.predly, self.frame) as f64
+        if let Some(transport) = &self.sync {
+            transport.note_div_ms(self.predly.read(self.frame)).min(5000.0) as f64
+        } else {
+            denorm::PVerb::predly(&self.predly, self.frame) as f64
+        }
     }
     fn time_scale(&self) -> f64 {
         denorm::PVerb::size(&self.size, self.frame) as f64
@@ -124,6 +130,10 @@ impl PVerb {
         "PVerb rhpf\nReverb tank high-pass filter cutoff frequency.\nRange: (0..1)";
     pub const mix: &'static str =
         "PVerb mix\nDry/Wet mix between the input and the diffused output.\nRange: (0..1)";
+    pub const sync: &'static str =
+        "PVerb sync\nIf set to 'Tempo', the 'predly' parameter selects a note \
+        length (1/64 to 1/1, dotted or triplets) relative to the tempo of the \
+        transport.";
     pub const DESC: &'static str = r#"Plate Reverb
 
 This is a simple but yet powerful small plate reverb based on the design by Jon Dattorro. It should suit your needs from small rooms up to large athmospheric sound scapes.
@@ -142,6 +152,9 @@ the bandwidth of the reverbs.
 
 Internal modulation keeps the sound alive and spreads it even more.
 
+With the 'sync' setting the pre-delay follows the tempo of the transport
+and is selected as note length.
+
 Structure of the reverb is:
 
     Left       Right
@@ -189,14 +202,14 @@ impl DspNode for PVerb {
     fn process<T: NodeAudioContext>(
         &mut self,
         ctx: &mut T,
-        _ectx: &mut NodeExecContext,
+        ectx: &mut NodeExecContext,
         nctx: &NodeContext,
-        _atoms: &[SAtom],
+        atoms: &[SAtom],
         inputs: &[ProcBuf],
         outputs: &mut [ProcBuf],
         ctx_vals: LedPhaseVals,
     ) {
-        use crate::dsp::{inp, out_idx};
+        use crate::dsp::{at, inp, out_idx};
 
         let mut in_l = inp::PVerb::in_l(inputs);
         let mut in_r = inp::PVerb::in_r(inputs);
@@ -209,8 +222,11 @@ impl DspNode for PVerb {
             }
         }
 
+        let sync = at::PVerb::sync(atoms).i() != 0;
+
         let mut params = DatParams {
             frame: 0,
+            sync: if sync { Some(ectx.transport) } else { None },
             predly: *inp::PVerb::predly(inputs),
             size: *inp::PVerb::size(inputs),
             dcy: *inp::PVerb::dcy(inputs),
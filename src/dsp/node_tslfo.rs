@@ -31,6 +31,10 @@ impl TsLFO {
         be (almost) a (reversed) saw tooth. Node: A perfect sawtooth can not be \
         achieved with this oscillator, as there will always be a minimal \
         rise/fall time.\nRange: (0..1)\n";
+    pub const sync: &'static str =
+        "TsLFO sync\nIf set to 'Tempo', the 'time' parameter selects a note \
+        length (1/64 to 1/1, dotted or triplets) relative to the tempo of the \
+        transport as period time of the LFO.";
     pub const sig: &'static str = "TsLFO sig\nThe LFO output.\nRange: (0..1)";
     pub const DESC: &'static str = r#"TriSaw LFO
 
@@ -43,6 +47,10 @@ triangular to sawtooth waveforms using the 'rev' parameter.
 
 Using the 'trig' input you can reset the LFO phase, which allows to use it
 kind of like an envelope.
+
+With the 'sync' setting the period 'time' follows the tempo of the
+transport and is selected as note length. Reset the LFO with a 'beat'
+trigger of the 'Clock' node to keep it in phase with the song.
 "#;
 }
 
@@ -64,18 +72,19 @@ impl DspNode for TsLFO {
     fn process<T: NodeAudioContext>(
         &mut self,
         ctx: &mut T,
-        _ectx: &mut NodeExecContext,
+        ectx: &mut NodeExecContext,
         _nctx: &NodeContext,
-        _atoms: &[SAtom],
+        atoms: &[SAtom],
         inputs: &[ProcBuf],
         outputs: &mut [ProcBuf],
         ctx_vals: LedPhaseVals,
     ) {
-        use crate::dsp::{denorm, inp, out};
+        use crate::dsp::{at, denorm, inp, out};
 
         let time = inp::TsLFO::time(inputs);
         let trig = inp::TsLFO::trig(inputs);
         let rev = inp::TsLFO::rev(inputs);
+        let sync = at::TsLFO::sync(atoms).i() != 0;
         let out = out::TsLFO::sig(outputs);
 
         let lfo = &mut *self.lfo;
@@ -85,7 +94,12 @@ impl DspNode for TsLFO {
                 lfo.reset();
             }
 
-            let time_ms = denorm::TsLFO::time(time, frame).clamp(0.1, 300000.0);
+            let time_ms = if sync {
+                ectx.transport.note_div_ms(time.read(frame))
+            } else {
+                denorm::TsLFO::time(time, frame)
+            };
+            let time_ms = time_ms.clamp(0.1, 300000.0);
 
             lfo.set((1000.0 / time_ms) as f64, denorm::TsLFO::rev(rev, frame) as f64);
 
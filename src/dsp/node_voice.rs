@@ -0,0 +1,96 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{DspNode, LedPhaseVals, NodeContext, NodeId, ProcBuf, SAtom};
+use crate::nodes::{NodeAudioContext, NodeExecContext};
+
+/// Provides the note of the currently executed voice
+#[derive(Debug, Clone)]
+pub struct Voice {
+    last_serial: u64,
+}
+
+impl Voice {
+    pub fn new(_nid: &NodeId) -> Self {
+        Self { last_serial: 0 }
+    }
+    pub const det: &'static str = "Voice det\nDetune the 'freq' output in semitones and cents.\n\
+        Range: (-1..1)\n";
+    pub const freq: &'static str =
+        "Voice freq\nThe pitch of the note that is played by this voice.\n\
+        Connect this to the 'freq' input of an oscillator.\nRange: (-1..1)\n";
+    pub const gate: &'static str =
+        "Voice gate\nIs high as long as the note of this voice is held.\n\
+        If the voice is stolen by a new note, the gate goes low for one sample \
+        to retrigger envelopes.\nRange: (0..1)\n";
+    pub const vel: &'static str =
+        "Voice vel\nThe velocity of the note of this voice.\nRange: (0..1)\n";
+    pub const DESC: &'static str = r#"Voice Note Input
+
+Outputs the pitch, gate and velocity of the voice that is currently executed. Use this inside the voice region of a polyphonic patch.
+"#;
+    pub const HELP: &'static str = r#"Voice - Voice Note Input
+
+This node provides the note information for one voice of a polyphonic
+patch. All nodes in the voice region are executed once for each voice,
+and this node outputs the pitch ('freq'), the gate and the velocity of the
+note that was assigned to that voice by the voice allocator.
+
+The outputs of the voice region that are connected to nodes outside
+of the region are summed up over all voices. This way you can put your
+shared effects after the voices.
+
+Outside of a voice region this node outputs the state of the first voice.
+
+The 'det' parameter allows to detune the 'freq' output.
+"#;
+}
+
+impl DspNode for Voice {
+    fn outputs() -> usize {
+        3
+    }
+
+    fn set_sample_rate(&mut self, _srate: f32) {}
+    fn reset(&mut self) {
+        self.last_serial = 0;
+    }
+
+    #[inline]
+    fn process<T: NodeAudioContext>(
+        &mut self,
+        ctx: &mut T,
+        ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        _atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        use crate::dsp::{inp, out_buf};
+
+        let det = inp::Voice::det(inputs);
+        let mut freq = out_buf::Voice::freq(outputs);
+        let mut gate = out_buf::Voice::gate(outputs);
+        let mut vel = out_buf::Voice::vel(outputs);
+
+        let voice = *ectx.voice_alloc.voice(ectx.cur_voice);
+
+        // A new note on a voice that is still gated needs a short low
+        // gate, so that envelopes are retriggered:
+        let retrigger = voice.serial != self.last_serial;
+        self.last_serial = voice.serial;
+
+        let pitch = (voice.note as f32 - 69.0) / 120.0;
+        let gate_v = if voice.gate { 1.0 } else { 0.0 };
+
+        for frame in 0..ctx.nframes() {
+            freq.write(frame, pitch + det.read(frame));
+            gate.write(frame, if retrigger && frame == 0 { 0.0 } else { gate_v });
+            vel.write(frame, voice.vel);
+        }
+
+        ctx_vals[0].set(gate_v);
+    }
+}
@@ -0,0 +1,603 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+/*! A DSP graph without the hexagonal [crate::Matrix] grid.
+
+The [crate::Graph] is a thin layer on top of the [crate::NodeConfigurator] for
+applications that don't have a hex UI. It keeps a list of nodes and the edges
+between their outputs and inputs, and takes care of creating the nodes,
+ordering them and uploading the [crate::nodes::NodeProg] on [crate::Graph::sync].
+Like with the [crate::Matrix], the ordering of `BusSend` and `BusRecv` nodes
+is derived from their `bus` and `name` settings.
+
+```
+ use hexodsp::*;
+
+ let (node_conf, mut node_exec) = new_node_engine();
+ let mut graph = Graph::new(node_conf);
+
+ graph.connect(NodeId::Sin(0), "sig", NodeId::Amp(0), "inp").unwrap();
+ graph.connect(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1").unwrap();
+ graph.set_denorm(NodeId::Amp(0), "att", 0.5).unwrap();
+ assert!(graph.sync().unwrap());
+
+ // Nothing changed in the topology, so no new program is uploaded:
+ assert!(!graph.sync().unwrap());
+
+ let (out_l, _out_r) = node_exec.test_run(0.1, false);
+```
+*/
+
+use crate::dsp::{NodeId, ParamId, SAtom};
+use crate::matrix_repr::{deserialize_atom, serialize_atom};
+use crate::nodes::{NodeConfigurator, NodeGraphOrdering};
+use serde_json::{json, Value};
+
+/// An edge from an output of a node to an input of another node in the [Graph].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GraphEdge {
+    pub from: NodeId,
+    pub from_out: u8,
+    pub to: NodeId,
+    pub to_input: u8,
+}
+
+/// Error type for the [crate::Graph].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    UnknownNode(String),
+    UnknownOutput(NodeId, String),
+    UnknownInput(NodeId, String),
+    /// The node has no such parameter, or it is an atom
+    /// and was set with [Graph::set_denorm].
+    UnknownParam(NodeId, String),
+    /// The input already has an edge from the output `from`.
+    InputConnected {
+        to: (NodeId, String),
+        from: (NodeId, String),
+    },
+    /// The edge or the bus ordering would create a cycle.
+    CycleDetected,
+    /// There are more bus names than free buses,
+    /// see [NodeConfigurator::assign_buses].
+    TooManyBuses,
+    /// A node of the voice region reads from a node outside of it,
+    /// that depends on the region. See [NodeConfigurator::check_voice_region].
+    VoiceRegionDependency,
+    /// All instances of the node are already in the graph,
+    /// see [Graph::new_node].
+    NoFreeInstance(NodeId),
+    /// The loaded node has an instance outside of `0..=255`.
+    InvalidInstance(String, i64),
+    Deserialization(String),
+}
+
+impl From<serde_json::Error> for GraphError {
+    fn from(err: serde_json::Error) -> GraphError {
+        GraphError::Deserialization(format!("{}", err))
+    }
+}
+
+/// The state of the graph that determines the [crate::nodes::NodeProg].
+/// It's compared with the last synced state to find out whether
+/// a new program needs to be uploaded.
+#[derive(Debug, Clone, PartialEq)]
+struct GraphTopology {
+    nodes: Vec<NodeId>,
+    edges: Vec<GraphEdge>,
+    /// The `bus` settings of the `BusSend` and `BusRecv` nodes.
+    buses: Vec<(NodeId, i64)>,
+}
+
+impl GraphTopology {
+    fn calculate_order(&self, ordering: &mut NodeGraphOrdering) -> Option<Vec<NodeId>> {
+        ordering.clear();
+
+        for node_id in self.nodes.iter() {
+            ordering.add_node(*node_id);
+        }
+
+        for edge in self.edges.iter() {
+            ordering.add_edge(edge.from, edge.to);
+        }
+
+        for (send_id, send_bus) in self.buses.iter() {
+            if let NodeId::BusSend(_) = send_id {
+                for (recv_id, recv_bus) in self.buses.iter() {
+                    if let NodeId::BusRecv(_) = recv_id {
+                        if send_bus == recv_bus {
+                            ordering.add_edge(*send_id, *recv_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut order = vec![];
+        if ordering.calculate_order(&mut order) {
+            Some(order)
+        } else {
+            None
+        }
+    }
+}
+
+/// A DSP graph of nodes and edges, that is synchronized with the
+/// [crate::nodes::NodeExecutor] without the hexagonal [crate::Matrix].
+///
+/// Nodes are added with [Graph::add_node] or implicitly by [Graph::connect].
+/// Changes to the nodes and edges only take effect on [Graph::sync],
+/// parameter changes are sent to the DSP thread right away.
+pub struct Graph {
+    config: NodeConfigurator,
+    nodes: Vec<NodeId>,
+    edges: Vec<GraphEdge>,
+    ordering: NodeGraphOrdering,
+    /// The topology of the last uploaded program.
+    synced: Option<GraphTopology>,
+    /// Set if a modulation amount was added or removed,
+    /// which requires a new program.
+    needs_rebuild: bool,
+}
+
+impl Graph {
+    pub fn new(config: NodeConfigurator) -> Self {
+        Self {
+            config,
+            nodes: vec![],
+            edges: vec![],
+            ordering: NodeGraphOrdering::new(),
+            synced: None,
+            needs_rebuild: false,
+        }
+    }
+
+    pub fn config(&self) -> &NodeConfigurator {
+        &self.config
+    }
+
+    /// Gives access to the [NodeConfigurator], for instance to set the
+    /// [crate::nodes::NodeState] of nodes or to monitor them.
+    /// Don't create a [crate::nodes::NodeProg] here, [Graph::sync]
+    /// will overwrite it.
+    pub fn config_mut(&mut self) -> &mut NodeConfigurator {
+        &mut self.config
+    }
+
+    /// The nodes in the order they were added.
+    pub fn nodes(&self) -> &[NodeId] {
+        &self.nodes[..]
+    }
+
+    pub fn edges(&self) -> &[GraphEdge] {
+        &self.edges[..]
+    }
+
+    pub fn contains(&self, node_id: NodeId) -> bool {
+        self.nodes.contains(&node_id)
+    }
+
+    /// Adds the node to the graph, if it's not already in there.
+    /// [NodeId::Nop] is ignored.
+    pub fn add_node(&mut self, node_id: NodeId) {
+        if node_id != NodeId::Nop && !self.contains(node_id) {
+            self.nodes.push(node_id);
+        }
+    }
+
+    /// Adds the first instance of `node_id` (starting with the instance
+    /// of `node_id`) that is not in the graph yet and returns it.
+    /// Returns [GraphError::NoFreeInstance] if all instances
+    /// up to 255 are in the graph.
+    pub fn new_node(&mut self, node_id: NodeId) -> Result<NodeId, GraphError> {
+        for instance in node_id.instance()..=(u8::MAX as usize) {
+            let node_id = node_id.to_instance(instance);
+            if !self.contains(node_id) {
+                self.add_node(node_id);
+                return Ok(node_id);
+            }
+        }
+
+        Err(GraphError::NoFreeInstance(node_id))
+    }
+
+    /// Removes the node and all edges from and to it from the graph.
+    /// Returns `false` if the node was not in the graph.
+    pub fn remove_node(&mut self, node_id: NodeId) -> bool {
+        if !self.contains(node_id) {
+            return false;
+        }
+
+        self.nodes.retain(|n| *n != node_id);
+        self.edges.retain(|e| e.from != node_id && e.to != node_id);
+        true
+    }
+
+    fn edge_for(
+        &self,
+        from: NodeId,
+        out: &str,
+        to: NodeId,
+        inp: &str,
+    ) -> Result<GraphEdge, GraphError> {
+        let from_out =
+            from.out(out).ok_or_else(|| GraphError::UnknownOutput(from, out.to_string()))?;
+        let to_input = to.inp(inp).ok_or_else(|| GraphError::UnknownInput(to, inp.to_string()))?;
+
+        Ok(GraphEdge { from, from_out, to, to_input })
+    }
+
+    /// Returns the output that is connected to the input `inp` of the node `to`.
+    pub fn input_source(&self, to: NodeId, inp: &str) -> Option<(NodeId, u8)> {
+        let to_input = to.inp(inp)?;
+
+        self.edges
+            .iter()
+            .find(|e| e.to == to && e.to_input == to_input)
+            .map(|e| (e.from, e.from_out))
+    }
+
+    /// Connects the output `out` of the node `from` with the input `inp`
+    /// of the node `to`. Both nodes are added to the graph if necessary.
+    ///
+    /// Returns an error if the input is already connected or if the
+    /// edge would create a cycle. Use a `FbWr` and `FbRd` pair for
+    /// feedback in the graph.
+    pub fn connect(
+        &mut self,
+        from: NodeId,
+        out: &str,
+        to: NodeId,
+        inp: &str,
+    ) -> Result<(), GraphError> {
+        let edge = self.edge_for(from, out, to, inp)?;
+
+        if let Some((src, src_out)) = self.input_source(to, inp) {
+            if src == from && src_out == edge.from_out {
+                return Ok(());
+            }
+
+            return Err(GraphError::InputConnected {
+                to: (to, inp.to_string()),
+                from: (src, src.out_name_by_idx(src_out).unwrap_or("?").to_string()),
+            });
+        }
+
+        let mut nodes = self.nodes.clone();
+        for node_id in [from, to].iter() {
+            if !nodes.contains(node_id) {
+                nodes.push(*node_id);
+            }
+        }
+        let mut edges = self.edges.clone();
+        edges.push(edge);
+
+        let buses = self.config.assign_buses(&nodes).ok_or(GraphError::TooManyBuses)?;
+        let topology = GraphTopology { nodes, edges, buses };
+        if topology.calculate_order(&mut self.ordering).is_none() {
+            return Err(GraphError::CycleDetected);
+        }
+
+        self.add_node(from);
+        self.add_node(to);
+        self.edges.push(edge);
+
+        Ok(())
+    }
+
+    /// Removes the edge from the output `out` of the node `from` to the
+    /// input `inp` of the node `to`. Returns `false` if there was no such edge.
+    pub fn disconnect(
+        &mut self,
+        from: NodeId,
+        out: &str,
+        to: NodeId,
+        inp: &str,
+    ) -> Result<bool, GraphError> {
+        let edge = self.edge_for(from, out, to, inp)?;
+
+        let len = self.edges.len();
+        self.edges.retain(|e| *e != edge);
+        Ok(self.edges.len() != len)
+    }
+
+    fn param_for(&self, node_id: NodeId, param: &str) -> Result<ParamId, GraphError> {
+        node_id.inp_param(param).ok_or_else(|| GraphError::UnknownParam(node_id, param.to_string()))
+    }
+
+    /// Sets the denormalized value of the input parameter `param` of the node.
+    pub fn set_denorm(
+        &mut self,
+        node_id: NodeId,
+        param: &str,
+        denorm: f32,
+    ) -> Result<(), GraphError> {
+        let pid = self.param_for(node_id, param)?;
+        if pid.is_atom() {
+            return Err(GraphError::UnknownParam(node_id, param.to_string()));
+        }
+
+        self.config.set_param(pid, SAtom::param(pid.norm(denorm)));
+        Ok(())
+    }
+
+    /// Sets the [SAtom] value of the atom or input parameter `param` of the node.
+    /// Input parameters take the normalized value.
+    pub fn set_atom(&mut self, node_id: NodeId, param: &str, at: SAtom) -> Result<(), GraphError> {
+        let pid = self.param_for(node_id, param)?;
+        self.config.set_param(pid, at);
+        Ok(())
+    }
+
+    /// Returns the denormalized value of the input parameter `param` of the node.
+    pub fn get_denorm(&self, node_id: NodeId, param: &str) -> Option<f32> {
+        let pid = node_id.inp_param(param)?;
+        if pid.is_atom() {
+            return None;
+        }
+
+        Some(pid.denorm(self.config.get_param(&pid)?.f()))
+    }
+
+    /// Returns the [SAtom] value of the atom or input parameter `param` of the
+    /// node. Input parameters are returned normalized.
+    pub fn get_atom(&self, node_id: NodeId, param: &str) -> Option<SAtom> {
+        self.config.get_param(&node_id.inp_param(param)?)
+    }
+
+    /// Sets the modulation amount of the input parameter `param` of the node.
+    /// Adding or removing the modulation amount only takes effect
+    /// on the next [Graph::sync].
+    pub fn set_modamt(
+        &mut self,
+        node_id: NodeId,
+        param: &str,
+        modamt: Option<f32>,
+    ) -> Result<(), GraphError> {
+        let pid = self.param_for(node_id, param)?;
+        if self.config.set_param_modamt(pid, modamt) {
+            self.needs_rebuild = true;
+        }
+        Ok(())
+    }
+
+    pub fn get_modamt(&self, node_id: NodeId, param: &str) -> Option<f32> {
+        self.config.get_param_modamt(&node_id.inp_param(param)?)
+    }
+
+    fn topology(&self) -> Result<GraphTopology, GraphError> {
+        let buses = self.config.assign_buses(&self.nodes).ok_or(GraphError::TooManyBuses)?;
+
+        Ok(GraphTopology { nodes: self.nodes.clone(), edges: self.edges.clone(), buses })
+    }
+
+    /// Returns the order the nodes are executed in.
+    pub fn order(&mut self) -> Result<Vec<NodeId>, GraphError> {
+        self.topology()?.calculate_order(&mut self.ordering).ok_or(GraphError::CycleDetected)
+    }
+
+    /// Returns `true` if the nodes, edges or bus settings changed since the
+    /// last [Graph::sync].
+    pub fn is_dirty(&self) -> bool {
+        self.needs_rebuild || self.synced.as_ref() != self.topology().ok().as_ref()
+    }
+
+    /// Synchronizes the graph with the DSP thread. The nodes that don't exist
+    /// in the [NodeConfigurator] yet are created and a new
+    /// [crate::nodes::NodeProg] is uploaded.
+    ///
+    /// If nothing changed since the last call, see [Graph::is_dirty],
+    /// nothing is uploaded and `false` is returned. Otherwise the whole
+    /// program is rebuilt from the nodes and edges, like with
+    /// [crate::Matrix::sync]. The existing node instances are reused,
+    /// so they keep their DSP state.
+    pub fn sync(&mut self) -> Result<bool, GraphError> {
+        let topology = self.topology()?;
+        if !self.needs_rebuild && self.synced.as_ref() == Some(&topology) {
+            return Ok(false);
+        }
+
+        let order =
+            topology.calculate_order(&mut self.ordering).ok_or(GraphError::CycleDetected)?;
+
+        for node_id in topology.nodes.iter() {
+            if self.config.unique_index_for(node_id).is_none() {
+                // The previous instances need to exist too:
+                for inst in 0..node_id.instance() {
+                    let hole_filler_node_id = node_id.to_instance(inst);
+
+                    if self.config.unique_index_for(&hole_filler_node_id).is_none() {
+                        self.config.create_node(hole_filler_node_id).expect("NodeInfo existent");
+                    }
+                }
+
+                self.config.create_node(*node_id).expect("NodeInfo existent");
+            }
+        }
+
+        let mut prog = self.config.rebuild_node_ports();
+
+        for node_id in order.iter() {
+            self.config.add_prog_node(&mut prog, node_id);
+        }
+
+        for edge in topology.edges.iter() {
+            self.config.set_prog_node_exec_connection(
+                &mut prog,
+                (edge.to, edge.to_input),
+                (edge.from, edge.from_out),
+            );
+        }
+
+        if !self.config.check_voice_region(&prog) {
+            return Err(GraphError::VoiceRegionDependency);
+        }
+
+        // The named buses are read from the `bus` setting by the backend:
+        self.config.set_buses(&topology.buses);
+        self.config.upload_prog(prog, true); // true => copy_old_out
+
+        self.synced = Some(topology);
+        self.needs_rebuild = false;
+
+        Ok(true)
+    }
+
+    /// Serializes the nodes, edges and the parameters of the nodes into
+    /// a JSON string, which can be loaded again with [Graph::load].
+    pub fn serialize(&self) -> String {
+        let (mut params, mut atoms) = self.config.dump_param_values();
+        params.retain(|(p, _, _)| self.contains(p.node_id()));
+        atoms.retain(|(p, _)| self.contains(p.node_id()));
+        params.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        atoms.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let nodes: Vec<Value> =
+            self.nodes.iter().map(|n| json!([n.name(), n.instance()])).collect();
+
+        let edges: Vec<Value> = self
+            .edges
+            .iter()
+            .map(|e| {
+                json!([
+                    e.from.name(),
+                    e.from.instance(),
+                    e.from.out_name_by_idx(e.from_out).unwrap_or("?"),
+                    e.to.name(),
+                    e.to.instance(),
+                    e.to.inp_name_by_idx(e.to_input).unwrap_or("?"),
+                ])
+            })
+            .collect();
+
+        let params: Vec<Value> = params
+            .iter()
+            .map(|(p, v, ma)| {
+                let mut param_v = vec![
+                    json!(p.node_id().name()),
+                    json!(p.node_id().instance()),
+                    json!(p.name()),
+                    json!(v),
+                ];
+                if let Some(ma) = ma {
+                    param_v.push(json!(ma));
+                }
+                Value::Array(param_v)
+            })
+            .collect();
+
+        let atoms: Vec<Value> = atoms
+            .iter()
+            .map(|(p, v)| {
+                json!([p.node_id().name(), p.node_id().instance(), p.name(), serialize_atom(v)])
+            })
+            .collect();
+
+        json!({
+            "VERSION": 1,
+            "nodes": nodes,
+            "edges": edges,
+            "params": params,
+            "atoms": atoms,
+        })
+        .to_string()
+    }
+
+    /// Replaces the nodes and edges with the ones from the JSON string written
+    /// by [Graph::serialize] and sets the parameters. Call [Graph::sync]
+    /// afterwards to upload the new graph.
+    ///
+    /// If an error is returned, the graph is left unchanged.
+    pub fn load(&mut self, s: &str) -> Result<(), GraphError> {
+        let v: Value = serde_json::from_str(s)?;
+
+        if v["VERSION"].as_i64().unwrap_or(0) > 1 {
+            return Err(GraphError::Deserialization("Unsupported version".to_string()));
+        }
+
+        let mut nodes = vec![];
+        for n in v["nodes"].as_array().unwrap_or(&vec![]).iter() {
+            let node_id = node_id_from_value(n, 0)?;
+            if !nodes.contains(&node_id) {
+                nodes.push(node_id);
+            }
+        }
+
+        let mut edges = vec![];
+        for e in v["edges"].as_array().unwrap_or(&vec![]).iter() {
+            let from = node_id_from_value(e, 0)?;
+            let to = node_id_from_value(e, 3)?;
+            let edge =
+                self.edge_for(from, e[2].as_str().unwrap_or(""), to, e[5].as_str().unwrap_or(""))?;
+
+            if let Some(dup) =
+                edges.iter().find(|d: &&GraphEdge| d.to == to && d.to_input == edge.to_input)
+            {
+                return Err(GraphError::InputConnected {
+                    to: (to, e[5].as_str().unwrap_or("").to_string()),
+                    from: (
+                        dup.from,
+                        dup.from.out_name_by_idx(dup.from_out).unwrap_or("?").to_string(),
+                    ),
+                });
+            }
+
+            for node_id in [from, to].iter() {
+                if !nodes.contains(node_id) {
+                    nodes.push(*node_id);
+                }
+            }
+            edges.push(edge);
+        }
+
+        let mut params = vec![];
+        for p in v["params"].as_array().unwrap_or(&vec![]).iter() {
+            let node_id = node_id_from_value(p, 0)?;
+            let name = p[2].as_str().unwrap_or("");
+            let pid = self.param_for(node_id, name)?;
+            params.push((
+                pid,
+                p[3].as_f64().unwrap_or(0.0) as f32,
+                p[4].as_f64().map(|v| v as f32),
+            ));
+        }
+
+        let mut atoms = vec![];
+        for a in v["atoms"].as_array().unwrap_or(&vec![]).iter() {
+            let node_id = node_id_from_value(a, 0)?;
+            let name = a[2].as_str().unwrap_or("");
+            let pid = self.param_for(node_id, name)?;
+            let at = deserialize_atom(&a[3])
+                .map_err(|e| GraphError::Deserialization(format!("{:?}", e)))?;
+            atoms.push((pid, at));
+        }
+
+        // The bus settings are not set yet, so only the edges are checked here:
+        let topology = GraphTopology { nodes: nodes.clone(), edges: edges.clone(), buses: vec![] };
+        if topology.calculate_order(&mut self.ordering).is_none() {
+            return Err(GraphError::CycleDetected);
+        }
+
+        self.nodes = nodes;
+        self.edges = edges;
+        self.config.load_dumped_param_values(&params[..], &atoms[..], true);
+        self.needs_rebuild = true;
+
+        Ok(())
+    }
+}
+
+fn node_id_from_value(v: &Value, idx: usize) -> Result<NodeId, GraphError> {
+    let name = v[idx].as_str().unwrap_or("???");
+    let node_id = NodeId::from_str(name);
+    if node_id == NodeId::Nop {
+        return Err(GraphError::UnknownNode(name.to_string()));
+    }
+
+    let instance = v[idx + 1].as_i64().unwrap_or(0);
+    if !(0..=255).contains(&instance) {
+        return Err(GraphError::InvalidInstance(name.to_string(), instance));
+    }
+
+    Ok(node_id.to_instance(instance as usize))
+}
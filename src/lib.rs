@@ -55,11 +55,16 @@ And following DSP nodes:
 | Ctrl    | Map         | Control signal mapper |
 | Ctrl    | CQnt        | Control signal pitch quantizer |
 | Ctrl    | Quant       | Pitch signal quantizer |
+| Ctrl    | MidiIn      | MIDI note, pitch bend, CC and aftertouch input |
+| Ctrl    | Voice       | Note input for the voices of a polyphonic voice region |
 | Mod     | TSeq        | Tracker/pattern sequencer |
+| Mod     | Clock       | Beat/bar triggers and phases in sync with the transport |
 | Mod     | Ad          | Attack-Decay envelope |
 | Mod     | TsLFO       | Tri/Saw waveform low frequency oscillator (LFO) |
 | Mod     | RndWk       | Random walker, a Sample & Hold noise generator |
+| IO Util | Inp         | Audio input port with selectable channel pair |
 | IO Util | FbWr / FbRd | Utility modules for feedback in patches |
+| IO Util | BusSend / BusRecv | Buses to route signals without delay across the patch |
 | IO Util | Scope       | Oscilloscope for up to 3 channels |
 
 ## API Examples
@@ -165,6 +170,40 @@ let (out_l, out_r) = node_exec.test_run(0.11, true);
 // samples now.
 ```
 
+### Grid-free Graph API
+
+Applications without a hexagonal grid UI can use the [crate::Graph], which
+connects nodes by their port names and takes care of creating the nodes and
+ordering them:
+
+```rust
+use hexodsp::*;
+
+let (node_conf, mut node_exec) = new_node_engine();
+let mut graph = Graph::new(node_conf);
+
+graph.connect(NodeId::Sin(0), "sig", NodeId::Amp(0), "inp").unwrap();
+graph.connect(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1").unwrap();
+graph.set_denorm(NodeId::Amp(0), "att", 0.5).unwrap();
+graph.sync().unwrap();
+
+let (out_l, out_r) = node_exec.test_run(0.11, true);
+```
+
+The nodes and edges of a [crate::Graph] can also be written in a small
+text language with [crate::PatchText]:
+
+```rust
+use hexodsp::*;
+
+let (node_conf, mut node_exec) = new_node_engine();
+let mut graph = Graph::new(node_conf);
+
+let patch = PatchText::parse("sin(freq=220) -> amp(att=0.5) -> out.ch1").unwrap();
+patch.apply(&mut graph).unwrap();
+graph.sync().unwrap();
+```
+
 ## State of Development
 
 As of 2022-07-30: The architecture and it's functionality have been mostly
@@ -314,11 +353,18 @@ pub mod cell_dir;
 pub mod chain_builder;
 #[allow(unused_macros, non_snake_case)]
 pub mod dsp;
+pub mod graph;
 pub mod log;
 pub mod matrix;
+pub mod matrix_history;
+pub mod matrix_layout;
+pub mod matrix_region;
 pub mod matrix_repr;
 pub mod monitor;
 pub mod nodes;
+pub mod offline_render;
+pub mod patch_export;
+pub mod patch_text;
 pub mod sample_lib;
 pub mod scope_handle;
 pub mod wblockdsp;
@@ -326,12 +372,20 @@ mod util;
 
 pub use cell_dir::CellDir;
 pub use chain_builder::MatrixCellChain;
-pub use dsp::{NodeId, NodeInfo, ParamId, SAtom};
+pub use dsp::{NodeId, NodeInfo, ParamId, SAtom, Smoothing};
+pub use graph::Graph;
 pub use log::log;
 pub use matrix::{Cell, Matrix};
+pub use matrix_layout::MatrixLayout;
 pub use matrix_repr::load_patch_from_file;
 pub use matrix_repr::save_patch_to_file;
-pub use nodes::{new_node_engine, NodeConfigurator, NodeExecutor};
+pub use nodes::{
+    new_node_engine, DspLoad, MidiEvent, MidiEventKind, NodeConfigurator, NodeEngineBuilder,
+    NodeExecutor, NodeState, ParamEventMode, SignalFault, Transport,
+};
+pub use offline_render::{RenderError, RenderLength, RenderSettings, RenderedAudio, WavFormat};
+pub use patch_export::PatchExport;
+pub use patch_text::PatchText;
 pub use sample_lib::{SampleLibrary, SampleLoadError};
 pub use scope_handle::ScopeHandle;
 
@@ -339,6 +393,10 @@ pub struct Context<'a, 'b, 'c, 'd> {
     pub nframes: usize,
     pub output: &'a mut [&'b mut [f32]],
     pub input: &'c [&'d [f32]],
+    /// The MIDI events of the current buffer period, sorted by their frame offset.
+    pub events: &'c [MidiEvent],
+    /// The transport state of the host, if it provides one.
+    pub transport: Option<Transport>,
 }
 
 impl<'a, 'b, 'c, 'd> nodes::NodeAudioContext for Context<'a, 'b, 'c, 'd> {
@@ -349,12 +407,28 @@ impl<'a, 'b, 'c, 'd> nodes::NodeAudioContext for Context<'a, 'b, 'c, 'd> {
 
     #[inline]
     fn output(&mut self, channel: usize, frame: usize, v: f32) {
-        self.output[channel][frame] = v;
+        if let Some(output) = self.output.get_mut(channel) {
+            output[frame] = v;
+        }
     }
 
     #[inline]
     fn input(&mut self, channel: usize, frame: usize) -> f32 {
-        self.input[channel][frame]
+        if let Some(input) = self.input.get(channel) {
+            input[frame]
+        } else {
+            0.0
+        }
+    }
+
+    #[inline]
+    fn events(&self) -> &[MidiEvent] {
+        self.events
+    }
+
+    #[inline]
+    fn transport(&self) -> Option<Transport> {
+        self.transport
     }
 }
 
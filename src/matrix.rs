@@ -2,15 +2,22 @@
 // This file is a part of HexoDSP. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
-use crate::dsp::tracker::PatternData;
-use crate::dsp::{NodeId, NodeInfo, ParamId, SAtom};
+use crate::dsp::tracker::{PatternData, UIPatternModel, MAX_PATTERN_LEN};
+use crate::dsp::{NodeId, NodeInfo, ParamId, SAtom, Smoothing};
+use crate::graph::GraphEdge;
+use crate::matrix_history::{Edit, MatrixHistory};
+use crate::matrix_region::to_axial;
+pub use crate::matrix_region::{MatrixRegion, RegionShape};
 use crate::matrix_repr::*;
 pub use crate::monitor::MON_SIG_CNT;
 pub use crate::nodes::MinMaxMonitorSamples;
-use crate::nodes::{NodeConfigurator, NodeGraphOrdering, NodeProg, MAX_ALLOCATED_NODES};
+use crate::nodes::{
+    DspLoad, NodeConfigurator, NodeGraphOrdering, NodeProg, NodeState, ParamEventMode, SignalFault,
+    VoiceStealMode, MAX_ALLOCATED_NODES,
+};
 pub use crate::CellDir;
 use crate::ScopeHandle;
-use crate::wblockdsp::{BlockFun, BlockFunSnapshot, BlkJITCompileError};
+use crate::wblockdsp::{BlkJITCompileError, BlockCodeView, BlockFun};
 
 use std::collections::{HashMap, HashSet};
 
@@ -426,12 +433,28 @@ impl Cell {
 use std::sync::{Arc, Mutex};
 
 /// To report back cycle errors from [Matrix::check] and [Matrix::sync].
+/// `VoiceRegionDependency` is returned by [Matrix::sync] if a node of the
+/// voice region reads from a node outside of it, that depends on the region.
+/// `TooManyBuses` is returned if there are more bus names than free buses,
+/// see [NodeConfigurator::assign_buses].
+///
+/// [Matrix::resize] returns `CellOutsideGrid` if it would drop an occupied
+/// cell and [Matrix::resize_shifted] returns `OddColumnShift` if the
+/// cells would be moved by an odd number of columns.
+///
+/// [Matrix::transform_region] returns `PortOnWrongEdge` if a port of the
+/// cell would be turned onto an edge of the other kind.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum MatrixError {
     CycleDetected,
     DuplicatedInput { output1: (NodeId, u8), output2: (NodeId, u8) },
     NonEmptyCell { cell: Cell },
     PosOutOfRange,
+    CellOutsideGrid { cell: Cell },
+    OddColumnShift,
+    PortOnWrongEdge { cell: Cell },
+    VoiceRegionDependency,
+    TooManyBuses,
 }
 
 /// An intermediate data structure to store a single edge in the [Matrix].
@@ -459,6 +482,11 @@ pub trait MatrixObserver {
     /// Not called, when [MatrixObserver::update_all] tells you that
     /// everything has changed.
     fn update_param(&self, param_id: &ParamId);
+    /// Called when a node is bypassed, muted or made active again
+    /// via [Matrix::set_node_state].
+    /// Not called, when [MatrixObserver::update_all] tells you that
+    /// everything has changed.
+    fn update_node_state(&self, _node_id: &NodeId) {}
     /// Called when the matrix graph was changed, usually called
     /// when [Matrix::sync] is called.
     /// Usually also called when [MatrixObserver::update_all] was called.
@@ -517,6 +545,9 @@ pub struct Matrix {
 
     /// A trait object that tracks changed on the [Matrix].
     observer: Option<Arc<dyn MatrixObserver>>,
+
+    /// The undo/redo history, see [Matrix::undo] and [Matrix::redo].
+    history: MatrixHistory,
 }
 
 unsafe impl Send for Matrix {}
@@ -535,6 +566,7 @@ impl Matrix {
             assigned_inputs: HashSet::new(),
             properties: HashMap::new(),
             observer: None,
+            history: MatrixHistory::new(),
             config,
             w,
             h,
@@ -551,6 +583,102 @@ impl Matrix {
         (self.w, self.h)
     }
 
+    /// Changes the size of the hexagonal grid to `w` x `h` cells. All
+    /// cells keep their position and connections. Returns an error
+    /// if an occupied cell would be outside the new grid.
+    ///
+    /// This calls [Matrix::sync], the size is saved in the [MatrixRepr].
+    ///
+    ///```
+    /// use hexodsp::*;
+    /// use hexodsp::matrix::MatrixError;
+    ///
+    /// let (node_conf, mut _node_exec) = new_node_engine();
+    /// let mut matrix = Matrix::new(node_conf, 3, 3);
+    ///
+    /// matrix.place(2, 2, Cell::empty(NodeId::Sin(0)));
+    ///
+    /// matrix.resize(8, 6).unwrap();
+    /// assert_eq!(matrix.size(), (8, 6));
+    /// assert_eq!(matrix.get(2, 2).unwrap().node_id(), NodeId::Sin(0));
+    ///
+    /// assert_eq!(
+    ///     matrix.resize(2, 2),
+    ///     Err(MatrixError::CellOutsideGrid { cell: *matrix.get(2, 2).unwrap() }));
+    ///```
+    pub fn resize(&mut self, w: usize, h: usize) -> Result<(), MatrixError> {
+        self.resize_shifted(w, h, 0, 0)
+    }
+
+    /// Like [Matrix::resize], but also moves all cells by `shift_x` columns
+    /// and `shift_y` rows, for instance to grow the grid at the top or left
+    /// side. The neighbours of a cell in the hexagonal grid depend on
+    /// whether it's column is even or odd, so `shift_x` has to be even to
+    /// keep the connections.
+    pub fn resize_shifted(
+        &mut self,
+        w: usize,
+        h: usize,
+        shift_x: i32,
+        shift_y: i32,
+    ) -> Result<(), MatrixError> {
+        // The cell positions are stored in an u8:
+        if w > 256 || h > 256 {
+            return Err(MatrixError::PosOutOfRange);
+        }
+
+        if shift_x % 2 != 0 {
+            return Err(MatrixError::OddColumnShift);
+        }
+
+        let shift = |x: u8, y: u8| {
+            let x = x as i32 + shift_x;
+            let y = y as i32 + shift_y;
+            if x < 0 || y < 0 || x as usize >= w || y as usize >= h {
+                None
+            } else {
+                Some((x as usize, y as usize))
+            }
+        };
+
+        let mut cells = vec![];
+        for cell in self.matrix.iter() {
+            if cell.is_empty() {
+                continue;
+            }
+
+            if let Some(pos) = shift(cell.x, cell.y) {
+                cells.push((pos, *cell));
+            } else {
+                return Err(MatrixError::CellOutsideGrid { cell: *cell });
+            }
+        }
+
+        self.history.record(Edit::Resize {
+            old: (self.w, self.h),
+            new: (w, h),
+            shift: (shift_x, shift_y),
+        });
+
+        self.w = w;
+        self.h = h;
+        self.matrix.clear();
+        self.matrix.resize(w * h, Cell::empty(NodeId::Nop));
+
+        for ((x, y), mut cell) in cells {
+            cell.x = x as u8;
+            cell.y = y as u8;
+            self.matrix[x * h + y] = cell;
+        }
+
+        if let Some((x, y)) = shift(self.monitored_cell.x, self.monitored_cell.y) {
+            self.monitored_cell.x = x as u8;
+            self.monitored_cell.y = y as u8;
+        }
+
+        self.sync()
+    }
+
     pub fn unique_index_for(&self, node_id: &NodeId) -> Option<usize> {
         self.config.unique_index_for(node_id)
     }
@@ -567,6 +695,39 @@ impl Matrix {
         self.config.led_value_for(node_id)
     }
 
+    /// Enables or disables the DSP load measurement of the nodes.
+    /// See also [NodeConfigurator::set_dsp_profiling].
+    pub fn set_dsp_profiling(&mut self, enabled: bool) {
+        self.config.set_dsp_profiling(enabled);
+    }
+
+    /// Returns the DSP load of the node.
+    /// See also [NodeConfigurator::dsp_load_for].
+    pub fn dsp_load_for(&self, node_id: &NodeId) -> Option<DspLoad> {
+        self.config.dsp_load_for(node_id)
+    }
+
+    /// Returns the DSP load of the node in the cell at `x`/`y`,
+    /// so that the frontend can highlight the expensive cells.
+    pub fn dsp_load_at(&self, x: usize, y: usize) -> Option<DspLoad> {
+        let cell = self.get(x, y)?;
+        if cell.node_id() == NodeId::Nop {
+            return None;
+        }
+        self.config.dsp_load_for(&cell.node_id())
+    }
+
+    /// Returns the DSP load of the whole graph.
+    /// See also [NodeConfigurator::graph_dsp_load].
+    pub fn graph_dsp_load(&self) -> DspLoad {
+        self.config.graph_dsp_load()
+    }
+
+    /// Resets the peak times of the DSP loads.
+    pub fn reset_dsp_load_peaks(&self) {
+        self.config.reset_dsp_load_peaks();
+    }
+
     pub fn update_filters(&mut self) {
         self.config.update_filters();
     }
@@ -631,6 +792,15 @@ impl Matrix {
     /// See also [Matrix::change_matrix], [Matrix::check].
     pub fn restore_matrix(&mut self) {
         if let Some(matrix) = self.saved_matrix.take() {
+            if self.history.is_recording() && matrix.len() == self.matrix.len() {
+                for (i, (old, new)) in self.matrix.iter().zip(matrix.iter()).enumerate() {
+                    if old != new {
+                        let (x, y) = (i / self.h, i % self.h);
+                        self.history.record(Edit::Cell { x, y, old: *old, new: *new });
+                    }
+                }
+            }
+
             self.matrix = matrix;
         }
     }
@@ -661,16 +831,20 @@ impl Matrix {
     where
         F: FnMut(&mut Self),
     {
+        self.begin_transaction();
         self.save_matrix();
 
         f(self);
 
-        if let Err(e) = self.check() {
+        let ret = if let Err(e) = self.check() {
             self.restore_matrix();
             Err(e)
         } else {
             Ok(())
-        }
+        };
+
+        self.end_transaction();
+        ret
     }
 
     /// Like [Matrix::change_matrix] but the function passed to this
@@ -679,19 +853,21 @@ impl Matrix {
     where
         F: FnMut(&mut Self) -> Result<(), MatrixError>,
     {
+        self.begin_transaction();
         self.save_matrix();
 
-        if let Err(e) = f(self) {
+        let ret = if let Err(e) = f(self) {
             self.restore_matrix();
-            return Err(e);
-        }
-
-        if let Err(e) = self.check() {
+            Err(e)
+        } else if let Err(e) = self.check() {
             self.restore_matrix();
             Err(e)
         } else {
             Ok(())
-        }
+        };
+
+        self.end_transaction();
+        ret
     }
 
     /// Tries to place all `cells` at once, if they are placed in empty
@@ -747,6 +923,10 @@ impl Matrix {
             return;
         }
 
+        if self.history.is_recording() {
+            let old = self.matrix[x * self.h + y];
+            self.history.record(Edit::Cell { x, y, old, new: cell });
+        }
         self.matrix[x * self.h + y] = cell;
     }
 
@@ -757,8 +937,229 @@ impl Matrix {
         self.place(cell.pos().0, cell.pos().1, cell);
     }
 
+    /// Copies the cells in the `shape` region with the origin at `x`/`y`,
+    /// together with the parameters, atoms and modulation amounts of their
+    /// nodes. Empty cells and cells outside the grid are skipped.
+    ///
+    /// See also [MatrixRegion], [Matrix::paste_region] and [Matrix::move_region].
+    pub fn copy_region(&self, x: usize, y: usize, shape: RegionShape) -> MatrixRegion {
+        let (oq, or) = to_axial(x as i32, y as i32);
+        let mut region = MatrixRegion::new();
+
+        for (px, py) in shape.positions(x, y) {
+            if px < 0 || py < 0 {
+                continue;
+            }
+
+            if let Some(cell) = self.get(px as usize, py as usize) {
+                if !cell.is_empty() {
+                    let (q, r) = to_axial(px, py);
+                    region.add_cell((q - oq, r - or), *cell);
+                }
+            }
+        }
+
+        for node_id in region.node_ids() {
+            let mut idx = 0;
+            while let Some(param) = node_id.param_by_idx(idx) {
+                if let Some(at) = self.get_param(&param) {
+                    region.add_param(param, at);
+                }
+                if let Some(modamt) = self.get_param_modamt(&param) {
+                    region.add_modamt(param, modamt);
+                }
+
+                idx += 1;
+            }
+        }
+
+        region
+    }
+
+    /// Places the `region` with its origin at `x`/`y`. The nodes get fresh
+    /// instances (see [Matrix::get_unused_instance_node_id]) with the copied
+    /// parameters, atoms and modulation amounts.
+    ///
+    /// Returns an error and leaves the matrix unchanged if a cell would be
+    /// placed outside the grid or on a non empty cell, or if the result
+    /// fails the [Matrix::check]. Pasting is one undo step and
+    /// calls [Matrix::sync].
+    pub fn paste_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        region: &MatrixRegion,
+    ) -> Result<(), MatrixError> {
+        let node_ids = self.fresh_node_ids(&region.node_ids());
+        self.transaction(|matrix| matrix.place_region(x, y, region, &[], &node_ids))
+    }
+
+    /// Moves the cells in the `shape` region with the origin at `x`/`y`,
+    /// so that the origin ends up at `to_x`/`to_y`. The nodes are kept.
+    /// The target cells have to be empty, unless they are part of the region.
+    ///
+    /// See also [Matrix::paste_region] for the errors.
+    pub fn move_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        shape: RegionShape,
+        to_x: usize,
+        to_y: usize,
+    ) -> Result<(), MatrixError> {
+        self.relocate_region(x, y, shape, to_x, to_y, |_| Ok(()))
+    }
+
+    /// Rotates the cells in the `shape` region clockwise by `steps` of 60°
+    /// around the origin at `x`/`y`. Negative `steps` rotate counter clockwise.
+    /// See [Matrix::transform_region] for the errors.
+    pub fn rotate_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        shape: RegionShape,
+        steps: i32,
+    ) -> Result<(), MatrixError> {
+        self.transform_region(x, y, shape, false, steps)
+    }
+
+    /// Mirrors the cells in the `shape` region at the vertical axis
+    /// through the origin at `x`/`y`. See [Matrix::transform_region]
+    /// for the errors.
+    pub fn mirror_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        shape: RegionShape,
+    ) -> Result<(), MatrixError> {
+        self.transform_region(x, y, shape, true, 0)
+    }
+
+    /// Mirrors and rotates the cells in the `shape` region around the origin
+    /// at `x`/`y` in one step, see [MatrixRegion::transform].
+    ///
+    /// Returns `PortOnWrongEdge` and leaves the matrix unchanged if a port
+    /// would end up on an edge of the other kind, as it's connection would
+    /// be lost. See also [Matrix::paste_region] for the other errors.
+    pub fn transform_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        shape: RegionShape,
+        mirror: bool,
+        steps: i32,
+    ) -> Result<(), MatrixError> {
+        self.relocate_region(x, y, shape, x, y, |region| region.transform(mirror, steps))
+    }
+
+    fn relocate_region<F: FnOnce(&mut MatrixRegion) -> Result<(), MatrixError>>(
+        &mut self,
+        x: usize,
+        y: usize,
+        shape: RegionShape,
+        to_x: usize,
+        to_y: usize,
+        f: F,
+    ) -> Result<(), MatrixError> {
+        let mut region = self.copy_region(x, y, shape);
+        let sources: Vec<(usize, usize)> = region
+            .cells_at(x, y)
+            .iter()
+            .map(|((sx, sy), _)| (*sx as usize, *sy as usize))
+            .collect();
+        f(&mut region)?;
+
+        let node_ids: Vec<(NodeId, NodeId)> =
+            region.node_ids().iter().map(|node_id| (*node_id, *node_id)).collect();
+        self.transaction(|matrix| matrix.place_region(to_x, to_y, &region, &sources, &node_ids))
+    }
+
+    /// Allocates a fresh instance for each of the `node_ids`, skipping the
+    /// ones that are placed in the grid but not synced yet.
+    fn fresh_node_ids(&self, node_ids: &[NodeId]) -> Vec<(NodeId, NodeId)> {
+        let mut used = vec![];
+        self.for_each(|_x, _y, cell| used.push(cell.node_id()));
+
+        let mut map = vec![];
+        for node_id in node_ids.iter() {
+            let mut new_id = self.get_unused_instance_node_id(node_id.to_instance(0));
+            while used.contains(&new_id) {
+                new_id =
+                    self.get_unused_instance_node_id(new_id.to_instance(new_id.instance() + 1));
+            }
+
+            used.push(new_id);
+            map.push((*node_id, new_id));
+        }
+
+        map
+    }
+
+    /// Clears the `sources` and places the `region` at `x`/`y`, replacing the
+    /// nodes as given by `node_ids`. The copied parameters are only assigned
+    /// to replaced nodes.
+    fn place_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        region: &MatrixRegion,
+        sources: &[(usize, usize)],
+        node_ids: &[(NodeId, NodeId)],
+    ) -> Result<(), MatrixError> {
+        let new_id = |node_id: NodeId| {
+            node_ids.iter().find(|(old, _)| *old == node_id).map(|(_, new)| *new).unwrap_or(node_id)
+        };
+
+        let (w, h) = (self.w as i32, self.h as i32);
+        let mut cells = vec![];
+        for ((px, py), mut cell) in region.cells_at(x, y) {
+            if px < 0 || py < 0 || px >= w || py >= h {
+                return Err(MatrixError::PosOutOfRange);
+            }
+
+            cell.set_node_id_keep_ios(new_id(cell.node_id()));
+            cell.x = px as u8;
+            cell.y = py as u8;
+            cells.push(cell);
+        }
+
+        self.change_matrix_err(|matrix| {
+            for (sx, sy) in sources.iter() {
+                matrix.place(*sx, *sy, Cell::empty(NodeId::Nop));
+            }
+
+            matrix.place_multiple(&cells[..])
+        })?;
+
+        for (param, at) in region.params().iter() {
+            let node_id = new_id(param.node_id());
+            if node_id != param.node_id() {
+                if let Some(param) = node_id.param_by_idx(param.inp() as usize) {
+                    self.set_param(param, at.clone());
+                }
+            }
+        }
+
+        for (param, modamt) in region.modamts().iter() {
+            let node_id = new_id(param.node_id());
+            if node_id != param.node_id() {
+                if let Some(param) = node_id.param_by_idx(param.inp() as usize) {
+                    self.set_param_modamt(param, Some(*modamt))?;
+                }
+            }
+        }
+
+        self.sync()
+    }
+
     /// Clears the contents of the matrix. It's completely empty after this.
+    /// This can be undone, see [Matrix::undo].
     pub fn clear(&mut self) {
+        if self.history.is_recording() {
+            let old = Box::new(self.to_repr_without_data());
+            self.history.record(Edit::Clear { old });
+        }
+
         for cell in self.matrix.iter_mut() {
             *cell = Cell::empty(NodeId::Nop);
         }
@@ -796,6 +1197,281 @@ impl Matrix {
         self.gen_counter
     }
 
+    /// Sets the maximum number of undo steps, older steps are dropped.
+    /// The default is [crate::matrix_history::DEFAULT_HISTORY_LEN],
+    /// 0 disables the history.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// Drops all undo and redo steps, for instance after loading a new patch.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// Starts a transaction, all edits until the matching
+    /// [Matrix::end_transaction] are undone in one step. Transactions can be
+    /// nested, only the outermost one creates an undo step.
+    ///
+    /// Every edit of the [Matrix] (placing cells, parameters, modulation
+    /// amounts, properties and node states) is undoable on it's own, the
+    /// history stores the old and new value of each edit. Consecutive edits
+    /// of the same parameter or modulation amount, like dragging a knob,
+    /// are merged into one undo step.
+    ///
+    /// Changes of the tracker patterns (see [Matrix::get_pattern_data])
+    /// and block functions (see [Matrix::get_block_function]) are only
+    /// recorded by a surrounding transaction. Undoing a step only restores
+    /// the patterns and block functions that it changed, so edits of them
+    /// outside of a transaction are kept.
+    pub fn begin_transaction(&mut self) {
+        if self.history.begin() && self.history.is_recording() {
+            self.update_data_history();
+        }
+    }
+
+    /// Ends a transaction started by [Matrix::begin_transaction].
+    /// No undo step is recorded if nothing changed.
+    pub fn end_transaction(&mut self) {
+        if let Some(mut edits) = self.history.end() {
+            if self.history.is_recording() {
+                edits.extend(self.update_data_history());
+                self.history.push_step(edits);
+            }
+        }
+    }
+
+    /// Remembers the current tracker patterns and block functions
+    /// for the next transaction. Returns the edits for the ones that
+    /// changed since the last call.
+    fn update_data_history(&mut self) -> Vec<Edit> {
+        let mut edits = vec![];
+
+        let mut id = 0;
+        while let Some(pdata) = self.get_pattern_data(id) {
+            let pdata = pdata.lock().unwrap();
+            let gen = pdata.get_generation();
+
+            if id >= self.history.patterns.len() {
+                self.history.patterns.push((usize::MAX, None));
+            }
+
+            let (last_gen, last) = &mut self.history.patterns[id];
+            if *last_gen != gen {
+                let new = if pdata.is_unset() { None } else { Some(pdata.to_repr()) };
+                // The first snapshot is no change:
+                if *last_gen != usize::MAX && *last != new {
+                    edits.push(Edit::Pattern { id, old: last.clone(), new: new.clone() });
+                }
+                *last_gen = gen;
+                *last = new;
+            }
+
+            id += 1;
+        }
+
+        let mut id = 0;
+        while let Some(bf) = self.get_block_function(id) {
+            let bf = bf.lock().unwrap();
+            let gen = bf.generation();
+
+            if id >= self.history.block_funs.len() {
+                self.history.block_funs.push((u64::MAX, None));
+            }
+
+            let (last_gen, last) = &mut self.history.block_funs[id];
+            if *last_gen != gen {
+                let new = if bf.is_unset() { None } else { Some(bf.save_snapshot()) };
+                if *last_gen != u64::MAX {
+                    edits.push(Edit::BlockFun { id, old: last.clone(), new: new.clone() });
+                }
+                *last_gen = gen;
+                *last = new;
+            }
+
+            id += 1;
+        }
+
+        edits
+    }
+
+    /// Runs `f` inside a transaction, see [Matrix::begin_transaction].
+    ///
+    ///```
+    /// use hexodsp::*;
+    ///
+    /// let (node_conf, mut _node_exec) = new_node_engine();
+    /// let mut matrix = Matrix::new(node_conf, 3, 3);
+    ///
+    /// matrix.transaction(|matrix| {
+    ///     matrix.place(0, 0, Cell::empty(NodeId::Sin(0)).out(None, Some(0), None));
+    ///     matrix.place(1, 0, Cell::empty(NodeId::Out(0)).input(None, Some(0), None));
+    /// });
+    /// matrix.sync().unwrap();
+    ///
+    /// assert!(matrix.undo().unwrap());
+    /// assert!(matrix.get(0, 0).unwrap().is_empty());
+    /// assert!(matrix.get(1, 0).unwrap().is_empty());
+    ///
+    /// assert!(matrix.redo().unwrap());
+    /// assert_eq!(matrix.get(1, 0).unwrap().node_id(), NodeId::Out(0));
+    ///```
+    pub fn transaction<R, F: FnOnce(&mut Self) -> R>(&mut self, f: F) -> R {
+        self.begin_transaction();
+        let ret = f(self);
+        self.end_transaction();
+        ret
+    }
+
+    /// Returns true if there is an edit to undo.
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    /// Returns true if there is an undone edit to redo.
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// Restores the state before the last edit or transaction.
+    /// Returns `Ok(false)` if there is nothing to undo
+    /// or a transaction is still open.
+    ///
+    /// Only the cells, parameters, modulation amounts, properties and node
+    /// states that were edited are set back, so the nodes keep running.
+    /// Undoing [Matrix::clear] recreates the nodes like [Matrix::from_repr].
+    pub fn undo(&mut self) -> Result<bool, MatrixError> {
+        if self.history.in_transaction() {
+            return Ok(false);
+        }
+
+        if let Some(step) = self.history.take_undo() {
+            let ret = self.apply_edits(&step, true);
+            self.history.push_redo(step);
+            ret.map(|_| true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Restores the state that was undone by the last [Matrix::undo]. Any new
+    /// edit after an undo drops the redo steps. Returns `Ok(false)` if there
+    /// is nothing to redo or a transaction is still open.
+    pub fn redo(&mut self) -> Result<bool, MatrixError> {
+        if self.history.in_transaction() {
+            return Ok(false);
+        }
+
+        if let Some(step) = self.history.take_redo() {
+            let ret = self.apply_edits(&step, false);
+            self.history.push_undone(step);
+            ret.map(|_| true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Sets the old values of the `edits` in reverse order if `undo` is set,
+    /// otherwise the new values. The edits done for that are not recorded.
+    fn apply_edits(&mut self, edits: &[Edit], undo: bool) -> Result<(), MatrixError> {
+        self.history.set_restoring(true);
+
+        let mut ret = Ok(());
+        // Cells and atoms like the `bus` of the `BusSend` nodes
+        // change the order of the nodes:
+        let mut needs_sync = false;
+
+        let edits: Vec<&Edit> =
+            if undo { edits.iter().rev().collect() } else { edits.iter().collect() };
+
+        for edit in edits.into_iter() {
+            match edit {
+                Edit::Cell { x, y, old, new } => {
+                    self.place(*x, *y, if undo { *old } else { *new });
+                    needs_sync = true;
+                }
+                Edit::Param { param, old, new } => {
+                    let at = match (undo, old) {
+                        (false, _) => new.clone(),
+                        (true, Some(old)) => old.clone(),
+                        // The parameter was not set before the edit:
+                        (true, None) if param.is_atom() => param.as_atom_def(),
+                        (true, None) => SAtom::param(param.norm_def()),
+                    };
+                    self.set_param(*param, at);
+                    needs_sync = needs_sync || param.is_atom();
+                }
+                Edit::ModAmt { param, old, new } => {
+                    let res = self.set_param_modamt(*param, if undo { *old } else { *new });
+                    ret = ret.and(res);
+                }
+                Edit::Prop { key, old, new } => match (undo, old) {
+                    (false, _) => self.set_prop(key, new.clone()),
+                    (true, Some(old)) => self.set_prop(key, old.clone()),
+                    (true, None) => {
+                        self.gen_counter += 1;
+                        self.properties.remove(key);
+                        if let Some(obs) = &self.observer {
+                            obs.update_prop(key);
+                        }
+                    }
+                },
+                Edit::NodeState { node_id, old, new } => {
+                    self.set_node_state(*node_id, if undo { *old } else { *new });
+                }
+                Edit::Resize { old, new, shift } => {
+                    let res = if undo {
+                        self.resize_shifted(old.0, old.1, -shift.0, -shift.1)
+                    } else {
+                        self.resize_shifted(new.0, new.1, shift.0, shift.1)
+                    };
+                    ret = ret.and(res);
+                }
+                Edit::Clear { old } => {
+                    if undo {
+                        ret = ret.and(self.load_repr(old, false));
+                    } else {
+                        self.clear();
+                    }
+                    needs_sync = false;
+                }
+                Edit::Pattern { id, old, new } => {
+                    if let Some(pd) = self.get_pattern_data(*id) {
+                        let mut pd = pd.lock().unwrap();
+                        match if undo { old } else { new } {
+                            Some(pat) => pd.from_repr(pat),
+                            None => *pd = PatternData::new(MAX_PATTERN_LEN),
+                        }
+                    }
+                }
+                Edit::BlockFun { id, old, new } => {
+                    if let Some(bf) = self.get_block_function(*id) {
+                        let mut bf = bf.lock().unwrap();
+                        match if undo { old } else { new } {
+                            Some(block_fun) => bf.load_snapshot(block_fun),
+                            None => {
+                                let empty = BlockFun::new(bf.block_language()).save_snapshot();
+                                bf.load_snapshot(&empty);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if needs_sync {
+            ret = ret.and(self.sync());
+        }
+
+        self.history.set_restoring(false);
+
+        if let Some(obs) = &self.observer {
+            obs.update_all();
+        }
+
+        ret
+    }
+
     /// Returns a serializable representation of the matrix.
     /// This representation contains all parameters,
     /// created nodes, connections and the tracker's pattern data.
@@ -823,19 +1499,11 @@ impl Matrix {
     ///
     /// See also [MatrixRepr::serialize].
     pub fn to_repr(&self) -> MatrixRepr {
-        let (params, atoms) = self.config.dump_param_values();
+        let mut repr = self.to_repr_without_data();
 
-        let mut cells: Vec<CellRepr> = vec![];
-        self.for_each(|_x, _y, cell| {
-            if cell.node_id() != NodeId::Nop {
-                cells.push(cell.to_repr())
-            }
-        });
-
-        let mut patterns: Vec<Option<PatternRepr>> = vec![];
         let mut tracker_id = 0;
         while let Some(pdata) = self.get_pattern_data(tracker_id) {
-            patterns.push(if pdata.lock().unwrap().is_unset() {
+            repr.patterns.push(if pdata.lock().unwrap().is_unset() {
                 None
             } else {
                 Some(pdata.lock().unwrap().to_repr())
@@ -844,10 +1512,9 @@ impl Matrix {
             tracker_id += 1;
         }
 
-        let mut block_funs: Vec<Option<BlockFunSnapshot>> = vec![];
         let mut bf_id = 0;
         while let Some(bf) = self.get_block_function(bf_id) {
-            block_funs.push(if bf.lock().unwrap().is_unset() {
+            repr.block_funs.push(if bf.lock().unwrap().is_unset() {
                 None
             } else {
                 Some(bf.lock().unwrap().save_snapshot())
@@ -856,19 +1523,66 @@ impl Matrix {
             bf_id += 1;
         }
 
+        repr
+    }
+
+    /// Like [Matrix::to_repr], but without the tracker patterns and block functions.
+    fn to_repr_without_data(&self) -> MatrixRepr {
+        let (params, atoms) = self.config.dump_param_values();
+
+        let mut cells: Vec<CellRepr> = vec![];
+        self.for_each(|_x, _y, cell| {
+            if cell.node_id() != NodeId::Nop {
+                cells.push(cell.to_repr())
+            }
+        });
+
         let properties = self.properties.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
 
-        MatrixRepr { cells, params, atoms, patterns, block_funs, properties, version: 2 }
+        let node_states = self.config.dump_node_states();
+
+        MatrixRepr {
+            size: Some((self.w, self.h)),
+            cells,
+            params,
+            atoms,
+            patterns: vec![],
+            block_funs: vec![],
+            properties,
+            node_states,
+            version: 2,
+        }
     }
 
     /// Loads the matrix from a previously my [Matrix::to_repr]
     /// generated matrix representation.
     ///
     /// This function will call [Matrix::sync] after loading and
-    /// overwriting the current matrix contents.
+    /// overwriting the current matrix contents. Loading a patch
+    /// is not undoable, it clears the undo history.
     pub fn from_repr(&mut self, repr: &MatrixRepr) -> Result<(), MatrixError> {
+        self.history.set_restoring(true);
+        let ret = self.load_repr(repr, true);
+        self.history.set_restoring(false);
+        self.clear_history();
+        ret
+    }
+
+    /// Loads the `repr`, the tracker patterns and block functions
+    /// are only loaded if `with_data` is set.
+    fn load_repr(&mut self, repr: &MatrixRepr, with_data: bool) -> Result<(), MatrixError> {
         self.clear();
 
+        // Patches without a size are loaded into the current grid:
+        if let Some((w, h)) = repr.size {
+            if w <= 256 && h <= 256 {
+                self.w = w;
+                self.h = h;
+                self.matrix.clear();
+                self.matrix.resize(w * h, Cell::empty(NodeId::Nop));
+            }
+        }
+
         let normalize_params = repr.version > 1;
 
         self.config.load_dumped_param_values(&repr.params[..], &repr.atoms[..], normalize_params);
@@ -877,25 +1591,17 @@ impl Matrix {
             self.properties.insert(key.to_string(), val.clone());
         }
 
+        for (node_id, state) in repr.node_states.iter() {
+            self.config.set_node_state(*node_id, *state);
+        }
+
         for cell_repr in repr.cells.iter() {
             let cell = Cell::from_repr(cell_repr);
             self.place(cell.x as usize, cell.y as usize, cell);
         }
 
-        for (tracker_id, pat) in repr.patterns.iter().enumerate() {
-            if let Some(pat) = pat {
-                if let Some(pd) = self.get_pattern_data(tracker_id) {
-                    pd.lock().unwrap().from_repr(pat);
-                }
-            }
-        }
-
-        for (bf_id, block_fun) in repr.block_funs.iter().enumerate() {
-            if let Some(block_fun) = block_fun {
-                if let Some(bf) = self.get_block_function(bf_id) {
-                    bf.lock().unwrap().load_snapshot(block_fun);
-                }
-            }
+        if with_data {
+            self.load_data(repr);
         }
 
         let ret = self.sync();
@@ -907,6 +1613,36 @@ impl Matrix {
         ret
     }
 
+    /// Loads the tracker patterns and block functions of the `repr`.
+    fn load_data(&mut self, repr: &MatrixRepr) {
+        // Patterns and block functions that are not in the `repr` are reset,
+        // so that an undo step restores them too:
+        let mut tracker_id = 0;
+        while let Some(pd) = self.get_pattern_data(tracker_id) {
+            let mut pd = pd.lock().unwrap();
+            if let Some(Some(pat)) = repr.patterns.get(tracker_id) {
+                pd.from_repr(pat);
+            } else if !pd.is_unset() {
+                *pd = PatternData::new(MAX_PATTERN_LEN);
+            }
+
+            tracker_id += 1;
+        }
+
+        let mut bf_id = 0;
+        while let Some(bf) = self.get_block_function(bf_id) {
+            let mut bf = bf.lock().unwrap();
+            if let Some(Some(block_fun)) = repr.block_funs.get(bf_id) {
+                bf.load_snapshot(block_fun);
+            } else if !bf.is_unset() {
+                let empty = BlockFun::new(bf.block_language()).save_snapshot();
+                bf.load_snapshot(&empty);
+            }
+
+            bf_id += 1;
+        }
+    }
+
     /// Saves a property in the matrix, these can be retrieved
     /// using [Matrix::get_prop] and are saved/loaded along with
     /// the [MatrixRepr]. See also [Matrix::to_repr] and [Matrix::from_repr].
@@ -930,6 +1666,10 @@ impl Matrix {
     /// assert_eq!(matrix2.get_prop("test").unwrap().i(), 31337);
     ///```
     pub fn set_prop(&mut self, key: &str, val: SAtom) {
+        if self.history.is_recording() {
+            let old = self.properties.get(key).cloned();
+            self.history.record(Edit::Prop { key: key.to_string(), old, new: val.clone() });
+        }
         self.gen_counter += 1;
         self.properties.insert(key.to_string(), val);
         if let Some(obs) = &self.observer {
@@ -990,13 +1730,181 @@ impl Matrix {
         self.config.pop_error()
     }
 
+    /// Sets the number of worker threads for the parallel execution of the graph.
+    /// See also [NodeConfigurator::set_worker_threads].
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        self.config.set_worker_threads(threads);
+    }
+
+    /// Enables or disables the crossfade between the old and the new
+    /// program when the matrix is synced with [Matrix::sync].
+    /// See also [NodeConfigurator::set_prog_crossfade].
+    pub fn set_prog_crossfade(&mut self, fade_ms: Option<f32>) {
+        self.config.set_prog_crossfade(fade_ms);
+    }
+
+    /// Returns whether the program of the last [Matrix::sync] is crossfaded.
+    /// See also [NodeConfigurator::is_prog_crossfaded].
+    pub fn is_prog_crossfaded(&self) -> bool {
+        self.config.is_prog_crossfaded()
+    }
+
+    /// Enables or disables the signal guard of the backend.
+    /// See also [NodeConfigurator::set_signal_guard].
+    pub fn set_signal_guard(&mut self, limit: Option<f32>) {
+        self.config.set_signal_guard(limit);
+    }
+
+    /// Bypasses or mutes the node `node_id`, or makes it active again.
+    /// The state is saved with the [MatrixRepr].
+    /// See also [NodeConfigurator::set_node_state].
+    ///
+    ///```
+    /// use hexodsp::*;
+    ///
+    /// let (node_conf, mut _node_exec) = new_node_engine();
+    /// let mut matrix = Matrix::new(node_conf, 3, 3);
+    ///
+    /// matrix.place(0, 0, Cell::empty(NodeId::Amp(0)));
+    /// matrix.sync().unwrap();
+    ///
+    /// matrix.set_node_state(NodeId::Amp(0), NodeState::Bypass);
+    /// assert_eq!(matrix.node_state(&NodeId::Amp(0)), NodeState::Bypass);
+    /// assert_eq!(matrix.node_state(&NodeId::Amp(1)), NodeState::Active);
+    ///```
+    pub fn set_node_state(&mut self, node_id: NodeId, state: NodeState) {
+        if self.history.is_recording() {
+            let old = self.node_state(&node_id);
+            self.history.record(Edit::NodeState { node_id, old, new: state });
+        }
+        self.gen_counter += 1;
+        self.config.set_node_state(node_id, state);
+        if let Some(obs) = &self.observer {
+            obs.update_node_state(&node_id);
+        }
+    }
+
+    /// Returns the state of the node `node_id`, see [Matrix::set_node_state].
+    pub fn node_state(&self, node_id: &NodeId) -> NodeState {
+        self.config.node_state(node_id)
+    }
+
+    /// Solos the output port `out` of the node `node_id` to the audio output
+    /// channels `channel` and `channel + 1`, while the `Out` nodes are muted.
+    /// See also [NodeConfigurator::cue_output].
+    pub fn cue_output(&mut self, node_id: NodeId, out: u8, channel: usize) {
+        self.config.cue_output(node_id, out, channel);
+    }
+
+    /// Ends the solo of [Matrix::cue_output].
+    /// See also [NodeConfigurator::stop_cue].
+    pub fn stop_cue(&mut self) {
+        self.config.stop_cue();
+    }
+
+    /// Returns the soloed output port and the first output channel.
+    /// See also [NodeConfigurator::cued_output].
+    pub fn cued_output(&self) -> Option<(NodeId, u8, usize)> {
+        self.config.cued_output()
+    }
+
+    /// Returns the next node that tripped the signal guard.
+    /// See also [NodeConfigurator::pop_signal_fault].
+    pub fn pop_signal_fault(&mut self) -> Option<(NodeId, SignalFault)> {
+        self.config.pop_signal_fault()
+    }
+
+    /// Marks the nodes of a subgraph of the matrix as voice region, which
+    /// is executed `voices` times. See also [NodeConfigurator::set_voice_region].
+    ///
+    /// Pass an empty `nodes` slice to remove the voice region.
+    /// If the [Matrix::sync] fails, the previous voice region is kept.
+    pub fn set_voice_region(
+        &mut self,
+        nodes: &[NodeId],
+        voices: usize,
+        steal_mode: VoiceStealMode,
+    ) -> Result<(), MatrixError> {
+        let prev_region = self.config.voice_region().cloned();
+
+        if nodes.is_empty() {
+            self.config.clear_voice_region();
+        } else {
+            self.config.set_voice_region(nodes, voices, steal_mode);
+        }
+
+        let res = self.sync();
+        if res.is_err() {
+            if let Some((nodes, voices, steal_mode)) = prev_region {
+                self.config.set_voice_region(&nodes, voices, steal_mode);
+            } else {
+                self.config.clear_voice_region();
+            }
+        }
+
+        res
+    }
+
+    /// Returns the nodes, voice count and stealing mode of the voice region.
+    pub fn voice_region(&self) -> Option<&(Vec<NodeId>, usize, VoiceStealMode)> {
+        self.config.voice_region()
+    }
+
+    /// Assigns a voice of the voice region to the note.
+    /// See also [NodeConfigurator::note_on].
+    pub fn note_on(&mut self, note: u8, vel: f32) {
+        self.config.note_on(note, vel);
+    }
+
+    /// Releases the voice of the voice region that plays the note.
+    pub fn note_off(&mut self, note: u8) {
+        self.config.note_off(note);
+    }
+
+    /// Sets the tempo of the transport. See also [NodeConfigurator::set_tempo].
+    pub fn set_tempo(&mut self, bpm: f64) {
+        self.config.set_tempo(bpm);
+    }
+
+    /// Sets the time signature of the transport.
+    /// See also [NodeConfigurator::set_time_signature].
+    pub fn set_time_signature(&mut self, num: u8, denom: u8) {
+        self.config.set_time_signature(num, denom);
+    }
+
+    /// Starts or stops the transport. See also [NodeConfigurator::set_playing].
+    pub fn set_playing(&mut self, playing: bool) {
+        self.config.set_playing(playing);
+    }
+
+    /// Moves the song position of the transport to `beat_pos` quarter notes.
+    /// See also [NodeConfigurator::locate].
+    pub fn locate(&mut self, beat_pos: f64) {
+        self.config.locate(beat_pos);
+    }
+
     /// Retrieve [SAtom] values for input parameters and atoms.
     pub fn get_param(&self, param: &ParamId) -> Option<SAtom> {
         self.config.get_param(param)
     }
 
+    /// Formats the normalized value `v` of the parameter, showing note divisions
+    /// for tempo synced time parameters. See [NodeConfigurator::format_param].
+    pub fn format_param(
+        &self,
+        f: &mut dyn std::io::Write,
+        param: &ParamId,
+        v: f32,
+    ) -> Option<std::io::Result<()>> {
+        self.config.format_param(f, param, v)
+    }
+
     /// Assign [SAtom] values to input parameters and atoms.
     pub fn set_param(&mut self, param: ParamId, at: SAtom) {
+        if self.history.is_recording() {
+            let old = self.get_param(&param);
+            self.history.record(Edit::Param { param, old, new: at.clone() });
+        }
         self.config.set_param(param.clone(), at);
         self.gen_counter += 1;
         if let Some(obs) = &self.observer {
@@ -1004,6 +1912,28 @@ impl Matrix {
         }
     }
 
+    /// Schedules a sample accurate update of the input parameter.
+    /// See [NodeConfigurator::set_param_at] for the details.
+    pub fn set_param_at(&mut self, param: ParamId, value: f32, frame: usize, mode: ParamEventMode) {
+        self.config.set_param_at(param, value, frame, mode);
+        self.gen_counter += 1;
+        if let Some(obs) = &self.observer {
+            obs.update_param(&param);
+        }
+    }
+
+    /// Returns the smoothing policy of the input parameter.
+    /// See also [NodeConfigurator::get_param_smoothing].
+    pub fn get_param_smoothing(&self, param: &ParamId) -> Smoothing {
+        self.config.get_param_smoothing(param)
+    }
+
+    /// Overrides the smoothing policy of the input parameter, `None` restores
+    /// the default. See also [NodeConfigurator::set_param_smoothing].
+    pub fn set_param_smoothing(&mut self, param: ParamId, smoothing: Option<Smoothing>) {
+        self.config.set_param_smoothing(param, smoothing);
+    }
+
     /// Retrieve the modulation amount of the input parameter.
     pub fn get_param_modamt(&self, param: &ParamId) -> Option<f32> {
         self.config.get_param_modamt(param)
@@ -1015,6 +1945,10 @@ impl Matrix {
         param: ParamId,
         modamt: Option<f32>,
     ) -> Result<(), MatrixError> {
+        if self.history.is_recording() {
+            let old = self.get_param_modamt(&param);
+            self.history.record(Edit::ModAmt { param, old, new: modamt });
+        }
         if self.config.set_param_modamt(param.clone(), modamt) {
             if let Some(obs) = &self.observer {
                 obs.update_param(&param);
@@ -1214,6 +2148,24 @@ impl Matrix {
         self.assigned_inputs.contains(&p)
     }
 
+    /// Returns the edges between the outputs and inputs of the nodes,
+    /// as they were found by the last [Matrix::sync] or [Matrix::check].
+    pub fn edges(&self) -> Vec<GraphEdge> {
+        self.edges
+            .iter()
+            .map(|e| GraphEdge {
+                from: e.from,
+                from_out: e.from_out,
+                to: e.to,
+                to_input: e.to_input,
+            })
+            .collect()
+    }
+
+    pub(crate) fn config(&self) -> &NodeConfigurator {
+        &self.config
+    }
+
     pub fn get_unused_instance_node_id(&self, id: NodeId) -> NodeId {
         self.config.unused_instance_node_id(id)
     }
@@ -1250,7 +2202,7 @@ impl Matrix {
         }
     }
 
-    fn update_graph_ordering_and_edges(&mut self) {
+    fn update_graph_ordering_and_edges(&mut self) -> Result<(), MatrixError> {
         self.graph_ordering.clear();
         self.edges.clear();
         self.assigned_inputs.clear();
@@ -1314,6 +2266,40 @@ impl Matrix {
                 self.assigned_inputs.insert(pid);
             }
         }
+
+        self.add_bus_ordering()
+    }
+
+    /// Orders all `BusSend` nodes before the `BusRecv` nodes of their bus.
+    /// The `bus` and `name` settings are read here, so changing them requires
+    /// a [Matrix::sync]. The buses of the named nodes are written into their
+    /// `bus` setting, see [NodeConfigurator::assign_buses].
+    fn add_bus_ordering(&mut self) -> Result<(), MatrixError> {
+        let mut node_ids = vec![];
+        for cell in self.matrix.iter() {
+            if matches!(cell.node_id, NodeId::BusSend(_) | NodeId::BusRecv(_))
+                && !node_ids.contains(&cell.node_id)
+            {
+                node_ids.push(cell.node_id);
+            }
+        }
+
+        let buses = self.config.assign_buses(&node_ids).ok_or(MatrixError::TooManyBuses)?;
+        self.config.set_buses(&buses);
+
+        for (send_id, send_bus) in buses.iter() {
+            if let NodeId::BusSend(_) = send_id {
+                for (recv_id, recv_bus) in buses.iter() {
+                    if let NodeId::BusRecv(_) = recv_id {
+                        if send_bus == recv_bus {
+                            self.graph_ordering.add_edge(*send_id, *recv_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
     }
 
     /// Compiles a [NodeProg] from the data collected by the previous
@@ -1341,6 +2327,10 @@ impl Matrix {
             );
         }
 
+        if !self.config.check_voice_region(&prog) {
+            return Err(MatrixError::VoiceRegionDependency);
+        }
+
         Ok(prog)
     }
 
@@ -1385,7 +2375,7 @@ impl Matrix {
     /// assert!(error);
     ///```
     pub fn check(&mut self) -> Result<(), MatrixError> {
-        self.update_graph_ordering_and_edges();
+        self.update_graph_ordering_and_edges()?;
 
         let mut edge_map = std::collections::HashMap::new();
         for edge in self.edges.iter() {
@@ -1420,7 +2410,7 @@ impl Matrix {
     pub fn sync(&mut self) -> Result<(), MatrixError> {
         self.create_intermediate_nodes();
 
-        self.update_graph_ordering_and_edges();
+        self.update_graph_ordering_and_edges()?;
         let prog = self.build_prog()?;
 
         self.config.upload_prog(prog, true); // true => copy_old_out
@@ -1459,6 +2449,8 @@ impl Matrix {
     }
 }
 
+/// Returns true if the tracker patterns or block functions of
+/// `a` and `b` differ, the rest of the [MatrixRepr] is ignored.
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,332 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::matrix::Cell;
+use crate::matrix_repr::{MatrixRepr, PatternRepr};
+use crate::nodes::NodeState;
+use crate::wblockdsp::BlockFunSnapshot;
+use crate::{NodeId, ParamId, SAtom};
+use std::collections::{HashMap, VecDeque};
+
+/// The default number of undo steps the [crate::Matrix] keeps.
+pub const DEFAULT_HISTORY_LEN: usize = 100;
+
+/// A single edit of the [crate::Matrix], with the values before and after it.
+/// An undo step applies the old values of it's edits in reverse order,
+/// a redo step the new values.
+#[derive(Debug, Clone)]
+pub(crate) enum Edit {
+    Cell {
+        x: usize,
+        y: usize,
+        old: Cell,
+        new: Cell,
+    },
+    /// `old` is `None` if the parameter was not set before.
+    Param {
+        param: ParamId,
+        old: Option<SAtom>,
+        new: SAtom,
+    },
+    ModAmt {
+        param: ParamId,
+        old: Option<f32>,
+        new: Option<f32>,
+    },
+    Prop {
+        key: String,
+        old: Option<SAtom>,
+        new: SAtom,
+    },
+    NodeState {
+        node_id: NodeId,
+        old: NodeState,
+        new: NodeState,
+    },
+    Resize {
+        old: (usize, usize),
+        new: (usize, usize),
+        shift: (i32, i32),
+    },
+    /// The state before [crate::Matrix::clear], without the patterns
+    /// and block functions, which are not cleared.
+    Clear {
+        old: Box<MatrixRepr>,
+    },
+    Pattern {
+        id: usize,
+        old: Option<PatternRepr>,
+        new: Option<PatternRepr>,
+    },
+    BlockFun {
+        id: usize,
+        old: Option<BlockFunSnapshot>,
+        new: Option<BlockFunSnapshot>,
+    },
+}
+
+/// Identifies what an [Edit] changed, consecutive edits of the same
+/// thing are merged into one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum EditKey {
+    Cell(usize, usize),
+    Param(ParamId),
+    ModAmt(ParamId),
+    Prop(String),
+    NodeState(NodeId),
+    Pattern(usize),
+    BlockFun(usize),
+}
+
+impl Edit {
+    /// Returns `None` for the edits that change the whole grid,
+    /// these can't be merged or reordered with other edits.
+    fn key(&self) -> Option<EditKey> {
+        match self {
+            Edit::Cell { x, y, .. } => Some(EditKey::Cell(*x, *y)),
+            Edit::Param { param, .. } => Some(EditKey::Param(*param)),
+            Edit::ModAmt { param, .. } => Some(EditKey::ModAmt(*param)),
+            Edit::Prop { key, .. } => Some(EditKey::Prop(key.to_string())),
+            Edit::NodeState { node_id, .. } => Some(EditKey::NodeState(*node_id)),
+            Edit::Pattern { id, .. } => Some(EditKey::Pattern(*id)),
+            Edit::BlockFun { id, .. } => Some(EditKey::BlockFun(*id)),
+            Edit::Resize { .. } | Edit::Clear { .. } => None,
+        }
+    }
+
+    /// Takes over the new value of the `later` edit of the same [EditKey].
+    fn merge(&mut self, later: Edit) {
+        match (self, later) {
+            (Edit::Cell { new, .. }, Edit::Cell { new: later, .. }) => *new = later,
+            (Edit::Param { new, .. }, Edit::Param { new: later, .. }) => *new = later,
+            (Edit::ModAmt { new, .. }, Edit::ModAmt { new: later, .. }) => *new = later,
+            (Edit::Prop { new, .. }, Edit::Prop { new: later, .. }) => *new = later,
+            (Edit::NodeState { new, .. }, Edit::NodeState { new: later, .. }) => *new = later,
+            (Edit::Pattern { new, .. }, Edit::Pattern { new: later, .. }) => *new = later,
+            (Edit::BlockFun { new, .. }, Edit::BlockFun { new: later, .. }) => *new = later,
+            _ => (),
+        }
+    }
+
+    /// Returns true if the edit did not change anything.
+    pub fn is_noop(&self) -> bool {
+        match self {
+            Edit::Cell { old, new, .. } => old == new,
+            Edit::Param { old, new, .. } => old.as_ref() == Some(new),
+            Edit::ModAmt { old, new, .. } => old == new,
+            Edit::Prop { old, new, .. } => old.as_ref() == Some(new),
+            Edit::NodeState { old, new, .. } => old == new,
+            Edit::Resize { old, new, shift } => old == new && *shift == (0, 0),
+            Edit::Pattern { old, new, .. } => old == new,
+            Edit::Clear { .. } | Edit::BlockFun { .. } => false,
+        }
+    }
+}
+
+/// Merges the edits of the same [EditKey] between the edits
+/// that change the whole grid and drops the edits without an effect.
+fn coalesce(edits: Vec<Edit>) -> Vec<Edit> {
+    let mut out: Vec<Edit> = vec![];
+    let mut index: HashMap<EditKey, usize> = HashMap::new();
+
+    for edit in edits.into_iter() {
+        match edit.key() {
+            Some(key) => {
+                if let Some(i) = index.get(&key) {
+                    out[*i].merge(edit);
+                } else {
+                    index.insert(key, out.len());
+                    out.push(edit);
+                }
+            }
+            None => {
+                index.clear();
+                out.push(edit);
+            }
+        }
+    }
+
+    out.retain(|edit| !edit.is_noop());
+    out
+}
+
+/// The undo/redo history of the [crate::Matrix]. Each step is the list
+/// of [Edit]s of a single edit or transaction.
+#[derive(Debug, Clone)]
+pub(crate) struct MatrixHistory {
+    undo: VecDeque<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    limit: usize,
+    /// Nesting depth of the currently open transactions.
+    depth: usize,
+    /// The edits of the currently open transaction.
+    pending: Vec<Edit>,
+    /// Set while the last undo step is a single parameter edit
+    /// that the next edit of the same parameter is merged into.
+    merge_last: bool,
+    /// Set while a step is restored, so that the edits
+    /// done for that are not recorded.
+    restoring: bool,
+    /// The generation and contents of the tracker patterns at the start
+    /// of the last transaction, to record the patterns it changed.
+    pub patterns: Vec<(usize, Option<PatternRepr>)>,
+    /// Like `patterns` for the block functions.
+    pub block_funs: Vec<(u64, Option<BlockFunSnapshot>)>,
+}
+
+impl MatrixHistory {
+    pub fn new() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: vec![],
+            limit: DEFAULT_HISTORY_LEN,
+            depth: 0,
+            pending: vec![],
+            merge_last: false,
+            restoring: false,
+            patterns: vec![],
+            block_funs: vec![],
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        while self.undo.len() > limit {
+            self.undo.pop_front();
+        }
+        self.redo.truncate(limit);
+    }
+
+    /// Returns true if edits are recorded, so that the
+    /// caller needs to collect the old values for an [Edit].
+    pub fn is_recording(&self) -> bool {
+        self.limit > 0 && !self.restoring
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.depth > 0
+    }
+
+    pub fn set_restoring(&mut self, restoring: bool) {
+        self.restoring = restoring;
+    }
+
+    /// Records an edit, outside of a transaction it is it's own undo step.
+    /// This invalidates the redo steps.
+    pub fn record(&mut self, edit: Edit) {
+        if !self.is_recording() {
+            return;
+        }
+
+        if self.depth > 0 {
+            self.pending.push(edit);
+            return;
+        }
+
+        if edit.is_noop() {
+            return;
+        }
+
+        let mergeable = matches!(edit, Edit::Param { .. } | Edit::ModAmt { .. });
+        if mergeable && self.merge_last {
+            if let Some([last]) = self.undo.back_mut().map(|step| &mut step[..]) {
+                if last.key() == edit.key() {
+                    last.merge(edit);
+                    if last.is_noop() {
+                        self.undo.pop_back();
+                        self.merge_last = false;
+                    }
+                    self.redo.clear();
+                    return;
+                }
+            }
+        }
+
+        self.push_step(vec![edit]);
+        self.merge_last = mergeable;
+    }
+
+    /// Stores the `edits` of a transaction as one undo step,
+    /// nothing is stored if they did not change anything.
+    pub fn push_step(&mut self, edits: Vec<Edit>) {
+        let edits = coalesce(edits);
+        if edits.is_empty() || self.limit == 0 {
+            return;
+        }
+
+        self.redo.clear();
+        self.merge_last = false;
+        self.push_undo(edits);
+    }
+
+    fn push_undo(&mut self, step: Vec<Edit>) {
+        if self.undo.len() >= self.limit {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(step);
+    }
+
+    /// Opens a transaction, returns true for the outermost one.
+    pub fn begin(&mut self) -> bool {
+        self.depth += 1;
+        self.depth == 1
+    }
+
+    /// Closes a transaction, returns the edits of the outermost
+    /// transaction when that was closed.
+    pub fn end(&mut self) -> Option<Vec<Edit>> {
+        if self.depth == 0 {
+            return None;
+        }
+
+        self.depth -= 1;
+        if self.depth == 0 {
+            Some(std::mem::take(&mut self.pending))
+        } else {
+            None
+        }
+    }
+
+    /// Takes the edits of the last undo step, they have to be given back
+    /// with [MatrixHistory::push_redo] after they were undone.
+    pub fn take_undo(&mut self) -> Option<Vec<Edit>> {
+        self.merge_last = false;
+        self.undo.pop_back()
+    }
+
+    pub fn push_redo(&mut self, step: Vec<Edit>) {
+        self.redo.push(step);
+    }
+
+    /// Takes the edits of the last redo step, they have to be given back
+    /// with [MatrixHistory::push_undone] after they were redone.
+    pub fn take_redo(&mut self) -> Option<Vec<Edit>> {
+        self.merge_last = false;
+        self.redo.pop()
+    }
+
+    pub fn push_undone(&mut self, step: Vec<Edit>) {
+        self.push_undo(step);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.merge_last = false;
+    }
+}
+
+impl Default for MatrixHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,630 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+/*! Automatic placement of arbitrary DSP graphs on the hexagonal [crate::Matrix].
+
+The [crate::MatrixLayout] takes a list of nodes and the edges between them and
+searches for cell positions on the grid, so that the edges are formed by
+adjacent cells. Edges that can't be formed that way, either because there is
+no room next to the nodes or because they close a cycle, are routed through a
+pair of `FbWr` and `FbRd` nodes. Keep in mind that these add the delay of the
+`FbRd` node, see it's documentation.
+
+As a cell has only 3 input and 3 output edges, a node can't have edges into
+more than 3 of it's inputs. The cells already placed in the [crate::Matrix]
+are left alone, [crate::MatrixLayout::layout] only uses the empty cells.
+
+```
+ use hexodsp::*;
+
+ let (node_conf, _node_exec) = new_node_engine();
+ let mut matrix = Matrix::new(node_conf, 7, 7);
+
+ let mut layout = MatrixLayout::new();
+ layout
+     .edge(NodeId::Sin(0), "sig", NodeId::Amp(0), "inp")
+     .edge(NodeId::Sin(1), "sig", NodeId::Amp(0), "att")
+     .edge(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1")
+     .edge(NodeId::Amp(0), "sig", NodeId::Out(0), "ch2");
+
+ let cells = layout.layout(&matrix).expect("enough room on the grid");
+ matrix.change_matrix_err(|matrix| matrix.place_multiple(&cells)).unwrap();
+ matrix.sync().unwrap();
+```
+*/
+
+use crate::matrix_region::to_axial;
+use crate::nodes::MAX_FB_DELAYS;
+use crate::{Cell, CellDir, Matrix, NodeId};
+
+#[derive(Debug, Clone, Copy)]
+struct LayoutEdge {
+    from: usize,
+    out: u8,
+    to: usize,
+    inp: u8,
+}
+
+impl LayoutEdge {
+    fn names(&self, nodes: &[NodeId]) -> ((NodeId, String), (NodeId, String)) {
+        let from = nodes[self.from];
+        let to = nodes[self.to];
+        (
+            (from, from.out_name_by_idx(self.out).unwrap_or("?").to_string()),
+            (to, to.inp_name_by_idx(self.inp).unwrap_or("?").to_string()),
+        )
+    }
+}
+
+/// Error type for the [crate::MatrixLayout].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    UnknownOutput(NodeId, String),
+    UnknownInput(NodeId, String),
+    /// More than one edge goes into the same input.
+    DuplicatedInput(NodeId, String),
+    /// The node has edges into more than 3 inputs.
+    TooManyInputs(NodeId),
+    /// The node needs more than 3 output edges for it's direct edges
+    /// and the `FbWr` nodes of it's outputs.
+    TooManyOutputs(NodeId),
+    /// There is no free cell left on the grid for the node.
+    NoSpace(NodeId),
+    /// There is no free cell next to the output or input of the edge
+    /// for the `FbWr` or `FbRd` node.
+    NoFeedbackSpace {
+        from: (NodeId, String),
+        to: (NodeId, String),
+    },
+    /// All [crate::nodes::MAX_FB_DELAYS] feedback buffers are in use.
+    FeedbackDelaysExhausted,
+}
+
+/// Places an arbitrary DSP graph on the hexagonal [crate::Matrix].
+///
+/// See the [crate::matrix_layout] module documentation for an example.
+#[derive(Debug, Clone, Default)]
+pub struct MatrixLayout {
+    nodes: Vec<NodeId>,
+    edges: Vec<LayoutEdge>,
+    error: Option<LayoutError>,
+}
+
+const OUTPUT_DIRS: [CellDir; 3] = [CellDir::TR, CellDir::BR, CellDir::B];
+const INPUT_DIRS: [CellDir; 3] = [CellDir::T, CellDir::TL, CellDir::BL];
+
+/// The state of one layout run.
+struct Placement {
+    w: usize,
+    h: usize,
+    occupied: Vec<bool>,
+    /// The index of the node at each grid position.
+    node_at: Vec<Option<usize>>,
+    pos: Vec<Option<(usize, usize)>>,
+    cells: Vec<Cell>,
+    /// The `FbWr` and `FbRd` cells.
+    extra: Vec<Cell>,
+    /// The edges that should be formed by adjacent cells.
+    direct: Vec<bool>,
+    formed: Vec<bool>,
+    out_needed: Vec<usize>,
+    in_needed: Vec<usize>,
+    /// The node ids that are in use, for allocating the feedback instances.
+    used_ids: Vec<NodeId>,
+    /// The feedback instance for each output that has a `FbWr`,
+    /// and whether the `FbWr` is placed.
+    writers: Vec<((usize, u8), usize, bool)>,
+}
+
+impl Placement {
+    fn is_free(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as usize) < self.w
+            && (y as usize) < self.h
+            && !self.occupied[x as usize * self.h + y as usize]
+    }
+
+    fn free_neighbour(&self, pos: (usize, usize), dir: CellDir) -> Option<(usize, usize)> {
+        let new_pos = dir.offs_pos(pos)?;
+        if self.is_free(new_pos.0 as i32, new_pos.1 as i32) {
+            Some(new_pos)
+        } else {
+            None
+        }
+    }
+
+    fn free_neighbours(&self, pos: (usize, usize)) -> usize {
+        (0..6).filter(|edge| self.free_neighbour(pos, CellDir::from(*edge)).is_some()).count()
+    }
+
+    fn occupy(&mut self, pos: (usize, usize), n: Option<usize>) {
+        self.occupied[pos.0 * self.h + pos.1] = true;
+        self.node_at[pos.0 * self.h + pos.1] = n;
+    }
+
+    fn node_at(&self, pos: (usize, usize)) -> Option<usize> {
+        if pos.0 < self.w && pos.1 < self.h {
+            self.node_at[pos.0 * self.h + pos.1]
+        } else {
+            None
+        }
+    }
+
+    /// Places the `node_id` with the port `cell_port` in a free cell next to the
+    /// node `n`, in one of the `dirs` where `n` has no port yet.
+    /// The port of `n` in that direction is set to `port`.
+    fn attach(
+        &mut self,
+        n: usize,
+        dirs: [CellDir; 3],
+        port: u8,
+        node_id: NodeId,
+        cell_port: u8,
+    ) -> Option<()> {
+        let n_pos = self.pos[n]?;
+        let (dir, pos) = dirs
+            .iter()
+            .filter(|dir| !self.cells[n].has_dir_set(**dir))
+            .find_map(|dir| self.free_neighbour(n_pos, *dir).map(|pos| (*dir, pos)))?;
+
+        self.cells[n].set_io_dir(dir, port as usize);
+        let mut cell = Cell::empty_at(node_id, pos.0 as u8, pos.1 as u8);
+        cell.set_io_dir(dir.flip(), cell_port as usize);
+        self.occupy(pos, None);
+        self.extra.push(cell);
+
+        Some(())
+    }
+
+    /// Returns the number of free neighbour cells on the output or input
+    /// side of the placed node `n`, that are left after the edges
+    /// it still needs there.
+    fn spare_slots(&self, n: usize, output: bool) -> i32 {
+        let (dirs, needed) = if output {
+            (OUTPUT_DIRS, self.out_needed[n])
+        } else {
+            (INPUT_DIRS, self.in_needed[n])
+        };
+
+        let pos = self.pos[n].expect("placed node");
+        let free = dirs.iter().filter(|d| self.free_neighbour(pos, **d).is_some()).count();
+        let used = dirs.iter().filter(|d| self.cells[n].has_dir_set(**d)).count();
+
+        free as i32 - (needed as i32 - used as i32)
+    }
+}
+
+fn hex_distance(a: (usize, usize), b: (usize, usize)) -> i32 {
+    let (aq, ar) = to_axial(a.0 as i32, a.1 as i32);
+    let (bq, br) = to_axial(b.0 as i32, b.1 as i32);
+    let (dq, dr) = (aq - bq, ar - br);
+    (dq.abs() + dr.abs() + (dq + dr).abs()) / 2
+}
+
+/// Returns the output direction of `from`, in which `to` is adjacent.
+fn output_dir(from: (usize, usize), to: (usize, usize)) -> Option<CellDir> {
+    match CellDir::are_adjacent(from, to) {
+        Some(dir) if dir.is_output() => Some(dir),
+        _ => None,
+    }
+}
+
+impl MatrixLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn node_idx(&mut self, node_id: NodeId) -> usize {
+        if let Some(idx) = self.nodes.iter().position(|n| *n == node_id) {
+            idx
+        } else {
+            self.nodes.push(node_id);
+            self.nodes.len() - 1
+        }
+    }
+
+    /// Adds a node to the graph. Nodes are also added by [MatrixLayout::edge],
+    /// so this is only needed for unconnected nodes or to influence
+    /// the order the nodes are placed in.
+    pub fn node(&mut self, node_id: NodeId) -> &mut Self {
+        self.node_idx(node_id);
+        self
+    }
+
+    /// Adds an edge from the output `out` of `from` to the input `inp` of `to`.
+    ///
+    /// If the output or input names are unknown or the input already has an edge,
+    /// the error will be returned by [MatrixLayout::layout].
+    pub fn edge(&mut self, from: NodeId, out: &str, to: NodeId, inp: &str) -> &mut Self {
+        let out_idx = if let Some(idx) = from.out(out) {
+            idx
+        } else {
+            self.error = Some(LayoutError::UnknownOutput(from, out.to_string()));
+            return self;
+        };
+
+        let inp_idx = if let Some(idx) = to.inp(inp) {
+            idx
+        } else {
+            self.error = Some(LayoutError::UnknownInput(to, inp.to_string()));
+            return self;
+        };
+
+        let from = self.node_idx(from);
+        let to_idx = self.node_idx(to);
+
+        if self.edges.iter().any(|e| e.to == to_idx && e.inp == inp_idx) {
+            self.error = Some(LayoutError::DuplicatedInput(to, inp.to_string()));
+            return self;
+        }
+
+        self.edges.push(LayoutEdge { from, out: out_idx, to: to_idx, inp: inp_idx });
+        self
+    }
+
+    /// Returns true if `to` can be reached from `from` via the `edges`.
+    fn reaches(&self, edges: &[LayoutEdge], from: usize, to: usize) -> bool {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![from];
+
+        while let Some(n) = stack.pop() {
+            if n == to {
+                return true;
+            }
+
+            if !visited[n] {
+                visited[n] = true;
+                stack.extend(edges.iter().filter(|e| e.from == n).map(|e| e.to));
+            }
+        }
+
+        false
+    }
+
+    /// Returns the order in which the nodes are placed: Breadth first
+    /// along the edges, starting with the nodes in the order they were added.
+    fn placement_order(&self) -> Vec<usize> {
+        let mut order = vec![];
+        let mut queued = vec![false; self.nodes.len()];
+
+        for start in 0..self.nodes.len() {
+            if queued[start] {
+                continue;
+            }
+
+            queued[start] = true;
+            let mut queue = std::collections::VecDeque::from(vec![start]);
+
+            while let Some(n) = queue.pop_front() {
+                order.push(n);
+
+                for e in self.edges.iter() {
+                    let other = if e.from == n {
+                        e.to
+                    } else if e.to == n {
+                        e.from
+                    } else {
+                        continue;
+                    };
+
+                    if !queued[other] {
+                        queued[other] = true;
+                        queue.push_back(other);
+                    }
+                }
+            }
+        }
+
+        order
+    }
+
+    /// Searches a layout for the graph in the free cells of the `matrix`.
+    ///
+    /// Returns the cells for [crate::Matrix::place_multiple], including
+    /// the `FbWr` and `FbRd` nodes with unused instances for the
+    /// edges that could not be formed by adjacent cells.
+    pub fn layout(&self, matrix: &Matrix) -> Result<Vec<Cell>, LayoutError> {
+        if let Some(err) = &self.error {
+            return Err(err.clone());
+        }
+
+        let (w, h) = matrix.size();
+        let mut occupied = vec![false; w * h];
+        let mut used_ids = self.nodes.clone();
+        matrix.for_each(|x, y, cell| {
+            if !cell.is_empty() {
+                occupied[x * h + y] = true;
+                used_ids.push(cell.node_id());
+            }
+        });
+
+        let mut pl = Placement {
+            w,
+            h,
+            occupied,
+            node_at: vec![None; w * h],
+            pos: vec![None; self.nodes.len()],
+            cells: self.nodes.iter().map(|n| Cell::empty(*n)).collect(),
+            extra: vec![],
+            direct: vec![],
+            formed: vec![false; self.edges.len()],
+            out_needed: vec![0; self.nodes.len()],
+            in_needed: vec![0; self.nodes.len()],
+            used_ids,
+            writers: vec![],
+        };
+
+        // Only edges that don't close a cycle can be formed by adjacent cells,
+        // and only one edge between two nodes:
+        let mut direct: Vec<LayoutEdge> = vec![];
+        let mut is_direct = vec![false; self.edges.len()];
+        for (i, e) in self.edges.iter().enumerate() {
+            let parallel = direct.iter().any(|d| d.from == e.from && d.to == e.to);
+            if !parallel && !self.reaches(&direct, e.to, e.from) {
+                direct.push(*e);
+                is_direct[i] = true;
+            }
+        }
+
+        for n in 0..self.nodes.len() {
+            let inputs = self.edges.iter().filter(|e| e.to == n).count();
+            if inputs > 3 {
+                return Err(LayoutError::TooManyInputs(self.nodes[n]));
+            }
+            pl.in_needed[n] = inputs;
+
+            pl.out_needed[n] = loop {
+                let slots = self.output_slots(&is_direct, n);
+                if slots <= 3 {
+                    break slots;
+                }
+
+                // A FbWr is shared by all edges from the same output:
+                let last_direct =
+                    (0..self.edges.len()).rev().find(|i| is_direct[*i] && self.edges[*i].from == n);
+                if let Some(i) = last_direct {
+                    is_direct[i] = false;
+                } else {
+                    return Err(LayoutError::TooManyOutputs(self.nodes[n]));
+                }
+            };
+        }
+        pl.direct = is_direct;
+
+        for n in self.placement_order() {
+            let pos = self.find_position(&pl, n).ok_or(LayoutError::NoSpace(self.nodes[n]))?;
+            pl.occupy(pos, Some(n));
+            pl.pos[n] = Some(pos);
+            pl.cells[n] = Cell::empty_at(self.nodes[n], pos.0 as u8, pos.1 as u8);
+
+            for (i, e) in self.edges.iter().enumerate() {
+                if !pl.direct[i] || pl.formed[i] || (e.from != n && e.to != n) {
+                    continue;
+                }
+
+                if let (Some(from_pos), Some(to_pos)) = (pl.pos[e.from], pl.pos[e.to]) {
+                    if let Some(dir) = output_dir(from_pos, to_pos) {
+                        if !pl.cells[e.from].has_dir_set(dir)
+                            && !pl.cells[e.to].has_dir_set(dir.flip())
+                        {
+                            pl.cells[e.from].set_io_dir(dir, e.out as usize);
+                            pl.cells[e.to].set_io_dir(dir.flip(), e.inp as usize);
+                            pl.formed[i] = true;
+                        }
+                    }
+                }
+            }
+
+            // The feedback cells are placed right away, so that no
+            // other node takes their place:
+            for (i, e) in self.edges.iter().enumerate() {
+                if !pl.direct[i] {
+                    if e.from == n {
+                        self.place_writer(matrix, &mut pl, e)?;
+                    }
+                    if e.to == n {
+                        self.place_reader(matrix, &mut pl, e)?;
+                    }
+                }
+            }
+        }
+
+        // The direct edges that could not be formed also need feedback cells:
+        for (i, e) in self.edges.iter().enumerate() {
+            if pl.direct[i] && !pl.formed[i] {
+                self.place_writer(matrix, &mut pl, e)?;
+                self.place_reader(matrix, &mut pl, e)?;
+            }
+        }
+
+        let mut cells = pl.cells;
+        cells.append(&mut pl.extra);
+        Ok(cells)
+    }
+
+    fn no_feedback_space(&self, e: &LayoutEdge) -> LayoutError {
+        let (from, to) = e.names(&self.nodes);
+        LayoutError::NoFeedbackSpace { from, to }
+    }
+
+    /// Returns the feedback instance for the output of the edge `e`.
+    /// All edges from the same output share it.
+    fn feedback_idx(
+        &self,
+        matrix: &Matrix,
+        pl: &mut Placement,
+        e: &LayoutEdge,
+    ) -> Result<usize, LayoutError> {
+        if let Some((_, idx, _)) = pl.writers.iter().find(|(o, _, _)| *o == (e.from, e.out)) {
+            return Ok(*idx);
+        }
+
+        let idx = next_feedback_idx(matrix, &pl.used_ids)?;
+        pl.used_ids.push(NodeId::FbWr(0).to_instance(idx));
+        pl.used_ids.push(NodeId::FbRd(0).to_instance(idx));
+        pl.writers.push(((e.from, e.out), idx, false));
+        Ok(idx)
+    }
+
+    /// Places the `FbWr` next to the output of the edge `e`,
+    /// unless the output already has one.
+    fn place_writer(
+        &self,
+        matrix: &Matrix,
+        pl: &mut Placement,
+        e: &LayoutEdge,
+    ) -> Result<(), LayoutError> {
+        let idx = self.feedback_idx(matrix, pl, e)?;
+        if pl.writers.iter().any(|(o, _, placed)| *o == (e.from, e.out) && *placed) {
+            return Ok(());
+        }
+
+        let wr = NodeId::FbWr(0).to_instance(idx);
+        pl.attach(e.from, OUTPUT_DIRS, e.out, wr, 0).ok_or_else(|| self.no_feedback_space(e))?;
+
+        for (o, _, placed) in pl.writers.iter_mut() {
+            if *o == (e.from, e.out) {
+                *placed = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Places the `FbRd` next to the input of the edge `e`.
+    fn place_reader(
+        &self,
+        matrix: &Matrix,
+        pl: &mut Placement,
+        e: &LayoutEdge,
+    ) -> Result<(), LayoutError> {
+        let idx = self.feedback_idx(matrix, pl, e)?;
+        let rd = NodeId::FbRd(0).to_instance(idx);
+        pl.attach(e.to, INPUT_DIRS, e.inp, rd, 0).ok_or_else(|| self.no_feedback_space(e))
+    }
+
+    /// Returns the number of output edges the node `n` needs: One for each
+    /// target of the direct edges and one for each `FbWr`.
+    fn output_slots(&self, is_direct: &[bool], n: usize) -> usize {
+        let mut targets = vec![];
+        let mut fb_outs = vec![];
+
+        for (i, e) in self.edges.iter().enumerate() {
+            if e.from != n {
+                continue;
+            }
+
+            if is_direct[i] {
+                if !targets.contains(&e.to) {
+                    targets.push(e.to);
+                }
+            } else if !fb_outs.contains(&e.out) {
+                fb_outs.push(e.out);
+            }
+        }
+
+        targets.len() + fb_outs.len()
+    }
+
+    /// Returns true if there is a direct edge from `from` to `to`,
+    /// that is not formed yet.
+    fn has_open_edge(&self, pl: &Placement, from: usize, to: usize) -> bool {
+        self.edges
+            .iter()
+            .enumerate()
+            .any(|(i, e)| pl.direct[i] && !pl.formed[i] && e.from == from && e.to == to)
+    }
+
+    /// Finds the free cell for the node `n`. The best cell does not take
+    /// away the room the placed nodes need for their edges, forms the most
+    /// edges to them, is closest to them and leaves the most room
+    /// for further edges.
+    fn find_position(&self, pl: &Placement, n: usize) -> Option<(usize, usize)> {
+        let mut best: Option<((i32, usize, i32, usize), (usize, usize))> = None;
+
+        for x in 0..pl.w {
+            for y in 0..pl.h {
+                if !pl.is_free(x as i32, y as i32) {
+                    continue;
+                }
+
+                let pos = (x, y);
+                let mut formed_out = 0;
+                let mut formed_in = 0;
+                let mut blocked = 0;
+
+                for edge in 0..6 {
+                    let dir = CellDir::from(edge);
+                    let m = if let Some(m) = dir.offs_pos(pos).and_then(|p| pl.node_at(p)) {
+                        m
+                    } else {
+                        continue;
+                    };
+
+                    // The direction from the placed node `m` to `pos`:
+                    let m_dir = dir.flip();
+                    if m_dir.is_output() && self.has_open_edge(pl, m, n) {
+                        formed_in += 1;
+                    } else if m_dir.is_input() && self.has_open_edge(pl, n, m) {
+                        formed_out += 1;
+                    } else if pl.spare_slots(m, m_dir.is_output()) <= 0 {
+                        blocked += 1;
+                    }
+                }
+
+                let free_out = OUTPUT_DIRS.iter().filter(|d| pl.free_neighbour(pos, **d).is_some());
+                let free_in = INPUT_DIRS.iter().filter(|d| pl.free_neighbour(pos, **d).is_some());
+                let cramped = (pl.out_needed[n] as i32 - formed_out - free_out.count() as i32)
+                    .max(0)
+                    + (pl.in_needed[n] as i32 - formed_in - free_in.count() as i32).max(0);
+
+                let mut distance = 0;
+                for e in self.edges.iter() {
+                    let other = if e.from == n {
+                        e.to
+                    } else if e.to == n {
+                        e.from
+                    } else {
+                        continue;
+                    };
+
+                    if let Some(other_pos) = pl.pos[other] {
+                        distance += hex_distance(pos, other_pos);
+                    }
+                }
+
+                let score = (
+                    -(blocked + cramped),
+                    (formed_out + formed_in) as usize,
+                    -distance,
+                    pl.free_neighbours(pos),
+                );
+                if best.map(|(best_score, _)| score > best_score).unwrap_or(true) {
+                    best = Some((score, pos));
+                }
+            }
+        }
+
+        best.map(|(_, pos)| pos)
+    }
+}
+
+/// Finds the lowest instance index, that is neither used by a
+/// `FbWr` nor a `FbRd` node.
+fn next_feedback_idx(matrix: &Matrix, used_ids: &[NodeId]) -> Result<usize, LayoutError> {
+    for idx in 0..MAX_FB_DELAYS {
+        let wr = NodeId::FbWr(0).to_instance(idx);
+        let rd = NodeId::FbRd(0).to_instance(idx);
+
+        if !used_ids.contains(&wr)
+            && !used_ids.contains(&rd)
+            && matrix.get_unused_instance_node_id(wr) == wr
+            && matrix.get_unused_instance_node_id(rd) == rd
+        {
+            return Ok(idx);
+        }
+    }
+
+    Err(LayoutError::FeedbackDelaysExhausted)
+}
@@ -0,0 +1,274 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{NodeId, ParamId, SAtom};
+use crate::matrix::{Cell, MatrixError};
+use crate::CellDir;
+
+/// Converts a grid position into axial hex coordinates. The odd columns
+/// of the grid are shifted down by half a cell, see [CellDir::as_offs].
+pub(crate) fn to_axial(x: i32, y: i32) -> (i32, i32) {
+    (x, y - (x - (x & 1)) / 2)
+}
+
+/// Converts axial hex coordinates back into a grid position.
+pub(crate) fn from_axial(q: i32, r: i32) -> (i32, i32) {
+    (q, r + (q - (q & 1)) / 2)
+}
+
+/// The shape of a region of the [crate::Matrix], see [crate::Matrix::copy_region].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionShape {
+    /// `w` columns and `h` rows, the origin is the top left cell.
+    Rect { w: usize, h: usize },
+    /// All cells at most `radius` steps away from the origin.
+    Hex { radius: usize },
+}
+
+impl RegionShape {
+    /// Returns the grid positions of the region with the origin at `x`/`y`.
+    /// The positions are not clipped to the grid.
+    pub fn positions(&self, x: usize, y: usize) -> Vec<(i32, i32)> {
+        let (x, y) = (x as i32, y as i32);
+
+        match *self {
+            RegionShape::Rect { w, h } => {
+                let mut pos = vec![];
+                for px in x..(x + w as i32) {
+                    for py in y..(y + h as i32) {
+                        pos.push((px, py));
+                    }
+                }
+                pos
+            }
+            RegionShape::Hex { radius } => {
+                let radius = radius as i32;
+                let (oq, or) = to_axial(x, y);
+
+                let mut pos = vec![];
+                for q in -radius..=radius {
+                    for r in (-radius).max(-q - radius)..=radius.min(radius - q) {
+                        pos.push(from_axial(oq + q, or + r));
+                    }
+                }
+                pos
+            }
+        }
+    }
+}
+
+/// A copy of a region of the [crate::Matrix], made with [crate::Matrix::copy_region].
+/// It stores the cells and the parameters, atoms and modulation amounts of
+/// their nodes. The cell positions are relative to the origin of the region,
+/// so it can be pasted anywhere with [crate::Matrix::paste_region].
+///
+/// The region can be rotated and mirrored. As the cells only have inputs at
+/// the top and left edges and outputs at the bottom and right edges, a
+/// transformation that turns a port onto an edge of the other kind is
+/// rejected. `transform(true, -2)` keeps all ports, it mirrors the region
+/// at the axis from the top left to the bottom right.
+///
+///```
+/// use hexodsp::*;
+/// use hexodsp::matrix::RegionShape;
+///
+/// let (node_conf, mut node_exec) = new_node_engine();
+/// let mut matrix = Matrix::new(node_conf, 5, 5);
+///
+/// let mut chain = MatrixCellChain::new(CellDir::B);
+/// chain.node_out("sin", "sig").node_inp("amp", "inp").place(&mut matrix, 0, 0).unwrap();
+///
+/// let mut region = matrix.copy_region(0, 0, RegionShape::Rect { w: 1, h: 2 });
+/// region.transform(true, -2).unwrap();
+/// matrix.paste_region(2, 2, &region).unwrap();
+///
+/// assert_eq!(matrix.get(2, 2).unwrap().node_id(), NodeId::Sin(1));
+/// // The Amp is now at the top right of the Sin:
+/// assert_eq!(matrix.get(3, 1).unwrap().node_id(), NodeId::Amp(1));
+/// assert_eq!(matrix.get_adjacent_output(3, 1, CellDir::BL), Some((NodeId::Sin(1), 0)));
+///```
+#[derive(Debug, Clone, Default)]
+pub struct MatrixRegion {
+    /// The cells with the axial coordinates relative to the origin.
+    cells: Vec<((i32, i32), Cell)>,
+    params: Vec<(ParamId, SAtom)>,
+    modamts: Vec<(ParamId, f32)>,
+}
+
+impl MatrixRegion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn add_cell(&mut self, offs: (i32, i32), cell: Cell) {
+        self.cells.push((offs, cell));
+    }
+
+    pub(crate) fn add_param(&mut self, param: ParamId, at: SAtom) {
+        self.params.push((param, at));
+    }
+
+    pub(crate) fn add_modamt(&mut self, param: ParamId, modamt: f32) {
+        self.modamts.push((param, modamt));
+    }
+
+    pub(crate) fn params(&self) -> &[(ParamId, SAtom)] {
+        &self.params[..]
+    }
+
+    pub(crate) fn modamts(&self) -> &[(ParamId, f32)] {
+        &self.modamts[..]
+    }
+
+    /// The number of (non empty) cells in the region.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Returns the nodes in the region, each only once.
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        let mut node_ids = vec![];
+        for (_, cell) in self.cells.iter() {
+            if !node_ids.contains(&cell.node_id()) {
+                node_ids.push(cell.node_id());
+            }
+        }
+        node_ids
+    }
+
+    /// Returns the cells with the grid positions they get when the region
+    /// is placed with the origin at `x`/`y`. The positions are not clipped
+    /// to the grid.
+    pub fn cells_at(&self, x: usize, y: usize) -> Vec<((i32, i32), Cell)> {
+        let (oq, or) = to_axial(x as i32, y as i32);
+
+        self.cells.iter().map(|((q, r), cell)| (from_axial(oq + q, or + r), *cell)).collect()
+    }
+
+    /// Rotates the region clockwise by `steps` of 60° around its origin,
+    /// negative `steps` rotate counter clockwise. See [MatrixRegion::transform]
+    /// for the errors.
+    pub fn rotate(&mut self, steps: i32) -> Result<(), MatrixError> {
+        self.transform(false, steps)
+    }
+
+    /// Mirrors the region at the vertical axis through its origin.
+    /// See [MatrixRegion::transform] for the errors.
+    pub fn mirror(&mut self) -> Result<(), MatrixError> {
+        self.transform(true, 0)
+    }
+
+    /// Mirrors the region at the vertical axis through its origin if `mirror`
+    /// is set, and then rotates it clockwise by `steps` of 60°.
+    ///
+    /// Returns [MatrixError::PortOnWrongEdge] with the first cell that has
+    /// a port which would end up on an edge of the other kind. The connection
+    /// of that port could not be kept, so the region is left unchanged.
+    pub fn transform(&mut self, mirror: bool, steps: i32) -> Result<(), MatrixError> {
+        let steps = steps.rem_euclid(6);
+        let map_dir = |dir: CellDir| (if mirror { dir.mirror() } else { dir }).rotate(steps);
+
+        let mut cells = vec![];
+        for ((q, r), cell) in self.cells.iter() {
+            let (mut q, mut r) = if mirror { (-*q, *q + *r) } else { (*q, *r) };
+            for _ in 0..steps {
+                let (nq, nr) = (-r, q + r);
+                q = nq;
+                r = nr;
+            }
+
+            let new =
+                map_ports(cell, map_dir).ok_or(MatrixError::PortOnWrongEdge { cell: *cell })?;
+            cells.push(((q, r), new));
+        }
+
+        self.cells = cells;
+        Ok(())
+    }
+}
+
+/// Moves the ports of the `cell` to the directions returned by `f`.
+/// Returns `None` if an input ends up on an output edge or vice versa.
+fn map_ports<F: Fn(CellDir) -> CellDir>(cell: &Cell, f: F) -> Option<Cell> {
+    let mut new = *cell;
+    new.clear_io_dir(CellDir::C);
+
+    for edge in 0..6 {
+        let dir = CellDir::from(edge);
+
+        if let Some(idx) = cell.local_port_idx(dir) {
+            let new_dir = f(dir);
+            if new_dir.is_input() != dir.is_input() {
+                return None;
+            }
+
+            new.set_io_dir(new_dir, idx as usize);
+        }
+    }
+
+    Some(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_axial_roundtrip() {
+        for x in -3..7 {
+            for y in -3..7 {
+                let (q, r) = to_axial(x, y);
+                assert_eq!(from_axial(q, r), (x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn check_axial_neighbours() {
+        // The axial offsets of the directions TR, BR, B, BL, TL and T:
+        let axial_offs = [(1, -1), (1, 0), (0, 1), (-1, 1), (-1, 0), (0, -1)];
+
+        for x in 1..5 {
+            for edge in 0..6 {
+                let dir = CellDir::from(edge);
+                let pos = dir.offs_pos((x, 3)).unwrap();
+                let (q, r) = to_axial(x as i32, 3);
+                let (nq, nr) = to_axial(pos.0 as i32, pos.1 as i32);
+                assert_eq!((nq - q, nr - r), axial_offs[edge as usize], "{:?} at x={}", dir, x);
+            }
+        }
+    }
+
+    #[test]
+    fn check_hex_shape() {
+        let pos = RegionShape::Hex { radius: 1 }.positions(3, 3);
+        assert_eq!(pos.len(), 7);
+        for edge in 0..6 {
+            let (x, y) = CellDir::from(edge).offs_pos((3, 3)).unwrap();
+            assert!(pos.contains(&(x as i32, y as i32)));
+        }
+
+        assert_eq!(RegionShape::Hex { radius: 2 }.positions(4, 4).len(), 19);
+    }
+
+    #[test]
+    fn check_rotate_ports() {
+        let cell = Cell::empty(NodeId::Sin(0)).input(Some(0), None, None).out(Some(0), None, None);
+
+        // T => TR would turn the input onto an output edge:
+        assert_eq!(map_ports(&cell, |dir| dir.rotate(1)), None);
+
+        // TR => BR is kept:
+        let out = Cell::empty(NodeId::Sin(0)).out(Some(0), None, None);
+        let rotated = map_ports(&out, |dir| dir.rotate(1)).unwrap();
+        assert_eq!(rotated.local_port_idx(CellDir::BR), Some(0));
+        assert!(!rotated.has_dir_set(CellDir::TR));
+
+        assert_eq!(map_ports(&cell, |dir| dir.rotate(6)), Some(cell));
+    }
+}
@@ -3,6 +3,7 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{NodeId, ParamId, SAtom};
+use crate::nodes::NodeState;
 use serde_json::{json, Value};
 use crate::wblockdsp::BlockFunSnapshot;
 
@@ -103,7 +104,7 @@ impl CellRepr {
 
 use crate::dsp::tracker::{MAX_COLS, MAX_PATTERN_LEN};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PatternRepr {
     pub col_types: [u8; MAX_COLS],
     pub data: Vec<Vec<i32>>,
@@ -183,12 +184,15 @@ impl PatternRepr {
 
 #[derive(Debug, Clone)]
 pub struct MatrixRepr {
+    /// The width and height of the grid, not set by older patches.
+    pub size: Option<(usize, usize)>,
     pub cells: Vec<CellRepr>,
     pub params: Vec<(ParamId, f32, Option<f32>)>,
     pub atoms: Vec<(ParamId, SAtom)>,
     pub patterns: Vec<Option<PatternRepr>>,
     pub properties: Vec<(String, SAtom)>,
     pub block_funs: Vec<Option<BlockFunSnapshot>>,
+    pub node_states: Vec<(NodeId, NodeState)>,
     pub version: i64,
 }
 
@@ -227,7 +231,7 @@ impl From<std::io::Error> for MatrixDeserError {
     }
 }
 
-fn deserialize_atom(v: &Value) -> Result<SAtom, MatrixDeserError> {
+pub(crate) fn deserialize_atom(v: &Value) -> Result<SAtom, MatrixDeserError> {
     match v[0].as_str().unwrap_or("?") {
         "i" => {
             if let Some(v) = v[1].as_i64() {
@@ -274,7 +278,7 @@ fn deserialize_atom(v: &Value) -> Result<SAtom, MatrixDeserError> {
     }
 }
 
-fn serialize_atom(atom: &SAtom) -> Value {
+pub(crate) fn serialize_atom(atom: &SAtom) -> Value {
     match atom {
         SAtom::MicroSample(s) => json!(["ms", s[0], s[1], s[2], s[3], s[4], s[5], s[6], s[7],]),
         SAtom::Str(s) => json!(["s", s]),
@@ -292,8 +296,19 @@ impl MatrixRepr {
         let patterns = vec![];
         let properties = vec![];
         let block_funs = vec![];
-
-        Self { cells, params, atoms, patterns, block_funs, properties, version: 2 }
+        let node_states = vec![];
+
+        Self {
+            size: None,
+            cells,
+            params,
+            atoms,
+            patterns,
+            block_funs,
+            properties,
+            node_states,
+            version: 2,
+        }
     }
 
     pub fn write_to_file(&mut self, filepath: &str) -> std::io::Result<()> {
@@ -390,6 +405,29 @@ impl MatrixRepr {
             }
         }
 
+        if let Value::Array(size) = &v["size"] {
+            if let (Some(w), Some(h)) =
+                (size.get(0).and_then(|w| w.as_u64()), size.get(1).and_then(|h| h.as_u64()))
+            {
+                m.size = Some((w as usize, h as usize));
+            }
+        }
+
+        let node_states = &v["node_states"];
+        if let Value::Array(node_states) = node_states {
+            for v in node_states.iter() {
+                let node_id = deserialize_node_id(&v, 0, 1)?;
+                if let Some(state) = NodeState::from_name(v[2].as_str().unwrap_or("")) {
+                    m.node_states.push((node_id, state));
+                } else {
+                    return Err(MatrixDeserError::Deserialization(format!(
+                        "Unknown node state: {}",
+                        v
+                    )));
+                }
+            }
+        }
+
         let patterns = &v["patterns"];
         if let Value::Array(patterns) = patterns {
             for p in patterns.iter() {
@@ -423,6 +461,7 @@ impl MatrixRepr {
         self.properties.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
         self.params.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
         self.atoms.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        self.node_states.sort_by(|a, b| a.0.cmp(&b.0));
 
         let mut params = json!([]);
         if let Value::Array(params) = &mut params {
@@ -464,6 +503,23 @@ impl MatrixRepr {
 
         v["props"] = props;
 
+        if let Some((w, h)) = self.size {
+            v["size"] = json!([w, h]);
+        }
+
+        // Only saved if some nodes are bypassed or muted,
+        // so that the other patches stay the same:
+        if !self.node_states.is_empty() {
+            let mut node_states = json!([]);
+            if let Value::Array(node_states) = &mut node_states {
+                for (node_id, state) in self.node_states.iter() {
+                    node_states.push(json!([node_id.name(), node_id.instance(), state.name()]));
+                }
+            }
+
+            v["node_states"] = node_states;
+        }
+
         let mut cells = json!([]);
         if let Value::Array(cells) = &mut cells {
             for cell in self.cells.iter() {
@@ -556,7 +612,7 @@ mod tests {
         let s = mr.serialize();
 
         assert_eq!(s,
-            "{\"VERSION\":2,\"atoms\":[[\"out\",0,\"mono\",[\"i\",0]]],\"cells\":[[\"sin\",2,0,0,[-1,-1,-1],[-1,\"sig\",-1]],[\"out\",0,1,0,[-1,\"ch1\",-1],[-1,-1,-1]]],\"params\":[[\"out\",0,\"ch1\",0.0],[\"out\",0,\"ch2\",0.0],[\"sin\",0,\"det\",0.0],[\"sin\",1,\"det\",0.0],[\"sin\",2,\"det\",0.0],[\"sin\",0,\"freq\",440.0],[\"sin\",1,\"freq\",440.0],[\"sin\",2,\"freq\",220.0],[\"out\",0,\"gain\",1.0]],\"patterns\":[null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null],\"props\":[]}");
+            "{\"VERSION\":2,\"atoms\":[[\"out\",0,\"mono\",[\"i\",0]]],\"cells\":[[\"sin\",2,0,0,[-1,-1,-1],[-1,\"sig\",-1]],[\"out\",0,1,0,[-1,\"ch1\",-1],[-1,-1,-1]]],\"params\":[[\"out\",0,\"ch1\",0.0],[\"out\",0,\"ch2\",0.0],[\"sin\",0,\"det\",0.0],[\"sin\",1,\"det\",0.0],[\"sin\",2,\"det\",0.0],[\"sin\",0,\"freq\",440.0],[\"sin\",1,\"freq\",440.0],[\"sin\",2,\"freq\",220.0],[\"out\",0,\"gain\",1.0]],\"patterns\":[null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null],\"props\":[],\"size\":[3,3]}");
         let mut mr2 = MatrixRepr::deserialize(&s).unwrap();
 
         let s2 = mr2.serialize();
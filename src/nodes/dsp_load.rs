@@ -0,0 +1,180 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use synfx_dsp::AtomicFloat;
+
+use std::sync::Arc;
+
+/// The coefficient of the exponential moving average per block.
+/// Roughly averages over the last 20 blocks.
+const AVG_COEF: f32 = 0.05;
+
+/// The DSP load of a node or the whole graph, as measured by the
+/// [crate::nodes::NodeExecutor] while profiling is enabled.
+/// See also [crate::nodes::NodeConfigurator::set_dsp_profiling].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DspLoad {
+    /// Moving average of the processing time per block in nanoseconds.
+    pub avg_ns: f32,
+    /// The longest processing time of a block in nanoseconds
+    /// since the last reset of the peaks.
+    pub peak_ns: f32,
+    /// Moving average of the used fraction of the real time that
+    /// is available for a block. `1.0` means 100% of one CPU core.
+    pub load: f32,
+}
+
+type LoadSlot = [AtomicFloat; 3];
+
+fn new_slot() -> LoadSlot {
+    [AtomicFloat::new(0.0), AtomicFloat::new(0.0), AtomicFloat::new(0.0)]
+}
+
+fn load_of(v: &LoadSlot) -> DspLoad {
+    DspLoad { avg_ns: v[0].get(), peak_ns: v[1].get(), load: v[2].get() }
+}
+
+/// The DSP load values that are published lock-free by the audio thread.
+/// Holds one slot per node index and one for the whole graph.
+pub(crate) struct DspLoadValues {
+    values: Vec<LoadSlot>,
+    graph: LoadSlot,
+}
+
+impl DspLoadValues {
+    pub(crate) fn new_shared(node_count: usize) -> Arc<Self> {
+        let mut values = Vec::new();
+        values.resize_with(node_count, new_slot);
+        Arc::new(Self { values, graph: new_slot() })
+    }
+
+    pub(crate) fn get(&self, idx: usize) -> DspLoad {
+        self.values.get(idx).map(load_of).unwrap_or_default()
+    }
+
+    pub(crate) fn graph(&self) -> DspLoad {
+        load_of(&self.graph)
+    }
+
+    pub(crate) fn reset_peaks(&self) {
+        for v in self.values.iter().chain(std::iter::once(&self.graph)) {
+            v[1].set(0.0);
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        for v in self.values.iter().chain(std::iter::once(&self.graph)) {
+            for av in v.iter() {
+                av.set(0.0);
+            }
+        }
+    }
+
+    fn publish(v: &LoadSlot, ns: u64, budget_ns: f32) {
+        let ns = ns as f32;
+
+        let avg = v[0].get();
+        v[0].set(avg + (ns - avg) * AVG_COEF);
+
+        if ns > v[1].get() {
+            v[1].set(ns);
+        }
+
+        let load = v[2].get();
+        v[2].set(load + (ns / budget_ns - load) * AVG_COEF);
+    }
+}
+
+/// Accumulates the processing times of the nodes on the audio thread
+/// during one block. The times of the per voice copies of a node
+/// are added up.
+pub(crate) struct DspLoadMeter {
+    pub(crate) enabled: bool,
+    node_ns: Vec<u64>,
+    shared: Arc<DspLoadValues>,
+}
+
+impl DspLoadMeter {
+    pub(crate) fn new(shared: Arc<DspLoadValues>) -> Self {
+        let node_ns = vec![0; shared.values.len()];
+        Self { enabled: false, node_ns, shared }
+    }
+
+    /// Swaps in the larger storage that was allocated by the frontend
+    /// for more nodes. The old storage is returned in the arguments.
+    pub(crate) fn swap_storage(&mut self, node_ns: &mut Vec<u64>, shared: &mut Arc<DspLoadValues>) {
+        std::mem::swap(&mut self.node_ns, node_ns);
+        std::mem::swap(&mut self.shared, shared);
+        self.node_ns.fill(0);
+    }
+
+    #[inline]
+    pub(crate) fn add(&mut self, idx: usize, ns: u64) {
+        self.node_ns[idx] += ns;
+    }
+
+    /// For accumulating the times of the ops that are executed
+    /// on the worker threads, see [crate::nodes::WorkerPool].
+    #[inline]
+    pub(crate) fn node_ns_ptr(&mut self) -> *mut u64 {
+        self.node_ns.as_mut_ptr()
+    }
+
+    /// Publishes the accumulated times of the nodes at the indices
+    /// `idxs` and the time of the whole graph `graph_ns`.
+    pub(crate) fn publish<I: Iterator<Item = usize>>(
+        &mut self,
+        idxs: I,
+        graph_ns: u64,
+        nframes: usize,
+        srate: f32,
+    ) {
+        let budget_ns = (nframes as f32 * 1e9) / srate;
+
+        for idx in idxs {
+            DspLoadValues::publish(&self.shared.values[idx], self.node_ns[idx], budget_ns);
+            self.node_ns[idx] = 0;
+        }
+
+        DspLoadValues::publish(&self.shared.graph, graph_ns, budget_ns);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.node_ns.fill(0);
+        self.shared.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_dsp_load_meter() {
+        let shared = DspLoadValues::new_shared(16);
+        let mut meter = DspLoadMeter::new(shared.clone());
+
+        meter.add(3, 1000);
+        meter.add(3, 500);
+        meter.publish([3].into_iter(), 2000, 441, 44100.0);
+
+        let l = shared.get(3);
+        assert!((l.avg_ns - 75.0).abs() < 0.001);
+        assert!((l.peak_ns - 1500.0).abs() < 0.001);
+        assert!((l.load - 0.05 * 1500.0 / 1e7).abs() < 0.0000001);
+        assert!((shared.graph().peak_ns - 2000.0).abs() < 0.001);
+
+        meter.publish([3].into_iter(), 100, 441, 44100.0);
+        assert!((shared.get(3).peak_ns - 1500.0).abs() < 0.001);
+        assert!(shared.get(3).avg_ns < 75.0);
+
+        shared.reset_peaks();
+        assert_eq!(shared.get(3).peak_ns, 0.0);
+        assert!(shared.get(3).avg_ns > 0.0);
+
+        meter.clear();
+        assert_eq!(shared.get(3), DspLoad::default());
+        assert_eq!(shared.get(16), DspLoad::default());
+    }
+}
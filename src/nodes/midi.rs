@@ -0,0 +1,78 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+/// The kind of a [MidiEvent]. All values are already normalized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiEventKind {
+    /// A note was pressed, `vel` is in the range 0.0 to 1.0.
+    NoteOn { note: u8, vel: f32 },
+    /// A note was released.
+    NoteOff { note: u8 },
+    /// The pitch bend wheel, `value` is in the range -1.0 to 1.0.
+    PitchBend { value: f32 },
+    /// A control change, `value` is in the range 0.0 to 1.0.
+    CC { cc: u8, value: f32 },
+    /// Channel pressure, `value` is in the range 0.0 to 1.0.
+    Aftertouch { value: f32 },
+    /// Polyphonic key pressure, `value` is in the range 0.0 to 1.0.
+    PolyAftertouch { note: u8, value: f32 },
+}
+
+/// A sample timestamped MIDI event, that is passed to the [crate::nodes::NodeExecutor]
+/// via [crate::nodes::NodeAudioContext::events].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiEvent {
+    /// The frame offset inside the current buffer period.
+    pub frame: usize,
+    /// The MIDI channel from 0 to 15.
+    pub channel: u8,
+    pub kind: MidiEventKind,
+}
+
+impl MidiEvent {
+    pub fn new(frame: usize, channel: u8, kind: MidiEventKind) -> Self {
+        Self { frame, channel, kind }
+    }
+
+    /// Parses a raw MIDI message, like it's delivered by Jack for instance.
+    /// Returns `None` for messages that are not supported.
+    ///
+    ///```
+    /// use hexodsp::nodes::{MidiEvent, MidiEventKind};
+    ///
+    /// let ev = MidiEvent::from_bytes(10, &[0x91, 60, 127]).unwrap();
+    /// assert_eq!(ev.frame, 10);
+    /// assert_eq!(ev.channel, 1);
+    /// assert_eq!(ev.kind, MidiEventKind::NoteOn { note: 60, vel: 1.0 });
+    ///```
+    pub fn from_bytes(frame: usize, data: &[u8]) -> Option<Self> {
+        let status = *data.first()?;
+        let channel = status & 0x0F;
+        let d1 = data.get(1).copied().unwrap_or(0) & 0x7F;
+        let d2 = data.get(2).copied().unwrap_or(0) & 0x7F;
+
+        let kind = match status & 0xF0 {
+            0x80 => MidiEventKind::NoteOff { note: d1 },
+            0x90 => {
+                if d2 == 0 {
+                    MidiEventKind::NoteOff { note: d1 }
+                } else {
+                    MidiEventKind::NoteOn { note: d1, vel: d2 as f32 / 127.0 }
+                }
+            }
+            0xA0 => MidiEventKind::PolyAftertouch { note: d1, value: d2 as f32 / 127.0 },
+            0xB0 => MidiEventKind::CC { cc: d1, value: d2 as f32 / 127.0 },
+            0xD0 => MidiEventKind::Aftertouch { value: d1 as f32 / 127.0 },
+            0xE0 => {
+                let bend = ((d2 as u16) << 7) | (d1 as u16);
+                MidiEventKind::PitchBend {
+                    value: ((bend as f32 - 8192.0) / 8192.0).clamp(-1.0, 1.0),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(Self { frame, channel, kind })
+    }
+}
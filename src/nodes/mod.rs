@@ -14,24 +14,42 @@ pub const FB_DELAY_TIME_US: usize = 3140; // 3.14ms (should be enough for MAX_BL
                                           // This means, until 384000 sample rate the times are accurate.
 pub const MAX_FB_DELAY_SRATE: usize = 48000 * 8;
 pub const MAX_FB_DELAY_SIZE: usize = (MAX_FB_DELAY_SRATE * FB_DELAY_TIME_US) / 1000000;
+pub const MAX_VOICES: usize = 16;
+pub const MAX_BUSES: usize = 16; // Buses of the BusSend and BusRecv nodes
+pub const MAX_PERIOD_EVENTS: usize = 1024; // MIDI events per sub-block of a host period
+pub const MAX_PARAM_EVENTS: usize = 512; // Scheduled parameter events in the NodeExecutor
 
 mod drop_thread;
+mod dsp_load;
 mod feedback_filter;
+mod midi;
 mod node_conf;
 mod node_exec;
 mod node_graph_ordering;
 mod node_prog;
+mod prog_crossfade;
+mod transport;
 pub mod visual_sampling_filter;
+mod voice_alloc;
+mod worker_pool;
 
 pub(crate) use visual_sampling_filter::*;
 
+pub use dsp_load::DspLoad;
+pub(crate) use dsp_load::{DspLoadMeter, DspLoadValues};
 pub use feedback_filter::*;
+pub use midi::*;
 pub use node_conf::*;
 pub use node_exec::*;
 pub use node_graph_ordering::NodeGraphOrdering;
 pub use node_prog::*;
+pub(crate) use prog_crossfade::ProgCrossfade;
+pub use transport::*;
+pub use voice_alloc::*;
+pub(crate) use worker_pool::OpExecPtrs;
+pub use worker_pool::WorkerPool;
 
-use crate::dsp::{Node, SAtom};
+use crate::dsp::{Node, SAtom, Smoothing};
 pub use crate::monitor::MinMaxMonitorSamples;
 use crate::monitor::MON_SIG_CNT;
 
@@ -41,6 +59,66 @@ pub(crate) enum DropMsg {
     Node { node: Node },
     Prog { prog: NodeProg },
     Atom { atom: SAtom },
+    Workers { pool: WorkerPool },
+    Storage { storage: Box<NodeStorage> },
+    Nodes { nodes: Vec<Node> },
+}
+
+/// How a parameter event that is scheduled with
+/// [NodeConfigurator::set_param_at] changes the parameter value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamEventMode {
+    /// The value is set exactly at the frame of the event, without smoothing.
+    Step,
+    /// The value is smoothed from the current value, starting at
+    /// the frame of the event. Like the parameter updates of
+    /// [NodeConfigurator::set_param], with the smoothing policy of
+    /// the parameter.
+    Ramp,
+}
+
+/// The kind of signal fault that tripped the signal guard of the
+/// [NodeExecutor], see [NodeConfigurator::set_signal_guard].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalFault {
+    /// An output of the node produced a NaN or infinite sample.
+    NonFinite,
+    /// An output of the node exceeded the limit of the signal guard.
+    OutOfBounds,
+}
+
+/// The processing state of a node, see [NodeConfigurator::set_node_state].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NodeState {
+    /// The node is executed normally.
+    #[default]
+    Active,
+    /// The node is not executed. The input that is declared for bypassing
+    /// is passed through to the first output, see [crate::dsp::NodeId::bypass_inp].
+    /// All other outputs are silent. Nodes without such an input are muted.
+    Bypass,
+    /// The node is not executed and all of its outputs are silent.
+    Mute,
+}
+
+impl NodeState {
+    /// The name of the state, as it is saved in the [crate::matrix_repr::MatrixRepr].
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeState::Active => "active",
+            NodeState::Bypass => "bypass",
+            NodeState::Mute => "mute",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "active" => Some(NodeState::Active),
+            "bypass" => Some(NodeState::Bypass),
+            "mute" => Some(NodeState::Mute),
+            _ => None,
+        }
+    }
 }
 
 /// Messages for updating the NodeExecutor thread.
@@ -49,13 +127,30 @@ pub(crate) enum DropMsg {
 #[derive(Debug)]
 pub enum GraphMessage {
     NewNode {
-        index: u8,
+        index: usize,
+        node: Node,
+    },
+    /// Places the per voice copy of the node at `index` for the
+    /// voice `voice` (starting at 1, voice 0 is the node itself).
+    NewVoiceNode {
+        voice: u8,
+        index: usize,
         node: Node,
     },
     NewProg {
         prog: NodeProg,
         copy_old_out: bool,
     },
+    /// Like [GraphMessage::NewProg], but the old program keeps running
+    /// and is faded out over `fade_ms` milliseconds, while the new program
+    /// is faded in. The new program runs on the fresh node instances in
+    /// `nodes`, which hold a node at the index of each op of `prog`.
+    CrossfadeProg {
+        prog: NodeProg,
+        copy_old_out: bool,
+        nodes: Vec<Node>,
+        fade_ms: f32,
+    },
     Clear {
         prog: NodeProg,
     },
@@ -74,23 +169,187 @@ pub enum GraphMessage {
         mod_idx: usize,
         modamt: f32,
     },
+    /// Sets the smoothing policy of the parameter at `input_idx`.
+    SmoothingUpdate {
+        input_idx: usize,
+        smoothing: Smoothing,
+    },
+    /// A parameter update that is applied at `frame`, which is the
+    /// frame offset from the start of the next processed block.
+    ParamEvent {
+        input_idx: usize,
+        value: f32,
+        frame: usize,
+        mode: ParamEventMode,
+    },
     /// Sets the buffer indices to monitor with the FeedbackProcessor.
     SetMonitor {
         bufs: [usize; MON_SIG_CNT],
     },
+    /// Assigns a voice of the voice region to the note.
+    NoteOn {
+        note: u8,
+        vel: f32,
+    },
+    /// Releases the voice of the voice region that plays the note.
+    NoteOff {
+        note: u8,
+    },
+    /// Sets the tempo of the [Transport] in quarter notes per minute.
+    SetTempo {
+        bpm: f64,
+    },
+    /// Sets the time signature of the [Transport].
+    SetTimeSig {
+        num: u8,
+        denom: u8,
+    },
+    /// Starts or stops the [Transport].
+    SetPlaying {
+        playing: bool,
+    },
+    /// Moves the song position of the [Transport] to `beat_pos` quarter notes.
+    Locate {
+        beat_pos: f64,
+    },
+    /// Enables or disables the measurement of the [DspLoad] of the nodes.
+    SetDspProfiling {
+        enabled: bool,
+    },
+    /// Enables the signal guard with the given absolute sample limit,
+    /// or disables it with `None`.
+    SetSignalGuard {
+        limit: Option<f32>,
+    },
+    /// Sets the processing state of the node at `index`.
+    SetNodeState {
+        index: usize,
+        state: NodeState,
+    },
+    /// Solos the output buffer at the index `cue.0` of the [NodeProg] to the
+    /// audio output channels `cue.1` and `cue.1 + 1`, while the `Out` nodes
+    /// are muted. An index that is out of range is soloed as silence.
+    /// `None` ends the solo.
+    SetCue {
+        cue: Option<(usize, usize)>,
+    },
+    /// Replaces the worker threads for the parallel execution
+    /// of the [NodeProg], `None` executes it sequentially.
+    SetWorkers {
+        pool: Option<WorkerPool>,
+    },
+    /// Hands over larger storage for the nodes or the feedback delay
+    /// buffers, the old storage is sent back to the drop thread.
+    GrowStorage {
+        storage: Box<NodeStorage>,
+    },
 }
 
 pub const UNUSED_MONITOR_IDX: usize = 99999;
 
 /// Creates a NodeConfigurator and a NodeExecutor which are interconnected
-/// by ring buffers.
+/// by ring buffers. Uses the default limits, see [NodeEngineBuilder]
+/// for configuring them.
 pub fn new_node_engine() -> (NodeConfigurator, NodeExecutor) {
-    let (nc, shared_exec) = NodeConfigurator::new();
-    let ne = NodeExecutor::new(shared_exec);
+    NodeEngineBuilder::new().build()
+}
+
+/// Configures the limits of a node engine and creates it, like
+/// [new_node_engine] does with the default limits:
+///
+///```
+/// use hexodsp::nodes::NodeEngineBuilder;
+///
+/// let (node_conf, node_exec) = NodeEngineBuilder::new().nodes(64).scopes(2).build();
+///```
+///
+/// The number of nodes and feedback delay buffers are only the initial
+/// capacities. The [NodeConfigurator] allocates more storage when
+/// it runs out of it and hands it over to the [NodeExecutor]. The same
+/// goes for the per voice copies of the nodes in a voice region.
+#[derive(Debug, Clone)]
+pub struct NodeEngineBuilder {
+    pub(crate) nodes: usize,
+    pub(crate) scopes: usize,
+    pub(crate) trackers: usize,
+    pub(crate) code_engines: usize,
+    pub(crate) feedback_delays: usize,
+    pub(crate) message_queue: Option<usize>,
+}
 
-    // XXX: This is one of the earliest and most consistent points
-    //      in runtime to do this kind of initialization:
-    synfx_dsp::init_cos_tab();
+impl NodeEngineBuilder {
+    pub fn new() -> Self {
+        Self {
+            nodes: MAX_ALLOCATED_NODES,
+            scopes: MAX_SCOPES,
+            trackers: MAX_AVAIL_TRACKERS,
+            code_engines: MAX_AVAIL_CODE_ENGINES,
+            feedback_delays: MAX_FB_DELAYS,
+            message_queue: None,
+        }
+    }
+
+    /// The initial number of node slots, defaults to [MAX_ALLOCATED_NODES].
+    pub fn nodes(&mut self, nodes: usize) -> &mut Self {
+        self.nodes = nodes.max(1);
+        self
+    }
+
+    /// The number of [crate::ScopeHandle], defaults to [MAX_SCOPES].
+    pub fn scopes(&mut self, scopes: usize) -> &mut Self {
+        self.scopes = scopes;
+        self
+    }
+
+    /// The number of trackers for the `TSeq` nodes,
+    /// defaults to [MAX_AVAIL_TRACKERS].
+    pub fn trackers(&mut self, trackers: usize) -> &mut Self {
+        self.trackers = trackers;
+        self
+    }
+
+    /// The number of code engines for the `Code` nodes,
+    /// defaults to [MAX_AVAIL_CODE_ENGINES].
+    pub fn code_engines(&mut self, code_engines: usize) -> &mut Self {
+        self.code_engines = code_engines;
+        self
+    }
+
+    /// The initial number of feedback delay buffers for `FbWr` and `FbRd`,
+    /// defaults to [MAX_FB_DELAYS].
+    pub fn feedback_delays(&mut self, feedback_delays: usize) -> &mut Self {
+        self.feedback_delays = feedback_delays;
+        self
+    }
+
+    /// The length of the message queues between the [NodeConfigurator] and
+    /// the [NodeExecutor], defaults to twice the initial number of nodes,
+    /// but at least twice [MAX_ALLOCATED_NODES]. The queues can't grow,
+    /// they have to hold all the updates that are sent between two audio
+    /// periods, like one message per created node.
+    pub fn message_queue(&mut self, len: usize) -> &mut Self {
+        self.message_queue = Some(len.max(1));
+        self
+    }
+
+    pub(crate) fn queue_len(&self) -> usize {
+        self.message_queue.unwrap_or(2 * self.nodes.max(MAX_ALLOCATED_NODES))
+    }
+
+    pub fn build(&self) -> (NodeConfigurator, NodeExecutor) {
+        let (nc, shared_exec) = NodeConfigurator::new(self);
+        let ne = NodeExecutor::new(shared_exec, self);
+
+        // XXX: This is one of the earliest and most consistent points
+        //      in runtime to do this kind of initialization:
+        synfx_dsp::init_cos_tab();
+
+        (nc, ne)
+    }
+}
 
-    (nc, ne)
+impl Default for NodeEngineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -3,12 +3,13 @@
 // See README.md and COPYING for details.
 
 use super::{
-    FeedbackFilter, GraphMessage, NodeOp, NodeProg, MAX_ALLOCATED_NODES, MAX_AVAIL_CODE_ENGINES,
-    MAX_AVAIL_TRACKERS, MAX_INPUTS, MAX_SCOPES, UNUSED_MONITOR_IDX,
+    DspLoad, DspLoadValues, FeedbackBuffer, FeedbackFilter, GraphMessage, NodeEngineBuilder,
+    NodeOp, NodeProg, NodeState, NodeStorage, ParamEventMode, SignalFault, VoiceStealMode,
+    WorkerPool, MAX_BUSES, MAX_INPUTS, MAX_VOICES, UNUSED_MONITOR_IDX,
 };
 use crate::wblockdsp::*;
 use crate::dsp::tracker::{PatternData, Tracker};
-use crate::dsp::{node_factory, Node, NodeId, NodeInfo, ParamId, SAtom};
+use crate::dsp::{node_factory, Node, NodeId, NodeInfo, ParamId, SAtom, Smoothing};
 use crate::monitor::{new_monitor_processor, MinMaxMonitorSamples, Monitor, MON_SIG_CNT};
 use crate::nodes::drop_thread::DropThread;
 #[cfg(feature = "synfx-dsp-jit")]
@@ -16,7 +17,7 @@ use synfx_dsp_jit::engine::CodeEngine;
 use crate::SampleLibrary;
 use crate::ScopeHandle;
 
-use ringbuf::{Producer, RingBuffer};
+use ringbuf::{Consumer, Producer, RingBuffer};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
@@ -74,7 +75,7 @@ impl NodeInstance {
 
     pub fn as_op(&self) -> NodeOp {
         NodeOp {
-            idx: self.prog_idx as u8,
+            idx: self.prog_idx,
             out_idxlen: (self.out_start, self.out_end),
             in_idxlen: (self.in_start, self.in_end),
             at_idxlen: (self.at_start, self.at_end),
@@ -82,6 +83,9 @@ impl NodeInstance {
             out_connected: 0x0,
             in_connected: 0x0,
             inputs: vec![],
+            node_id: self.id,
+            state: NodeState::Active,
+            bypass_inp: self.id.bypass_inp().map(|i| i as usize),
         }
     }
 
@@ -210,10 +214,15 @@ pub struct NodeConfigurator {
     param_values: std::collections::HashMap<ParamId, f32>,
     /// Stores the modulation amount of a parameter
     param_modamt: std::collections::HashMap<ParamId, Option<f32>>,
+    /// Stores the smoothing policies that override [ParamId::smoothing]
+    param_smoothing: std::collections::HashMap<ParamId, Smoothing>,
     /// Contains non automateable atom data for the nodes
     atoms: std::collections::HashMap<ParamId, NodeInputAtom>,
     /// Stores the most recently set atoms
     atom_values: std::collections::HashMap<ParamId, SAtom>,
+    /// Stores the nodes that are bypassed or muted,
+    /// see [NodeConfigurator::set_node_state].
+    node_states: HashMap<NodeId, NodeState>,
 
     /// Holds a copy of the most recently updated output port feedback
     /// values. Update this by calling [NodeConfigurator::update_output_feedback].
@@ -222,6 +231,36 @@ pub struct NodeConfigurator {
     /// Holds the channel to the backend that sends output port feedback.
     /// This is queried by [NodeConfigurator::update_output_feedback].
     output_fb_cons: Option<Output<Vec<f32>>>,
+
+    /// The nodes that are executed once per voice, the number of voices
+    /// and the voice stealing mode.
+    /// See also [NodeConfigurator::set_voice_region].
+    voice_region: Option<(Vec<NodeId>, usize, VoiceStealMode)>,
+
+    /// Remembers how many voices of a node were already sent to the backend.
+    voice_copies: HashMap<NodeId, usize>,
+
+    /// The number of voices beyond the first one that the backend has
+    /// node slots for. See also [NodeConfigurator::grow_voice_nodes].
+    voice_slots: usize,
+
+    /// The number of feedback delay buffers that were sent to the backend.
+    /// See also [NodeConfigurator::grow_feedback_delays].
+    feedback_delays: usize,
+
+    /// The crossfade time for uploading a new [NodeProg] in milliseconds.
+    /// See also [NodeConfigurator::set_prog_crossfade].
+    prog_crossfade: Option<f32>,
+    /// The sorted inputs of each node of the last uploaded [NodeProg],
+    /// or `None` if it had a voice region and can't be crossfaded.
+    prev_prog_inputs: Option<HashMap<usize, Vec<(usize, usize, bool)>>>,
+    /// Whether the last uploaded [NodeProg] was crossfaded,
+    /// see [NodeConfigurator::is_prog_crossfaded].
+    prog_crossfaded: bool,
+
+    /// The soloed output port and the first output channel,
+    /// see [NodeConfigurator::cue_output].
+    cue: Option<(NodeId, u8, usize)>,
 }
 
 pub(crate) struct SharedNodeConf {
@@ -234,59 +273,76 @@ pub(crate) struct SharedNodeConf {
     /// Handles deallocation of dead nodes from the backend.
     #[allow(dead_code)]
     pub(crate) drop_thread: DropThread,
+    /// Holds the DSP load of the nodes, measured by the backend.
+    pub(crate) dsp_load: Arc<DspLoadValues>,
+    /// For receiving the node indices that tripped the signal guard.
+    pub(crate) signal_fault_con: Consumer<(usize, SignalFault)>,
 }
 
 use super::node_exec::SharedNodeExec;
 
 impl SharedNodeConf {
-    pub(crate) fn new() -> (Self, SharedNodeExec) {
-        let rb_graph = RingBuffer::new(MAX_ALLOCATED_NODES * 2);
-        let rb_drop = RingBuffer::new(MAX_ALLOCATED_NODES * 2);
+    pub(crate) fn new(limits: &NodeEngineBuilder) -> (Self, SharedNodeExec) {
+        let rb_graph = RingBuffer::new(limits.queue_len());
+        let rb_drop = RingBuffer::new(limits.queue_len());
+        let rb_fault = RingBuffer::new(limits.queue_len());
 
         let (rb_graph_prod, rb_graph_con) = rb_graph.split();
         let (rb_drop_prod, rb_drop_con) = rb_drop.split();
+        let (rb_fault_prod, rb_fault_con) = rb_fault.split();
 
         let drop_thread = DropThread::new(rb_drop_con);
 
         let (monitor_backend, monitor) = new_monitor_processor();
 
         let mut node_ctx_values = Vec::new();
-        node_ctx_values.resize_with(2 * MAX_ALLOCATED_NODES, || Arc::new(AtomicFloat::new(0.0)));
+        node_ctx_values.resize_with(2 * limits.nodes, || Arc::new(AtomicFloat::new(0.0)));
 
         let mut exec_node_ctx_vals = Vec::new();
         for ctx_val in node_ctx_values.iter() {
             exec_node_ctx_vals.push(ctx_val.clone());
         }
 
+        let dsp_load = DspLoadValues::new_shared(limits.nodes);
+
         (
-            Self { node_ctx_values, graph_update_prod: rb_graph_prod, monitor, drop_thread },
+            Self {
+                node_ctx_values,
+                graph_update_prod: rb_graph_prod,
+                monitor,
+                drop_thread,
+                dsp_load: dsp_load.clone(),
+                signal_fault_con: rb_fault_con,
+            },
             SharedNodeExec {
                 node_ctx_values: exec_node_ctx_vals,
                 graph_update_con: rb_graph_con,
                 graph_drop_prod: rb_drop_prod,
                 monitor_backend,
+                dsp_load,
+                signal_fault_prod: rb_fault_prod,
             },
         )
     }
 }
 
 impl NodeConfigurator {
-    pub(crate) fn new() -> (Self, SharedNodeExec) {
+    pub(crate) fn new(limits: &NodeEngineBuilder) -> (Self, SharedNodeExec) {
         let mut nodes = Vec::new();
-        nodes.resize_with(MAX_ALLOCATED_NODES, || (NodeInfo::from_node_id(NodeId::Nop), None));
+        nodes.resize_with(limits.nodes, || (NodeInfo::from_node_id(NodeId::Nop), None));
 
-        let (shared, shared_exec) = SharedNodeConf::new();
+        let (shared, shared_exec) = SharedNodeConf::new(limits);
 
         let mut scopes = vec![];
-        scopes.resize_with(MAX_SCOPES, || ScopeHandle::new_shared());
+        scopes.resize_with(limits.scopes, || ScopeHandle::new_shared());
 
         #[cfg(feature = "synfx-dsp-jit")]
         let (code_engines, block_functions) = {
-            let code_engines = vec![CodeEngine::new_stdlib(); MAX_AVAIL_CODE_ENGINES];
+            let code_engines = vec![CodeEngine::new_stdlib(); limits.code_engines.max(1)];
 
             let lang = setup_hxdsp_block_language(code_engines[0].get_lib());
             let mut block_functions = vec![];
-            block_functions.resize_with(MAX_AVAIL_CODE_ENGINES, || {
+            block_functions.resize_with(code_engines.len(), || {
                 (0, Arc::new(Mutex::new(BlockFun::new(lang.clone()))))
             });
 
@@ -302,13 +358,23 @@ impl NodeConfigurator {
                 feedback_filter: FeedbackFilter::new(),
                 output_fb_values: vec![],
                 output_fb_cons: None,
+                voice_region: None,
+                voice_copies: HashMap::new(),
+                voice_slots: 0,
+                feedback_delays: limits.feedback_delays,
+                prog_crossfade: None,
+                prev_prog_inputs: Some(HashMap::new()),
+                prog_crossfaded: false,
+                node_states: HashMap::new(),
+                cue: None,
                 params: std::collections::HashMap::new(),
                 param_values: std::collections::HashMap::new(),
                 param_modamt: std::collections::HashMap::new(),
+                param_smoothing: std::collections::HashMap::new(),
                 atoms: std::collections::HashMap::new(),
                 atom_values: std::collections::HashMap::new(),
                 node2idx: HashMap::new(),
-                trackers: vec![Tracker::new(); MAX_AVAIL_TRACKERS],
+                trackers: vec![Tracker::new(); limits.trackers],
                 #[cfg(feature = "synfx-dsp-jit")]
                 code_engines,
                 #[cfg(feature = "synfx-dsp-jit")]
@@ -356,6 +422,151 @@ impl NodeConfigurator {
         self.errors.pop()
     }
 
+    /// Enables the signal guard of the backend. It checks the outputs
+    /// of each node after each processed block. If a node produces NaN,
+    /// infinite samples or samples with an absolute value above `limit`,
+    /// the node is reset and its outputs are replaced by silence.
+    /// Pass `f32::INFINITY` as limit to only catch non-finite samples.
+    /// `None` disables the guard, which is the default.
+    ///
+    /// The nodes that tripped the guard are reported by
+    /// [NodeConfigurator::pop_signal_fault].
+    pub fn set_signal_guard(&mut self, limit: Option<f32>) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetSignalGuard { limit });
+    }
+
+    /// Enables crossfading between the old and the new [NodeProg] in
+    /// [NodeConfigurator::upload_prog] over `fade_ms` milliseconds.
+    /// While fading, the backend runs both programs and blends the
+    /// outputs of their `Out` nodes, so that re-routing a live patch
+    /// does not click. Only the nodes whose inputs changed, or that
+    /// depend on such a node, start with fresh instances in the new
+    /// program, while the old program keeps their old instances. All other
+    /// nodes keep running on their existing instances and are shared by
+    /// both programs.
+    /// `None` replaces the program right away, which is the default.
+    ///
+    /// Programs with a voice region, or where a changed or removed node
+    /// can't be duplicated, are replaced right away, see
+    /// [NodeId::is_crossfade_safe] and [NodeConfigurator::is_prog_crossfaded].
+    pub fn set_prog_crossfade(&mut self, fade_ms: Option<f32>) {
+        self.prog_crossfade = fade_ms.filter(|ms| *ms > 0.0);
+    }
+
+    /// Returns whether the last [NodeProg] uploaded by
+    /// [NodeConfigurator::upload_prog] is crossfaded. This is `false` if
+    /// the crossfade is disabled or if the program had to be replaced
+    /// right away, see [NodeConfigurator::set_prog_crossfade].
+    pub fn is_prog_crossfaded(&self) -> bool {
+        self.prog_crossfaded
+    }
+
+    /// Bypasses or mutes the node `node_id`, or makes it active again.
+    /// A muted node is not executed and its outputs are silent.
+    /// A bypassed node passes the input that is declared with
+    /// [NodeId::bypass_inp] through to its first output instead.
+    ///
+    /// The state is applied right away and is kept for the
+    /// following programs, until the nodes are deleted.
+    pub fn set_node_state(&mut self, node_id: NodeId, state: NodeState) {
+        if state == NodeState::Active {
+            self.node_states.remove(&node_id);
+        } else {
+            self.node_states.insert(node_id, state);
+        }
+
+        if let Some(index) = self.unique_index_for(&node_id) {
+            let _ = self.shared.graph_update_prod.push(GraphMessage::SetNodeState { index, state });
+        }
+    }
+
+    /// Returns the state of the node `node_id`,
+    /// see [NodeConfigurator::set_node_state].
+    pub fn node_state(&self, node_id: &NodeId) -> NodeState {
+        self.node_states.get(node_id).copied().unwrap_or_default()
+    }
+
+    /// Solos the output port `out` of the node `node_id` to the audio output
+    /// channels `channel` and `channel + 1`, without changing the [NodeProg].
+    /// The `Out` nodes are muted meanwhile. Pass `0` as `channel` to listen
+    /// to the port on the main output, or the first channel of a cue
+    /// channel pair that the audio driver provides.
+    ///
+    /// The port stays soloed for the following programs, until
+    /// [NodeConfigurator::stop_cue] is called or the nodes are deleted.
+    /// A port of a node that is not part of the program is silent.
+    pub fn cue_output(&mut self, node_id: NodeId, out: u8, channel: usize) {
+        self.cue = Some((node_id, out, channel));
+        self.send_cue();
+    }
+
+    /// Ends the solo of [NodeConfigurator::cue_output] and
+    /// unmutes the `Out` nodes.
+    pub fn stop_cue(&mut self) {
+        self.cue = None;
+        self.send_cue();
+    }
+
+    /// Returns the soloed output port and the first output channel,
+    /// see [NodeConfigurator::cue_output].
+    pub fn cued_output(&self) -> Option<(NodeId, u8, usize)> {
+        self.cue
+    }
+
+    /// Sends the output buffer index of the soloed output port to the
+    /// backend. Must be repeated for each new [NodeProg], because the
+    /// buffer indices might change.
+    fn send_cue(&mut self) {
+        let cue = self.cue.map(|(node_id, out, channel)| {
+            let out_idx = match self.node_by_id(&node_id) {
+                Some((_, Some(node_instance))) => node_instance.out_local2global(out),
+                _ => None,
+            };
+
+            (out_idx.unwrap_or(usize::MAX), channel)
+        });
+
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetCue { cue });
+    }
+
+    /// Returns all nodes that are not [NodeState::Active].
+    /// Most useful for serialization and saving patches.
+    pub fn dump_node_states(&self) -> Vec<(NodeId, NodeState)> {
+        self.node_states.iter().map(|(node_id, state)| (*node_id, *state)).collect()
+    }
+
+    /// Starts `threads` worker threads, that execute the nodes of the
+    /// graph in parallel with the audio thread. Nodes that don't depend
+    /// on each other, for instance separate oscillator and effect branches,
+    /// are then spread over multiple CPU cores. The output is the same as
+    /// with the sequential execution. Nodes in the voice region and nodes
+    /// that access the audio driver are always executed on the audio thread,
+    /// see [crate::dsp::NodeId::is_parallel_safe].
+    ///
+    /// Pass `0` to go back to the sequential execution, which is the default.
+    /// Keep in mind that the synchronization with the workers costs a bit
+    /// of time for each level of the graph, so this only pays off for
+    /// large patches.
+    ///
+    /// The worker threads are started with the default priority. The audio
+    /// thread busy waits until the workers finished their ops of a level,
+    /// so a worker that is preempted by the OS delays the audio thread.
+    /// Give the workers a real-time priority yourself if your platform
+    /// requires it to avoid dropouts.
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        let pool = if threads > 0 { Some(WorkerPool::new(threads)) } else { None };
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetWorkers { pool });
+    }
+
+    /// Returns the next node that tripped the signal guard, which is
+    /// enabled with [NodeConfigurator::set_signal_guard]. A node that keeps
+    /// producing a bad signal is reported once per processed block.
+    pub fn pop_signal_fault(&mut self) -> Option<(NodeId, SignalFault)> {
+        let (idx, fault) = self.shared.signal_fault_con.pop()?;
+        let node_id = self.nodes.get(idx).map(|n| n.0.to_id()).unwrap_or(NodeId::Nop);
+        Some((node_id, fault))
+    }
+
     pub fn unique_index_for(&self, ni: &NodeId) -> Option<usize> {
         self.node2idx.get(&ni).copied()
     }
@@ -420,6 +631,36 @@ impl NodeConfigurator {
         }
     }
 
+    /// Returns the smoothing policy of the input parameter. That is either the
+    /// one set by [NodeConfigurator::set_param_smoothing] or the default
+    /// of the parameter, see [ParamId::smoothing].
+    pub fn get_param_smoothing(&self, param: &ParamId) -> Smoothing {
+        self.param_smoothing.get(param).copied().unwrap_or_else(|| param.smoothing())
+    }
+
+    /// Overrides the smoothing policy of the input parameter at runtime.
+    /// Passing `None` restores the default of the parameter.
+    pub fn set_param_smoothing(&mut self, param: ParamId, smoothing: Option<Smoothing>) {
+        if param.is_atom() {
+            return;
+        }
+
+        if let Some(smoothing) = smoothing {
+            self.param_smoothing.insert(param, smoothing);
+        } else {
+            self.param_smoothing.remove(&param);
+        }
+
+        if let Some(nparam) = self.params.get(&param) {
+            let input_idx = nparam.input_idx;
+            let smoothing = self.get_param_smoothing(&param);
+            let _ = self
+                .shared
+                .graph_update_prod
+                .push(GraphMessage::SmoothingUpdate { input_idx, smoothing });
+        }
+    }
+
     /// Retrieve [SAtom] values for input parameters and atoms.
     pub fn get_param(&self, param: &ParamId) -> Option<SAtom> {
         if param.is_atom() {
@@ -429,6 +670,28 @@ impl NodeConfigurator {
         }
     }
 
+    /// Formats the normalized value `v` of the parameter like [ParamId::format],
+    /// but takes the `sync` setting of tempo synced time parameters into account,
+    /// see also [ParamId::sync_atom].
+    pub fn format_param(
+        &self,
+        f: &mut dyn std::io::Write,
+        param: &ParamId,
+        v: f32,
+    ) -> Option<std::io::Result<()>> {
+        let synced = param
+            .sync_atom()
+            .and_then(|sync| self.get_param(&sync))
+            .map(|sync| sync.i() != 0)
+            .unwrap_or(false);
+
+        if synced {
+            param.format_note_div(f, v)
+        } else {
+            param.format(f, v)
+        }
+    }
+
     /// Assign [SAtom] values to input parameters and atoms.
     ///
     /// Only updates the DSP backend if [NodeConfigurator::rebuild_node_ports] was called
@@ -483,6 +746,39 @@ impl NodeConfigurator {
         }
     }
 
+    /// Schedules a sample accurate update of the input parameter `param`.
+    /// The new value is applied at `frame`, which is the frame offset from
+    /// the start of the next block that is processed by the
+    /// [crate::nodes::NodeExecutor]. With `mode` you choose whether the value
+    /// steps to the new value at that frame or is smoothed starting there.
+    ///
+    /// This is intended for automation from a host or sequencer, that is
+    /// sent right before the corresponding buffer period is processed.
+    /// Atoms can't be scheduled, use [NodeConfigurator::set_param] for them.
+    ///
+    /// Events that are still pending when a new program is uploaded with
+    /// [NodeConfigurator::upload_prog] are dropped. The new program starts
+    /// with the values of these events right away.
+    pub fn set_param_at(&mut self, param: ParamId, value: f32, frame: usize, mode: ParamEventMode) {
+        if param.is_atom() {
+            return;
+        }
+
+        self.param_values.insert(param, value);
+
+        if let Some(nparam) = self.params.get_mut(&param) {
+            nparam.value = value;
+
+            let input_idx = nparam.input_idx;
+            let _ = self.shared.graph_update_prod.push(GraphMessage::ParamEvent {
+                input_idx,
+                value,
+                frame,
+                mode,
+            });
+        }
+    }
+
     /// Dumps all set parameters (inputs and atoms).
     /// Most useful for serialization and saving patches.
     #[allow(clippy::type_complexity)]
@@ -567,6 +863,41 @@ impl NodeConfigurator {
         }
     }
 
+    /// Enables or disables the measurement of the DSP load of the nodes
+    /// in the backend. It's disabled by default, because reading the
+    /// clock for each node costs a bit of CPU time itself.
+    /// Disabling it resets the measured values.
+    ///
+    /// See also [NodeConfigurator::dsp_load_for] and
+    /// [NodeConfigurator::graph_dsp_load].
+    pub fn set_dsp_profiling(&mut self, enabled: bool) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetDspProfiling { enabled });
+    }
+
+    /// Returns the DSP load of the given node, the time per processed block
+    /// that is spent in the node. The times of all voices of a node in the
+    /// voice region are added up. Returns `None` if the node does not exist.
+    ///
+    /// The values are only measured if enabled with
+    /// [NodeConfigurator::set_dsp_profiling].
+    pub fn dsp_load_for(&self, ni: &NodeId) -> Option<DspLoad> {
+        let idx = self.unique_index_for(ni)?;
+        Some(self.shared.dsp_load.get(idx))
+    }
+
+    /// Returns the DSP load of the whole graph, including the
+    /// parameter smoothing and the voice region mixing.
+    /// See also [NodeConfigurator::set_dsp_profiling].
+    pub fn graph_dsp_load(&self) -> DspLoad {
+        self.shared.dsp_load.graph()
+    }
+
+    /// Resets the peak times of [NodeConfigurator::dsp_load_for]
+    /// and [NodeConfigurator::graph_dsp_load].
+    pub fn reset_dsp_load_peaks(&self) {
+        self.shared.dsp_load.reset_peaks();
+    }
+
     /// Triggers recalculation of the filtered values from the
     /// current LED values and output feedback.
     ///
@@ -742,78 +1073,457 @@ impl NodeConfigurator {
         }
     }
 
+    /// Marks the given nodes as voice region. The region is executed
+    /// `voices` times by the [crate::nodes::NodeExecutor], each time with
+    /// it's own copy of the node states and buffers. Use the `Voice` node
+    /// inside the region to get the pitch, gate and velocity of a voice.
+    ///
+    /// Outputs of the region that are connected to nodes outside of
+    /// it are summed up over all voices.
+    ///
+    /// `voices` is clamped to the range `1..=MAX_VOICES`.
+    /// The region is applied with the next [NodeConfigurator::upload_prog].
+    ///
+    /// Please note that nodes which are connected to frontend handles,
+    /// like `TSeq`, `Code` and `Scope`, only work in the first voice.
+    pub fn set_voice_region(
+        &mut self,
+        nodes: &[NodeId],
+        voices: usize,
+        steal_mode: VoiceStealMode,
+    ) {
+        self.voice_region = Some((nodes.to_vec(), voices.clamp(1, MAX_VOICES), steal_mode));
+    }
+
+    /// Removes the voice region. Applied with the next
+    /// [NodeConfigurator::upload_prog].
+    pub fn clear_voice_region(&mut self) {
+        self.voice_region = None;
+    }
+
+    /// Returns the nodes, voice count and stealing mode of the voice region.
+    pub fn voice_region(&self) -> Option<&(Vec<NodeId>, usize, VoiceStealMode)> {
+        self.voice_region.as_ref()
+    }
+
+    /// Checks if the voice region can be executed as one block in `prog`,
+    /// see [NodeProg::check_voice_region]. [NodeConfigurator::upload_prog]
+    /// removes a voice region that fails this check and reports it
+    /// via [NodeConfigurator::pop_error].
+    pub fn check_voice_region(&self, prog: &NodeProg) -> bool {
+        if let Some((node_ids, _, _)) = &self.voice_region {
+            let idxs: Vec<usize> =
+                node_ids.iter().filter_map(|node_id| self.unique_index_for(node_id)).collect();
+            prog.check_voice_region(&idxs)
+        } else {
+            true
+        }
+    }
+
+    /// Returns the bus of each `BusSend` and `BusRecv` node in `node_ids`.
+    /// All nodes with the same non-empty `name` share a bus. The names get
+    /// the buses that are not used by the `bus` setting of the unnamed nodes,
+    /// in alphabetical order. Returns `None` if there are not enough
+    /// buses for all names, see [MAX_BUSES].
+    ///
+    /// The bus of a named node is only used by the backend after
+    /// [NodeConfigurator::set_buses] wrote it into the `bus` setting.
+    pub fn assign_buses(&self, node_ids: &[NodeId]) -> Option<Vec<(NodeId, i64)>> {
+        let atom = |node_id: &NodeId, param: &str| {
+            node_id.inp_param(param).and_then(|p| self.get_param(&p))
+        };
+
+        let mut buses = vec![];
+        let mut used = [false; MAX_BUSES];
+        let mut names = vec![];
+
+        for node_id in node_ids.iter() {
+            if !matches!(node_id, NodeId::BusSend(_) | NodeId::BusRecv(_)) {
+                continue;
+            }
+
+            let name = atom(node_id, "name").map(|at| at.s()).unwrap_or_default();
+            let bus = atom(node_id, "bus").map_or(0, |at| at.i());
+
+            if name.is_empty() {
+                if let Some(used) = used.get_mut(bus as usize) {
+                    *used = true;
+                }
+            } else {
+                names.push(name.clone());
+            }
+
+            buses.push((*node_id, bus, name));
+        }
+
+        names.sort();
+        names.dedup();
+
+        let mut free = (0..MAX_BUSES).filter(|bus| !used[*bus]);
+        let mut name_buses = HashMap::new();
+        for name in names {
+            name_buses.insert(name, free.next()? as i64);
+        }
+
+        Some(
+            buses
+                .into_iter()
+                .map(|(node_id, bus, name)| (node_id, *name_buses.get(&name).unwrap_or(&bus)))
+                .collect(),
+        )
+    }
+
+    /// Writes the buses returned by [NodeConfigurator::assign_buses]
+    /// into the `bus` setting of the nodes, where it differs.
+    pub fn set_buses(&mut self, buses: &[(NodeId, i64)]) {
+        for (node_id, bus) in buses.iter() {
+            if let Some(pid) = node_id.inp_param("bus") {
+                if self.get_param(&pid).map_or(true, |at| at.i() != *bus) {
+                    self.set_param(pid, SAtom::setting(*bus));
+                }
+            }
+        }
+    }
+
+    /// Sends a note on event to the voice allocator of the backend.
+    /// `vel` is the velocity in the range 0.0 to 1.0.
+    pub fn note_on(&mut self, note: u8, vel: f32) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::NoteOn { note, vel });
+    }
+
+    /// Sends a note off event to the voice allocator of the backend.
+    pub fn note_off(&mut self, note: u8) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::NoteOff { note });
+    }
+
+    /// Sets the tempo of the [crate::nodes::Transport] of the backend
+    /// in quarter notes per minute. Please note that a host that provides
+    /// it's own transport via [crate::nodes::NodeAudioContext::transport]
+    /// overrides this.
+    pub fn set_tempo(&mut self, bpm: f64) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetTempo { bpm });
+    }
+
+    /// Sets the time signature of the [crate::nodes::Transport], for instance `(3, 4)`.
+    pub fn set_time_signature(&mut self, num: u8, denom: u8) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetTimeSig { num, denom });
+    }
+
+    /// Starts or stops the [crate::nodes::Transport].
+    pub fn set_playing(&mut self, playing: bool) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetPlaying { playing });
+    }
+
+    /// Moves the song position of the [crate::nodes::Transport]
+    /// to `beat_pos` quarter notes.
+    pub fn locate(&mut self, beat_pos: f64) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::Locate { beat_pos });
+    }
+
+    /// Sends the missing per voice copies of the nodes in the voice region
+    /// to the backend and returns the node indices of the region.
+    fn create_voice_nodes(&mut self) -> Vec<usize> {
+        let (node_ids, voices) = if let Some((node_ids, voices, _)) = &self.voice_region {
+            (node_ids.clone(), *voices)
+        } else {
+            return vec![];
+        };
+
+        self.grow_voice_nodes(voices);
+
+        let mut idxs = vec![];
+
+        for node_id in node_ids.iter() {
+            let index = if let Some(index) = self.unique_index_for(node_id) {
+                index
+            } else {
+                continue;
+            };
+
+            let copies = self.voice_copies.get(node_id).copied().unwrap_or(1);
+
+            for voice in copies..voices {
+                if let Some((node, _info)) = node_factory(*node_id) {
+                    let _ = self.shared.graph_update_prod.push(GraphMessage::NewVoiceNode {
+                        voice: voice as u8,
+                        index,
+                        node,
+                    });
+                }
+            }
+
+            if voices > copies {
+                self.voice_copies.insert(*node_id, voices);
+            }
+
+            idxs.push(index);
+        }
+
+        idxs
+    }
+
     pub fn delete_nodes(&mut self) {
         self.node2idx.clear();
+        self.voice_copies.clear();
         self.nodes.fill_with(|| (NodeInfo::from_node_id(NodeId::Nop), None));
         self.params.clear();
         self.param_values.clear();
         self.param_modamt.clear();
+        self.param_smoothing.clear();
         self.atoms.clear();
         self.atom_values.clear();
+        self.node_states.clear();
+        self.cue = None;
+        self.prev_prog_inputs = Some(HashMap::new());
 
         let _ = self.shared.graph_update_prod.push(GraphMessage::Clear { prog: NodeProg::empty() });
     }
 
-    pub fn create_node(&mut self, ni: NodeId) -> Option<(&NodeInfo, u8)> {
-        if let Some((mut node, info)) = node_factory(ni) {
-            let mut index: Option<usize> = None;
+    /// Creates a new instance of the node `ni` and connects it
+    /// with its frontend parts, like the tracker or the scope handle.
+    fn instantiate_node(&mut self, ni: NodeId) -> Option<(Node, NodeInfo)> {
+        let (mut node, info) = node_factory(ni)?;
 
-            if let Node::TSeq { node } = &mut node {
-                let tracker_idx = ni.instance();
-                if let Some(trk) = self.trackers.get_mut(tracker_idx) {
-                    node.set_backend(trk.get_backend());
-                }
+        if let Node::TSeq { node } = &mut node {
+            let tracker_idx = ni.instance();
+            if let Some(trk) = self.trackers.get_mut(tracker_idx) {
+                node.set_backend(trk.get_backend());
             }
+        }
 
-            #[cfg(feature = "synfx-dsp-jit")]
-            if let Node::Code { node } = &mut node {
-                let code_idx = ni.instance();
-                if let Some(cod) = self.code_engines.get_mut(code_idx) {
-                    node.set_backend(cod.get_backend());
-                }
+        #[cfg(feature = "synfx-dsp-jit")]
+        if let Node::Code { node } = &mut node {
+            let code_idx = ni.instance();
+            if let Some(cod) = self.code_engines.get_mut(code_idx) {
+                node.set_backend(cod.get_backend());
             }
+        }
+
+        if let Node::Scope { node } = &mut node {
+            if let Some(handle) = self.scopes.get(ni.instance()) {
+                node.set_scope_handle(handle.clone());
+            }
+        }
+
+        Some((node, info))
+    }
+
+    /// Returns the sorted inputs of each node of `prog`, which are compared
+    /// with the next program in [NodeConfigurator::crossfade_nodes].
+    fn prog_inputs(prog: &NodeProg) -> Option<HashMap<usize, Vec<(usize, usize, bool)>>> {
+        if prog.voice_region.is_some() {
+            return None;
+        }
+
+        let mut inputs = HashMap::new();
+        for op in prog.prog.iter() {
+            let mut op_inputs: Vec<(usize, usize, bool)> = op
+                .inputs
+                .iter()
+                .map(|(out_idx, in_idx, m)| (*out_idx, *in_idx, m.is_some()))
+                .collect();
+            op_inputs.sort_unstable();
+            inputs.insert(op.idx, op_inputs);
+        }
+
+        Some(inputs)
+    }
+
+    /// Creates the fresh node instances for crossfading to `prog`, see
+    /// [NodeConfigurator::set_prog_crossfade]. Only the nodes of the old
+    /// program whose inputs changed, or that read from a changed or new node,
+    /// get a fresh instance. The `Out` nodes always do. Returns `None` if
+    /// the program can't be crossfaded.
+    fn crossfade_nodes(
+        &mut self,
+        prog: &NodeProg,
+        prog_inputs: &Option<HashMap<usize, Vec<(usize, usize, bool)>>>,
+    ) -> Option<Vec<Node>> {
+        let (prev_inputs, inputs) = match (&self.prev_prog_inputs, prog_inputs) {
+            (Some(prev_inputs), Some(inputs)) => (prev_inputs, inputs),
+            _ => return None,
+        };
 
-            if let Node::Scope { node } = &mut node {
-                if let Some(handle) = self.scopes.get(ni.instance()) {
-                    node.set_scope_handle(handle.clone());
+        // The removed nodes keep running in the old program only:
+        for idx in prev_inputs.keys() {
+            if !inputs.contains_key(idx) && !self.nodes[*idx].0.to_id().is_crossfade_safe() {
+                return None;
+            }
+        }
+
+        let mut changed_outs = vec![false; prog.out.len()];
+        let mut changed = vec![];
+
+        for op in prog.prog.iter() {
+            let node_id = self.nodes[op.idx].0.to_id();
+
+            let is_changed = matches!(node_id, NodeId::Out(_))
+                || prev_inputs.get(&op.idx) != inputs.get(&op.idx)
+                || op.inputs.iter().any(|(out_idx, _, _)| changed_outs[*out_idx]);
+
+            if !is_changed {
+                continue;
+            }
+
+            for changed_out in changed_outs[op.out_idxlen.0..op.out_idxlen.1].iter_mut() {
+                *changed_out = true;
+            }
+
+            // The nodes that are new in this program are not
+            // executed by the old program and don't need a fresh instance:
+            if prev_inputs.contains_key(&op.idx) {
+                if !node_id.is_crossfade_safe() {
+                    return None;
                 }
+
+                changed.push((op.idx, node_id));
             }
+        }
+
+        let mut nodes = Vec::new();
+        nodes.resize_with(self.nodes.len(), || Node::Nop);
+
+        for (idx, node_id) in changed {
+            nodes[idx] = self.instantiate_node(node_id)?.0;
+        }
+
+        Some(nodes)
+    }
+
+    /// Grows the node slots to `node_count` and hands the larger
+    /// storage over to the backend. The LED values of the existing
+    /// nodes are shared with the new storage.
+    fn grow_nodes(&mut self, node_count: usize) {
+        if node_count <= self.nodes.len() {
+            return;
+        }
+
+        self.nodes.resize_with(node_count, || (NodeInfo::from_node_id(NodeId::Nop), None));
+        self.shared.node_ctx_values.resize_with(2 * node_count, || Arc::new(AtomicFloat::new(0.0)));
+        self.shared.dsp_load = DspLoadValues::new_shared(node_count);
+
+        let mut nodes = Vec::new();
+        nodes.resize_with(node_count, || Node::Nop);
+
+        let storage = NodeStorage {
+            nodes,
+            voice_nodes: self.new_voice_nodes(self.voice_slots),
+            node_ctx_values: self.shared.node_ctx_values.clone(),
+            node_ns: vec![0; node_count],
+            dsp_load: Some(self.shared.dsp_load.clone()),
+            feedback_delay_buffers: vec![],
+        };
+
+        let _ = self
+            .shared
+            .graph_update_prod
+            .push(GraphMessage::GrowStorage { storage: Box::new(storage) });
+    }
+
+    /// Allocates the node slots for `voice_slots` voices beyond the first one.
+    fn new_voice_nodes(&self, voice_slots: usize) -> Vec<Vec<Node>> {
+        let mut voice_nodes = Vec::new();
+        voice_nodes.resize_with(voice_slots, || {
+            let mut nodes = Vec::new();
+            nodes.resize_with(self.nodes.len(), || Node::Nop);
+            nodes
+        });
+        voice_nodes
+    }
+
+    /// Hands the node slots for `voices` voices over to the backend, if it
+    /// does not have them yet. The per voice copies of the nodes are only
+    /// allocated once a voice region needs them.
+    fn grow_voice_nodes(&mut self, voices: usize) {
+        let voice_slots = voices.saturating_sub(1);
+        if voice_slots <= self.voice_slots {
+            return;
+        }
+
+        let storage = NodeStorage {
+            nodes: vec![],
+            voice_nodes: self.new_voice_nodes(voice_slots),
+            node_ctx_values: vec![],
+            node_ns: vec![],
+            dsp_load: None,
+            feedback_delay_buffers: vec![],
+        };
+
+        let _ = self
+            .shared
+            .graph_update_prod
+            .push(GraphMessage::GrowStorage { storage: Box::new(storage) });
+
+        self.voice_slots = voice_slots;
+    }
+
+    /// Grows the feedback delay buffers of the backend
+    /// to hold at least `count` buffers.
+    fn grow_feedback_delays(&mut self, count: usize) {
+        if count <= self.feedback_delays {
+            return;
+        }
+
+        let count = count.max(self.feedback_delays * 2);
+
+        let mut feedback_delay_buffers = vec![];
+        feedback_delay_buffers.resize_with(count, FeedbackBuffer::new);
+
+        let storage = NodeStorage {
+            nodes: vec![],
+            voice_nodes: vec![],
+            node_ctx_values: vec![],
+            node_ns: vec![],
+            dsp_load: None,
+            feedback_delay_buffers,
+        };
+
+        let _ = self
+            .shared
+            .graph_update_prod
+            .push(GraphMessage::GrowStorage { storage: Box::new(storage) });
+
+        self.feedback_delays = count;
+    }
+
+    /// Creates the node `ni` in the backend, if it doesn't exist yet, and
+    /// returns its [NodeInfo] and node index. Grows the storage of
+    /// the nodes and feedback delay buffers if necessary.
+    pub fn create_node(&mut self, ni: NodeId) -> Option<(&NodeInfo, usize)> {
+        if let Some((node, info)) = self.instantiate_node(ni) {
+            let mut index: Option<usize> = None;
 
             for i in 0..self.nodes.len() {
                 if let NodeId::Nop = self.nodes[i].0.to_id() {
                     index = Some(i);
                     break;
                 } else if ni == self.nodes[i].0.to_id() {
-                    return Some((&self.nodes[i].0, i as u8));
+                    return Some((&self.nodes[i].0, i));
                 }
             }
 
+            if let NodeId::FbWr(_) | NodeId::FbRd(_) = ni {
+                self.grow_feedback_delays(ni.instance() + 1);
+            }
+
             if let Some(index) = index {
                 self.node2idx.insert(ni, index);
 
                 self.nodes[index] = (info, None);
 
-                let _ = self
-                    .shared
-                    .graph_update_prod
-                    .push(GraphMessage::NewNode { index: index as u8, node });
+                let _ = self.shared.graph_update_prod.push(GraphMessage::NewNode { index, node });
 
-                Some((&self.nodes[index].0, index as u8))
+                Some((&self.nodes[index].0, index))
             } else {
                 let index = self.nodes.len();
                 self.node2idx.insert(ni, index);
 
-                self.nodes.resize_with((self.nodes.len() + 1) * 2, || {
-                    (NodeInfo::from_node_id(NodeId::Nop), None)
-                });
+                self.grow_nodes((self.nodes.len() + 1) * 2);
                 self.nodes[index] = (info, None);
 
-                let _ = self
-                    .shared
-                    .graph_update_prod
-                    .push(GraphMessage::NewNode { index: index as u8, node });
+                let _ = self.shared.graph_update_prod.push(GraphMessage::NewNode { index, node });
 
-                Some((&self.nodes[index].0, index as u8))
+                Some((&self.nodes[index].0, index))
             }
         } else {
             None
@@ -1041,8 +1751,9 @@ impl NodeConfigurator {
         // Copy the parameter values and atom data into the program:
         // They are extracted by process_graph_updates() later to
         // reset the inp[] input value vector.
-        for (_param_id, param) in self.params.iter() {
+        for (param_id, param) in self.params.iter() {
             prog.params_mut()[param.input_idx] = param.value;
+            prog.smoothing_mut()[param.input_idx] = self.get_param_smoothing(param_id);
 
             if let Some((mod_idx, amt)) = param.modamt {
                 prog.modops_mut()[mod_idx].set_amt(amt);
@@ -1054,9 +1765,55 @@ impl NodeConfigurator {
             prog.atoms_mut()[param.at_idx] = param.value.clone();
         }
 
+        for (node_id, state) in self.node_states.iter() {
+            if let Some(index) = self.unique_index_for(node_id) {
+                prog.set_node_state(index, *state);
+            }
+        }
+
+        let region_idxs = self.create_voice_nodes();
+        let region_ok = if let Some((_, voices, steal_mode)) = &self.voice_region {
+            prog.set_voice_region(&region_idxs, *voices, *steal_mode)
+        } else {
+            true
+        };
+
+        if !region_ok {
+            self.voice_region = None;
+            self.errors.push(
+                "Voice Region Error
+                 The voice region reads from nodes that depend on it                  and was removed."
+                    .to_string(),
+            );
+        }
+        prog.calculate_levels();
+
         self.output_fb_cons = prog.take_feedback_consumer();
 
-        let _ = self.shared.graph_update_prod.push(GraphMessage::NewProg { prog, copy_old_out });
+        let prog_inputs = Self::prog_inputs(&prog);
+        let crossfade = if let Some(fade_ms) = self.prog_crossfade {
+            self.crossfade_nodes(&prog, &prog_inputs).map(|nodes| (nodes, fade_ms))
+        } else {
+            None
+        };
+        self.prev_prog_inputs = prog_inputs;
+        self.prog_crossfaded = crossfade.is_some();
+
+        if let Some((nodes, fade_ms)) = crossfade {
+            let _ = self.shared.graph_update_prod.push(GraphMessage::CrossfadeProg {
+                prog,
+                copy_old_out,
+                nodes,
+                fade_ms,
+            });
+        } else {
+            let _ =
+                self.shared.graph_update_prod.push(GraphMessage::NewProg { prog, copy_old_out });
+        }
+
+        if self.cue.is_some() {
+            self.send_cue();
+        }
     }
 
     /// Retrieves the feedback value for a specific output port of the
@@ -3,10 +3,14 @@
 // See README.md and COPYING for details.
 
 use super::{
-    DropMsg, GraphMessage, NodeProg, FB_DELAY_TIME_US, MAX_ALLOCATED_NODES, MAX_FB_DELAY_SIZE,
-    MAX_SMOOTHERS, UNUSED_MONITOR_IDX,
+    DropMsg, DspLoadMeter, DspLoadValues, GraphMessage, MidiEvent, MidiEventKind,
+    NodeEngineBuilder, NodeProg, NodeState, OpExecPtrs, ParamEventMode, ProgCrossfade, ProgLevel,
+    SignalFault, Transport, VoiceAllocator, WorkerPool, MAX_BUSES, MAX_FB_DELAY_SIZE,
+    MAX_PARAM_EVENTS, MAX_PERIOD_EVENTS, MAX_SMOOTHERS, UNUSED_MONITOR_IDX,
+};
+use crate::dsp::{
+    Node, NodeContext, NodeId, ProcBuf, Smoothing, DEFAULT_SMOOTHING, MAX_BLOCK_SIZE,
 };
-use crate::dsp::{Node, NodeContext, NodeId, MAX_BLOCK_SIZE};
 use crate::monitor::{MonitorBackend, MON_SIG_CNT};
 use crate::util::Smoother;
 use synfx_dsp::AtomicFloat;
@@ -16,6 +20,7 @@ use std::io::Write;
 
 use ringbuf::{Consumer, Producer};
 use std::sync::Arc;
+use std::time::Instant;
 
 use core::arch::x86_64::{
     _MM_FLUSH_ZERO_ON,
@@ -41,13 +46,25 @@ pub struct NodeExecutor {
     /// is sent back using the free-ringbuffer.
     pub(crate) nodes: Vec<Node>,
 
+    /// Contains the per voice copies of the nodes in the voice region
+    /// of the [NodeProg]. The first vector holds the nodes for voice 1,
+    /// the second for voice 2 and so on. Voice 0 is executed by `nodes`.
+    /// Empty until a voice region with more than one voice is set up.
+    pub(crate) voice_nodes: Vec<Vec<Node>>,
+
     /// Contains the stand-by smoothing operators for incoming parameter changes.
+    /// It's replaced by the larger pool of a new [NodeProg] if necessary,
+    /// see [NodeExecutor::grow_smoother_pool].
     pub(crate) smoothers: Vec<(usize, Smoother)>,
 
     /// Contains target parameter values after a smoother finished,
     /// these will refresh the input buffers:
     pub(crate) target_refresh: Vec<(usize, f32)>,
 
+    /// The scheduled parameter events, sorted by their frame offset
+    /// relative to the start of the next block.
+    param_events: Vec<ParamEvent>,
+
     /// Contains the to be executed nodes and output operations.
     /// Is copied from the input ringbuffer when a corresponding
     /// message arrives.
@@ -62,6 +79,35 @@ pub struct NodeExecutor {
     /// Context that can be accessed by all (executed) nodes at runtime.
     pub(crate) exec_ctx: NodeExecContext,
 
+    /// Holds the MIDI events of the current sub-block, relative to
+    /// the start of the sub-block. See also [NodeExecutor::process].
+    sub_block_events: Vec<MidiEvent>,
+
+    /// Holds the MIDI events at the end of the last period that did not
+    /// fit into its last sub-block. They are passed at the start of
+    /// the next period. See also [NodeExecutor::process].
+    carried_events: Vec<MidiEvent>,
+
+    /// Measures the processing time of the nodes, if enabled
+    /// with [GraphMessage::SetDspProfiling].
+    dsp_load: DspLoadMeter,
+
+    /// The worker threads that execute the independent ops of the
+    /// [NodeProg] in parallel. See also [GraphMessage::SetWorkers].
+    workers: Option<WorkerPool>,
+
+    /// The absolute sample limit of the signal guard, which
+    /// silences and resets nodes that produce non-finite or
+    /// runaway signals. Set with [GraphMessage::SetSignalGuard].
+    signal_guard: Option<f32>,
+
+    /// The old [NodeProg] while it is faded out, see [GraphMessage::CrossfadeProg].
+    crossfade: Option<ProgCrossfade>,
+
+    /// The soloed output buffer index and the first output channel,
+    /// see [GraphMessage::SetCue].
+    cue: Option<(usize, usize)>,
+
     /// The connection with the [crate::nodes::NodeConfigurator].
     shared: SharedNodeExec,
 
@@ -82,6 +128,34 @@ pub(crate) struct SharedNodeExec {
     pub(crate) graph_drop_prod: Producer<DropMsg>,
     /// For sending feedback to the frontend thread.
     pub(crate) monitor_backend: MonitorBackend,
+    /// For publishing the DSP load of the nodes to the frontend.
+    pub(crate) dsp_load: Arc<DspLoadValues>,
+    /// For reporting the node indices that tripped the signal guard.
+    pub(crate) signal_fault_prod: Producer<(usize, SignalFault)>,
+}
+
+/// Larger storage for the [NodeExecutor], allocated by the
+/// [crate::nodes::NodeConfigurator] when it runs out of node slots or
+/// feedback delay buffers. Empty vectors leave the corresponding storage
+/// unchanged. See also [GraphMessage::GrowStorage].
+pub struct NodeStorage {
+    pub(crate) nodes: Vec<Node>,
+    pub(crate) voice_nodes: Vec<Vec<Node>>,
+    pub(crate) node_ctx_values: Vec<Arc<AtomicFloat>>,
+    pub(crate) node_ns: Vec<u64>,
+    pub(crate) dsp_load: Option<Arc<DspLoadValues>>,
+    pub(crate) feedback_delay_buffers: Vec<FeedbackBuffer>,
+}
+
+impl std::fmt::Debug for NodeStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "NodeStorage(nodes={}, feedback_delay_buffers={})",
+            self.nodes.len(),
+            self.feedback_delay_buffers.len()
+        )
+    }
 }
 
 /// Contains audio driver context informations. Such as the number
@@ -89,46 +163,122 @@ pub(crate) struct SharedNodeExec {
 /// writing output samples and reading input samples.
 pub trait NodeAudioContext {
     fn nframes(&self) -> usize;
+    /// Writes an output sample. Channels that are not provided by
+    /// the audio driver should be ignored.
     fn output(&mut self, channel: usize, frame: usize, v: f32);
+    /// Returns an input sample. Channels that are not provided by
+    /// the audio driver should return `0.0`.
     fn input(&mut self, channel: usize, frame: usize) -> f32;
+
+    /// Returns the MIDI events of the current buffer period.
+    /// They must be sorted by their [MidiEvent::frame] offset.
+    /// The default implementation provides no events.
+    fn events(&self) -> &[MidiEvent] {
+        &[]
+    }
+
+    /// Returns the transport state of the host at the start of the
+    /// current buffer period. If `None` is returned, the [NodeExecutor]
+    /// advances its own [Transport], which is controlled by the frontend.
+    fn transport(&self) -> Option<Transport> {
+        None
+    }
+}
+
+/// A parameter update that is scheduled for a specific frame,
+/// see also [GraphMessage::ParamEvent].
+#[derive(Debug, Clone, Copy)]
+struct ParamEvent {
+    frame: usize,
+    input_idx: usize,
+    value: f32,
+    mode: ParamEventMode,
+}
+
+/// Checks the output buffers of a node for the signal guard.
+/// Non-finite samples take precedence over samples that exceed `limit`.
+fn check_signal(outputs: &[ProcBuf], nframes: usize, limit: f32) -> Option<SignalFault> {
+    let mut fault = None;
+
+    for pb in outputs.iter() {
+        for frame in 0..nframes {
+            let s = pb.read(frame);
+            if !s.is_finite() {
+                return Some(SignalFault::NonFinite);
+            } else if s.abs() > limit {
+                fault = Some(SignalFault::OutOfBounds);
+            }
+        }
+    }
+
+    fault
+}
+
+/// Provides a sub-block of a host buffer period to the nodes.
+/// Used by [NodeExecutor::process] to split buffer periods that are
+/// longer than [crate::dsp::MAX_BLOCK_SIZE].
+struct SubBlockContext<'a, 'b, T: NodeAudioContext> {
+    ctx: &'a mut T,
+    offs: usize,
+    nframes: usize,
+    events: &'b [MidiEvent],
+}
+
+impl<'a, 'b, T: NodeAudioContext> NodeAudioContext for SubBlockContext<'a, 'b, T> {
+    #[inline]
+    fn nframes(&self) -> usize {
+        self.nframes
+    }
+
+    #[inline]
+    fn output(&mut self, channel: usize, frame: usize, v: f32) {
+        self.ctx.output(channel, self.offs + frame, v)
+    }
+
+    #[inline]
+    fn input(&mut self, channel: usize, frame: usize) -> f32 {
+        self.ctx.input(channel, self.offs + frame)
+    }
+
+    #[inline]
+    fn events(&self) -> &[MidiEvent] {
+        self.events
+    }
 }
 
 /// Implements a trivial buffer for the feedback nodes
 /// FbWr and FbRd.
 ///
+/// The samples of a block are written relative to the start of the
+/// current block and read relative to it with a delay. The buffer is a ring
+/// of [MAX_FB_DELAY_SIZE] samples, just like it always was, so the fixed
+/// 3.14ms delay of FbRd does not depend on the block size. A delay of at
+/// least the block length also does not depend on whether the FbWr or
+/// the FbRd node is executed first.
+///
 /// Note that the previous audio period or even the first one ever may
-/// produce less than 128 samples. This means we need to keep track
-/// how many samples were actually written to the feedback buffer!
-/// See also `sample_count` field.
+/// produce less than 128 samples. The start of the block is moved by the
+/// actual number of frames, see [FeedbackBuffer::next_block].
 pub struct FeedbackBuffer {
-    /// The feedback buffer that holds the samples of the previous period.
+    /// The feedback buffer that holds the samples of the previous periods.
     buffer: [f32; MAX_FB_DELAY_SIZE],
-    /// The write pointer.
-    write_ptr: usize,
-    /// Read pointer, is always behind write_ptr by an initial amount
-    read_ptr: usize,
+    /// Index of the first sample of the current block.
+    block_ptr: usize,
 }
 
 impl FeedbackBuffer {
     pub fn new() -> Self {
-        let delay_sample_count = (44100.0 as usize * FB_DELAY_TIME_US) / 1000000;
-        Self {
-            buffer: [0.0; MAX_FB_DELAY_SIZE],
-            write_ptr: delay_sample_count % MAX_FB_DELAY_SIZE,
-            read_ptr: 0,
-        }
+        Self { buffer: [0.0; MAX_FB_DELAY_SIZE], block_ptr: 0 }
     }
 
     pub fn clear(&mut self) {
         self.buffer = [0.0; MAX_FB_DELAY_SIZE];
     }
 
-    pub fn set_sample_rate(&mut self, sr: f32) {
-        self.buffer = [0.0; MAX_FB_DELAY_SIZE];
+    pub fn set_sample_rate(&mut self, _sr: f32) {
         // The delay sample count maximum is defined by MAX_FB_DELAY_SRATE,
-        // after that the feedback delays become shorter than they should be
-        // and things won't sound the same at sample rate
-        // exceeding MAX_FB_DELAY_SRATE.
+        // after that the longest feedback delays become shorter than they
+        // should be at sample rates exceeding MAX_FB_DELAY_SRATE.
         //
         // This is a tradeoff of wasted memory and not having to reallocate
         // these delays for sample rate changes and providing delay buffers
@@ -136,26 +286,48 @@ impl FeedbackBuffer {
         //
         // For more elaborate and longer delays an extra delay node should
         // be used before FbWr or after FbRd.
+        self.clear();
+        self.block_ptr = 0;
+    }
+
+    /// The longest delay in samples that [FeedbackBuffer::read] supports.
+    pub const fn max_delay() -> usize {
+        MAX_FB_DELAY_SIZE - MAX_BLOCK_SIZE - 2
+    }
 
-        let delay_sample_count = (sr as usize * FB_DELAY_TIME_US) / 1000000;
-        self.write_ptr = delay_sample_count % MAX_FB_DELAY_SIZE;
-        self.read_ptr = 0;
+    /// Writes the sample `s` for the `frame` of the current block.
+    #[inline]
+    pub fn write(&mut self, frame: usize, s: f32) {
+        self.buffer[(self.block_ptr + frame) % MAX_FB_DELAY_SIZE] = s;
     }
 
+    /// Reads the sample that was written `delay` samples before the `frame`
+    /// of the current block, with linear interpolation. The `delay` has to
+    /// be at most [FeedbackBuffer::max_delay]. If it is shorter than the
+    /// `frame`, the sample is only read correctly if it was already written
+    /// in this block, otherwise the sample from one ring length ago is read.
     #[inline]
-    pub fn write(&mut self, s: f32) {
-        self.write_ptr = (self.write_ptr + 1) % MAX_FB_DELAY_SIZE;
-        self.buffer[self.write_ptr] = s;
-        //d// println!("WRITE[{}] = {:8.3}", self.write_ptr, s);
+    pub fn read(&self, frame: usize, delay: f32) -> f32 {
+        // Splitting off the fractional part keeps the precision of
+        // the interpolation independent of the buffer position:
+        let delay_int = delay.ceil();
+        let fract = delay_int - delay;
+        let i = self.block_ptr + MAX_FB_DELAY_SIZE + frame - delay_int as usize;
+
+        let s0 = self.buffer[i % MAX_FB_DELAY_SIZE];
+        if fract == 0.0 {
+            s0
+        } else {
+            let s1 = self.buffer[(i + 1) % MAX_FB_DELAY_SIZE];
+            s0 + (s1 - s0) * fract
+        }
     }
 
+    /// Moves the start of the block by the `nframes` of the
+    /// current block, called after the [NodeProg] was executed.
     #[inline]
-    pub fn read(&mut self) -> f32 {
-        self.read_ptr = (self.read_ptr + 1) % MAX_FB_DELAY_SIZE;
-        self.buffer[self.read_ptr]
-        //d// let s = self.buffer[self.read_ptr];
-        //d// println!("READ[{}] = {:8.3}", self.read_ptr, s);
-        //d// s
+    pub fn next_block(&mut self, nframes: usize) {
+        self.block_ptr = (self.block_ptr + nframes) % MAX_FB_DELAY_SIZE;
     }
 }
 
@@ -169,13 +341,43 @@ impl Default for FeedbackBuffer {
 /// This is used for instance to implement the feedbackd delay nodes.
 pub struct NodeExecContext {
     pub feedback_delay_buffers: Vec<FeedbackBuffer>,
+    /// Assigns notes to the voices of the voice region.
+    pub voice_alloc: VoiceAllocator,
+    /// The voice that is currently executed. Always 0 outside
+    /// of the voice region.
+    pub cur_voice: usize,
+    /// The song position and tempo at the start of the current block.
+    pub transport: Transport,
+    /// The sums of the `BusSend` nodes, which are read by the `BusRecv` nodes.
+    /// Cleared at the start of each block, see [NodeExecContext::clear_buses].
+    pub bus_buffers: Vec<[f32; MAX_BLOCK_SIZE]>,
 }
 
 impl NodeExecContext {
-    fn new() -> Self {
+    fn new(feedback_delays: usize) -> Self {
         let mut fbdb = vec![];
-        fbdb.resize_with(MAX_ALLOCATED_NODES, FeedbackBuffer::new);
-        Self { feedback_delay_buffers: fbdb }
+        fbdb.resize_with(feedback_delays, FeedbackBuffer::new);
+        Self {
+            feedback_delay_buffers: fbdb,
+            voice_alloc: VoiceAllocator::new(),
+            cur_voice: 0,
+            transport: Transport::new(),
+            bus_buffers: vec![[0.0; MAX_BLOCK_SIZE]; MAX_BUSES],
+        }
+    }
+
+    /// Moves all feedback buffers to the next block, see [FeedbackBuffer::next_block].
+    pub(crate) fn next_feedback_block(&mut self, nframes: usize) {
+        for b in self.feedback_delay_buffers.iter_mut() {
+            b.next_block(nframes);
+        }
+    }
+
+    /// Silences the buses before a [NodeProg] is executed.
+    pub(crate) fn clear_buses(&mut self) {
+        for buf in self.bus_buffers.iter_mut() {
+            buf.fill(0.0);
+        }
     }
 
     fn set_sample_rate(&mut self, srate: f32) {
@@ -188,28 +390,40 @@ impl NodeExecContext {
         for b in self.feedback_delay_buffers.iter_mut() {
             b.clear();
         }
+        self.voice_alloc.all_notes_off();
+        self.cur_voice = 0;
+        self.clear_buses();
     }
 }
 
 impl NodeExecutor {
-    pub(crate) fn new(shared: SharedNodeExec) -> Self {
+    pub(crate) fn new(shared: SharedNodeExec, limits: &NodeEngineBuilder) -> Self {
         let mut nodes = Vec::new();
-        nodes.resize_with(MAX_ALLOCATED_NODES, || Node::Nop);
+        nodes.resize_with(limits.nodes, || Node::Nop);
 
         let mut smoothers = Vec::new();
         smoothers.resize_with(MAX_SMOOTHERS, || (0, Smoother::new()));
 
-        let target_refresh = Vec::with_capacity(MAX_SMOOTHERS);
+        let target_refresh = Vec::with_capacity(MAX_SMOOTHERS + 2 * MAX_PARAM_EVENTS);
 
         NodeExecutor {
             nodes,
+            voice_nodes: vec![],
             smoothers,
             target_refresh,
+            param_events: Vec::with_capacity(MAX_PARAM_EVENTS),
             sample_rate: 44100.0,
             prog: NodeProg::empty(),
             monitor_signal_cur_inp_indices: [UNUSED_MONITOR_IDX; MON_SIG_CNT],
-            exec_ctx: NodeExecContext::new(),
+            exec_ctx: NodeExecContext::new(limits.feedback_delays),
+            sub_block_events: Vec::with_capacity(MAX_PERIOD_EVENTS),
+            carried_events: Vec::with_capacity(MAX_PERIOD_EVENTS),
             dsp_log_init: false,
+            dsp_load: DspLoadMeter::new(shared.dsp_load.clone()),
+            signal_guard: None,
+            workers: None,
+            crossfade: None,
+            cue: None,
             shared,
         }
     }
@@ -223,8 +437,15 @@ impl NodeExecutor {
         while let Some(upd) = self.shared.graph_update_con.pop() {
             match upd {
                 GraphMessage::NewNode { index, mut node } => {
+                    let slot = if let Some(slot) = self.nodes.get_mut(index) {
+                        slot
+                    } else {
+                        let _ = self.shared.graph_drop_prod.push(DropMsg::Node { node });
+                        continue;
+                    };
+
                     node.set_sample_rate(self.sample_rate);
-                    let prev_node = std::mem::replace(&mut self.nodes[index as usize], node);
+                    let prev_node = std::mem::replace(slot, node);
 
                     log(|w| {
                         let _ = write!(w, "[dbg] Create node index={}", index);
@@ -232,8 +453,31 @@ impl NodeExecutor {
 
                     let _ = self.shared.graph_drop_prod.push(DropMsg::Node { node: prev_node });
                 }
+                GraphMessage::NewVoiceNode { voice, index, mut node } => {
+                    // The slots of the voice were handed over with a
+                    // GrowStorage message before, see grow_voice_nodes():
+                    let voice_nodes = if voice == 0 {
+                        None
+                    } else {
+                        self.voice_nodes.get_mut(voice as usize - 1)
+                    };
+
+                    let slot = if let Some(slot) = voice_nodes.and_then(|n| n.get_mut(index)) {
+                        slot
+                    } else {
+                        let _ = self.shared.graph_drop_prod.push(DropMsg::Node { node });
+                        continue;
+                    };
+
+                    node.set_sample_rate(self.sample_rate);
+                    let prev_node = std::mem::replace(slot, node);
+
+                    let _ = self.shared.graph_drop_prod.push(DropMsg::Node { node: prev_node });
+                }
                 GraphMessage::Clear { prog } => {
-                    for n in self.nodes.iter_mut() {
+                    self.finish_crossfade();
+
+                    for n in self.nodes.iter_mut().chain(self.voice_nodes.iter_mut().flatten()) {
                         if n.to_id(0) != NodeId::Nop {
                             let prev_node = std::mem::replace(n, Node::Nop);
                             let _ =
@@ -242,6 +486,9 @@ impl NodeExecutor {
                     }
 
                     self.exec_ctx.clear();
+                    self.param_events.clear();
+                    self.cue = None;
+                    self.dsp_load.clear();
 
                     self.monitor_signal_cur_inp_indices = [UNUSED_MONITOR_IDX; MON_SIG_CNT];
 
@@ -253,73 +500,16 @@ impl NodeExecutor {
                     let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog: prev_prog });
                 }
                 GraphMessage::NewProg { prog, copy_old_out } => {
-                    let mut prev_prog = std::mem::replace(&mut self.prog, prog);
-
-                    unsafe {
-                        _MM_SET_FLUSH_ZERO_MODE(_MM_FLUSH_ZERO_ON);
-                    }
-
-                    self.monitor_signal_cur_inp_indices = [UNUSED_MONITOR_IDX; MON_SIG_CNT];
-
-                    // XXX: Copying from the old vector works, because we only
-                    //      append nodes to the _end_ of the node instance vector.
-                    //      If we do a garbage collection, we can't do this.
-                    //
-                    // XXX: Also, we need to initialize the input parameter
-                    //      vector, because we don't know if they are updated from
-                    //      the new program outputs anymore. So we need to
-                    //      copy the old paramters to the inputs.
-                    //
-                    //      => This does not apply to atom data, because that
-                    //         is always sent with the new program and "should"
-                    //         be up to date, even if we have a slight possible race
-                    //         condition between GraphMessage::NewProg
-                    //         and GraphMessage::AtomUpdate.
-
-                    // First overwrite by the current input parameters,
-                    // to make sure _all_ inputs have a proper value
-                    // (not just those that existed before).
-                    //
-                    // We preserve the modulation history in the next step.
-                    // This is also to make sure that new input ports
-                    // have a proper value too.
-                    self.prog.initialize_input_buffers();
-
-                    if copy_old_out {
-                        // XXX: The following is commented out, because presisting
-                        //      the output proc buffers does not make sense anymore.
-                        //      Because we don't allow cycles, so there is no
-                        //      way that a node can read from the previous
-                        //      iteration anyways.
-                        //
-                        // // Swap the old out buffers into the new NodeProg
-                        // // TODO: If we toss away most of the buffers anyways,
-                        // //       we could optimize this step with more
-                        // //       intelligence in the matrix compiler.
-                        // for (old_pb, new_pb) in
-                        //     prev_prog.out.iter_mut().zip(
-                        //         self.prog.out.iter_mut())
-                        // {
-                        //     std::mem::swap(old_pb, new_pb);
-                        // }
-
-                        // Then overwrite the inputs by the more current previous
-                        // input processing buffers, so we keep any modulation
-                        // (smoothed) history of the block too.
-                        self.prog.swap_previous_outputs(&mut prev_prog);
-                    }
-
-                    self.prog.assign_outputs();
+                    self.finish_crossfade();
 
+                    let prev_prog = self.install_prog(prog, copy_old_out);
                     let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog: prev_prog });
+                }
+                GraphMessage::CrossfadeProg { prog, copy_old_out, nodes, fade_ms } => {
+                    self.finish_crossfade();
 
-                    log(|w| {
-                        let _ = write!(
-                            w,
-                            "[dbg] Created new graph (node count={})",
-                            self.prog.prog.len()
-                        );
-                    });
+                    let prev_prog = self.install_prog(prog, copy_old_out);
+                    self.start_crossfade(prev_prog, nodes, fade_ms);
                 }
                 GraphMessage::AtomUpdate { at_idx, value } => {
                     let prog = &mut self.prog;
@@ -333,20 +523,248 @@ impl NodeExecutor {
                 GraphMessage::ModamtUpdate { mod_idx, modamt } => {
                     self.set_modamt(mod_idx, modamt);
                 }
+                GraphMessage::SmoothingUpdate { input_idx, smoothing } => {
+                    if let Some(s) = self.prog.smoothing.get_mut(input_idx) {
+                        *s = smoothing;
+                    }
+                }
+                GraphMessage::ParamEvent { input_idx, value, frame, mode } => {
+                    self.schedule_param_event(ParamEvent { frame, input_idx, value, mode });
+                }
                 GraphMessage::SetMonitor { bufs } => {
                     self.monitor_signal_cur_inp_indices = bufs;
                 }
+                GraphMessage::NoteOn { note, vel } => {
+                    self.exec_ctx.voice_alloc.note_on(note, vel);
+                }
+                GraphMessage::NoteOff { note } => {
+                    self.exec_ctx.voice_alloc.note_off(note);
+                }
+                GraphMessage::SetTempo { bpm } => {
+                    self.exec_ctx.transport.bpm = bpm.max(1.0);
+                }
+                GraphMessage::SetTimeSig { num, denom } => {
+                    self.exec_ctx.transport.sig_num = num.max(1);
+                    self.exec_ctx.transport.sig_denom = denom.max(1);
+                }
+                GraphMessage::SetPlaying { playing } => {
+                    self.exec_ctx.transport.playing = playing;
+                }
+                GraphMessage::Locate { beat_pos } => {
+                    self.exec_ctx.transport.locate(beat_pos, self.sample_rate);
+                }
+                GraphMessage::SetDspProfiling { enabled } => {
+                    if !enabled {
+                        self.dsp_load.clear();
+                    }
+                    self.dsp_load.enabled = enabled;
+                }
+                GraphMessage::SetSignalGuard { limit } => {
+                    self.signal_guard = limit.map(|l| l.abs());
+                }
+                GraphMessage::SetCue { cue } => {
+                    self.cue = cue;
+                }
+                GraphMessage::SetNodeState { index, state } => {
+                    self.prog.set_node_state(index, state);
+                }
+                GraphMessage::SetWorkers { pool } => {
+                    if let Some(prev_pool) = std::mem::replace(&mut self.workers, pool) {
+                        let _ =
+                            self.shared.graph_drop_prod.push(DropMsg::Workers { pool: prev_pool });
+                    }
+                }
+                GraphMessage::GrowStorage { mut storage } => {
+                    self.grow_storage(&mut storage);
+                    let _ = self.shared.graph_drop_prod.push(DropMsg::Storage { storage });
+                }
+            }
+        }
+    }
+
+    /// Replaces the current [NodeProg] with `prog` and returns the
+    /// previous one, which must be sent to the drop thread eventually.
+    fn install_prog(&mut self, prog: NodeProg, copy_old_out: bool) -> NodeProg {
+        let mut prev_prog = std::mem::replace(&mut self.prog, prog);
+        self.grow_smoother_pool();
+
+        // The input indices of the pending parameter events might refer
+        // to other parameters in the new program. The values of the events
+        // are already contained in the parameters of the new program,
+        // because the frontend stores them right when they are scheduled.
+        self.param_events.clear();
+
+        unsafe {
+            _MM_SET_FLUSH_ZERO_MODE(_MM_FLUSH_ZERO_ON);
+        }
+
+        self.monitor_signal_cur_inp_indices = [UNUSED_MONITOR_IDX; MON_SIG_CNT];
+
+        // XXX: Copying from the old vector works, because we only
+        //      append nodes to the _end_ of the node instance vector.
+        //      If we do a garbage collection, we can't do this.
+        //
+        // XXX: Also, we need to initialize the input parameter
+        //      vector, because we don't know if they are updated from
+        //      the new program outputs anymore. So we need to
+        //      copy the old paramters to the inputs.
+        //
+        //      => This does not apply to atom data, because that
+        //         is always sent with the new program and "should"
+        //         be up to date, even if we have a slight possible race
+        //         condition between GraphMessage::NewProg
+        //         and GraphMessage::AtomUpdate.
+
+        // First overwrite by the current input parameters,
+        // to make sure _all_ inputs have a proper value
+        // (not just those that existed before).
+        //
+        // We preserve the modulation history in the next step.
+        // This is also to make sure that new input ports
+        // have a proper value too.
+        self.prog.initialize_input_buffers();
+
+        if copy_old_out {
+            // XXX: The following is commented out, because presisting
+            //      the output proc buffers does not make sense anymore.
+            //      Because we don't allow cycles, so there is no
+            //      way that a node can read from the previous
+            //      iteration anyways.
+            //
+            // // Swap the old out buffers into the new NodeProg
+            // // TODO: If we toss away most of the buffers anyways,
+            // //       we could optimize this step with more
+            // //       intelligence in the matrix compiler.
+            // for (old_pb, new_pb) in
+            //     prev_prog.out.iter_mut().zip(
+            //         self.prog.out.iter_mut())
+            // {
+            //     std::mem::swap(old_pb, new_pb);
+            // }
+
+            // Then overwrite the inputs by the more current previous
+            // input processing buffers, so we keep any modulation
+            // (smoothed) history of the block too.
+            self.prog.swap_previous_outputs(&mut prev_prog);
+        }
+
+        self.prog.assign_outputs();
+
+        if let Some(region) = &self.prog.voice_region {
+            self.exec_ctx.voice_alloc.set_voice_count(region.voices);
+            self.exec_ctx.voice_alloc.set_steal_mode(region.steal_mode);
+        } else {
+            self.exec_ctx.voice_alloc.set_voice_count(1);
+        }
+
+        log(|w| {
+            let _ = write!(w, "[dbg] Created new graph (node count={})", self.prog.prog.len());
+        });
+
+        prev_prog
+    }
+
+    /// Starts fading out `prev_prog` and fading in the current program.
+    /// The changed nodes of the current program run on the fresh instances
+    /// in `nodes`, all other nodes are `Node::Nop` there. Falls back to
+    /// replacing the program right away if a voice region is involved,
+    /// because the per voice nodes can't be duplicated.
+    fn start_crossfade(&mut self, mut prev_prog: NodeProg, mut nodes: Vec<Node>, fade_ms: f32) {
+        let len = ((fade_ms * self.sample_rate) / 1000.0) as usize;
+
+        if len == 0 || prev_prog.voice_region.is_some() || self.prog.voice_region.is_some() {
+            let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog: prev_prog });
+            let _ = self.shared.graph_drop_prod.push(DropMsg::Nodes { nodes });
+            return;
+        }
+
+        // The input buffers might have been swapped with the current program:
+        prev_prog.initialize_input_buffers();
+        prev_prog.assign_outputs();
+
+        for op in self.prog.prog.iter() {
+            if let (Some(node), Some(new_node)) =
+                (self.nodes.get_mut(op.idx), nodes.get_mut(op.idx))
+            {
+                if let Node::Nop = new_node {
+                    continue;
+                }
+
+                new_node.set_sample_rate(self.sample_rate);
+                std::mem::swap(node, new_node);
             }
         }
+
+        self.crossfade = Some(ProgCrossfade::new(prev_prog, nodes, len, &self.prog));
+    }
+
+    /// Ends the current crossfade right away and sends the old program
+    /// and the old node instances to the drop thread.
+    fn finish_crossfade(&mut self) {
+        if let Some(xfade) = self.crossfade.take() {
+            let (prog, nodes) = xfade.into_parts();
+            let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog });
+            let _ = self.shared.graph_drop_prod.push(DropMsg::Nodes { nodes });
+        }
+    }
+
+    /// Moves the nodes, the per voice nodes and the feedback delay buffers
+    /// into the larger `storage` and swaps it in. The old storage is left
+    /// in `storage`, so that it can be dropped outside of the audio thread.
+    fn grow_storage(&mut self, storage: &mut NodeStorage) {
+        if storage.nodes.len() > self.nodes.len() {
+            for (new_node, node) in storage.nodes.iter_mut().zip(self.nodes.iter_mut()) {
+                std::mem::swap(new_node, node);
+            }
+            std::mem::swap(&mut self.nodes, &mut storage.nodes);
+
+            std::mem::swap(&mut self.shared.node_ctx_values, &mut storage.node_ctx_values);
+
+            if let Some(dsp_load) = &mut storage.dsp_load {
+                self.dsp_load.swap_storage(&mut storage.node_ns, dsp_load);
+            }
+        }
+
+        // Sent by grow_nodes() with the current number of voices
+        // or by grow_voice_nodes() with more voices:
+        if !storage.voice_nodes.is_empty() && storage.voice_nodes.len() >= self.voice_nodes.len() {
+            for (new_nodes, nodes) in
+                storage.voice_nodes.iter_mut().zip(self.voice_nodes.iter_mut())
+            {
+                for (new_node, node) in new_nodes.iter_mut().zip(nodes.iter_mut()) {
+                    std::mem::swap(new_node, node);
+                }
+            }
+            std::mem::swap(&mut self.voice_nodes, &mut storage.voice_nodes);
+        }
+
+        let fb_bufs = &mut self.exec_ctx.feedback_delay_buffers;
+        if storage.feedback_delay_buffers.len() > fb_bufs.len() {
+            let old_len = fb_bufs.len();
+            for (new_buf, buf) in storage.feedback_delay_buffers.iter_mut().zip(fb_bufs.iter_mut())
+            {
+                std::mem::swap(new_buf, buf);
+            }
+            for buf in storage.feedback_delay_buffers.iter_mut().skip(old_len) {
+                buf.set_sample_rate(self.sample_rate);
+            }
+            std::mem::swap(fb_bufs, &mut storage.feedback_delay_buffers);
+        }
     }
 
     pub fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
         self.exec_ctx.set_sample_rate(sample_rate);
-        for n in self.nodes.iter_mut() {
+        for n in self.nodes.iter_mut().chain(self.voice_nodes.iter_mut().flatten()) {
             n.set_sample_rate(sample_rate);
         }
 
+        if let Some(xfade) = self.crossfade.as_mut() {
+            for n in xfade.nodes_mut().iter_mut() {
+                n.set_sample_rate(sample_rate);
+            }
+        }
+
         for sm in self.smoothers.iter_mut() {
             sm.1.set_sample_rate(sample_rate);
         }
@@ -369,6 +787,31 @@ impl NodeExecutor {
         }
     }
 
+    /// Takes over the smoother pool of the current [NodeProg], if it's
+    /// larger than the current one. The running smoothers are moved to
+    /// the new pool and the old pool is dropped together with the [NodeProg]
+    /// later, so that there is no allocation in the audio thread.
+    fn grow_smoother_pool(&mut self) {
+        if self.prog.smoothers.len() <= self.smoothers.len()
+            || self.prog.target_refresh.capacity() < self.target_refresh.len()
+        {
+            return;
+        }
+
+        for (new_sm, sm) in self.prog.smoothers.iter_mut().zip(self.smoothers.iter()) {
+            *new_sm = *sm;
+        }
+        for sm in self.prog.smoothers.iter_mut() {
+            sm.1.set_sample_rate(self.sample_rate);
+        }
+
+        self.prog.target_refresh.clear();
+        self.prog.target_refresh.extend(self.target_refresh.drain(..));
+
+        std::mem::swap(&mut self.smoothers, &mut self.prog.smoothers);
+        std::mem::swap(&mut self.target_refresh, &mut self.prog.target_refresh);
+    }
+
     #[inline]
     fn set_param(&mut self, input_idx: usize, value: f32) {
         let prog = &mut self.prog;
@@ -377,53 +820,175 @@ impl NodeExecutor {
             return;
         }
 
+        let smoothing = prog.smoothing.get(input_idx).copied().unwrap_or(DEFAULT_SMOOTHING);
+        if !Self::start_smoother(&mut self.smoothers, &prog.params, input_idx, value, smoothing) {
+            // The pool is exhausted, rather jump to the value than to drop the update:
+            prog.params[input_idx] = value;
+            prog.inp[input_idx].fill(value);
+        }
+    }
+
+    /// Starts smoothing the parameter at `input_idx` from it's current value
+    /// to `value`. Returns false if no smoother was available.
+    #[inline]
+    fn start_smoother(
+        smoothers: &mut [(usize, Smoother)],
+        params: &[f32],
+        input_idx: usize,
+        value: f32,
+        smoothing: Smoothing,
+    ) -> bool {
         // First check if we already have a running smoother for this param:
-        for (sm_inp_idx, smoother) in self.smoothers.iter_mut().filter(|s| !s.1.is_done()) {
+        for (sm_inp_idx, smoother) in smoothers.iter_mut().filter(|s| !s.1.is_done()) {
             if *sm_inp_idx == input_idx {
-                smoother.set(prog.params[input_idx], value);
+                smoother.set(params[input_idx], value, smoothing);
                 //d// println!("RE-SET SMOOTHER {} {:6.3} (old = {:6.3})",
-                //d//          input_idx, value, prog.params[input_idx]);
-                return;
+                //d//          input_idx, value, params[input_idx]);
+                return true;
             }
         }
 
         // Find unused smoother and set it:
-        if let Some(sm) = self.smoothers.iter_mut().find(|s| s.1.is_done()) {
+        if let Some(sm) = smoothers.iter_mut().find(|s| s.1.is_done()) {
             sm.0 = input_idx;
-            sm.1.set(prog.params[input_idx], value);
+            sm.1.set(params[input_idx], value, smoothing);
             //d// println!("SET SMOOTHER {} {:6.3} (old = {:6.3})",
-            //d//          input_idx, value, prog.params[input_idx]);
+            //d//          input_idx, value, params[input_idx]);
+            return true;
+        }
+
+        false
+    }
+
+    /// Inserts the event into the queue of scheduled parameter events,
+    /// behind the events with the same frame offset.
+    /// If the queue is full, the parameter is updated right away.
+    #[inline]
+    fn schedule_param_event(&mut self, ev: ParamEvent) {
+        if self.param_events.len() == self.param_events.capacity() {
+            self.set_param(ev.input_idx, ev.value);
+            return;
         }
+
+        let pos = self.param_events.partition_point(|e| e.frame <= ev.frame);
+        self.param_events.insert(pos, ev);
     }
 
     #[inline]
     fn process_smoothers(&mut self, nframes: usize) {
         let prog = &mut self.prog;
+        let smoothers = &mut self.smoothers;
+        let target_refresh = &mut self.target_refresh;
+        let param_events = &mut self.param_events;
 
-        while let Some((idx, v)) = self.target_refresh.pop() {
+        for (idx, v) in target_refresh.drain(..) {
             prog.inp[idx].fill(v);
         }
 
-        for (idx, smoother) in self.smoothers.iter_mut().filter(|s| !s.1.is_done()) {
-            let inp = &mut prog.inp[*idx];
-            let mut last_v = 0.0;
+        // The block is processed in segments, that are split
+        // at the frames of the scheduled parameter events:
+        let mut ev_idx = 0;
+        let mut start = 0;
+        loop {
+            let end = param_events
+                .get(ev_idx)
+                .map(|ev| ev.frame.clamp(start, nframes))
+                .unwrap_or(nframes);
+
+            if end > start {
+                for (idx, smoother) in smoothers.iter_mut().filter(|s| !s.1.is_done()) {
+                    let inp = &mut prog.inp[*idx];
+                    let mut last_v = 0.0;
+
+                    for frame in start..end {
+                        let v = smoother.next();
+
+                        inp.write(frame, v);
+                        last_v = v;
+                    }
+
+                    prog.params[*idx] = last_v;
 
-            for frame in 0..nframes {
-                let v = smoother.next();
+                    if smoother.is_done() {
+                        for frame in end..nframes {
+                            inp.write(frame, last_v);
+                        }
+                    }
+
+                    if smoother.is_done() || end == nframes {
+                        target_refresh.push((*idx, last_v));
+                    }
+                }
+            }
 
-                inp.write(frame, v);
-                last_v = v;
+            if end >= nframes {
+                break;
             }
 
-            prog.params[*idx] = last_v;
-            self.target_refresh.push((*idx, last_v));
+            while let Some(ev) = param_events.get(ev_idx) {
+                if ev.frame > end {
+                    break;
+                }
+                ev_idx += 1;
+
+                if ev.input_idx >= prog.params.len() {
+                    continue;
+                }
+
+                let smoothing = prog.smoothing[ev.input_idx];
+                let started = ev.mode == ParamEventMode::Ramp
+                    && Self::start_smoother(
+                        smoothers,
+                        &prog.params,
+                        ev.input_idx,
+                        ev.value,
+                        smoothing,
+                    );
+
+                if !started {
+                    for (_, smoother) in
+                        smoothers.iter_mut().filter(|s| !s.1.is_done() && s.0 == ev.input_idx)
+                    {
+                        smoother.stop();
+                    }
+
+                    let inp = &mut prog.inp[ev.input_idx];
+                    for frame in end..nframes {
+                        inp.write(frame, ev.value);
+                    }
+
+                    prog.params[ev.input_idx] = ev.value;
+                    target_refresh.push((ev.input_idx, ev.value));
+                }
+            }
+
+            start = end;
+        }
+
+        param_events.drain(0..ev_idx);
+        for ev in param_events.iter_mut() {
+            ev.frame -= nframes;
         }
     }
 
-    #[inline]
+    /// Processes one buffer period of the audio driver.
+    ///
+    /// The period may have any length. Periods longer than
+    /// [crate::dsp::MAX_BLOCK_SIZE] are split up into sub-blocks.
+    /// Between the sub-blocks the graph updates from the
+    /// [crate::nodes::NodeConfigurator] are processed, so that parameter
+    /// changes and their smoothing are not delayed until the next period.
+    /// The inputs, outputs and MIDI events are passed to the nodes
+    /// relative to the start of the sub-block.
+    ///
+    /// A sub-block holds at most [crate::nodes::MAX_PERIOD_EVENTS] MIDI
+    /// events. If there are more, the sub-block is ended before the first
+    /// event that does not fit. More events than that on a single frame are
+    /// passed one frame later, or at the start of the next period.
+    ///
+    /// You should still call [NodeExecutor::process_graph_updates]
+    /// before calling this function.
     pub fn process<T: NodeAudioContext>(&mut self, ctx: &mut T) {
-        // let tb = std::time::Instant::now();
-
         if !self.dsp_log_init && crate::log::init_thread_logger("dsp") {
             self.dsp_log_init = true;
             crate::log(|w| {
@@ -431,53 +996,377 @@ impl NodeExecutor {
             });
         }
 
+        if let Some(transport) = ctx.transport() {
+            self.exec_ctx.transport = transport;
+        }
+
+        let nframes = ctx.nframes();
+        if nframes <= MAX_BLOCK_SIZE && self.carried_events.is_empty() {
+            self.process_block(ctx);
+            return;
+        }
+
+        // Take the buffers out, so that we can pass them along with
+        // a mutable borrow of `self`:
+        let mut events = std::mem::take(&mut self.sub_block_events);
+        let mut carried = std::mem::take(&mut self.carried_events);
+
+        let mut next_ev = 0;
+        let mut offs = 0;
+        while offs < nframes {
+            let mut cur_nframes = (nframes - offs).min(MAX_BLOCK_SIZE);
+
+            if offs > 0 {
+                self.process_graph_updates();
+            }
+
+            events.clear();
+            events.extend(carried.drain(..).map(|mut ev| {
+                ev.frame = 0;
+                ev
+            }));
+
+            // Events before `offs` did not fit into the previous sub-block:
+            while let Some(ev) = ctx.events().get(next_ev) {
+                if ev.frame >= offs + cur_nframes {
+                    break;
+                }
+
+                if events.len() == events.capacity() {
+                    cur_nframes = ev.frame.saturating_sub(offs).max(1);
+                    while events.last().map(|e| e.frame >= cur_nframes).unwrap_or(false) {
+                        events.pop();
+                        next_ev -= 1;
+                    }
+                    break;
+                }
+
+                let mut ev = *ev;
+                ev.frame = ev.frame.saturating_sub(offs);
+                events.push(ev);
+                next_ev += 1;
+            }
+
+            let mut sub_ctx =
+                SubBlockContext { ctx: &mut *ctx, offs, nframes: cur_nframes, events: &events[..] };
+            self.process_block(&mut sub_ctx);
+
+            offs += cur_nframes;
+        }
+
+        for ev in ctx.events()[next_ev..].iter().filter(|ev| ev.frame < nframes) {
+            if carried.len() == carried.capacity() {
+                crate::log(|w| {
+                    let _ = write!(w, "MIDI event overflow, dropped events");
+                });
+                break;
+            }
+            carried.push(*ev);
+        }
+
+        self.sub_block_events = events;
+        self.carried_events = carried;
+    }
+
+    /// Processes one block of at most [crate::dsp::MAX_BLOCK_SIZE] frames.
+    /// While a crossfade is running, the current program is executed first
+    /// and its output is blended with the output of the old program, which
+    /// reads the outputs of the shared nodes from the current program.
+    /// The transport and the feedback buffers are advanced after both
+    /// programs ran, so that they see the same block.
+    #[inline]
+    fn process_block<T: NodeAudioContext>(&mut self, ctx: &mut T) {
+        if let Some(mut xfade) = self.crossfade.take() {
+            self.exec_block(&mut xfade.capture(ctx));
+            xfade.run_old(
+                ctx,
+                &mut self.nodes,
+                &self.prog.out[..],
+                &mut self.exec_ctx,
+                &self.shared.node_ctx_values,
+            );
+
+            let done = xfade.is_done();
+            self.crossfade = Some(xfade);
+            if done {
+                self.finish_crossfade();
+            }
+        } else {
+            self.exec_block(ctx);
+        }
+
+        let nframes = ctx.nframes();
+        self.exec_ctx.transport.advance(nframes, self.sample_rate);
+        self.exec_ctx.next_feedback_block(nframes);
+
+        self.write_cue(ctx);
+    }
+
+    /// Replaces the output of the `Out` nodes by the soloed
+    /// output buffer, see [GraphMessage::SetCue].
+    fn write_cue<T: NodeAudioContext>(&mut self, ctx: &mut T) {
+        let (out_idx, channel) = if let Some(cue) = self.cue { cue } else { return };
+        let cue_buf = self.prog.out.get(out_idx);
+
+        for frame in 0..ctx.nframes() {
+            ctx.output(0, frame, 0.0);
+            ctx.output(1, frame, 0.0);
+
+            let s = cue_buf.map(|pb| pb.read(frame)).unwrap_or(0.0);
+            ctx.output(channel, frame, s);
+            ctx.output(channel + 1, frame, s);
+        }
+    }
+
+    #[inline]
+    fn exec_block<T: NodeAudioContext>(&mut self, ctx: &mut T) {
+        let profiling = self.dsp_load.enabled;
+        let graph_start = if profiling { Some(Instant::now()) } else { None };
+
         self.process_smoothers(ctx.nframes());
+        self.exec_ctx.clear_buses();
+
+        // Note events of the audio driver also drive the voice region:
+        for ev in ctx.events().iter() {
+            match ev.kind {
+                MidiEventKind::NoteOn { note, vel } => {
+                    self.exec_ctx.voice_alloc.note_on(note, vel);
+                }
+                MidiEventKind::NoteOff { note } => {
+                    self.exec_ctx.voice_alloc.note_off(note);
+                }
+                _ => (),
+            }
+        }
 
         let nodes = &mut self.nodes;
+        let voice_nodes = &mut self.voice_nodes;
         let ctx_vals = &mut self.shared.node_ctx_values;
         let prog = &mut self.prog;
         let exec_ctx = &mut self.exec_ctx;
+        let dsp_load = &mut self.dsp_load;
+        let signal_guard = self.signal_guard;
+        let signal_fault_prod = &mut self.shared.signal_fault_prod;
+        let workers = &mut self.workers;
 
         let prog_out_fb = prog.out_feedback.input_buffer();
 
         let nframes = ctx.nframes();
 
-        for op in prog.prog.iter() {
-            let out = op.out_idxlen;
-            let inp = op.in_idxlen;
-            let at = op.at_idxlen;
-            let md = op.mod_idxlen;
-            let ctx_idx = op.idx as usize * 2;
+        // Checks the outputs of the op with the signal guard and
+        // evaluates to true if they were silenced:
+        macro_rules! guard_op {
+            ($node: expr, $op: expr) => {{
+                let mut tripped = false;
+
+                if let Some(limit) = signal_guard {
+                    let out = $op.out_idxlen;
+                    if let Some(fault) = check_signal(&prog.out[out.0..out.1], nframes, limit) {
+                        $node.reset();
+                        for pb in prog.out[out.0..out.1].iter_mut() {
+                            pb.fill(0.0);
+                        }
 
-            for modop in prog.modops[md.0..md.1].iter_mut() {
-                modop.process(nframes);
-            }
+                        let _ = signal_fault_prod.push(($op.idx, fault));
+                        tripped = true;
+                    }
+                }
 
-            nodes[op.idx as usize].process(
-                ctx,
-                exec_ctx,
-                &NodeContext {
-                    out_connected: op.out_connected,
-                    in_connected: op.in_connected,
-                    params: &prog.inp[inp.0..inp.1],
-                },
-                &prog.atoms[at.0..at.1],
-                &prog.cur_inp[inp.0..inp.1],
-                &mut prog.out[out.0..out.1],
-                &ctx_vals[ctx_idx..ctx_idx + 2],
-            );
+                tripped
+            }};
+        }
+
+        macro_rules! exec_op {
+            ($node: expr, $op: expr) => {{
+                let op = $op;
+                let out = op.out_idxlen;
+                let inp = op.in_idxlen;
+                let at = op.at_idxlen;
+                let md = op.mod_idxlen;
+                let ctx_idx = op.idx * 2;
+
+                let op_start = if profiling { Some(Instant::now()) } else { None };
+
+                for modop in prog.modops[md.0..md.1].iter_mut() {
+                    modop.process(nframes);
+                }
+
+                if op.state == NodeState::Active {
+                    $node.process(
+                        ctx,
+                        exec_ctx,
+                        &NodeContext {
+                            out_connected: op.out_connected,
+                            in_connected: op.in_connected,
+                            params: &prog.inp[inp.0..inp.1],
+                        },
+                        &prog.atoms[at.0..at.1],
+                        &prog.cur_inp[inp.0..inp.1],
+                        &mut prog.out[out.0..out.1],
+                        &ctx_vals[ctx_idx..ctx_idx + 2],
+                    );
+                } else {
+                    op.process_inactive(
+                        &$node,
+                        ctx,
+                        &prog.cur_inp[inp.0..inp.1],
+                        &mut prog.out[out.0..out.1],
+                        nframes,
+                    );
+                }
+
+                if let Some(op_start) = op_start {
+                    dsp_load.add(op.idx, op_start.elapsed().as_nanos() as u64);
+                }
+
+                guard_op!($node, op);
+
+                let last_frame_idx = nframes - 1;
+                for (pb, out_buf_idx) in prog.out[out.0..out.1].iter().zip(out.0..out.1) {
+                    prog_out_fb[out_buf_idx] = pb.read(last_frame_idx);
+                }
+            }};
+        }
+
+        let (region_start, region_end, voices) = if let Some(region) = &prog.voice_region {
+            (region.op_range.0, region.op_range.1, region.voices.clamp(1, voice_nodes.len() + 1))
+        } else {
+            (0, 0, 1)
+        };
+
+        macro_rules! exec_voice_region {
+            () => {{
+                for voice in 0..voices {
+                    exec_ctx.cur_voice = voice;
+
+                    for op in prog.prog[region_start..region_end].iter() {
+                        if voice == 0 {
+                            exec_op!(nodes[op.idx], op);
+                        } else {
+                            exec_op!(voice_nodes[voice - 1][op.idx], op);
+                        }
+                    }
+
+                    if let Some(region) = prog.voice_region.as_mut() {
+                        let mut level: f32 = 0.0;
+
+                        for (sum_buf, out_idx) in
+                            region.sum_bufs.iter_mut().zip(region.sum_outputs.iter())
+                        {
+                            let voice_out = &prog.out[*out_idx];
+
+                            for frame in 0..nframes {
+                                let s = voice_out.read(frame);
+                                level = level.max(s.abs());
+
+                                if voice == 0 {
+                                    sum_buf.write(frame, s);
+                                } else {
+                                    sum_buf.write(frame, sum_buf.read(frame) + s);
+                                }
+                            }
+                        }
+
+                        exec_ctx.voice_alloc.set_level(voice, level);
+                    }
+                }
+
+                exec_ctx.cur_voice = 0;
 
-            let last_frame_idx = nframes - 1;
-            for (pb, out_buf_idx) in prog.out[out.0..out.1].iter().zip(out.0..out.1) {
-                prog_out_fb[out_buf_idx] = pb.read(last_frame_idx);
+                if let Some(region) = prog.voice_region.as_ref() {
+                    for (sum_buf, out_idx) in region.sum_bufs.iter().zip(region.sum_outputs.iter())
+                    {
+                        let out_buf = &mut prog.out[*out_idx];
+
+                        for frame in 0..nframes {
+                            out_buf.write(frame, sum_buf.read(frame));
+                        }
+                    }
+                }
+            }};
+        }
+
+        if let (Some(workers), false) = (workers.as_mut(), prog.levels.is_empty()) {
+            for level_i in 0..prog.levels.len() {
+                let (start, par_end, end) = match prog.levels[level_i] {
+                    ProgLevel::Ops { start, par_end, end } => (start, par_end, end),
+                    ProgLevel::VoiceRegion => {
+                        exec_voice_region!();
+                        continue;
+                    }
+                };
+
+                let ptrs = OpExecPtrs {
+                    nodes: nodes.as_mut_ptr(),
+                    ops: prog.prog.as_ptr(),
+                    modops: prog.modops.as_mut_ptr(),
+                    inp: prog.inp.as_ptr(),
+                    cur_inp: prog.cur_inp.as_ptr(),
+                    atoms: prog.atoms.as_ptr(),
+                    out: prog.out.as_mut_ptr(),
+                    out_fb: prog_out_fb.as_mut_ptr(),
+                    ctx_vals: ctx_vals.as_ptr(),
+                    node_ns: dsp_load.node_ns_ptr(),
+                    profiling,
+                    nframes,
+                };
+
+                // Handing out a single op is not worth the synchronization:
+                let threaded = par_end - start > 1;
+                let main_start = if threaded { par_end } else { start };
+
+                if threaded {
+                    workers.start(ptrs, &prog.level_ops[start..par_end], exec_ctx.transport);
+                }
+
+                // Safety: The ops of one level don't share any nodes or buffers,
+                // and the workers are done before `ptrs` is invalidated.
+                for op_i in prog.level_ops[main_start..end].iter() {
+                    unsafe {
+                        ptrs.run(*op_i, ctx, exec_ctx);
+                    }
+                }
+
+                if threaded {
+                    workers.run_claimed(ctx, exec_ctx);
+                    workers.wait();
+                }
+
+                for op_i in prog.level_ops[start..end].iter() {
+                    let op = &prog.prog[*op_i];
+                    if guard_op!(nodes[op.idx], op) {
+                        for out_buf_idx in op.out_idxlen.0..op.out_idxlen.1 {
+                            prog_out_fb[out_buf_idx] = 0.0;
+                        }
+                    }
+                }
+            }
+        } else {
+            for (op_i, op) in prog.prog.iter().enumerate() {
+                if op_i < region_start || op_i >= region_end {
+                    exec_op!(nodes[op.idx], op);
+                    continue;
+                } else if op_i > region_start {
+                    // Already executed with the rest of the voice region.
+                    continue;
+                }
+
+                exec_voice_region!();
             }
         }
 
         prog.out_feedback.publish();
 
-        self.shared.monitor_backend.check_recycle();
+        if let Some(graph_start) = graph_start {
+            dsp_load.publish(
+                prog.prog.iter().map(|op| op.idx),
+                graph_start.elapsed().as_nanos() as u64,
+                nframes,
+                self.sample_rate,
+            );
+        }
 
-        // let ta = std::time::Instant::now();
+        self.shared.monitor_backend.check_recycle();
 
         for (i, idx) in self.monitor_signal_cur_inp_indices.iter().enumerate() {
             if *idx == UNUSED_MONITOR_IDX {
@@ -494,11 +1383,6 @@ impl NodeExecutor {
                 self.shared.monitor_backend.send_mon_buf(mon);
             }
         }
-
-        // let ta = std::time::Instant::now().duration_since(ta);
-        // let tb = std::time::Instant::now().duration_since(tb);
-        // println!("ta Elapsed: {:?}", ta);
-        // println!("tb Elapsed: {:?}", tb);
     }
 
     /// This is a convenience function used for testing
@@ -515,13 +1399,50 @@ impl NodeExecutor {
     /// You can use it's source as reference for your own audio
     /// DSP thread processing function.
     pub fn test_run(&mut self, seconds: f32, realtime: bool) -> (Vec<f32>, Vec<f32>) {
+        self.test_run_with_events(seconds, realtime, &[])
+    }
+
+    /// Like [NodeExecutor::test_run], but also passes the MIDI `events`
+    /// to the DSP graph. The [MidiEvent::frame] of the events is the
+    /// absolute sample offset from the start of this run,
+    /// and the events must be sorted by it.
+    pub fn test_run_with_events(
+        &mut self,
+        seconds: f32,
+        realtime: bool,
+        events: &[MidiEvent],
+    ) -> (Vec<f32>, Vec<f32>) {
+        let nframes = (seconds * 44100.0) as usize;
+        let input = vec![0.0; nframes];
+        self.test_run_input(&[&input[..]], realtime, events)
+    }
+
+    /// Like [NodeExecutor::test_run_with_events], but also passes the given
+    /// `input` channels to the DSP graph. The length of the run is
+    /// determined by the longest input channel, shorter channels
+    /// are padded with silence.
+    pub fn test_run_input(
+        &mut self,
+        input: &[&[f32]],
+        realtime: bool,
+        events: &[MidiEvent],
+    ) -> (Vec<f32>, Vec<f32>) {
         const SAMPLE_RATE: f32 = 44100.0;
         self.set_sample_rate(SAMPLE_RATE);
         self.process_graph_updates();
 
-        let mut nframes = (seconds * SAMPLE_RATE) as usize;
+        let mut nframes = input.iter().map(|chan| chan.len()).max().unwrap_or(0);
+
+        let input: Vec<Vec<f32>> = input
+            .iter()
+            .map(|chan| {
+                let mut chan = chan.to_vec();
+                chan.resize(nframes, 0.0);
+                chan
+            })
+            .collect();
+        let mut block_input: Vec<&[f32]> = Vec::with_capacity(input.len());
 
-        let input = vec![0.0; nframes];
         let mut output_l = vec![0.0; nframes];
         let mut output_r = vec![0.0; nframes];
 
@@ -529,18 +1450,37 @@ impl NodeExecutor {
             output_l[i] = 0.0;
             output_r[i] = 0.0;
         }
+
+        let mut block_events = Vec::with_capacity(events.len());
+        let mut ev_idx = 0;
+
         let mut offs = 0;
         while nframes > 0 {
             let cur_nframes = if nframes >= MAX_BLOCK_SIZE { MAX_BLOCK_SIZE } else { nframes };
             nframes -= cur_nframes;
 
+            block_input.clear();
+            for chan in input.iter() {
+                block_input.push(&chan[offs..(offs + cur_nframes)]);
+            }
+
+            block_events.clear();
+            while ev_idx < events.len() && events[ev_idx].frame < (offs + cur_nframes) {
+                let mut ev = events[ev_idx];
+                ev.frame = ev.frame.saturating_sub(offs);
+                block_events.push(ev);
+                ev_idx += 1;
+            }
+
             let mut context = crate::Context {
                 nframes: cur_nframes,
                 output: &mut [
                     &mut output_l[offs..(offs + cur_nframes)],
                     &mut output_r[offs..(offs + cur_nframes)],
                 ],
-                input: &[&input[offs..(offs + cur_nframes)]],
+                input: &block_input[..],
+                events: &block_events[..],
+                transport: None,
             };
 
             self.process(&mut context);
@@ -47,9 +47,10 @@ impl Node {
 pub struct NodeGraphOrdering {
     node2idx: HashMap<NodeId, usize>,
     node_count: usize,
-    nodes: [Node; MAX_ALLOCATED_NODES],
+    /// Grows beyond [MAX_ALLOCATED_NODES] if necessary.
+    nodes: Vec<Node>,
 
-    in_degree: [usize; MAX_ALLOCATED_NODES],
+    in_degree: Vec<usize>,
 }
 
 impl NodeGraphOrdering {
@@ -57,8 +58,8 @@ impl NodeGraphOrdering {
         Self {
             node2idx: HashMap::new(),
             node_count: 0,
-            nodes: [Node::new(); MAX_ALLOCATED_NODES],
-            in_degree: [0; MAX_ALLOCATED_NODES],
+            nodes: vec![Node::new(); MAX_ALLOCATED_NODES],
+            in_degree: vec![0; MAX_ALLOCATED_NODES],
         }
     }
 
@@ -74,6 +75,11 @@ impl NodeGraphOrdering {
             let idx = self.node_count;
             self.node_count += 1;
 
+            if idx >= self.nodes.len() {
+                self.nodes.resize(self.nodes.len() * 2, Node::new());
+                self.in_degree.resize(self.nodes.len(), 0);
+            }
+
             self.nodes[idx].clear();
             self.nodes[idx].node_id = node_id;
             self.node2idx.insert(node_id, idx);
@@ -357,4 +363,23 @@ mod tests {
 
         assert!(!ng.has_path(NodeId::Sin(2), NodeId::Sin(1)).unwrap());
     }
+
+    #[test]
+    fn check_ngraph_grows() {
+        let mut ng = NodeGraphOrdering::new();
+
+        let mut ids: Vec<NodeId> = (0..=255).map(NodeId::Sin).collect();
+        ids.extend((0..100).map(NodeId::Amp));
+
+        for id in ids.iter().rev() {
+            ng.add_node(*id);
+        }
+        for pair in ids.windows(2) {
+            ng.add_edge(pair[0], pair[1]);
+        }
+
+        let mut out = vec![];
+        assert!(ng.calculate_order(&mut out));
+        assert_eq!(out, ids);
+    }
 }
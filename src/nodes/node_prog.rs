@@ -2,7 +2,9 @@
 // This file is a part of HexoDSP. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
-use crate::dsp::{ProcBuf, SAtom};
+use super::{NodeAudioContext, NodeState, VoiceStealMode, MAX_PARAM_EVENTS, MAX_SMOOTHERS};
+use crate::dsp::{Node, NodeId, ProcBuf, SAtom, Smoothing, DEFAULT_SMOOTHING};
+use crate::util::Smoother;
 use std::cell::RefCell;
 use triple_buffer::{Input, Output, TripleBuffer};
 
@@ -70,7 +72,7 @@ impl ModOp {
 #[derive(Debug, Clone)]
 pub struct NodeOp {
     /// Stores the index of the node
-    pub idx: u8,
+    pub idx: usize,
     /// Output index and length of the node:
     pub out_idxlen: (usize, usize),
     /// Input index and length of the node:
@@ -89,6 +91,14 @@ pub struct NodeOp {
     /// A bit mask which indicates which of the output ports are actually
     /// used/connected to some input.
     pub out_connected: u64,
+    /// The node that is executed. [NodeProg::calculate_levels] only puts
+    /// nodes on worker threads that are [NodeId::is_parallel_safe].
+    pub node_id: NodeId,
+    /// The processing state of the node, see [NodeState].
+    pub state: NodeState,
+    /// The input that is passed through while the node is bypassed,
+    /// see [crate::dsp::NodeId::bypass_inp].
+    pub bypass_inp: Option<usize>,
 }
 
 impl NodeOp {
@@ -117,6 +127,42 @@ impl NodeOp {
         let local_idx = global_idx - self.out_idxlen.0;
         self.out_connected |= 0x1 << local_idx;
     }
+
+    /// Writes the outputs of a node that is not [NodeState::Active],
+    /// instead of executing it. The `Out` node writes to the audio
+    /// driver directly, so silence is written there.
+    #[inline]
+    pub(crate) fn process_inactive<T: NodeAudioContext>(
+        &self,
+        node: &Node,
+        ctx: &mut T,
+        inputs: &[ProcBuf],
+        outputs: &mut [ProcBuf],
+        nframes: usize,
+    ) {
+        let bypass = match self.state {
+            NodeState::Bypass => self.bypass_inp.and_then(|i| inputs.get(i)),
+            _ => None,
+        };
+
+        for (i, pb) in outputs.iter_mut().enumerate() {
+            match bypass {
+                Some(inp) if i == 0 => {
+                    for frame in 0..nframes {
+                        pb.write(frame, inp.read(frame));
+                    }
+                }
+                _ => pb.fill(0.0),
+            }
+        }
+
+        if let Node::Out { .. } = node {
+            for frame in 0..nframes {
+                ctx.output(0, frame, 0.0);
+                ctx.output(1, frame, 0.0);
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for NodeOp {
@@ -151,6 +197,47 @@ impl std::fmt::Display for NodeOp {
     }
 }
 
+/// Describes the part of a [NodeProg] that is executed once for each
+/// voice by the [crate::nodes::NodeExecutor]. The first voice is
+/// executed by the regular nodes, the others by per voice copies of
+/// them. The outputs of the region that are read by nodes outside of
+/// it are summed up over all voices.
+#[derive(Debug)]
+pub struct VoiceRegion {
+    /// The number of voices the region is executed for.
+    pub voices: usize,
+    /// How to steal voices if all are in use.
+    pub steal_mode: VoiceStealMode,
+    /// Start and end index of the region's ops in [NodeProg::prog].
+    pub op_range: (usize, usize),
+    /// The indices into [NodeProg::out] which are read by
+    /// nodes after the region and need to be summed up.
+    pub sum_outputs: Vec<usize>,
+    /// The buffers to accumulate the voices in, one for each
+    /// entry in `sum_outputs`.
+    pub sum_bufs: Vec<ProcBuf>,
+}
+
+/// One step of the parallel execution of a [NodeProg],
+/// see [NodeProg::calculate_levels].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgLevel {
+    /// The ops at the indices `level_ops[start..end]` don't depend on
+    /// each other. The ops in `level_ops[start..par_end]` may be executed
+    /// on worker threads, the rest is executed on the audio thread.
+    Ops { start: usize, par_end: usize, end: usize },
+    /// The [VoiceRegion], which is executed on the audio thread.
+    VoiceRegion,
+}
+
+impl Drop for VoiceRegion {
+    fn drop(&mut self) {
+        for buf in self.sum_bufs.iter_mut() {
+            buf.free();
+        }
+    }
+}
+
 /// A node graph execution program. It comes with buffers
 /// for the inputs, outputs and node parameters (knob values).
 #[derive(Debug)]
@@ -175,6 +262,19 @@ pub struct NodeProg {
     /// of the nodes, such as samples or integer settings.
     pub atoms: Vec<SAtom>,
 
+    /// The smoothing policy for each parameter in `params`.
+    pub smoothing: Vec<Smoothing>,
+
+    /// A smoother pool with one smoother for each parameter, so that
+    /// no parameter update has to be dropped. The [crate::nodes::NodeExecutor]
+    /// swaps it with it's own pool, if it's own pool is too small.
+    pub(crate) smoothers: Vec<(usize, Smoother)>,
+
+    /// Preallocated storage for the [crate::nodes::NodeExecutor] to
+    /// refresh the parameter input buffers after smoothing, that is swapped
+    /// together with `smoothers`.
+    pub(crate) target_refresh: Vec<(usize, f32)>,
+
     /// The node operations that are executed in the order they appear in this
     /// vector.
     pub prog: Vec<NodeOp>,
@@ -198,6 +298,17 @@ pub struct NodeProg {
     /// A unique ID assigned to the node prog. Mostly for debugging purposes.
     /// You should only read this field.
     pub unique_id: usize,
+
+    /// The part of the program that is executed for each voice.
+    /// See also [NodeProg::set_voice_region].
+    pub voice_region: Option<VoiceRegion>,
+
+    /// The dependency levels of `prog` for the parallel execution.
+    /// See also [NodeProg::calculate_levels].
+    pub levels: Vec<ProgLevel>,
+
+    /// The indices into `prog` of the ops of each level.
+    pub level_ops: Vec<usize>,
 }
 
 impl Drop for NodeProg {
@@ -231,12 +342,18 @@ impl NodeProg {
             cur_inp: vec![],
             params: vec![],
             atoms: vec![],
+            smoothing: vec![],
+            smoothers: vec![],
+            target_refresh: vec![],
             prog: vec![],
             modops: vec![],
             out_feedback: input_fb,
             out_fb_cons: Some(output_fb),
             locked_buffers: false,
             unique_id: new_node_prog_id(),
+            voice_region: None,
+            levels: vec![],
+            level_ops: vec![],
         }
     }
 
@@ -257,6 +374,12 @@ impl NodeProg {
         params.resize(inp_len, 0.0);
         let mut atoms = vec![];
         atoms.resize(at_len, SAtom::setting(0));
+        let smoothing = vec![DEFAULT_SMOOTHING; inp_len];
+
+        let smoother_count = inp_len.max(MAX_SMOOTHERS);
+        let mut smoothers = vec![];
+        smoothers.resize_with(smoother_count, || (0, Smoother::new()));
+        let target_refresh = Vec::with_capacity(smoother_count + 2 * MAX_PARAM_EVENTS);
         let mut modops = vec![];
         modops.resize_with(mod_len, ModOp::new);
 
@@ -266,12 +389,18 @@ impl NodeProg {
             cur_inp,
             params,
             atoms,
+            smoothing,
+            smoothers,
+            target_refresh,
             modops,
             prog: vec![],
             out_feedback: input_fb,
             out_fb_cons: Some(output_fb),
             locked_buffers: false,
             unique_id: new_node_prog_id(),
+            voice_region: None,
+            levels: vec![],
+            level_ops: vec![],
         }
     }
 
@@ -287,6 +416,10 @@ impl NodeProg {
         &mut self.atoms
     }
 
+    pub fn smoothing_mut(&mut self) -> &mut [Smoothing] {
+        &mut self.smoothing
+    }
+
     pub fn modops_mut(&mut self) -> &mut [ModOp] {
         &mut self.modops
     }
@@ -303,6 +436,15 @@ impl NodeProg {
         self.prog.push(node_op);
     }
 
+    /// Sets the processing state of the op of the node at `node_idx`.
+    pub fn set_node_state(&mut self, node_idx: usize, state: NodeState) {
+        for op in self.prog.iter_mut() {
+            if op.idx == node_idx {
+                op.state = state;
+            }
+        }
+    }
+
     pub fn append_edge(
         &mut self,
         node_op: NodeOp,
@@ -325,6 +467,207 @@ impl NodeProg {
         }
     }
 
+    /// Marks the ops that read directly or indirectly from the
+    /// ops of the nodes in `node_idxs`, including those ops themselves.
+    /// The ops are sorted topologically, so this is done in one pass.
+    fn region_dependencies(&self, node_idxs: &[usize]) -> Vec<bool> {
+        let mut depends = vec![false; self.prog.len()];
+        for i in 0..self.prog.len() {
+            let op = &self.prog[i];
+            let mut dep = node_idxs.contains(&op.idx);
+
+            for (out_idx, _, _) in op.inputs.iter() {
+                for (j, src_op) in self.prog[0..i].iter().enumerate() {
+                    if depends[j] && src_op.out_idx_belongs_to_nodeop(*out_idx) {
+                        dep = true;
+                    }
+                }
+            }
+
+            depends[i] = dep;
+        }
+
+        depends
+    }
+
+    /// Checks if the nodes with the indices in `node_idxs` can be executed
+    /// as one contiguous voice region. That is not possible if a node of the
+    /// region reads from a node outside of it, which itself depends on
+    /// the region.
+    pub fn check_voice_region(&self, node_idxs: &[usize]) -> bool {
+        let depends = self.region_dependencies(node_idxs);
+
+        for op in self.prog.iter().filter(|op| node_idxs.contains(&op.idx)) {
+            for (out_idx, _, _) in op.inputs.iter() {
+                for (j, src_op) in self.prog.iter().enumerate() {
+                    if depends[j]
+                        && !node_idxs.contains(&src_op.idx)
+                        && src_op.out_idx_belongs_to_nodeop(*out_idx)
+                    {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Marks the ops of the nodes with the indices in `node_idxs` as
+    /// voice region, that is executed `voices` times.
+    ///
+    /// The ops are reordered, so that the region is executed as one
+    /// contiguous block: First come all ops that don't depend on the
+    /// region, then the region itself and after that the ops that
+    /// read (directly or indirectly) from the region.
+    /// Call this after all ops and edges have been added.
+    ///
+    /// Returns `false` and leaves the program unchanged if the region
+    /// can't be executed as one block, see [NodeProg::check_voice_region].
+    pub fn set_voice_region(
+        &mut self,
+        node_idxs: &[usize],
+        voices: usize,
+        steal_mode: VoiceStealMode,
+    ) -> bool {
+        let in_region = |op: &NodeOp| node_idxs.contains(&op.idx);
+
+        if !self.prog.iter().any(in_region) {
+            self.voice_region = None;
+            return true;
+        }
+
+        if !self.check_voice_region(node_idxs) {
+            return false;
+        }
+
+        let depends = self.region_dependencies(node_idxs);
+
+        let mut pre = vec![];
+        let mut region = vec![];
+        let mut post = vec![];
+
+        for (op, dep) in std::mem::take(&mut self.prog).into_iter().zip(depends.iter()) {
+            if in_region(&op) {
+                region.push(op);
+            } else if *dep {
+                post.push(op);
+            } else {
+                pre.push(op);
+            }
+        }
+
+        let mut sum_outputs = vec![];
+        for op in post.iter() {
+            for (out_idx, _, _) in op.inputs.iter() {
+                if region.iter().any(|r| r.out_idx_belongs_to_nodeop(*out_idx))
+                    && !sum_outputs.contains(out_idx)
+                {
+                    sum_outputs.push(*out_idx);
+                }
+            }
+        }
+
+        let op_range = (pre.len(), pre.len() + region.len());
+
+        self.prog = pre;
+        self.prog.append(&mut region);
+        self.prog.append(&mut post);
+
+        let mut sum_bufs = vec![];
+        sum_bufs.resize_with(sum_outputs.len(), ProcBuf::new);
+
+        self.voice_region =
+            Some(VoiceRegion { voices, steal_mode, op_range, sum_outputs, sum_bufs });
+
+        true
+    }
+
+    /// Groups the ops into levels of ops that don't depend on each other,
+    /// so that the [crate::nodes::NodeExecutor] can execute the ops of one
+    /// level in parallel. The level of an op is one more than the highest
+    /// level of the ops it reads from. Ops that are not parallel safe, see
+    /// [NodeId::is_parallel_safe], are always executed on the audio thread.
+    /// They never get a lower level than a non parallel op before them in
+    /// the program. The voice region is one separate step. Call this after
+    /// all ops and edges have been added and after [NodeProg::set_voice_region].
+    pub fn calculate_levels(&mut self) {
+        self.levels.clear();
+        self.level_ops.clear();
+
+        let (region_start, region_end) = if let Some(region) = &self.voice_region {
+            region.op_range
+        } else {
+            (self.prog.len(), self.prog.len())
+        };
+
+        self.calculate_levels_of(0, region_start);
+
+        if region_start < region_end {
+            self.levels.push(ProgLevel::VoiceRegion);
+        }
+
+        self.calculate_levels_of(region_end, self.prog.len());
+    }
+
+    fn calculate_levels_of(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+
+        let mut op_levels = vec![0; end - start];
+        let mut max_level = 0;
+        let mut seq_level = 0;
+
+        // The ops are sorted topologically, so the levels of
+        // the ops an op depends on are already known:
+        for i in start..end {
+            let mut level = 0;
+
+            for (out_idx, _, _) in self.prog[i].inputs.iter() {
+                for j in start..i {
+                    if self.prog[j].out_idx_belongs_to_nodeop(*out_idx) {
+                        level = level.max(op_levels[j - start] + 1);
+                    }
+                }
+            }
+
+            // Ops that are not parallel safe may depend on each other without
+            // an edge (like the BusSend and BusRecv nodes), so they keep
+            // their order from the program:
+            if !self.prog[i].node_id.is_parallel_safe() {
+                level = level.max(seq_level);
+                seq_level = level;
+            }
+
+            op_levels[i - start] = level;
+            max_level = max_level.max(level);
+        }
+
+        for level in 0..=max_level {
+            let lvl_start = self.level_ops.len();
+
+            for parallel in [true, false] {
+                for (i, op_level) in op_levels.iter().enumerate() {
+                    if *op_level == level
+                        && self.prog[start + i].node_id.is_parallel_safe() == parallel
+                    {
+                        self.level_ops.push(start + i);
+                    }
+                }
+
+                if parallel {
+                    let par_end = self.level_ops.len();
+                    self.levels.push(ProgLevel::Ops { start: lvl_start, par_end, end: par_end });
+                }
+            }
+
+            if let Some(ProgLevel::Ops { end, .. }) = self.levels.last_mut() {
+                *end = self.level_ops.len();
+            }
+        }
+    }
+
     /// This is called right after the [crate::nodes::NodeExecutor]
     /// received this NodeProg from the [crate::nodes::NodeConfigurator].
     /// It initializes internal buffers with parameter data.
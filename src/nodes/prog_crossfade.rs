@@ -0,0 +1,273 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::{MidiEvent, NodeAudioContext, NodeExecContext, NodeProg, NodeState, Transport};
+use crate::dsp::{Node, NodeContext, ProcBuf, MAX_BLOCK_SIZE};
+use synfx_dsp::AtomicFloat;
+
+use std::sync::Arc;
+
+/// The number of output channels that are crossfaded,
+/// these are the channels of the `Out` node.
+const XFADE_CHANNELS: usize = 2;
+
+/// Holds the old [NodeProg] while it is faded out, after a new program
+/// was uploaded with a crossfade. See also
+/// [crate::nodes::NodeConfigurator::set_prog_crossfade].
+///
+/// The nodes that changed keep running on their old instances in the old
+/// program, while the new program runs on fresh instances of them. All other
+/// nodes are shared: They are only executed by the new program, which runs
+/// first, and the old program reads their outputs from it.
+pub(crate) struct ProgCrossfade {
+    prog: NodeProg,
+    /// The old instances of the nodes that run on fresh instances
+    /// in the new program, `Node::Nop` at all other node indices.
+    nodes: Vec<Node>,
+    /// For each op of the old program, the index of the first output
+    /// of the same node in the new program, if the node is shared.
+    shared_out: Vec<Option<usize>>,
+    /// The current position and the length of the crossfade in frames.
+    pos: usize,
+    len: usize,
+    /// The captured output of the new program in the current block.
+    out: [[f32; MAX_BLOCK_SIZE]; XFADE_CHANNELS],
+    written: [bool; XFADE_CHANNELS],
+}
+
+impl ProgCrossfade {
+    pub(crate) fn new(prog: NodeProg, nodes: Vec<Node>, len: usize, new_prog: &NodeProg) -> Self {
+        let shared_out = prog
+            .prog
+            .iter()
+            .map(|op| {
+                if !matches!(nodes.get(op.idx), Some(Node::Nop)) {
+                    return None;
+                }
+
+                new_prog.prog.iter().find(|new_op| new_op.idx == op.idx).map(|o| o.out_idxlen.0)
+            })
+            .collect();
+
+        Self {
+            prog,
+            nodes,
+            shared_out,
+            pos: 0,
+            len: len.max(1),
+            out: [[0.0; MAX_BLOCK_SIZE]; XFADE_CHANNELS],
+            written: [false; XFADE_CHANNELS],
+        }
+    }
+
+    pub(crate) fn nodes_mut(&mut self) -> &mut [Node] {
+        &mut self.nodes
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.pos >= self.len
+    }
+
+    pub(crate) fn into_parts(self) -> (NodeProg, Vec<Node>) {
+        (self.prog, self.nodes)
+    }
+
+    /// Returns the context for executing the new program,
+    /// which captures its output.
+    pub(crate) fn capture<'a, T: NodeAudioContext>(
+        &'a mut self,
+        ctx: &'a mut T,
+    ) -> CaptureContext<'a, T> {
+        self.written = [false; XFADE_CHANNELS];
+        CaptureContext { ctx, out: &mut self.out, written: &mut self.written }
+    }
+
+    /// Executes the old program after the new one and blends their
+    /// outputs. The nodes that changed are still in `nodes`, the outputs
+    /// of the shared nodes are copied from `new_out`, the outputs of
+    /// the new program.
+    pub(crate) fn run_old<T: NodeAudioContext>(
+        &mut self,
+        ctx: &mut T,
+        nodes: &mut [Node],
+        new_out: &[ProcBuf],
+        ectx: &mut NodeExecContext,
+        ctx_vals: &[Arc<AtomicFloat>],
+    ) {
+        let nframes = ctx.nframes();
+
+        let mut blend = BlendContext {
+            ctx,
+            out: &self.out,
+            new_written: &self.written,
+            pos: self.pos,
+            len: self.len,
+            written: [false; XFADE_CHANNELS],
+        };
+        let prog = &mut self.prog;
+
+        for (op, shared_out) in prog.prog.iter().zip(self.shared_out.iter()) {
+            let out = op.out_idxlen;
+            let inp = op.in_idxlen;
+            let at = op.at_idxlen;
+            let md = op.mod_idxlen;
+            let ctx_idx = op.idx * 2;
+
+            if let Some(new_out_idx) = shared_out {
+                for (i, pb) in prog.out[out.0..out.1].iter_mut().enumerate() {
+                    let new_pb = &new_out[new_out_idx + i];
+                    for frame in 0..nframes {
+                        pb.write(frame, new_pb.read(frame));
+                    }
+                }
+                continue;
+            }
+
+            let node = match self.nodes.get_mut(op.idx) {
+                Some(node) if !matches!(node, Node::Nop) => node,
+                _ => match nodes.get_mut(op.idx) {
+                    Some(node) => node,
+                    None => continue,
+                },
+            };
+
+            for modop in prog.modops[md.0..md.1].iter_mut() {
+                modop.process(nframes);
+            }
+
+            if op.state == NodeState::Active {
+                node.process(
+                    &mut blend,
+                    ectx,
+                    &NodeContext {
+                        out_connected: op.out_connected,
+                        in_connected: op.in_connected,
+                        params: &prog.inp[inp.0..inp.1],
+                    },
+                    &prog.atoms[at.0..at.1],
+                    &prog.cur_inp[inp.0..inp.1],
+                    &mut prog.out[out.0..out.1],
+                    &ctx_vals[ctx_idx..ctx_idx + 2],
+                );
+            } else {
+                op.process_inactive(
+                    node,
+                    &mut blend,
+                    &prog.cur_inp[inp.0..inp.1],
+                    &mut prog.out[out.0..out.1],
+                    nframes,
+                );
+            }
+        }
+
+        blend.finish();
+        self.pos += nframes;
+    }
+}
+
+/// The gain of the new program at `frame` of the current block.
+#[inline]
+fn new_gain(pos: usize, len: usize, frame: usize) -> f32 {
+    ((pos + frame) as f32 / len as f32).min(1.0)
+}
+
+/// Captures the output of the new program.
+pub(crate) struct CaptureContext<'a, T: NodeAudioContext> {
+    ctx: &'a mut T,
+    out: &'a mut [[f32; MAX_BLOCK_SIZE]; XFADE_CHANNELS],
+    written: &'a mut [bool; XFADE_CHANNELS],
+}
+
+impl<'a, T: NodeAudioContext> NodeAudioContext for CaptureContext<'a, T> {
+    #[inline]
+    fn nframes(&self) -> usize {
+        self.ctx.nframes()
+    }
+
+    #[inline]
+    fn output(&mut self, channel: usize, frame: usize, v: f32) {
+        if channel < XFADE_CHANNELS {
+            self.out[channel][frame] = v;
+            self.written[channel] = true;
+        } else {
+            self.ctx.output(channel, frame, v);
+        }
+    }
+
+    #[inline]
+    fn input(&mut self, channel: usize, frame: usize) -> f32 {
+        self.ctx.input(channel, frame)
+    }
+
+    #[inline]
+    fn events(&self) -> &[MidiEvent] {
+        self.ctx.events()
+    }
+
+    #[inline]
+    fn transport(&self) -> Option<Transport> {
+        self.ctx.transport()
+    }
+}
+
+/// Blends the output of the old program with the
+/// captured output of the new program.
+struct BlendContext<'a, T: NodeAudioContext> {
+    ctx: &'a mut T,
+    out: &'a [[f32; MAX_BLOCK_SIZE]; XFADE_CHANNELS],
+    new_written: &'a [bool; XFADE_CHANNELS],
+    pos: usize,
+    len: usize,
+    written: [bool; XFADE_CHANNELS],
+}
+
+impl<'a, T: NodeAudioContext> BlendContext<'a, T> {
+    /// Fades in the output of the new program on the channels
+    /// that the old program did not write.
+    fn finish(self) {
+        let nframes = self.ctx.nframes();
+
+        for (ch, written) in self.written.iter().enumerate() {
+            if *written || !self.new_written[ch] {
+                continue;
+            }
+
+            for (frame, s) in self.out[ch][..nframes].iter().enumerate() {
+                self.ctx.output(ch, frame, *s * new_gain(self.pos, self.len, frame));
+            }
+        }
+    }
+}
+
+impl<'a, T: NodeAudioContext> NodeAudioContext for BlendContext<'a, T> {
+    #[inline]
+    fn nframes(&self) -> usize {
+        self.ctx.nframes()
+    }
+
+    #[inline]
+    fn output(&mut self, channel: usize, frame: usize, v: f32) {
+        if channel < XFADE_CHANNELS {
+            let gain = new_gain(self.pos, self.len, frame);
+            let new = if self.new_written[channel] { self.out[channel][frame] } else { 0.0 };
+            self.written[channel] = true;
+            self.ctx.output(channel, frame, v * (1.0 - gain) + new * gain);
+        }
+    }
+
+    #[inline]
+    fn input(&mut self, channel: usize, frame: usize) -> f32 {
+        self.ctx.input(channel, frame)
+    }
+
+    #[inline]
+    fn events(&self) -> &[MidiEvent] {
+        self.ctx.events()
+    }
+
+    #[inline]
+    fn transport(&self) -> Option<Transport> {
+        self.ctx.transport()
+    }
+}
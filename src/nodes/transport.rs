@@ -0,0 +1,180 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+/// The note divisions that tempo synced time parameters are mapped to,
+/// ordered by their length in quarter notes. A '.' denotes dotted notes
+/// and a 'T' triplets.
+pub const NOTE_DIVISIONS: [(&str, f64); 21] = [
+    ("1/64T", 1.0 / 24.0),
+    ("1/64", 1.0 / 16.0),
+    ("1/32T", 1.0 / 12.0),
+    ("1/64.", 3.0 / 32.0),
+    ("1/32", 1.0 / 8.0),
+    ("1/16T", 1.0 / 6.0),
+    ("1/32.", 3.0 / 16.0),
+    ("1/16", 1.0 / 4.0),
+    ("1/8T", 1.0 / 3.0),
+    ("1/16.", 3.0 / 8.0),
+    ("1/8", 1.0 / 2.0),
+    ("1/4T", 2.0 / 3.0),
+    ("1/8.", 3.0 / 4.0),
+    ("1/4", 1.0),
+    ("1/2T", 4.0 / 3.0),
+    ("1/4.", 3.0 / 2.0),
+    ("1/2", 2.0),
+    ("1/1T", 8.0 / 3.0),
+    ("1/2.", 3.0),
+    ("1/1", 4.0),
+    ("1/1.", 6.0),
+];
+
+/// Maps the normalized value (0.0 to 1.0) of a tempo synced
+/// parameter to an index into [NOTE_DIVISIONS].
+#[inline]
+pub fn note_div_index(v: f32) -> usize {
+    (v.clamp(0.0, 1.0) * (NOTE_DIVISIONS.len() - 1) as f32).round() as usize
+}
+
+/// Returns the label of the note division for the normalized value `v`, like "1/8.".
+pub fn note_div_label(v: f32) -> &'static str {
+    NOTE_DIVISIONS[note_div_index(v)].0
+}
+
+/// The global musical time of the DSP graph. It lives in the
+/// [crate::nodes::NodeExecContext] and is either advanced by the
+/// [crate::nodes::NodeExecutor] itself, or provided by the host via
+/// [crate::nodes::NodeAudioContext::transport].
+///
+/// All musical positions are measured in quarter notes, like most
+/// plugin hosts do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transport {
+    /// The tempo in quarter notes per minute.
+    pub bpm: f64,
+    /// The numerator of the time signature, the beats per bar.
+    pub sig_num: u8,
+    /// The denominator of the time signature, the note value of one beat.
+    pub sig_denom: u8,
+    /// Whether the song position advances.
+    pub playing: bool,
+    /// The song position in samples.
+    pub sample_pos: u64,
+    /// The song position in quarter notes.
+    pub beat_pos: f64,
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Self { bpm: 120.0, sig_num: 4, sig_denom: 4, playing: false, sample_pos: 0, beat_pos: 0.0 }
+    }
+
+    /// The length of one beat of the time signature in quarter notes.
+    #[inline]
+    pub fn beat_len(&self) -> f64 {
+        4.0 / (self.sig_denom.max(1) as f64)
+    }
+
+    /// The length of one bar in quarter notes.
+    #[inline]
+    pub fn bar_len(&self) -> f64 {
+        self.beat_len() * (self.sig_num.max(1) as f64)
+    }
+
+    /// The number of quarter notes that pass with one sample.
+    #[inline]
+    pub fn quarters_per_sample(&self, srate: f32) -> f64 {
+        self.bpm / (60.0 * srate as f64)
+    }
+
+    /// The song position in quarter notes at the given frame offset
+    /// of the current block.
+    #[inline]
+    pub fn beat_pos_at(&self, frame: usize, srate: f32) -> f64 {
+        if self.playing {
+            self.beat_pos + (frame as f64) * self.quarters_per_sample(srate)
+        } else {
+            self.beat_pos
+        }
+    }
+
+    /// The bar number (starting with 0) at the song position.
+    pub fn bar(&self) -> i64 {
+        (self.beat_pos / self.bar_len()).floor() as i64
+    }
+
+    /// Returns the length in milliseconds of the note division that
+    /// the normalized value `v` of a tempo synced parameter maps to.
+    #[inline]
+    pub fn note_div_ms(&self, v: f32) -> f32 {
+        let quarters = NOTE_DIVISIONS[note_div_index(v)].1;
+        (quarters * 60000.0 / self.bpm.max(1.0)) as f32
+    }
+
+    /// Sets the song position in quarter notes and
+    /// recalculates the sample position.
+    pub fn locate(&mut self, beat_pos: f64, srate: f32) {
+        self.beat_pos = beat_pos;
+        let qps = self.quarters_per_sample(srate);
+        self.sample_pos = if qps > 0.0 { (beat_pos.max(0.0) / qps) as u64 } else { 0 };
+    }
+
+    /// Moves the song position by `nframes` if the transport is playing.
+    #[inline]
+    pub fn advance(&mut self, nframes: usize, srate: f32) {
+        if self.playing {
+            self.sample_pos += nframes as u64;
+            self.beat_pos += (nframes as f64) * self.quarters_per_sample(srate);
+        }
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_transport_advance() {
+        let mut t = Transport::new();
+        t.advance(44100, 44100.0);
+        assert_eq!(t.sample_pos, 0);
+
+        t.playing = true;
+        t.advance(44100, 44100.0);
+        assert_eq!(t.sample_pos, 44100);
+        assert!((t.beat_pos - 2.0).abs() < 0.000001);
+
+        t.sig_num = 3;
+        t.sig_denom = 8;
+        assert!((t.bar_len() - 1.5).abs() < 0.000001);
+        assert_eq!(t.bar(), 1);
+
+        t.locate(4.0, 44100.0);
+        assert_eq!(t.sample_pos, 88200);
+    }
+
+    #[test]
+    fn check_note_divisions() {
+        let mut t = Transport::new();
+        assert_eq!(note_div_label(0.0), "1/64T");
+        assert_eq!(note_div_label(1.0), "1/1.");
+        assert_eq!(note_div_label(0.5), "1/8");
+        assert_eq!(note_div_label(0.55), "1/4T");
+        assert_eq!(note_div_label(0.6), "1/8.");
+        assert!((t.note_div_ms(0.5) - 250.0).abs() < 0.001);
+
+        t.bpm = 60.0;
+        assert!((t.note_div_ms(0.55) - 666.6667).abs() < 0.001);
+        assert!((t.note_div_ms(0.6) - 750.0).abs() < 0.001);
+
+        for w in NOTE_DIVISIONS.windows(2) {
+            assert!(w[0].1 < w[1].1, "{:?}", w);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::MAX_VOICES;
+
+/// Determines which voice is taken away from a playing note
+/// if a note on event arrives and all voices are in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceStealMode {
+    /// Steals the voice that was started the longest time ago.
+    Oldest,
+    /// Steals the voice with the lowest output level in the previous block.
+    Quietest,
+}
+
+impl Default for VoiceStealMode {
+    fn default() -> Self {
+        VoiceStealMode::Oldest
+    }
+}
+
+/// The state of a single voice as seen by the nodes of the voice region.
+/// See also the `Voice` node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoiceState {
+    /// The MIDI note number that was last assigned to this voice.
+    pub note: u8,
+    /// Whether the note is still held.
+    pub gate: bool,
+    /// The velocity of the last note on event in the range 0.0 to 1.0.
+    pub vel: f32,
+    /// Serial number of the note on event that started this voice.
+    /// It's used by the nodes to detect a retrigger of a voice
+    /// that was still gated.
+    pub serial: u64,
+    /// The absolute peak output level of the voice in the previous block.
+    pub level: f32,
+}
+
+impl VoiceState {
+    pub fn new() -> Self {
+        Self { note: 69, gate: false, vel: 0.0, serial: 0, level: 0.0 }
+    }
+}
+
+impl Default for VoiceState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Distributes note on/off events to the voices of the voice region
+/// of a [crate::nodes::NodeProg].
+///
+/// It lives inside the [crate::nodes::NodeExecContext] on the audio thread
+/// and does not allocate.
+#[derive(Debug, Clone)]
+pub struct VoiceAllocator {
+    voices: [VoiceState; MAX_VOICES],
+    voice_count: usize,
+    steal_mode: VoiceStealMode,
+    serial: u64,
+}
+
+impl VoiceAllocator {
+    pub fn new() -> Self {
+        Self {
+            voices: [VoiceState::new(); MAX_VOICES],
+            voice_count: 1,
+            steal_mode: VoiceStealMode::Oldest,
+            serial: 0,
+        }
+    }
+
+    /// Sets the number of voices that are available, clamped
+    /// to the range `1..=MAX_VOICES`. Voices that are no longer
+    /// available are released.
+    pub fn set_voice_count(&mut self, count: usize) {
+        self.voice_count = count.clamp(1, MAX_VOICES);
+
+        for v in self.voices[self.voice_count..].iter_mut() {
+            v.gate = false;
+        }
+    }
+
+    pub fn voice_count(&self) -> usize {
+        self.voice_count
+    }
+
+    pub fn set_steal_mode(&mut self, mode: VoiceStealMode) {
+        self.steal_mode = mode;
+    }
+
+    pub fn steal_mode(&self) -> VoiceStealMode {
+        self.steal_mode
+    }
+
+    /// Returns the state of the voice `idx`.
+    #[inline]
+    pub fn voice(&self, idx: usize) -> &VoiceState {
+        &self.voices[idx.min(MAX_VOICES - 1)]
+    }
+
+    /// Stores the output level of a voice, which is used by
+    /// [VoiceStealMode::Quietest].
+    #[inline]
+    pub fn set_level(&mut self, idx: usize, level: f32) {
+        if let Some(v) = self.voices.get_mut(idx) {
+            v.level = level;
+        }
+    }
+
+    /// Assigns a voice to the note and returns it's index.
+    ///
+    /// A voice that already plays the same note is retriggered. Otherwise
+    /// the free voice that was started the longest time ago is used.
+    /// If no voice is free, one is stolen according to the [VoiceStealMode].
+    pub fn note_on(&mut self, note: u8, vel: f32) -> usize {
+        self.serial += 1;
+
+        let voices = &self.voices[0..self.voice_count];
+
+        let same_note = voices.iter().position(|v| v.gate && v.note == note);
+
+        let free = voices
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| !v.gate)
+            .min_by_key(|(_, v)| v.serial)
+            .map(|(i, _)| i);
+
+        let idx = if let Some(idx) = same_note {
+            idx
+        } else if let Some(idx) = free {
+            idx
+        } else {
+            match self.steal_mode {
+                VoiceStealMode::Oldest => voices
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, v)| v.serial)
+                    .map(|(i, _)| i)
+                    .unwrap_or(0),
+                VoiceStealMode::Quietest => voices
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| {
+                        a.level.partial_cmp(&b.level).unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .map(|(i, _)| i)
+                    .unwrap_or(0),
+            }
+        };
+
+        let v = &mut self.voices[idx];
+        v.note = note;
+        v.gate = true;
+        v.vel = vel.clamp(0.0, 1.0);
+        v.serial = self.serial;
+
+        idx
+    }
+
+    /// Releases the voice that plays the given note.
+    pub fn note_off(&mut self, note: u8) {
+        for v in self.voices[0..self.voice_count].iter_mut() {
+            if v.gate && v.note == note {
+                v.gate = false;
+            }
+        }
+    }
+
+    /// Releases all voices.
+    pub fn all_notes_off(&mut self) {
+        for v in self.voices.iter_mut() {
+            v.gate = false;
+        }
+    }
+}
+
+impl Default for VoiceAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_voice_alloc_free_voices() {
+        let mut va = VoiceAllocator::new();
+        va.set_voice_count(3);
+
+        assert_eq!(va.note_on(60, 1.0), 0);
+        assert_eq!(va.note_on(64, 1.0), 1);
+        assert_eq!(va.note_on(67, 1.0), 2);
+
+        va.note_off(64);
+        assert!(!va.voice(1).gate);
+        assert_eq!(va.note_on(65, 0.5), 1);
+        assert_eq!(va.voice(1).note, 65);
+        assert_eq!(va.voice(1).vel, 0.5);
+
+        // Retrigger of the same note uses the same voice:
+        assert_eq!(va.note_on(67, 1.0), 2);
+    }
+
+    #[test]
+    fn check_voice_alloc_steal_oldest() {
+        let mut va = VoiceAllocator::new();
+        va.set_voice_count(2);
+
+        assert_eq!(va.note_on(60, 1.0), 0);
+        assert_eq!(va.note_on(64, 1.0), 1);
+        assert_eq!(va.note_on(67, 1.0), 0);
+        assert_eq!(va.note_on(72, 1.0), 1);
+        assert_eq!(va.voice(0).note, 67);
+        assert_eq!(va.voice(1).note, 72);
+    }
+
+    #[test]
+    fn check_voice_alloc_steal_quietest() {
+        let mut va = VoiceAllocator::new();
+        va.set_voice_count(3);
+        va.set_steal_mode(VoiceStealMode::Quietest);
+
+        va.note_on(60, 1.0);
+        va.note_on(64, 1.0);
+        va.note_on(67, 1.0);
+        va.set_level(0, 0.8);
+        va.set_level(1, 0.1);
+        va.set_level(2, 0.5);
+
+        assert_eq!(va.note_on(72, 1.0), 1);
+        assert_eq!(va.voice(1).note, 72);
+    }
+}
@@ -0,0 +1,369 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::{
+    ModOp, NodeAudioContext, NodeExecContext, NodeOp, NodeState, Transport, VoiceAllocator,
+};
+use crate::dsp::{Node, NodeContext, ProcBuf, SAtom};
+use synfx_dsp::AtomicFloat;
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// How often an idle worker polls for new work before it parks itself.
+/// Between the levels of one block the workers usually don't park,
+/// between blocks they do.
+const SPIN_COUNT: usize = 2000;
+
+/// The audio driver context for the nodes that are executed on a
+/// worker thread. Nodes that access the audio driver are never executed
+/// there, see [crate::dsp::NodeId::is_parallel_safe].
+struct WorkerContext {
+    nframes: usize,
+}
+
+impl NodeAudioContext for WorkerContext {
+    #[inline]
+    fn nframes(&self) -> usize {
+        self.nframes
+    }
+
+    #[inline]
+    fn output(&mut self, _channel: usize, _frame: usize, _v: f32) {}
+
+    #[inline]
+    fn input(&mut self, _channel: usize, _frame: usize) -> f32 {
+        0.0
+    }
+}
+
+/// Raw views into the [crate::nodes::NodeExecutor] and its [NodeProg],
+/// so that the ops of one level can be executed by multiple threads
+/// at the same time. The ops of one level don't share any node, output
+/// buffer or modulator, so that these accesses don't overlap.
+#[derive(Clone, Copy)]
+pub(crate) struct OpExecPtrs {
+    pub(crate) nodes: *mut Node,
+    pub(crate) ops: *const NodeOp,
+    pub(crate) modops: *mut ModOp,
+    pub(crate) inp: *const ProcBuf,
+    pub(crate) cur_inp: *const ProcBuf,
+    pub(crate) atoms: *const SAtom,
+    pub(crate) out: *mut ProcBuf,
+    pub(crate) out_fb: *mut f32,
+    pub(crate) ctx_vals: *const Arc<AtomicFloat>,
+    /// The processing times of the nodes, only written if profiling.
+    pub(crate) node_ns: *mut u64,
+    pub(crate) profiling: bool,
+    pub(crate) nframes: usize,
+}
+
+unsafe impl Send for OpExecPtrs {}
+
+impl OpExecPtrs {
+    /// Executes the op at index `op_i` of [NodeProg::prog].
+    ///
+    /// # Safety
+    ///
+    /// The pointers must still be valid and no other thread may execute
+    /// an op that shares the node, outputs or modulators with this op.
+    #[inline]
+    pub(crate) unsafe fn run<T: NodeAudioContext>(
+        &self,
+        op_i: usize,
+        ctx: &mut T,
+        ectx: &mut NodeExecContext,
+    ) {
+        let op = &*self.ops.add(op_i);
+        let out = op.out_idxlen;
+        let inp = op.in_idxlen;
+        let at = op.at_idxlen;
+        let md = op.mod_idxlen;
+        let ctx_idx = op.idx * 2;
+        let nframes = self.nframes;
+
+        let op_start = if self.profiling { Some(Instant::now()) } else { None };
+
+        for modop in std::slice::from_raw_parts_mut(self.modops.add(md.0), md.1 - md.0) {
+            modop.process(nframes);
+        }
+
+        let params = std::slice::from_raw_parts(self.inp.add(inp.0), inp.1 - inp.0);
+        let outputs = std::slice::from_raw_parts_mut(self.out.add(out.0), out.1 - out.0);
+
+        let node = &mut *self.nodes.add(op.idx);
+        let inputs = std::slice::from_raw_parts(self.cur_inp.add(inp.0), inp.1 - inp.0);
+
+        if op.state == NodeState::Active {
+            node.process(
+                ctx,
+                ectx,
+                &NodeContext {
+                    out_connected: op.out_connected,
+                    in_connected: op.in_connected,
+                    params,
+                },
+                std::slice::from_raw_parts(self.atoms.add(at.0), at.1 - at.0),
+                inputs,
+                outputs,
+                std::slice::from_raw_parts(self.ctx_vals.add(ctx_idx), 2),
+            );
+        } else {
+            op.process_inactive(node, ctx, inputs, outputs, nframes);
+        }
+
+        if let Some(op_start) = op_start {
+            *self.node_ns.add(op.idx) += op_start.elapsed().as_nanos() as u64;
+        }
+
+        let last_frame_idx = nframes - 1;
+        for (i, pb) in outputs.iter().enumerate() {
+            *self.out_fb.add(out.0 + i) = pb.read(last_frame_idx);
+        }
+    }
+}
+
+/// The ops of one level that are handed to the workers.
+#[derive(Clone, Copy)]
+struct LevelJob {
+    ptrs: OpExecPtrs,
+    /// Indices into [NodeProg::prog].
+    op_idxs: *const usize,
+    transport: Transport,
+}
+
+struct WorkerSlot {
+    parked: AtomicBool,
+}
+
+struct PoolShared {
+    /// Packs the epoch of the current job (upper 32 bits) and the index
+    /// of the next op to claim (lower 32 bits) into one word. So a worker
+    /// that is late can't claim an op of the following job by accident.
+    work: AtomicU64,
+    /// The number of ops in the current job. Written before `work`.
+    count: AtomicUsize,
+    /// The number of finished ops of the current job.
+    done: AtomicUsize,
+    quit: AtomicBool,
+    /// Only written by the [crate::nodes::NodeExecutor] while
+    /// no worker works on a job.
+    job: UnsafeCell<Option<LevelJob>>,
+    slots: Vec<WorkerSlot>,
+}
+
+unsafe impl Sync for PoolShared {}
+unsafe impl Send for PoolShared {}
+
+#[inline]
+fn work_epoch(w: u64) -> u32 {
+    (w >> 32) as u32
+}
+
+impl PoolShared {
+    /// Claims the next op of the job with the given `epoch`.
+    #[inline]
+    fn claim(&self, epoch: u32) -> Option<usize> {
+        let mut w = self.work.load(Ordering::Acquire);
+
+        loop {
+            // A count of the following job is no problem here,
+            // because then the exchange of `w` fails:
+            let count = self.count.load(Ordering::Acquire);
+            let idx = (w & 0xFFFF_FFFF) as usize;
+
+            if work_epoch(w) != epoch || idx >= count {
+                return None;
+            }
+
+            match self.work.compare_exchange_weak(w, w + 1, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return Some(idx),
+                Err(cur) => w = cur,
+            }
+        }
+    }
+
+    fn worker_loop(&self, slot_idx: usize) {
+        let slot = &self.slots[slot_idx];
+
+        let mut ectx = NodeExecContext {
+            feedback_delay_buffers: vec![],
+            voice_alloc: VoiceAllocator::new(),
+            cur_voice: 0,
+            transport: Transport::new(),
+            bus_buffers: vec![],
+        };
+
+        let mut seen_epoch = 0;
+        let mut spins = 0;
+
+        loop {
+            if self.quit.load(Ordering::Acquire) {
+                return;
+            }
+
+            let epoch = work_epoch(self.work.load(Ordering::Acquire));
+            if epoch == seen_epoch {
+                if spins < SPIN_COUNT {
+                    spins += 1;
+                    std::hint::spin_loop();
+                } else {
+                    slot.parked.store(true, Ordering::SeqCst);
+                    if work_epoch(self.work.load(Ordering::SeqCst)) == seen_epoch
+                        && !self.quit.load(Ordering::SeqCst)
+                    {
+                        std::thread::park();
+                    }
+                    slot.parked.store(false, Ordering::SeqCst);
+                    spins = 0;
+                }
+                continue;
+            }
+
+            seen_epoch = epoch;
+            spins = 0;
+
+            while let Some(i) = self.claim(epoch) {
+                // Safety: The job is not modified until all claimed ops are done.
+                if let Some(job) = unsafe { *self.job.get() } {
+                    ectx.transport = job.transport;
+                    let mut ctx = WorkerContext { nframes: job.ptrs.nframes };
+                    unsafe {
+                        let op_i = *job.op_idxs.add(i);
+                        // The worker has no feedback buffers and buses,
+                        // see NodeProg::calculate_levels():
+                        debug_assert!((*job.ptrs.ops.add(op_i)).node_id.is_parallel_safe());
+                        job.ptrs.run(op_i, &mut ctx, &mut ectx);
+                    }
+                }
+
+                self.done.fetch_add(1, Ordering::Release);
+            }
+        }
+    }
+}
+
+/// A pool of worker threads that execute the independent ops of one
+/// level of the [NodeProg] in parallel with the audio thread.
+/// Create it with [crate::nodes::NodeConfigurator::set_worker_threads].
+///
+/// The audio thread does not allocate or lock for handing out work.
+/// Idle workers spin for a short while and then park themselves.
+/// The audio thread busy waits for the ops that the workers claimed,
+/// see [crate::nodes::NodeConfigurator::set_worker_threads].
+pub struct WorkerPool {
+    shared: Arc<PoolShared>,
+    threads: Vec<std::thread::JoinHandle<()>>,
+    epoch: u32,
+    count: usize,
+}
+
+impl std::fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WorkerPool(threads={})", self.threads.len())
+    }
+}
+
+impl WorkerPool {
+    pub(crate) fn new(threads: usize) -> Self {
+        let mut slots = vec![];
+        slots.resize_with(threads, || WorkerSlot { parked: AtomicBool::new(false) });
+
+        let shared = Arc::new(PoolShared {
+            work: AtomicU64::new(0),
+            count: AtomicUsize::new(0),
+            done: AtomicUsize::new(0),
+            quit: AtomicBool::new(false),
+            job: UnsafeCell::new(None),
+            slots,
+        });
+
+        let threads = (0..threads)
+            .map(|i| {
+                let shared = shared.clone();
+                std::thread::Builder::new()
+                    .name(format!("hexodsp-worker-{}", i))
+                    .spawn(move || shared.worker_loop(i))
+                    .expect("spawning a DSP worker thread")
+            })
+            .collect();
+
+        Self { shared, threads, epoch: 0, count: 0 }
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Hands out the ops at the indices `op_idxs` to the workers.
+    /// The audio thread must help out with [WorkerPool::run_claimed]
+    /// and then wait with [WorkerPool::wait] before the `ptrs` become
+    /// invalid or the next job is started.
+    pub(crate) fn start(&mut self, ptrs: OpExecPtrs, op_idxs: &[usize], transport: Transport) {
+        self.epoch = self.epoch.wrapping_add(1);
+        if self.epoch == 0 {
+            self.epoch = 1;
+        }
+        self.count = op_idxs.len();
+
+        // Safety: The previous job is done, no worker reads the job now.
+        unsafe {
+            *self.shared.job.get() = Some(LevelJob { ptrs, op_idxs: op_idxs.as_ptr(), transport });
+        }
+        self.shared.done.store(0, Ordering::Relaxed);
+        self.shared.count.store(self.count, Ordering::Release);
+        self.shared.work.store((self.epoch as u64) << 32, Ordering::SeqCst);
+
+        for (slot, th) in self.shared.slots.iter().zip(self.threads.iter()) {
+            if slot.parked.load(Ordering::SeqCst) {
+                th.thread().unpark();
+            }
+        }
+    }
+
+    /// Executes ops of the current job on the calling (audio) thread,
+    /// until no op is left to claim.
+    pub(crate) fn run_claimed<T: NodeAudioContext>(
+        &mut self,
+        ctx: &mut T,
+        ectx: &mut NodeExecContext,
+    ) {
+        while let Some(i) = self.shared.claim(self.epoch) {
+            if let Some(job) = unsafe { *self.shared.job.get() } {
+                unsafe {
+                    job.ptrs.run(*job.op_idxs.add(i), ctx, ectx);
+                }
+            }
+
+            self.shared.done.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    /// Waits until the workers finished all ops of the current job.
+    pub(crate) fn wait(&mut self) {
+        while self.shared.done.load(Ordering::Acquire) < self.count {
+            std::hint::spin_loop();
+        }
+
+        unsafe {
+            *self.shared.job.get() = None;
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shared.quit.store(true, Ordering::SeqCst);
+
+        for th in self.threads.iter() {
+            th.thread().unpark();
+        }
+
+        for th in self.threads.drain(..) {
+            let _ = th.join();
+        }
+    }
+}
@@ -0,0 +1,312 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Offline rendering of the DSP graph, for instance for bouncing
+//! patches to WAV files in batch jobs.
+//!
+//!```
+//! use hexodsp::*;
+//! use hexodsp::offline_render::*;
+//!
+//! let (node_conf, mut node_exec) = new_node_engine();
+//! let mut matrix = Matrix::new(node_conf, 3, 3);
+//!
+//! let mut chain = MatrixCellChain::new(CellDir::B);
+//! chain.node_out("sin", "sig").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+//! matrix.sync().unwrap();
+//!
+//! let settings = RenderSettings {
+//!     sample_rate: 48000.0,
+//!     length: RenderLength::Seconds(0.5),
+//!     channels: 1,
+//!     ..RenderSettings::default()
+//! };
+//!
+//! let audio = node_exec.render(&settings, |_frame, _time| {
+//!     // Change parameters via the matrix or NodeConfigurator here.
+//! }).unwrap();
+//!
+//! assert_eq!(audio.channels.len(), 1);
+//! assert_eq!(audio.len(), 24000);
+//!```
+
+use crate::dsp::MAX_BLOCK_SIZE;
+use crate::nodes::{NodeAudioContext, NodeExecutor};
+
+/// Error returned by [NodeExecutor::render], [RenderedAudio::write_wav]
+/// and [NodeExecutor::render_to_wav].
+#[derive(Debug)]
+pub enum RenderError {
+    /// [RenderSettings::channels] is not 1 or 2.
+    UnsupportedChannels(usize),
+    WavError(hound::Error),
+}
+
+impl From<hound::Error> for RenderError {
+    fn from(err: hound::Error) -> Self {
+        RenderError::WavError(err)
+    }
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::UnsupportedChannels(channels) => {
+                write!(f, "can't render {} channels, only 1 or 2", channels)
+            }
+            RenderError::WavError(err) => write!(f, "can't write WAV file: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::UnsupportedChannels(_) => None,
+            RenderError::WavError(err) => Some(err),
+        }
+    }
+}
+
+/// Determines how long [NodeExecutor::render] renders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderLength {
+    /// Renders the given number of seconds.
+    Seconds(f32),
+    /// Renders until the absolute output level stayed below `threshold`
+    /// for `hold` seconds, but at most `max` seconds. The silence before
+    /// the output first exceeds `threshold` is not counted, so that a
+    /// patch with a slow attack is not cut off before it starts.
+    UntilSilence { threshold: f32, hold: f32, max: f32 },
+}
+
+/// The sample format of the WAV file written by [RenderedAudio::write_wav].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavFormat {
+    Int16,
+    Int24,
+    Float32,
+}
+
+/// Settings for [NodeExecutor::render].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub sample_rate: f32,
+    pub length: RenderLength,
+    /// The number of rendered channels, 1 (mono) or 2 (stereo).
+    /// The mono channel is the first channel of the `Out` node.
+    pub channels: usize,
+    /// Seconds that are rendered additionally after the `length`,
+    /// for instance to capture the decay of a reverb.
+    pub tail: f32,
+    /// The number of frames between two calls of the automation callback.
+    pub block_size: usize,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            length: RenderLength::Seconds(1.0),
+            channels: 2,
+            tail: 0.0,
+            block_size: MAX_BLOCK_SIZE,
+        }
+    }
+}
+
+/// The result of [NodeExecutor::render].
+#[derive(Debug, Clone)]
+pub struct RenderedAudio {
+    pub sample_rate: f32,
+    /// One buffer per channel.
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl RenderedAudio {
+    /// The number of frames that were rendered.
+    pub fn len(&self) -> usize {
+        self.channels.first().map(|c| c.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes the rendered audio to a WAV file. Samples exceeding
+    /// the range -1.0 to 1.0 are clipped for the integer formats.
+    pub fn write_wav(&self, path: &str, format: WavFormat) -> Result<(), RenderError> {
+        let (bits_per_sample, sample_format) = match format {
+            WavFormat::Int16 => (16, hound::SampleFormat::Int),
+            WavFormat::Int24 => (24, hound::SampleFormat::Int),
+            WavFormat::Float32 => (32, hound::SampleFormat::Float),
+        };
+
+        let spec = hound::WavSpec {
+            channels: self.channels.len() as u16,
+            sample_rate: self.sample_rate as u32,
+            bits_per_sample,
+            sample_format,
+        };
+
+        let mut writer = hound::WavWriter::create(path, spec)?;
+
+        for i in 0..self.len() {
+            for chan in self.channels.iter() {
+                let s = chan[i];
+
+                match format {
+                    WavFormat::Int16 => {
+                        writer.write_sample((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+                    }
+                    WavFormat::Int24 => {
+                        writer.write_sample((s.clamp(-1.0, 1.0) * 8388607.0) as i32)?;
+                    }
+                    WavFormat::Float32 => {
+                        writer.write_sample(s)?;
+                    }
+                }
+            }
+        }
+
+        writer.finalize()?;
+
+        Ok(())
+    }
+}
+
+/// The audio context for offline rendering. There are no
+/// input channels and only the requested output channels are kept.
+struct RenderContext<'a> {
+    nframes: usize,
+    output: &'a mut [Vec<f32>],
+}
+
+impl<'a> NodeAudioContext for RenderContext<'a> {
+    #[inline]
+    fn nframes(&self) -> usize {
+        self.nframes
+    }
+
+    #[inline]
+    fn output(&mut self, channel: usize, frame: usize, v: f32) {
+        if let Some(out) = self.output.get_mut(channel) {
+            out[frame] = v;
+        }
+    }
+
+    #[inline]
+    fn input(&mut self, _channel: usize, _frame: usize) -> f32 {
+        0.0
+    }
+}
+
+impl NodeExecutor {
+    /// Renders the DSP graph offline, as fast as possible.
+    ///
+    /// Before each block of [RenderSettings::block_size] frames the
+    /// `automation` callback is called with the current frame offset and
+    /// time in seconds. Parameter changes that are made there via the
+    /// [crate::NodeConfigurator] or [crate::Matrix] are applied to the
+    /// following block.
+    ///
+    /// Please note that the sample rate of the NodeExecutor is changed
+    /// to [RenderSettings::sample_rate].
+    ///
+    /// Returns [RenderError::UnsupportedChannels] if
+    /// [RenderSettings::channels] is not 1 or 2.
+    pub fn render<F: FnMut(usize, f64)>(
+        &mut self,
+        settings: &RenderSettings,
+        mut automation: F,
+    ) -> Result<RenderedAudio, RenderError> {
+        let sample_rate = settings.sample_rate;
+        let channels = settings.channels;
+        if !(1..=2).contains(&channels) {
+            return Err(RenderError::UnsupportedChannels(channels));
+        }
+
+        let block_size = settings.block_size.max(1);
+        let tail_frames = (settings.tail.max(0.0) * sample_rate) as usize;
+
+        let (max_frames, silence) = match settings.length {
+            RenderLength::Seconds(secs) => ((secs.max(0.0) * sample_rate) as usize, None),
+            RenderLength::UntilSilence { threshold, hold, max } => (
+                (max.max(0.0) * sample_rate) as usize,
+                Some((threshold, (hold.max(0.0) * sample_rate) as usize)),
+            ),
+        };
+
+        self.set_sample_rate(sample_rate);
+        self.process_graph_updates();
+
+        let mut out: Vec<Vec<f32>> =
+            (0..channels).map(|_| Vec::with_capacity(max_frames + tail_frames)).collect();
+        let mut block = vec![vec![0.0; block_size]; channels];
+
+        let mut sounded = false;
+        let mut silent_frames = 0;
+        let mut tail_left = tail_frames;
+        let mut frame = 0;
+
+        loop {
+            let main_done = frame >= max_frames
+                || silence.map(|(_, hold)| sounded && silent_frames >= hold).unwrap_or(false);
+
+            let nframes = if main_done {
+                if tail_left == 0 {
+                    break;
+                }
+                tail_left.min(block_size)
+            } else {
+                (max_frames - frame).min(block_size)
+            };
+
+            automation(frame, frame as f64 / sample_rate as f64);
+            self.process_graph_updates();
+
+            for chan in block.iter_mut() {
+                chan.fill(0.0);
+            }
+
+            let mut ctx = RenderContext { nframes, output: &mut block[..] };
+            self.process(&mut ctx);
+
+            for (out_chan, block_chan) in out.iter_mut().zip(block.iter()) {
+                out_chan.extend_from_slice(&block_chan[0..nframes]);
+            }
+
+            if main_done {
+                tail_left -= nframes;
+            } else if let Some((threshold, _)) = silence {
+                for i in 0..nframes {
+                    if block.iter().any(|chan| chan[i].abs() > threshold) {
+                        sounded = true;
+                        silent_frames = 0;
+                    } else if sounded {
+                        silent_frames += 1;
+                    }
+                }
+            }
+
+            frame += nframes;
+        }
+
+        Ok(RenderedAudio { sample_rate, channels: out })
+    }
+
+    /// Renders the DSP graph like [NodeExecutor::render] and
+    /// writes the result directly to a WAV file at `path`.
+    pub fn render_to_wav<F: FnMut(usize, f64)>(
+        &mut self,
+        path: &str,
+        format: WavFormat,
+        settings: &RenderSettings,
+        automation: F,
+    ) -> Result<RenderedAudio, RenderError> {
+        let audio = self.render(settings, automation)?;
+        audio.write_wav(path, format)?;
+        Ok(audio)
+    }
+}
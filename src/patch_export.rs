@@ -0,0 +1,267 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+/*! Export of the DSP graph of a patch for visualization with other tools.
+
+The [crate::PatchExport] collects the nodes, the edges between them and the
+parameters of the nodes from a [crate::Matrix] or a [crate::Graph]. It can be
+written as Graphviz DOT with [crate::PatchExport::to_dot] or as a neutral JSON
+adjacency list with [crate::PatchExport::to_json]. Pairs of `FbWr` and `FbRd`
+nodes of the same instance are shown as dashed edges in the DOT output.
+
+```
+ use hexodsp::*;
+
+ let (node_conf, _node_exec) = new_node_engine();
+ let mut matrix = Matrix::new(node_conf, 3, 3);
+
+ let mut chain = MatrixCellChain::new(CellDir::B);
+ chain.node_out("sin", "sig").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+ matrix.sync().unwrap();
+
+ let dot = PatchExport::from_matrix(&matrix).to_dot();
+ assert!(dot.contains("\"sin_0\" -> \"out_0\" [taillabel=\"sig\", headlabel=\"ch1\"];"));
+ // Render it with: dot -Tsvg patch.dot > patch.svg
+```
+*/
+
+use crate::dsp::{NodeId, ParamId, SAtom};
+use crate::graph::{Graph, GraphEdge};
+use crate::nodes::NodeConfigurator;
+use crate::Matrix;
+use serde_json::{json, Value};
+
+/// A parameter of an [ExportNode]. Input parameters are stored
+/// denormalized as [SAtom::Param].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportParam {
+    pub param: ParamId,
+    pub value: SAtom,
+    /// The value formatted like in the UI.
+    pub text: String,
+    pub modamt: Option<f32>,
+}
+
+/// A node of the [PatchExport] with the parameters that differ from their
+/// defaults or have a modulation amount.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportNode {
+    pub node_id: NodeId,
+    pub params: Vec<ExportParam>,
+}
+
+/// The nodes and edges of a patch, see the [crate::patch_export] module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchExport {
+    nodes: Vec<ExportNode>,
+    edges: Vec<GraphEdge>,
+}
+
+fn export_node(node_id: NodeId, config: &NodeConfigurator) -> ExportNode {
+    let mut params = vec![];
+
+    let mut idx = 0;
+    while let Some(pid) = node_id.param_by_idx(idx) {
+        idx += 1;
+
+        let value = if let Some(value) = config.get_param(&pid) {
+            value
+        } else {
+            continue;
+        };
+        let modamt = config.get_param_modamt(&pid);
+
+        let is_default = if pid.is_atom() {
+            value == pid.as_atom_def()
+        } else {
+            value.f() == pid.norm_def() && modamt.is_none()
+        };
+        if is_default {
+            continue;
+        }
+
+        let text = match &value {
+            SAtom::Str(s) | SAtom::AudioSample((s, _)) => s.clone(),
+            SAtom::MicroSample(s) => format!("{:?}", s),
+            SAtom::Setting(_) | SAtom::Param(_) => {
+                let mut buf: Vec<u8> = vec![];
+                if let Some(Ok(_)) = config.format_param(&mut buf, &pid, value.f()) {
+                    String::from_utf8(buf).unwrap_or_default().trim().to_string()
+                } else {
+                    format!("{}", value.f())
+                }
+            }
+        };
+
+        let value = if pid.is_atom() { value } else { SAtom::param(pid.denorm(value.f())) };
+
+        params.push(ExportParam { param: pid, value, text, modamt });
+    }
+
+    ExportNode { node_id, params }
+}
+
+/// The identifier of the node in the DOT and JSON output.
+fn node_key(node_id: NodeId) -> String {
+    format!("{}_{}", node_id.name(), node_id.instance())
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn atom_value(at: &SAtom) -> Value {
+    match at {
+        SAtom::Str(s) | SAtom::AudioSample((s, _)) => json!(s),
+        SAtom::MicroSample(s) => json!(s),
+        SAtom::Setting(i) => json!(i),
+        SAtom::Param(v) => json!(v),
+    }
+}
+
+impl PatchExport {
+    fn new(node_ids: Vec<NodeId>, edges: Vec<GraphEdge>, config: &NodeConfigurator) -> Self {
+        let nodes = node_ids.iter().map(|node_id| export_node(*node_id, config)).collect();
+        Self { nodes, edges }
+    }
+
+    /// Collects the nodes of the cells and the edges found by the
+    /// last [Matrix::sync].
+    pub fn from_matrix(matrix: &Matrix) -> Self {
+        let mut node_ids = vec![];
+        matrix.for_each(|_x, _y, cell| {
+            let node_id = cell.node_id();
+            if node_id != NodeId::Nop && !node_ids.contains(&node_id) {
+                node_ids.push(node_id);
+            }
+        });
+
+        Self::new(node_ids, matrix.edges(), matrix.config())
+    }
+
+    pub fn from_graph(graph: &Graph) -> Self {
+        Self::new(graph.nodes().to_vec(), graph.edges().to_vec(), graph.config())
+    }
+
+    pub fn nodes(&self) -> &[ExportNode] {
+        &self.nodes[..]
+    }
+
+    pub fn edges(&self) -> &[GraphEdge] {
+        &self.edges[..]
+    }
+
+    /// Returns the `FbWr` and `FbRd` nodes that share a feedback buffer.
+    pub fn feedback_pairs(&self) -> Vec<(NodeId, NodeId)> {
+        let mut pairs = vec![];
+
+        for node in self.nodes.iter() {
+            if let NodeId::FbWr(i) = node.node_id {
+                if self.nodes.iter().any(|n| n.node_id == NodeId::FbRd(i)) {
+                    pairs.push((NodeId::FbWr(i), NodeId::FbRd(i)));
+                }
+            }
+        }
+
+        pairs
+    }
+
+    /// Writes the patch as Graphviz DOT. The labels of the nodes contain
+    /// the parameters, the edges are labelled with the port names.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph patch {\n    node [shape=box];\n");
+
+        for node in self.nodes.iter() {
+            let mut label = format!("{} {}", node.node_id.label(), node.node_id.instance());
+            for p in node.params.iter() {
+                label += &format!("\\n{} = {}", p.param.name(), dot_escape(&p.text));
+                if let Some(modamt) = p.modamt {
+                    label += &format!(" (mod {})", modamt);
+                }
+            }
+
+            dot += &format!("    \"{}\" [label=\"{}\"];\n", node_key(node.node_id), label);
+        }
+
+        for edge in self.edges.iter() {
+            dot += &format!(
+                "    \"{}\" -> \"{}\" [taillabel=\"{}\", headlabel=\"{}\"];\n",
+                node_key(edge.from),
+                node_key(edge.to),
+                edge.from.out_name_by_idx(edge.from_out).unwrap_or("?"),
+                edge.to.inp_name_by_idx(edge.to_input).unwrap_or("?"),
+            );
+        }
+
+        for (fbwr, fbrd) in self.feedback_pairs().iter() {
+            dot += &format!(
+                "    \"{}\" -> \"{}\" [style=dashed];\n",
+                node_key(*fbwr),
+                node_key(*fbrd)
+            );
+        }
+
+        dot += "}\n";
+        dot
+    }
+
+    /// Writes the patch as JSON object with the `nodes`, the `edges` between
+    /// their ports and the `feedback` pairs. The nodes are referred to by
+    /// their `id`, which is the name and the instance, eg. `"sin_0"`.
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<Value> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let params: Vec<Value> = node
+                    .params
+                    .iter()
+                    .map(|p| {
+                        let mut v = json!({
+                            "name": p.param.name(),
+                            "value": atom_value(&p.value),
+                            "text": p.text,
+                        });
+                        if let Some(modamt) = p.modamt {
+                            v["modamt"] = json!(modamt);
+                        }
+                        v
+                    })
+                    .collect();
+
+                json!({
+                    "id": node_key(node.node_id),
+                    "node": node.node_id.name(),
+                    "instance": node.node_id.instance(),
+                    "params": params,
+                })
+            })
+            .collect();
+
+        let edges: Vec<Value> = self
+            .edges
+            .iter()
+            .map(|e| {
+                json!({
+                    "from": node_key(e.from),
+                    "out": e.from.out_name_by_idx(e.from_out).unwrap_or("?"),
+                    "to": node_key(e.to),
+                    "inp": e.to.inp_name_by_idx(e.to_input).unwrap_or("?"),
+                })
+            })
+            .collect();
+
+        let feedback: Vec<Value> = self
+            .feedback_pairs()
+            .iter()
+            .map(|(fbwr, fbrd)| json!({ "from": node_key(*fbwr), "to": node_key(*fbrd) }))
+            .collect();
+
+        json!({
+            "nodes": nodes,
+            "edges": edges,
+            "feedback": feedback,
+        })
+        .to_string()
+    }
+}
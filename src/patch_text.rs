@@ -0,0 +1,770 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+/*! A small text language for describing patches.
+
+A [crate::PatchText] is a list of nodes with their parameters and the edges
+between them, which can be written and read by hand. It can be applied to a
+[crate::Graph] and written back from one:
+
+```text
+ # Comments start with a '#'.
+ sin(freq=220) -> amp(gain=0.5) -> out.ch1
+ sin:1(freq=110, det=0.1~0.5) -> amp.att
+ bosc(wtype=2)
+```
+
+* A node is referred to by it's lower case name and an optional instance
+after a `:`, `sin` is the same as `sin:0`.
+* Parameters are set in parenthesis after the node, at any place the node
+is mentioned. Input parameters take the denormalized value, an optional
+modulation amount follows after a `~`. Atoms take integers for settings,
+strings in double quotes for text and samples or a list of 8 numbers for
+micro samples.
+* `a -> b` connects an output of `a` with an input of `b`. Without a port
+the first output or input is used. At the start of a chain the port after
+the `.` names the output, everywhere else the input.
+* Statements are separated by new lines or `;`, a chain can be continued
+on the next line after a `->`.
+
+```
+ use hexodsp::*;
+
+ let patch = PatchText::parse("sin(freq=220) -> amp(gain=0.5) -> out.ch1").unwrap();
+ assert_eq!(patch.to_string(), "sin(freq=220)\namp(gain=0.5)\nout\n\nsin -> amp\namp -> out\n");
+
+ let (node_conf, mut node_exec) = new_node_engine();
+ let mut graph = Graph::new(node_conf);
+ patch.apply(&mut graph).unwrap();
+ graph.sync().unwrap();
+
+ let error = PatchText::parse("sin -> amp.foo").unwrap_err();
+ assert_eq!(error.to_string(), "1:12: unknown input 'foo' of amp");
+```
+*/
+
+use crate::dsp::{NodeId, ParamId, SAtom};
+use crate::graph::{Graph, GraphEdge, GraphError};
+
+/// A parameter value of a [PatchNode]. Input parameters are stored
+/// denormalized as [SAtom::Param].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchParam {
+    pub param: ParamId,
+    pub value: SAtom,
+    pub modamt: Option<f32>,
+}
+
+/// A node of the [PatchText] with the parameters that are set for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchNode {
+    pub node_id: NodeId,
+    pub params: Vec<PatchParam>,
+}
+
+/// An error of [PatchText::parse] with the 1-based line and column
+/// in the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchTextError {
+    pub line: usize,
+    pub col: usize,
+    pub msg: String,
+}
+
+impl std::fmt::Display for PatchTextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.col, self.msg)
+    }
+}
+
+impl std::error::Error for PatchTextError {}
+
+/// A patch in the text language described in the [crate::patch_text] module.
+/// Use [PatchText::parse] to read it and the [std::fmt::Display]
+/// implementation to write it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PatchText {
+    nodes: Vec<PatchNode>,
+    edges: Vec<GraphEdge>,
+}
+
+impl PatchText {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(src: &str) -> Result<Self, PatchTextError> {
+        Parser::new(src).parse_patch()
+    }
+
+    /// The nodes in the order they were mentioned first.
+    pub fn nodes(&self) -> &[PatchNode] {
+        &self.nodes[..]
+    }
+
+    pub fn edges(&self) -> &[GraphEdge] {
+        &self.edges[..]
+    }
+
+    fn node_mut(&mut self, node_id: NodeId) -> &mut PatchNode {
+        if let Some(idx) = self.nodes.iter().position(|n| n.node_id == node_id) {
+            &mut self.nodes[idx]
+        } else {
+            self.nodes.push(PatchNode { node_id, params: vec![] });
+            self.nodes.last_mut().unwrap()
+        }
+    }
+
+    fn set_param(&mut self, param: ParamId, value: SAtom, modamt: Option<f32>) {
+        let node = self.node_mut(param.node_id());
+        node.params.retain(|p| p.param != param);
+        node.params.push(PatchParam { param, value, modamt });
+    }
+
+    /// Writes the nodes, edges and the parameters that differ from their
+    /// defaults of the `graph` into a [PatchText].
+    pub fn from_graph(graph: &Graph) -> Self {
+        let mut patch = PatchText::new();
+
+        for node_id in graph.nodes().iter() {
+            patch.node_mut(*node_id);
+
+            let mut idx = 0;
+            while let Some(pid) = node_id.param_by_idx(idx) {
+                idx += 1;
+
+                let value = if let Some(value) = graph.config().get_param(&pid) {
+                    value
+                } else {
+                    continue;
+                };
+                let modamt = graph.config().get_param_modamt(&pid);
+
+                if pid.is_atom() {
+                    if value != pid.as_atom_def() {
+                        patch.set_param(pid, value, None);
+                    }
+                } else if value.f() != pid.norm_def() || modamt.is_some() {
+                    patch.set_param(pid, SAtom::param(pid.denorm(value.f())), modamt);
+                }
+            }
+        }
+
+        patch.edges = graph.edges().to_vec();
+        patch
+    }
+
+    /// Adds the nodes and edges to the `graph` and sets the parameters.
+    /// Call [Graph::sync] afterwards to upload the changes.
+    pub fn apply(&self, graph: &mut Graph) -> Result<(), GraphError> {
+        for node in self.nodes.iter() {
+            graph.add_node(node.node_id);
+
+            for p in node.params.iter() {
+                let (node_id, name) = (p.param.node_id(), p.param.name());
+
+                if p.param.is_atom() {
+                    graph.set_atom(node_id, name, p.value.clone())?;
+                } else {
+                    graph.set_denorm(node_id, name, p.value.f())?;
+                    graph.set_modamt(node_id, name, p.modamt)?;
+                }
+            }
+        }
+
+        for edge in self.edges.iter() {
+            graph.connect(
+                edge.from,
+                edge.from.out_name_by_idx(edge.from_out).unwrap_or("?"),
+                edge.to,
+                edge.to.inp_name_by_idx(edge.to_input).unwrap_or("?"),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_node_id(f: &mut std::fmt::Formatter<'_>, node_id: NodeId) -> std::fmt::Result {
+    if node_id.instance() == 0 {
+        write!(f, "{}", node_id.name())
+    } else {
+        write!(f, "{}:{}", node_id.name(), node_id.instance())
+    }
+}
+
+/// Formats a number with up to 6 significant digits, so that the
+/// rounding errors of the normalization don't end up in the text.
+fn format_num(v: f32) -> String {
+    if v == 0.0 || !v.is_finite() {
+        return format!("{}", v);
+    }
+
+    let int_digits = v.abs().log10().floor() as i32 + 1;
+    let decimals = (6 - int_digits).max(0) as usize;
+    let s = format!("{:.*}", decimals, v);
+
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        s
+    }
+}
+
+fn format_atom(param: &ParamId, at: &SAtom) -> String {
+    match at {
+        SAtom::Str(s) | SAtom::AudioSample((s, _)) => {
+            format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+        SAtom::MicroSample(s) => {
+            let nums: Vec<String> = s.iter().map(|v| format_num(*v)).collect();
+            format!("[{}]", nums.join(", "))
+        }
+        SAtom::Setting(i) => format!("{}", i),
+        SAtom::Param(v) => {
+            let s = format_num(*v);
+            // Otherwise it's read back as a setting:
+            if param.is_atom() && !s.contains('.') {
+                s + ".0"
+            } else {
+                s
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for PatchText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in self.nodes.iter() {
+            write_node_id(f, node.node_id)?;
+
+            if !node.params.is_empty() {
+                let params: Vec<String> = node
+                    .params
+                    .iter()
+                    .map(|p| {
+                        let mut s =
+                            format!("{}={}", p.param.name(), format_atom(&p.param, &p.value));
+                        if let Some(modamt) = p.modamt {
+                            s += &format!("~{}", format_num(modamt));
+                        }
+                        s
+                    })
+                    .collect();
+
+                write!(f, "({})", params.join(", "))?;
+            }
+
+            writeln!(f)?;
+        }
+
+        if !self.nodes.is_empty() && !self.edges.is_empty() {
+            writeln!(f)?;
+        }
+
+        for edge in self.edges.iter() {
+            write_node_id(f, edge.from)?;
+            if edge.from_out != 0 {
+                write!(f, ".{}", edge.from.out_name_by_idx(edge.from_out).unwrap_or("?"))?;
+            }
+
+            write!(f, " -> ")?;
+
+            write_node_id(f, edge.to)?;
+            if edge.to_input != 0 {
+                write!(f, ".{}", edge.to.inp_name_by_idx(edge.to_input).unwrap_or("?"))?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A node in a chain, with the optional port after the `.`.
+struct Endpoint {
+    node_id: NodeId,
+    pos: (usize, usize),
+    port: Option<(String, (usize, usize))>,
+    /// Set once the endpoint received an edge, then the port is an input.
+    is_target: bool,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    idx: usize,
+    line: usize,
+    col: usize,
+    patch: PatchText,
+}
+
+impl Parser {
+    fn new(src: &str) -> Self {
+        Self { chars: src.chars().collect(), idx: 0, line: 1, col: 1, patch: PatchText::new() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.idx).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.idx += 1;
+
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+
+        Some(c)
+    }
+
+    fn pos(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
+
+    fn error_at(&self, pos: (usize, usize), msg: String) -> PatchTextError {
+        PatchTextError { line: pos.0, col: pos.1, msg }
+    }
+
+    fn error(&self, msg: String) -> PatchTextError {
+        self.error_at(self.pos(), msg)
+    }
+
+    fn found(&self) -> String {
+        match self.peek() {
+            None => "end of text".to_string(),
+            Some('\n') => "end of line".to_string(),
+            Some(c) => format!("'{}'", c),
+        }
+    }
+
+    /// Skips white space and comments, but not the end of the line.
+    fn skip_ws(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.next();
+                }
+            } else if c.is_whitespace() && c != '\n' {
+                self.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn skip_ws_nl(&mut self) {
+        self.skip_ws();
+        while self.peek() == Some('\n') {
+            self.next();
+            self.skip_ws();
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), PatchTextError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}', found {}", c, self.found())))
+        }
+    }
+
+    fn ident(&mut self, what: &str) -> Result<(String, (usize, usize)), PatchTextError> {
+        let pos = self.pos();
+
+        if !matches!(self.peek(), Some(c) if c.is_ascii_alphabetic() || c == '_') {
+            return Err(self.error(format!("expected {}, found {}", what, self.found())));
+        }
+
+        let mut s = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                s.push(c);
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        Ok((s, pos))
+    }
+
+    /// Returns the number and whether it was written as an integer.
+    fn number(&mut self) -> Result<(f64, bool), PatchTextError> {
+        let pos = self.pos();
+
+        let mut s = String::new();
+        if let Some(c @ ('-' | '+')) = self.peek() {
+            s.push(c);
+            self.next();
+        }
+
+        let mut is_int = true;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
+                s.push(c);
+            } else if c == '.' || c == 'e' || c == 'E' {
+                is_int = false;
+                s.push(c);
+            } else if (c == '-' || c == '+') && s.ends_with(|c: char| c == 'e' || c == 'E') {
+                s.push(c);
+            } else {
+                break;
+            }
+            self.next();
+        }
+
+        s.parse::<f64>()
+            .map(|v| (v, is_int))
+            .map_err(|_| self.error_at(pos, format!("invalid number '{}'", s)))
+    }
+
+    fn string(&mut self) -> Result<String, PatchTextError> {
+        let pos = self.pos();
+        self.expect('"')?;
+
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => {
+                    return Err(self.error_at(pos, "unterminated string".to_string()));
+                }
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('n') => s.push('\n'),
+                    Some(c @ ('\\' | '"')) => s.push(c),
+                    _ => return Err(self.error("invalid escape in string".to_string())),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn parse_patch(mut self) -> Result<PatchText, PatchTextError> {
+        loop {
+            self.skip_ws_nl();
+            while self.eat(';') {
+                self.skip_ws_nl();
+            }
+
+            if self.peek().is_none() {
+                return Ok(self.patch);
+            }
+
+            self.statement()?;
+        }
+    }
+
+    fn statement(&mut self) -> Result<(), PatchTextError> {
+        let mut prev = self.endpoint()?;
+
+        loop {
+            self.skip_ws();
+
+            if self.eat('-') {
+                self.expect('>')?;
+                self.skip_ws_nl();
+
+                let mut next = self.endpoint()?;
+                self.connect(&prev, &next)?;
+                next.is_target = true;
+                prev = next;
+            } else {
+                break;
+            }
+        }
+
+        match self.peek() {
+            None | Some('\n') | Some(';') => Ok(()),
+            _ => {
+                Err(self
+                    .error(format!("expected '->', ';' or end of line, found {}", self.found())))
+            }
+        }
+    }
+
+    fn endpoint(&mut self) -> Result<Endpoint, PatchTextError> {
+        let (name, pos) = self.ident("a node name")?;
+
+        let node_id = NodeId::from_str(&name.to_lowercase());
+        if node_id == NodeId::Nop {
+            return Err(self.error_at(pos, format!("unknown node '{}'", name)));
+        }
+
+        let node_id = if self.eat(':') {
+            let inst_pos = self.pos();
+            let mut inst = String::new();
+            while let Some(c) = self.peek().filter(|c| c.is_ascii_digit()) {
+                inst.push(c);
+                self.next();
+            }
+
+            if inst.is_empty() {
+                return Err(self.error_at(
+                    inst_pos,
+                    format!("expected a node instance, found {}", self.found()),
+                ));
+            }
+
+            match inst.parse::<u8>() {
+                Ok(inst) => node_id.to_instance(inst as usize),
+                Err(_) => {
+                    return Err(self.error_at(
+                        inst_pos,
+                        format!("invalid node instance {}, the maximum is {}", inst, u8::MAX),
+                    ))
+                }
+            }
+        } else {
+            node_id
+        };
+
+        self.patch.node_mut(node_id);
+
+        self.skip_ws();
+        if self.eat('(') {
+            self.params(node_id)?;
+            self.skip_ws();
+        }
+
+        let port = if self.eat('.') { Some(self.ident("a port name")?) } else { None };
+
+        Ok(Endpoint { node_id, pos, port, is_target: false })
+    }
+
+    fn params(&mut self, node_id: NodeId) -> Result<(), PatchTextError> {
+        self.skip_ws_nl();
+        if self.eat(')') {
+            return Ok(());
+        }
+
+        loop {
+            let (name, pos) = self.ident("a parameter name")?;
+            let param = node_id.inp_param(&name).ok_or_else(|| {
+                self.error_at(pos, format!("unknown parameter '{}' of {}", name, node_id.name()))
+            })?;
+
+            self.skip_ws();
+            self.expect('=')?;
+            self.skip_ws();
+            let value = self.value(&param)?;
+
+            self.skip_ws();
+            let modamt = if self.peek() == Some('~') {
+                if param.is_atom() {
+                    return Err(self.error(format!("atom '{}' has no modulation amount", name)));
+                }
+                self.next();
+                self.skip_ws();
+                Some(self.number()?.0 as f32)
+            } else {
+                None
+            };
+
+            self.patch.set_param(param, value, modamt);
+
+            self.skip_ws_nl();
+            if self.eat(')') {
+                return Ok(());
+            }
+            self.expect(',')?;
+            self.skip_ws_nl();
+        }
+    }
+
+    fn expects_number(&self, pos: (usize, usize), param: &ParamId) -> PatchTextError {
+        self.error_at(
+            pos,
+            format!("parameter '{}' of {} expects a number", param.name(), param.node_id().name()),
+        )
+    }
+
+    fn value(&mut self, param: &ParamId) -> Result<SAtom, PatchTextError> {
+        let pos = self.pos();
+
+        match self.peek() {
+            Some('"') => {
+                if !param.is_atom() {
+                    return Err(self.expects_number(pos, param));
+                }
+
+                let s = self.string()?;
+                if let SAtom::AudioSample(_) = param.as_atom_def() {
+                    Ok(SAtom::audio_unloaded(&s))
+                } else {
+                    Ok(SAtom::str(&s))
+                }
+            }
+            Some('[') => {
+                if !param.is_atom() {
+                    return Err(self.expects_number(pos, param));
+                }
+
+                self.next();
+                let mut buf = vec![];
+                loop {
+                    self.skip_ws_nl();
+                    buf.push(self.number()?.0 as f32);
+                    self.skip_ws_nl();
+                    if self.eat(']') {
+                        break;
+                    }
+                    self.expect(',')?;
+                }
+
+                if buf.len() != 8 {
+                    return Err(self.error_at(pos, "a micro sample needs 8 numbers".to_string()));
+                }
+                Ok(SAtom::micro(&buf))
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let (v, is_int) = self.number()?;
+
+                if param.is_atom() && is_int {
+                    Ok(SAtom::setting(v as i64))
+                } else {
+                    Ok(SAtom::param(v as f32))
+                }
+            }
+            _ => Err(self.error(format!("expected a value, found {}", self.found()))),
+        }
+    }
+
+    fn connect(&mut self, from: &Endpoint, to: &Endpoint) -> Result<(), PatchTextError> {
+        let from_out = match &from.port {
+            Some((out, pos)) if !from.is_target => from.node_id.out(out).ok_or_else(|| {
+                self.error_at(*pos, format!("unknown output '{}' of {}", out, from.node_id.name()))
+            })?,
+            _ => {
+                if from.node_id.out_name_by_idx(0).is_none() {
+                    return Err(
+                        self.error_at(from.pos, format!("{} has no output", from.node_id.name()))
+                    );
+                }
+                0
+            }
+        };
+
+        let (to_input, pos) = match &to.port {
+            Some((inp, pos)) => (
+                to.node_id.inp(inp).ok_or_else(|| {
+                    self.error_at(*pos, format!("unknown input '{}' of {}", inp, to.node_id.name()))
+                })?,
+                *pos,
+            ),
+            None => {
+                if to.node_id.inp_name_by_idx(0).is_none() {
+                    return Err(
+                        self.error_at(to.pos, format!("{} has no input", to.node_id.name()))
+                    );
+                }
+                (0, to.pos)
+            }
+        };
+
+        if self.patch.edges.iter().any(|e| e.to == to.node_id && e.to_input == to_input) {
+            return Err(self.error_at(
+                pos,
+                format!(
+                    "input '{}' of {} is already connected",
+                    to.node_id.inp_name_by_idx(to_input).unwrap_or("?"),
+                    to.node_id.name()
+                ),
+            ));
+        }
+
+        self.patch.edges.push(GraphEdge { from: from.node_id, from_out, to: to.node_id, to_input });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_patch_text_parse() {
+        let patch = PatchText::parse(
+            "# A comment\n\
+             sin(freq=220) -> amp(gain=0.5) -> out.ch2 # trailing\n\
+             sin:1(det=0.1~-0.5, freq=110) ->\n    amp.att; mix3(ch1=0.5)\n",
+        )
+        .unwrap();
+
+        let ids: Vec<NodeId> = patch.nodes().iter().map(|n| n.node_id).collect();
+        assert_eq!(
+            ids,
+            vec![NodeId::Sin(0), NodeId::Amp(0), NodeId::Out(0), NodeId::Sin(1), NodeId::Mix3(0)]
+        );
+
+        let amp = NodeId::Amp(0);
+        assert_eq!(patch.edges()[1].to_input, NodeId::Out(0).inp("ch2").unwrap());
+        assert_eq!(patch.edges()[2].to, amp);
+        assert_eq!(patch.edges()[2].to_input, amp.inp("att").unwrap());
+
+        let det = &patch.nodes()[3].params[0];
+        assert_eq!(det.param, NodeId::Sin(1).inp_param("det").unwrap());
+        assert_eq!(det.value, SAtom::param(0.1));
+        assert_eq!(det.modamt, Some(-0.5));
+    }
+
+    #[test]
+    fn check_patch_text_atoms() {
+        let patch = PatchText::parse("bosc(wtype=2)\nsampl(sample=\"a \\\"b\\\".wav\")").unwrap();
+
+        assert_eq!(patch.nodes()[0].params[0].value, SAtom::setting(2));
+        assert_eq!(patch.nodes()[1].params[0].value, SAtom::audio_unloaded("a \"b\".wav"));
+
+        let text = patch.to_string();
+        assert_eq!(text, "bosc(wtype=2)\nsampl(sample=\"a \\\"b\\\".wav\")\n");
+        assert_eq!(PatchText::parse(&text).unwrap(), patch);
+    }
+
+    #[test]
+    fn check_patch_text_print() {
+        let src = "sin:2(freq=330.5~0.25)\namp\nout\n\nsin:2 -> amp.att\namp -> out.ch2\n";
+        let patch = PatchText::parse(src).unwrap();
+        assert_eq!(patch.to_string(), src);
+
+        assert_eq!(format_num(219.99998), "220");
+        assert_eq!(format_num(-0.00012345678), "-0.000123457");
+        assert_eq!(format_num(0.5), "0.5");
+    }
+
+    fn err(src: &str) -> String {
+        PatchText::parse(src).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn check_patch_text_errors() {
+        assert_eq!(err("sin -> foo"), "1:8: unknown node 'foo'");
+        assert_eq!(err("sin.foo -> amp"), "1:5: unknown output 'foo' of sin");
+        assert_eq!(err("\n  sin(fre=1)"), "2:7: unknown parameter 'fre' of sin");
+        assert_eq!(err("sin(freq=\"x\")"), "1:10: parameter 'freq' of sin expects a number");
+        assert_eq!(err("sin(freq=1 det=2)"), "1:12: expected ',', found 'd'");
+        assert_eq!(
+            err("sin -> amp -> out\nsin:1 -> out"),
+            "2:10: input 'ch1' of out is already connected"
+        );
+        assert_eq!(err("out -> amp"), "1:1: out has no output");
+        assert_eq!(err("sin amp"), "1:5: expected '->', ';' or end of line, found 'a'");
+        assert_eq!(err("sin ->"), "1:7: expected a node name, found end of text");
+        assert_eq!(err("sin:x"), "1:5: expected a node instance, found 'x'");
+        assert_eq!(err("sin(freq=1.2.3)"), "1:10: invalid number '1.2.3'");
+        assert_eq!(err("bosc(wtype=1~0.5)"), "1:13: atom 'wtype' has no modulation amount");
+    }
+}
@@ -2,12 +2,17 @@
 // This file is a part of HexoDSP. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
-const SMOOTHING_TIME_MS: f32 = 10.0;
+use crate::dsp::Smoothing;
 
+/// The remaining distance to the target is about 1% after an exponential ramp.
+const EXP_SMOOTHING_DECAY: f32 = 4.6;
+
+#[derive(Debug, Clone, Copy)]
 pub struct Smoother {
-    slope_samples: usize,
+    srate: f32,
     value: f32,
     inc: f32,
+    coef: f32,
     target: f32,
     count: usize,
     done: bool,
@@ -15,11 +20,11 @@ pub struct Smoother {
 
 impl Smoother {
     pub fn new() -> Self {
-        Self { slope_samples: 0, value: 0.0, inc: 0.0, count: 0, target: 0.0, done: true }
+        Self { srate: 44100.0, value: 0.0, inc: 0.0, coef: 0.0, count: 0, target: 0.0, done: true }
     }
 
     pub fn set_sample_rate(&mut self, sr: f32) {
-        self.slope_samples = ((sr * SMOOTHING_TIME_MS) / 1000.0).ceil() as usize;
+        self.srate = sr;
     }
 
     #[inline]
@@ -28,18 +33,42 @@ impl Smoother {
     }
 
     #[inline]
-    #[allow(dead_code)]
     pub fn stop(&mut self) {
         self.done = true;
     }
 
     #[inline]
-    pub fn set(&mut self, current: f32, target: f32) {
+    fn ramp_samples(&self, ms: f32) -> usize {
+        ((self.srate * ms.max(0.0)) / 1000.0).ceil() as usize
+    }
+
+    /// Starts smoothing from `current` to `target`. With [Smoothing::None]
+    /// the next call to [Smoother::next] already returns the target.
+    #[inline]
+    pub fn set(&mut self, current: f32, target: f32, smoothing: Smoothing) {
         self.value = current;
-        self.count = self.slope_samples;
-        self.inc = (target - current) / (self.count as f32);
         self.target = target;
         self.done = false;
+        self.inc = 0.0;
+        self.coef = 0.0;
+
+        match smoothing {
+            Smoothing::None => {
+                self.count = 0;
+            }
+            Smoothing::Linear(ms) => {
+                self.count = self.ramp_samples(ms);
+                if self.count > 0 {
+                    self.inc = (target - current) / (self.count as f32);
+                }
+            }
+            Smoothing::Exp(ms) => {
+                self.count = self.ramp_samples(ms);
+                if self.count > 0 {
+                    self.coef = (-EXP_SMOOTHING_DECAY / (self.count as f32)).exp();
+                }
+            }
+        }
     }
 
     #[inline]
@@ -53,7 +82,11 @@ impl Smoother {
 
             self.target
         } else {
-            self.value += self.inc;
+            if self.coef > 0.0 {
+                self.value = self.target + (self.value - self.target) * self.coef;
+            } else {
+                self.value += self.inc;
+            }
             self.count -= 1;
             self.value
         }
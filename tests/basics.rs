@@ -1190,3 +1190,99 @@ fn check_matrix_tseq_perf() {
         prev = dur.as_millis() as i64;
     }
 }
+
+fn process_period(
+    node_exec: &mut NodeExecutor,
+    nframes: usize,
+    events: &[MidiEvent],
+) -> (Vec<f32>, Vec<f32>) {
+    let input = vec![0.0; nframes];
+    let mut out_l = vec![0.0; nframes];
+    let mut out_r = vec![0.0; nframes];
+
+    let mut context = hexodsp::Context {
+        nframes,
+        output: &mut [&mut out_l[..], &mut out_r[..]],
+        input: &[&input[..]],
+        events,
+        transport: None,
+    };
+    node_exec.process(&mut context);
+
+    (out_l, out_r)
+}
+
+#[test]
+fn check_process_large_period() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sin", "sig").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    let (node_conf2, mut node_exec2) = new_node_engine();
+    let mut matrix2 = Matrix::new(node_conf2, 3, 3);
+    chain.place(&mut matrix2, 0, 0).unwrap();
+    matrix2.sync().unwrap();
+
+    let (blocks_l, _) = node_exec.test_run(1000.0 / 44100.0, false);
+
+    node_exec2.set_sample_rate(44100.0);
+    node_exec2.process_graph_updates();
+    let (period_l, _) = process_period(&mut node_exec2, 1000, &[]);
+
+    assert_eq!(blocks_l.len(), 1000);
+    assert_vec_feq!(blocks_l, period_l);
+}
+
+#[test]
+fn check_process_large_period_events() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midiin", "gate").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    node_exec.set_sample_rate(44100.0);
+    node_exec.process_graph_updates();
+
+    let events = [
+        MidiEvent::new(300, 0, MidiEventKind::NoteOn { note: 69, vel: 1.0 }),
+        MidiEvent::new(700, 0, MidiEventKind::NoteOff { note: 69 }),
+    ];
+    let (out_l, _) = process_period(&mut node_exec, 1024, &events[..]);
+    assert_eq!(collect_gates(&out_l[..]), vec![(300, 400)]);
+}
+
+#[test]
+fn check_process_large_period_event_overflow() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midiin", "gate").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    node_exec.set_sample_rate(44100.0);
+    node_exec.process_graph_updates();
+
+    let max_events = hexodsp::nodes::MAX_PERIOD_EVENTS;
+    let cc = MidiEvent::new(0, 0, MidiEventKind::CC { cc: 1, value: 0.5 });
+    let mut events = vec![MidiEvent::new(5, 0, MidiEventKind::NoteOn { note: 69, vel: 1.0 })];
+    events.extend(std::iter::repeat(MidiEvent { frame: 10, ..cc }).take(max_events + 100));
+    events.push(MidiEvent::new(20, 0, MidiEventKind::NoteOff { note: 69 }));
+    events.push(MidiEvent::new(30, 0, MidiEventKind::NoteOn { note: 70, vel: 1.0 }));
+    events.extend(std::iter::repeat(MidiEvent { frame: 1023, ..cc }).take(max_events));
+    events.push(MidiEvent::new(1023, 0, MidiEventKind::NoteOff { note: 70 }));
+
+    // The note off after the overflowing events is not lost:
+    let (out_l, _) = process_period(&mut node_exec, 1024, &events[..]);
+    assert_eq!(collect_gates(&out_l[..]), vec![(5, 15)]);
+    assert!(out_l[1023] > 0.5);
+
+    // The events that didn't fit into the last frame are carried over:
+    let (out_l, _) = process_period(&mut node_exec, 64, &[]);
+    assert_eq!(max_abs(&out_l), 0.0);
+}
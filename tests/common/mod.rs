@@ -5,6 +5,10 @@
 pub use hexodsp::dsp::*;
 pub use hexodsp::matrix::*;
 pub use hexodsp::nodes::new_node_engine;
+pub use hexodsp::nodes::{
+    DspLoad, MidiEvent, MidiEventKind, NodeEngineBuilder, NodeState, ParamEventMode, SignalFault,
+    Transport,
+};
 pub use hexodsp::MatrixCellChain;
 pub use hexodsp::NodeExecutor;
 
@@ -393,6 +397,32 @@ macro_rules! assert_minmax_of_rms {
     };
 }
 
+#[allow(unused)]
+pub fn max_abs(buf: &[f32]) -> f32 {
+    buf.iter().fold(0.0_f32, |m, s| m.max(s.abs()))
+}
+
+/// Places a `sin -> out` chain at 0/0 and syncs the matrix.
+#[allow(unused)]
+pub fn setup_sin_out(matrix: &mut Matrix) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sin", "sig").node_inp("out", "ch1").place(matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+}
+
+/// Creates a 3x3 matrix with only an `Out` node at 0/0.
+#[allow(unused)]
+pub fn setup_out_matrix() -> (Matrix, NodeExecutor) {
+    let (node_conf, node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let out = NodeId::Out(0);
+    matrix.place(0, 0, Cell::empty(out));
+    matrix.sync().unwrap();
+
+    (matrix, node_exec)
+}
+
 #[allow(unused)]
 pub fn wait_params_smooth(ne: &mut NodeExecutor) {
     run_for_ms(ne, 15.0);
@@ -426,6 +456,21 @@ pub fn node_pset_n(matrix: &mut Matrix, node: &str, instance: usize, parm: &str,
     matrix.set_param(p, SAtom::param(v_norm));
 }
 
+/// Schedules a parameter event at the sample offset `frame`, relative
+/// to the start of the next run of the [NodeExecutor].
+#[allow(unused)]
+pub fn pset_n_at(
+    matrix: &mut Matrix,
+    nid: NodeId,
+    parm: &str,
+    frame: usize,
+    v_norm: f32,
+    mode: ParamEventMode,
+) {
+    let p = nid.inp_param(parm).expect("param exists");
+    matrix.set_param_at(p, v_norm, frame, mode);
+}
+
 #[allow(unused)]
 pub fn pset_d(matrix: &mut Matrix, nid: NodeId, parm: &str, v_denorm: f32) {
     let p = nid.inp_param(parm).expect("param exists");
@@ -513,6 +558,23 @@ pub fn run_for_ms(node_exec: &mut hexodsp::nodes::NodeExecutor, ms: f32) -> (Vec
     run_realtime_no_input(node_exec, ms / 1000.0, false)
 }
 
+#[allow(dead_code)]
+pub fn run_for_ms_with_events(
+    node_exec: &mut hexodsp::nodes::NodeExecutor,
+    ms: f32,
+    events: &[MidiEvent],
+) -> (Vec<f32>, Vec<f32>) {
+    node_exec.test_run_with_events(ms / 1000.0, false, events)
+}
+
+#[allow(dead_code)]
+pub fn run_with_input(
+    node_exec: &mut hexodsp::nodes::NodeExecutor,
+    input: &[&[f32]],
+) -> (Vec<f32>, Vec<f32>) {
+    node_exec.test_run_input(input, false, &[])
+}
+
 pub fn run_realtime_no_input(
     node_exec: &mut hexodsp::nodes::NodeExecutor,
     seconds: f32,
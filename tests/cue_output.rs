@@ -0,0 +1,74 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_sin_amp_out(matrix: &mut Matrix) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        // Silences the Amp, so that only the cue lets the sine through:
+        .set_denorm("gain", 0.0)
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+}
+
+#[test]
+fn check_cue_output_main() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix);
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert_eq!(max_abs(&out_l), 0.0);
+
+    let sin = NodeId::Sin(0);
+    matrix.cue_output(sin, sin.out("sig").unwrap(), 0);
+    assert_eq!(matrix.cued_output(), Some((sin, 0, 0)));
+
+    let (out_l, out_r) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+    assert_eq!(out_l, out_r);
+
+    // The cue is kept for new programs:
+    matrix.sync().unwrap();
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+
+    matrix.stop_cue();
+    assert_eq!(matrix.cued_output(), None);
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert_eq!(max_abs(&out_l), 0.0);
+}
+
+#[test]
+fn check_cue_output_mutes_out() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix);
+    pset_d(matrix, NodeId::Amp(0), "gain", 1.0);
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+
+    // The test context has no cue channels, the Out node is still muted:
+    let sin = NodeId::Sin(0);
+    matrix.cue_output(sin, sin.out("sig").unwrap(), 2);
+    let (out_l, out_r) = run_for_ms(node_exec, 20.0);
+    assert_eq!(max_abs(&out_l), 0.0);
+    assert_eq!(max_abs(&out_r), 0.0);
+
+    // A node that is not part of the program is silent:
+    matrix.cue_output(NodeId::Sin(5), 0, 0);
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert_eq!(max_abs(&out_l), 0.0);
+
+    matrix.clear();
+    assert_eq!(matrix.cued_output(), None);
+}
@@ -0,0 +1,47 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+#[test]
+fn check_dsp_load_disabled() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+
+    run_for_ms(node_exec, 50.0);
+
+    assert_eq!(matrix.dsp_load_for(&NodeId::Sin(0)), Some(DspLoad::default()));
+    assert_eq!(matrix.graph_dsp_load(), DspLoad::default());
+    assert_eq!(matrix.dsp_load_for(&NodeId::Sin(1)), None);
+}
+
+#[test]
+fn check_dsp_load_measured() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+
+    matrix.set_dsp_profiling(true);
+    run_for_ms(node_exec, 100.0);
+
+    let sin = matrix.dsp_load_for(&NodeId::Sin(0)).unwrap();
+    assert!(sin.avg_ns > 0.0, "{:?}", sin);
+    assert!(sin.peak_ns >= sin.avg_ns, "{:?}", sin);
+    assert!(sin.load > 0.0, "{:?}", sin);
+
+    let graph = matrix.graph_dsp_load();
+    assert!(graph.avg_ns >= sin.avg_ns, "graph={:?} sin={:?}", graph, sin);
+    assert!(graph.peak_ns >= sin.peak_ns, "graph={:?} sin={:?}", graph, sin);
+
+    assert_eq!(matrix.dsp_load_at(0, 0), Some(sin));
+    assert_eq!(matrix.dsp_load_at(2, 2), None);
+
+    matrix.reset_dsp_load_peaks();
+    assert_eq!(matrix.dsp_load_for(&NodeId::Sin(0)).unwrap().peak_ns, 0.0);
+    assert_eq!(matrix.graph_dsp_load().peak_ns, 0.0);
+
+    matrix.set_dsp_profiling(false);
+    run_for_ms(node_exec, 10.0);
+    assert_eq!(matrix.graph_dsp_load(), DspLoad::default());
+}
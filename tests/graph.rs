@@ -0,0 +1,183 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::graph::{GraphEdge, GraphError};
+use hexodsp::Graph;
+
+fn setup_sin_amp_out(graph: &mut Graph) {
+    graph.connect(NodeId::Sin(0), "sig", NodeId::Amp(0), "inp").unwrap();
+    graph.connect(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1").unwrap();
+    graph.set_denorm(NodeId::Amp(0), "att", 0.5).unwrap();
+}
+
+#[test]
+fn check_graph_sync() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut graph = Graph::new(node_conf);
+    setup_sin_amp_out(&mut graph);
+
+    assert_eq!(graph.nodes(), &[NodeId::Sin(0), NodeId::Amp(0), NodeId::Out(0)]);
+    assert_eq!(graph.order().unwrap(), vec![NodeId::Sin(0), NodeId::Amp(0), NodeId::Out(0)]);
+    assert!(graph.is_dirty());
+    assert!(graph.sync().unwrap());
+    assert!(!graph.is_dirty());
+    assert!(!graph.sync().unwrap());
+
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.4 && max < 0.6, "max={}", max);
+
+    // Parameters don't need a sync:
+    graph.set_denorm(NodeId::Amp(0), "att", 1.0).unwrap();
+    assert_float_eq!(graph.get_denorm(NodeId::Amp(0), "att").unwrap(), 1.0);
+    assert!(!graph.is_dirty());
+    wait_params_smooth(&mut node_exec);
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    assert!(max_abs(&out_l) > 0.9);
+
+    // Adding a modulation amount requires a new program:
+    graph.set_modamt(NodeId::Sin(0), "freq", Some(0.1)).unwrap();
+    assert_eq!(graph.get_modamt(NodeId::Sin(0), "freq"), Some(0.1));
+    assert!(graph.is_dirty());
+    assert!(graph.sync().unwrap());
+
+    // Hole filler instances are created for higher instances:
+    graph.connect(NodeId::Sin(3), "sig", NodeId::Out(0), "ch2").unwrap();
+    assert!(graph.sync().unwrap());
+    assert!(graph.config().unique_index_for(&NodeId::Sin(2)).is_some());
+    let (_, out_r) = run_for_ms(&mut node_exec, 20.0);
+    assert!(max_abs(&out_r) > 0.9);
+}
+
+#[test]
+fn check_graph_disconnect() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut graph = Graph::new(node_conf);
+    setup_sin_amp_out(&mut graph);
+    graph.sync().unwrap();
+
+    assert_eq!(graph.input_source(NodeId::Out(0), "ch1"), Some((NodeId::Amp(0), 0)));
+    assert_eq!(graph.disconnect(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1"), Ok(true));
+    assert_eq!(graph.disconnect(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1"), Ok(false));
+    assert_eq!(graph.input_source(NodeId::Out(0), "ch1"), None);
+    assert!(graph.sync().unwrap());
+
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    assert_eq!(max_abs(&out_l[out_l.len() - 100..]), 0.0);
+
+    graph.connect(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1").unwrap();
+    assert!(graph.remove_node(NodeId::Sin(0)));
+    assert!(!graph.remove_node(NodeId::Sin(0)));
+    assert_eq!(
+        graph.edges(),
+        &[GraphEdge { from: NodeId::Amp(0), from_out: 0, to: NodeId::Out(0), to_input: 0 }]
+    );
+    assert_eq!(graph.new_node(NodeId::Amp(0)), Ok(NodeId::Amp(1)));
+    assert!(graph.sync().unwrap());
+
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    assert_eq!(max_abs(&out_l[out_l.len() - 100..]), 0.0);
+}
+
+#[test]
+fn check_graph_errors() {
+    let (node_conf, _node_exec) = new_node_engine();
+    let mut graph = Graph::new(node_conf);
+    setup_sin_amp_out(&mut graph);
+
+    assert_eq!(
+        graph.connect(NodeId::Sin(0), "foo", NodeId::Out(0), "ch2"),
+        Err(GraphError::UnknownOutput(NodeId::Sin(0), "foo".to_string()))
+    );
+    assert_eq!(
+        graph.connect(NodeId::Sin(0), "sig", NodeId::Out(0), "foo"),
+        Err(GraphError::UnknownInput(NodeId::Out(0), "foo".to_string()))
+    );
+    assert_eq!(
+        graph.connect(NodeId::Sin(1), "sig", NodeId::Out(0), "ch1"),
+        Err(GraphError::InputConnected {
+            to: (NodeId::Out(0), "ch1".to_string()),
+            from: (NodeId::Amp(0), "sig".to_string()),
+        })
+    );
+    assert_eq!(
+        graph.connect(NodeId::Amp(0), "sig", NodeId::Sin(0), "freq"),
+        Err(GraphError::CycleDetected)
+    );
+    assert_eq!(
+        graph.set_denorm(NodeId::Amp(0), "foo", 1.0),
+        Err(GraphError::UnknownParam(NodeId::Amp(0), "foo".to_string()))
+    );
+
+    // Nothing was added by the failed calls:
+    assert_eq!(graph.nodes().len(), 3);
+    assert_eq!(graph.edges().len(), 2);
+
+    // A bus from the Amp back to the Sin closes a cycle too:
+    graph.connect(NodeId::BusRecv(0), "sig", NodeId::Sin(0), "freq").unwrap();
+    assert_eq!(
+        graph.connect(NodeId::Amp(0), "sig", NodeId::BusSend(0), "inp"),
+        Err(GraphError::CycleDetected)
+    );
+    graph.set_atom(NodeId::BusRecv(0), "bus", SAtom::setting(1)).unwrap();
+    graph.connect(NodeId::Amp(0), "sig", NodeId::BusSend(0), "inp").unwrap();
+    assert_eq!(graph.sync(), Ok(true));
+
+    graph.set_atom(NodeId::BusRecv(0), "bus", SAtom::setting(0)).unwrap();
+    assert!(graph.is_dirty());
+    assert_eq!(graph.sync(), Err(GraphError::CycleDetected));
+}
+
+#[test]
+fn check_graph_serialize() {
+    let (node_conf, _node_exec) = new_node_engine();
+    let mut graph = Graph::new(node_conf);
+    setup_sin_amp_out(&mut graph);
+    graph.set_modamt(NodeId::Sin(0), "freq", Some(0.25)).unwrap();
+    graph.connect(NodeId::BOsc(0), "sig", NodeId::Out(0), "ch2").unwrap();
+    graph.set_atom(NodeId::BOsc(0), "wtype", SAtom::setting(2)).unwrap();
+    graph.sync().unwrap();
+
+    let s = graph.serialize();
+
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut graph2 = Graph::new(node_conf);
+    graph2.load(&s).unwrap();
+    assert_eq!(graph2.nodes(), graph.nodes());
+    assert_eq!(graph2.edges(), graph.edges());
+    assert_float_eq!(graph2.get_denorm(NodeId::Amp(0), "att").unwrap(), 0.5);
+    assert_eq!(graph2.get_modamt(NodeId::Sin(0), "freq"), Some(0.25));
+    assert_eq!(graph2.get_atom(NodeId::BOsc(0), "wtype").unwrap().i(), 2);
+    assert!(graph2.sync().unwrap());
+
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.4 && max < 0.6, "max={}", max);
+
+    assert!(matches!(graph2.load("{"), Err(GraphError::Deserialization(_))));
+    assert_eq!(
+        graph2.load(r#"{"nodes":[["foo",0]]}"#),
+        Err(GraphError::UnknownNode("foo".to_string()))
+    );
+    assert_eq!(
+        graph2.load(r#"{"nodes":[["sin",300]]}"#),
+        Err(GraphError::InvalidInstance("sin".to_string(), 300))
+    );
+    assert_eq!(graph2.nodes().len(), 4);
+}
+
+#[test]
+fn check_graph_new_node() {
+    let (node_conf, _node_exec) = new_node_engine();
+    let mut graph = Graph::new(node_conf);
+
+    assert_eq!(graph.new_node(NodeId::Sin(254)), Ok(NodeId::Sin(254)));
+    assert_eq!(graph.new_node(NodeId::Sin(254)), Ok(NodeId::Sin(255)));
+    assert_eq!(graph.new_node(NodeId::Sin(254)), Err(GraphError::NoFreeInstance(NodeId::Sin(254))));
+    assert_eq!(graph.new_node(NodeId::Sin(0)), Ok(NodeId::Sin(0)));
+    assert_eq!(graph.nodes().len(), 3);
+}
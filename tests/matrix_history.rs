@@ -0,0 +1,223 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::dsp::tracker::UIPatternModel;
+
+// Rounded, as the snapshots store the denormalized values:
+fn freq(matrix: &Matrix) -> f32 {
+    let p = NodeId::Sin(0).inp_param("freq").unwrap();
+    (p.denorm(matrix.get_param(&p).unwrap().f()) * 100.0).round() / 100.0
+}
+
+#[test]
+fn check_matrix_history_place() {
+    init_test!(matrix, node_exec, 3);
+    assert!(!matrix.can_undo());
+
+    setup_sin_out(matrix);
+    assert!(matrix.can_undo());
+    assert!(!matrix.can_redo());
+
+    // The chain is placed in one step:
+    assert!(matrix.undo().unwrap());
+    assert!(matrix.get(0, 0).unwrap().is_empty());
+    assert!(matrix.get(0, 1).unwrap().is_empty());
+    assert!(!matrix.can_undo());
+    assert!(!matrix.undo().unwrap());
+
+    assert!(matrix.redo().unwrap());
+    assert_eq!(matrix.get(0, 0).unwrap().node_id(), NodeId::Sin(0));
+    assert_eq!(matrix.get(0, 1).unwrap().node_id(), NodeId::Out(0));
+    assert!(!matrix.redo().unwrap());
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert!(out_l.iter().any(|s| s.abs() > 0.5));
+}
+
+#[test]
+fn check_matrix_history_params() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    matrix.clear_history();
+
+    // Consecutive edits of the same parameter are one step:
+    pset_d(matrix, NodeId::Sin(0), "freq", 220.0);
+    pset_d(matrix, NodeId::Sin(0), "freq", 110.0);
+    matrix.set_prop("test", SAtom::setting(1));
+    matrix.set_node_state(NodeId::Sin(0), NodeState::Mute);
+    let p = NodeId::Sin(0).inp_param("freq").unwrap();
+    matrix.set_param_modamt(p, Some(0.5)).unwrap();
+
+    assert!(matrix.undo().unwrap());
+    assert_eq!(matrix.get_param_modamt(&p), None);
+    assert!(matrix.undo().unwrap());
+    assert_eq!(matrix.node_state(&NodeId::Sin(0)), NodeState::Active);
+    assert!(matrix.undo().unwrap());
+    assert!(matrix.get_prop("test").is_none());
+    assert!(matrix.undo().unwrap());
+    assert_float_eq!(freq(matrix), 440.0);
+    assert!(!matrix.can_undo());
+
+    assert!(matrix.redo().unwrap());
+    assert_float_eq!(freq(matrix), 110.0);
+
+    // A new edit drops the redo steps, and is not merged
+    // into the step that was redone:
+    pset_d(matrix, NodeId::Sin(0), "freq", 880.0);
+    assert!(!matrix.can_redo());
+    assert!(matrix.undo().unwrap());
+    assert_float_eq!(freq(matrix), 110.0);
+
+    run_for_ms(node_exec, 10.0);
+}
+
+#[test]
+fn check_matrix_history_transaction() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    matrix.clear_history();
+
+    matrix.transaction(|matrix| {
+        for f in [100.0, 200.0, 300.0] {
+            pset_d(matrix, NodeId::Sin(0), "freq", f);
+        }
+
+        let pat = matrix.get_pattern_data(0).unwrap();
+        pat.lock().unwrap().set_cell_value(0, 0, 0x100);
+    });
+
+    assert_float_eq!(freq(matrix), 300.0);
+    assert!(matrix.undo().unwrap());
+    assert_float_eq!(freq(matrix), 440.0);
+    assert!(matrix.get_pattern_data(0).unwrap().lock().unwrap().is_unset());
+    assert!(!matrix.can_undo());
+
+    assert!(matrix.redo().unwrap());
+    assert_float_eq!(freq(matrix), 300.0);
+    assert_eq!(matrix.get_pattern_data(0).unwrap().lock().unwrap().get_cell_value(0, 0), 0x100);
+
+    // Empty transactions, transactions that end with the old values
+    // and failed matrix changes don't create undo steps:
+    matrix.clear_history();
+    matrix.transaction(|_| {});
+    matrix.transaction(|matrix| {
+        pset_d(matrix, NodeId::Sin(0), "freq", 100.0);
+        pset_d(matrix, NodeId::Sin(0), "freq", 300.0);
+
+        let pat = matrix.get_pattern_data(0).unwrap();
+        pat.lock().unwrap().set_cell_value(0, 0, 0x200);
+        pat.lock().unwrap().set_cell_value(0, 0, 0x100);
+    });
+    let res = matrix.change_matrix(|matrix| {
+        matrix.place(1, 1, Cell::empty(NodeId::Sin(1)).input(Some(0), None, None));
+        matrix.place(1, 0, Cell::empty(NodeId::Sin(1)).out(None, None, Some(0)));
+    });
+    assert!(res.is_err());
+    assert!(!matrix.can_undo());
+
+    run_for_ms(node_exec, 10.0);
+}
+
+#[test]
+fn check_matrix_history_keeps_nodes() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    matrix.clear_history();
+
+    let (mut out_l, _) = run_for_ms(node_exec, 10.0);
+
+    // Parameter steps are undone without recreating the nodes,
+    // so the Sin continues without a jump back to phase 0:
+    matrix.set_prop("test", SAtom::setting(1));
+    pset_d(matrix, NodeId::Sin(0), "freq", 880.0);
+    assert!(matrix.undo().unwrap());
+    assert!(matrix.undo().unwrap());
+    assert!(matrix.get_prop("test").is_none());
+
+    out_l.extend(run_for_ms(node_exec, 10.0).0);
+    let max_step = out_l.windows(2).map(|w| (w[1] - w[0]).abs()).fold(0.0, f32::max);
+    assert!(max_step < 0.1, "max_step={}", max_step);
+}
+
+#[test]
+fn check_matrix_history_pattern_outside_transaction() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    matrix.clear_history();
+
+    // Pattern edits outside of a transaction are not recorded,
+    // so undoing other steps keeps them:
+    pset_d(matrix, NodeId::Sin(0), "freq", 220.0);
+    let pat = matrix.get_pattern_data(0).unwrap();
+    pat.lock().unwrap().set_cell_value(0, 0, 0x100);
+
+    assert!(matrix.undo().unwrap());
+    assert_float_eq!(freq(matrix), 440.0);
+    assert_eq!(matrix.get_pattern_data(0).unwrap().lock().unwrap().get_cell_value(0, 0), 0x100);
+
+    // Also when the step changed the grid:
+    matrix.place(2, 2, Cell::empty(NodeId::Sin(1)));
+    matrix.sync().unwrap();
+    assert!(matrix.undo().unwrap());
+    assert!(matrix.get(2, 2).unwrap().is_empty());
+    assert_eq!(matrix.get_pattern_data(0).unwrap().lock().unwrap().get_cell_value(0, 0), 0x100);
+
+    run_for_ms(node_exec, 10.0);
+}
+
+#[test]
+fn check_matrix_history_limit() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    matrix.clear_history();
+    matrix.set_history_limit(2);
+
+    for f in [100.0, 200.0, 300.0, 400.0] {
+        pset_d(matrix, NodeId::Sin(0), "freq", f);
+        matrix.set_prop("freq", SAtom::param(f));
+    }
+
+    assert!(matrix.undo().unwrap());
+    assert!(matrix.undo().unwrap());
+    assert!(!matrix.undo().unwrap());
+    assert_float_eq!(freq(matrix), 300.0);
+
+    matrix.set_history_limit(0);
+    pset_d(matrix, NodeId::Sin(0), "freq", 500.0);
+    assert!(!matrix.can_undo());
+
+    run_for_ms(node_exec, 10.0);
+}
+
+#[test]
+fn check_matrix_history_resize_clear() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    matrix.clear_history();
+
+    matrix.resize_shifted(6, 5, 2, 1).unwrap();
+    assert_eq!(matrix.get(2, 1).unwrap().node_id(), NodeId::Sin(0));
+
+    pset_d(matrix, NodeId::Sin(0), "freq", 220.0);
+    matrix.clear();
+    assert!(matrix.get(2, 1).unwrap().is_empty());
+
+    assert!(matrix.undo().unwrap());
+    assert_eq!(matrix.size(), (6, 5));
+    assert_eq!(matrix.get(2, 1).unwrap().node_id(), NodeId::Sin(0));
+    assert_float_eq!(freq(matrix), 220.0);
+
+    assert!(matrix.undo().unwrap());
+    assert!(matrix.undo().unwrap());
+    assert_eq!(matrix.size(), (3, 3));
+    assert_eq!(matrix.get(0, 0).unwrap().node_id(), NodeId::Sin(0));
+    assert_float_eq!(freq(matrix), 440.0);
+    assert!(!matrix.can_undo());
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert!(max_abs(&out_l) > 0.5);
+}
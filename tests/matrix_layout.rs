@@ -0,0 +1,136 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::matrix_layout::LayoutError;
+use hexodsp::MatrixLayout;
+
+fn place(matrix: &mut Matrix, layout: &MatrixLayout) -> Vec<Cell> {
+    let cells = layout.layout(matrix).unwrap();
+    matrix.change_matrix_err(|matrix| matrix.place_multiple(&cells)).unwrap();
+    matrix.sync().unwrap();
+    cells
+}
+
+fn count_nodes(cells: &[Cell], name: &str) -> usize {
+    cells.iter().filter(|c| c.node_id().name() == name).count()
+}
+
+#[test]
+fn check_matrix_layout_chain() {
+    init_test!(matrix, node_exec, 5);
+
+    let mut layout = MatrixLayout::new();
+    layout.edge(NodeId::Sin(0), "sig", NodeId::Amp(0), "inp");
+    layout.edge(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1");
+    let cells = place(matrix, &layout);
+
+    // All edges are formed by adjacent cells:
+    assert_eq!(cells.len(), 3);
+    let (ax, ay) = cells[1].pos();
+    assert_eq!(cells[1].node_id(), NodeId::Amp(0));
+    let inputs: Vec<(NodeId, u8)> = [CellDir::T, CellDir::TL, CellDir::BL]
+        .iter()
+        .filter_map(|dir| matrix.get_adjacent_output(ax, ay, *dir))
+        .collect();
+    assert_eq!(inputs, vec![(NodeId::Sin(0), 0)]);
+
+    pset_d(matrix, NodeId::Amp(0), "att", 0.5);
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.4 && max < 0.6, "max={}", max);
+}
+
+#[test]
+fn check_matrix_layout_cycle() {
+    init_test!(matrix, node_exec, 5);
+
+    let mut layout = MatrixLayout::new();
+    layout
+        .edge(NodeId::Sin(0), "sig", NodeId::Amp(0), "inp")
+        .edge(NodeId::Amp(0), "sig", NodeId::Sin(0), "freq")
+        .edge(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1");
+    let cells = place(matrix, &layout);
+
+    // The edge back to the Sin goes through a feedback pair:
+    assert_eq!(cells.len(), 5);
+    assert_eq!(count_nodes(&cells, "fbwr"), 1);
+    assert_eq!(count_nodes(&cells, "fbrd"), 1);
+    let (x, y) = cells[3].pos();
+    assert_eq!(matrix.get(x, y).unwrap().node_id(), NodeId::FbRd(0));
+    assert_eq!(
+        matrix.get_adjacent_output(cells[0].pos().0, cells[0].pos().1, CellDir::T),
+        Some((NodeId::FbRd(0), 0))
+    );
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert!(max_abs(&out_l) > 0.5);
+}
+
+#[test]
+fn check_matrix_layout_fanout() {
+    init_test!(matrix, node_exec, 7);
+
+    // Cells already on the grid are left alone:
+    matrix.place(3, 3, Cell::empty(NodeId::Sin(3)));
+
+    let mut layout = MatrixLayout::new();
+    for i in 0..5 {
+        layout.edge(NodeId::Sin(0), "sig", NodeId::Amp(i), "inp");
+    }
+    layout.edge(NodeId::Amp(0), "sig", NodeId::Out(0), "ch1");
+    let cells = place(matrix, &layout);
+
+    // The Sin has only 3 output edges, the other Amps share one FbWr:
+    assert_eq!(count_nodes(&cells, "fbwr"), 1);
+    assert_eq!(count_nodes(&cells, "fbrd"), 3);
+    assert!(cells.iter().all(|c| c.pos() != (3, 3)));
+    assert_eq!(matrix.get(3, 3).unwrap().node_id(), NodeId::Sin(3));
+
+    // A second layout gets fresh feedback instances:
+    let mut layout = MatrixLayout::new();
+    layout.edge(NodeId::Amp(5), "sig", NodeId::Amp(5), "att");
+    let cells = place(matrix, &layout);
+    assert_eq!(cells[1].node_id(), NodeId::FbWr(1));
+    assert_eq!(cells[2].node_id(), NodeId::FbRd(1));
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert!(max_abs(&out_l) > 0.5);
+}
+
+#[test]
+fn check_matrix_layout_errors() {
+    init_test!(matrix, node_exec, 3);
+
+    let mut layout = MatrixLayout::new();
+    layout.edge(NodeId::Sin(0), "foo", NodeId::Out(0), "ch1");
+    assert_eq!(
+        layout.layout(matrix),
+        Err(LayoutError::UnknownOutput(NodeId::Sin(0), "foo".to_string()))
+    );
+
+    let mut layout = MatrixLayout::new();
+    layout.edge(NodeId::Sin(0), "sig", NodeId::Out(0), "ch1");
+    layout.edge(NodeId::Sin(1), "sig", NodeId::Out(0), "ch1");
+    assert_eq!(
+        layout.layout(matrix),
+        Err(LayoutError::DuplicatedInput(NodeId::Out(0), "ch1".to_string()))
+    );
+
+    let mut layout = MatrixLayout::new();
+    for (i, inp) in ["ch1", "ch2", "ch3", "gain1"].iter().enumerate() {
+        layout.edge(NodeId::Sin(i as u8), "sig", NodeId::Mix3(0), inp);
+    }
+    assert_eq!(layout.layout(matrix), Err(LayoutError::TooManyInputs(NodeId::Mix3(0))));
+
+    let mut layout = MatrixLayout::new();
+    for i in 0..10 {
+        layout.node(NodeId::Sin(i));
+    }
+    assert_eq!(layout.layout(matrix), Err(LayoutError::NoSpace(NodeId::Sin(9))));
+
+    run_for_ms(node_exec, 10.0);
+}
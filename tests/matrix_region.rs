@@ -0,0 +1,154 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_sin_amp_out(matrix: &mut Matrix, x: usize, y: usize) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        .set_denorm("att", 0.5)
+        .node_inp("out", "ch1")
+        .place(matrix, x, y)
+        .unwrap();
+    matrix.sync().unwrap();
+}
+
+#[test]
+fn check_matrix_region_copy_paste() {
+    init_test!(matrix, node_exec, 5);
+    setup_sin_amp_out(matrix, 0, 0);
+
+    let freq = NodeId::Sin(0).inp_param("freq").unwrap();
+    matrix.set_param_modamt(freq, Some(0.25)).unwrap();
+    matrix.clear_history();
+
+    let region = matrix.copy_region(0, 0, RegionShape::Rect { w: 1, h: 2 });
+    assert_eq!(region.len(), 2);
+    assert_eq!(region.node_ids(), vec![NodeId::Sin(0), NodeId::Amp(0)]);
+
+    // Pasted at an odd column, which shifts the cells half a row down:
+    matrix.paste_region(3, 1, &region).unwrap();
+    assert_eq!(matrix.get(3, 1).unwrap().node_id(), NodeId::Sin(1));
+    assert_eq!(matrix.get(3, 2).unwrap().node_id(), NodeId::Amp(1));
+    assert_eq!(matrix.get_adjacent_output(3, 2, CellDir::T), Some((NodeId::Sin(1), 0)));
+
+    let att = NodeId::Amp(1).inp_param("att").unwrap();
+    assert_float_eq!(att.denorm(matrix.get_param(&att).unwrap().f()), 0.5);
+    let freq = NodeId::Sin(1).inp_param("freq").unwrap();
+    assert_eq!(matrix.get_param_modamt(&freq), Some(0.25));
+
+    // A second paste gets new instances again:
+    matrix.paste_region(4, 0, &region).unwrap();
+    assert_eq!(matrix.get(4, 0).unwrap().node_id(), NodeId::Sin(2));
+    assert_eq!(matrix.get(4, 1).unwrap().node_id(), NodeId::Amp(2));
+
+    assert!(matrix.undo().unwrap());
+    assert!(matrix.get(4, 0).unwrap().is_empty());
+    assert_eq!(matrix.get(3, 1).unwrap().node_id(), NodeId::Sin(1));
+    assert!(matrix.undo().unwrap());
+    assert!(matrix.get(3, 1).unwrap().is_empty());
+    assert!(!matrix.can_undo());
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.4 && max < 0.6, "max={}", max);
+}
+
+#[test]
+fn check_matrix_region_paste_errors() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix, 0, 0);
+    matrix.clear_history();
+
+    let region = matrix.copy_region(0, 0, RegionShape::Rect { w: 1, h: 2 });
+
+    assert_eq!(
+        matrix.paste_region(0, 1, &region),
+        Err(MatrixError::NonEmptyCell { cell: *matrix.get(0, 1).unwrap() })
+    );
+    assert_eq!(matrix.paste_region(2, 2, &region), Err(MatrixError::PosOutOfRange));
+    assert!(matrix.get(2, 2).unwrap().is_empty());
+    assert!(!matrix.can_undo());
+
+    let empty = matrix.copy_region(2, 0, RegionShape::Hex { radius: 0 });
+    assert!(empty.is_empty());
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert!(max_abs(&out_l) > 0.4);
+}
+
+#[test]
+fn check_matrix_region_move() {
+    init_test!(matrix, node_exec, 5);
+    setup_sin_amp_out(matrix, 0, 0);
+    matrix.clear_history();
+
+    // Overlapping with the old position of the chain:
+    matrix.move_region(0, 0, RegionShape::Rect { w: 1, h: 3 }, 1, 1).unwrap();
+    assert!(matrix.get(0, 0).unwrap().is_empty());
+    assert_eq!(matrix.get(1, 1).unwrap().node_id(), NodeId::Sin(0));
+    assert_eq!(matrix.get(1, 2).unwrap().node_id(), NodeId::Amp(0));
+    assert_eq!(matrix.get(1, 3).unwrap().node_id(), NodeId::Out(0));
+    assert_eq!(matrix.get_adjacent_output(1, 3, CellDir::T), Some((NodeId::Amp(0), 0)));
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.4 && max < 0.6, "max={}", max);
+
+    assert!(matrix.undo().unwrap());
+    assert_eq!(matrix.get(0, 0).unwrap().node_id(), NodeId::Sin(0));
+    assert!(matrix.get(1, 1).unwrap().is_empty());
+}
+
+#[test]
+fn check_matrix_region_rotate_mirror() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix, 1, 0);
+    let shape = RegionShape::Hex { radius: 1 };
+
+    // Rotating by 60° would turn the T and B ports onto edges of the other
+    // kind, so the connections would be lost:
+    assert_eq!(
+        matrix.rotate_region(1, 1, shape, 1),
+        Err(MatrixError::PortOnWrongEdge { cell: *matrix.get(1, 0).unwrap() })
+    );
+    assert_eq!(matrix.get(1, 0).unwrap().node_id(), NodeId::Sin(0));
+    assert_eq!(matrix.get(1, 1).unwrap().node_id(), NodeId::Amp(0));
+    assert_eq!(matrix.get_adjacent_output(1, 1, CellDir::T), Some((NodeId::Sin(0), 0)));
+    assert_eq!(matrix.get_adjacent_output(1, 2, CellDir::T), Some((NodeId::Amp(0), 0)));
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert!(max_abs(&out_l[out_l.len() - 100..]) > 0.0);
+
+    // Mirrored at the axis from the top left to the bottom right:
+    matrix.transform_region(1, 1, shape, true, -2).unwrap();
+    assert_eq!(matrix.get(0, 2).unwrap().node_id(), NodeId::Sin(0));
+    assert_eq!(matrix.get(1, 1).unwrap().node_id(), NodeId::Amp(0));
+    assert_eq!(matrix.get(2, 1).unwrap().node_id(), NodeId::Out(0));
+    assert_eq!(matrix.get_adjacent_output(1, 1, CellDir::BL), Some((NodeId::Sin(0), 0)));
+    assert_eq!(matrix.get_adjacent_output(2, 1, CellDir::BL), Some((NodeId::Amp(0), 0)));
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert!(max_abs(&out_l) > 0.4);
+
+    // Mirroring turns the TR output of the Sin onto the TL input edge:
+    assert_eq!(
+        matrix.mirror_region(1, 1, shape),
+        Err(MatrixError::PortOnWrongEdge { cell: *matrix.get(0, 2).unwrap() })
+    );
+    assert_eq!(matrix.get(0, 2).unwrap().node_id(), NodeId::Sin(0));
+
+    // From BL/TR to TL/BR all ports are kept:
+    matrix.rotate_region(1, 1, shape, 1).unwrap();
+    assert_eq!(matrix.get(0, 1).unwrap().node_id(), NodeId::Sin(0));
+    assert_eq!(matrix.get(2, 2).unwrap().node_id(), NodeId::Out(0));
+    assert_eq!(matrix.get_adjacent_output(1, 1, CellDir::TL), Some((NodeId::Sin(0), 0)));
+    assert_eq!(matrix.get_adjacent_output(2, 2, CellDir::TL), Some((NodeId::Amp(0), 0)));
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert!(max_abs(&out_l[out_l.len() - 100..]) > 0.0);
+}
@@ -0,0 +1,99 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::matrix_repr::MatrixRepr;
+
+fn setup_sin_amp_out(matrix: &mut Matrix) {
+    let mut chain = MatrixCellChain::new(CellDir::BR);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+}
+
+// The connections of the cell without the positions:
+fn connections_at(matrix: &Matrix, x: usize, y: usize) -> Vec<(NodeId, CellDir, NodeId, CellDir)> {
+    matrix
+        .get_connections(x, y)
+        .unwrap_or_default()
+        .iter()
+        .map(|(a, b)| (a.0.node_id(), a.1, b.0.node_id(), b.1))
+        .collect()
+}
+
+#[test]
+fn check_matrix_resize_shifted() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix);
+    let connections = connections_at(matrix, 1, 0);
+    assert_eq!(connections.len(), 2);
+
+    matrix.resize_shifted(6, 5, 2, 1).unwrap();
+    assert_eq!(matrix.size(), (6, 5));
+    assert!(matrix.get(0, 0).unwrap().is_empty());
+    assert_eq!(matrix.get(2, 1).unwrap().node_id(), NodeId::Sin(0));
+    assert_eq!(matrix.get(3, 1).unwrap().pos(), (3, 1));
+    assert_eq!(connections_at(matrix, 3, 1), connections);
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+}
+
+#[test]
+fn check_matrix_resize_errors() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix);
+
+    let out = *matrix.get(2, 1).unwrap();
+    assert_eq!(matrix.resize(2, 3), Err(MatrixError::CellOutsideGrid { cell: out }));
+    assert_eq!(matrix.resize_shifted(4, 4, 1, 0), Err(MatrixError::OddColumnShift));
+    assert_eq!(
+        matrix.resize_shifted(3, 3, 0, -1),
+        Err(MatrixError::CellOutsideGrid { cell: *matrix.get(0, 0).unwrap() })
+    );
+    assert_eq!(matrix.size(), (3, 3));
+
+    // Shrinking over empty cells is fine:
+    matrix.resize(3, 2).unwrap();
+    assert_eq!(matrix.size(), (3, 2));
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+}
+
+#[test]
+fn check_matrix_resize_repr() {
+    let repr = {
+        init_test!(matrix, node_exec, 3);
+        setup_sin_amp_out(matrix);
+        matrix.resize(7, 4).unwrap();
+        run_for_ms(node_exec, 10.0);
+
+        let mut repr = matrix.to_repr();
+        MatrixRepr::deserialize(&repr.serialize()).unwrap()
+    };
+
+    assert_eq!(repr.size, Some((7, 4)));
+
+    init_test!(matrix, node_exec, 3);
+    matrix.place(0, 0, Cell::empty(NodeId::Sin(0)));
+    matrix.from_repr(&repr).unwrap();
+    assert_eq!(matrix.size(), (7, 4));
+    assert_eq!(matrix.get(2, 1).unwrap().node_id(), NodeId::Out(0));
+
+    // Loading a patch clears the history:
+    assert!(!matrix.can_undo());
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+}
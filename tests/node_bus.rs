@@ -0,0 +1,143 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::graph::GraphError;
+use hexodsp::Graph;
+
+fn place_send(matrix: &mut Matrix, x: usize, v: f32, bus: i64) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("amp", "sig")
+        .set_denorm("inp", v)
+        .node_inp("bussend", "inp")
+        .set_atom("bus", SAtom::setting(bus))
+        .place(matrix, x, 0)
+        .unwrap();
+}
+
+fn place_recv_out(matrix: &mut Matrix, x: usize, bus: i64) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("busrecv", "sig")
+        .set_atom("bus", SAtom::setting(bus))
+        .node_inp("out", "ch1")
+        .place(matrix, x, 0)
+        .unwrap();
+}
+
+#[test]
+fn check_node_bus_sum() {
+    init_test!(matrix, node_exec, 5);
+
+    // The receiving side comes first on the grid:
+    place_recv_out(matrix, 0, 0);
+    place_send(matrix, 2, 0.2, 0);
+    place_send(matrix, 3, 0.3, 0);
+    place_send(matrix, 4, 0.4, 1);
+    matrix.sync().unwrap();
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert_float_eq!(out_l[0], 0.5);
+    assert_float_eq!(out_l[100], 0.5);
+
+    pset_d(matrix, NodeId::BusRecv(0), "atv", -1.0);
+    pset_s(matrix, NodeId::BusRecv(0), "bus", 1);
+    matrix.sync().unwrap();
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert_float_eq!(out_l[out_l.len() - 1], -0.4);
+}
+
+#[test]
+fn check_node_bus_with_workers() {
+    init_test!(matrix, node_exec, 5);
+    matrix.set_worker_threads(2);
+
+    place_recv_out(matrix, 0, 3);
+    place_send(matrix, 2, 0.2, 3);
+    place_send(matrix, 3, 0.3, 3);
+    matrix.sync().unwrap();
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert_float_eq!(out_l[0], 0.5);
+    assert_float_eq!(out_l[out_l.len() - 1], 0.5);
+}
+
+#[test]
+fn check_node_bus_cycle() {
+    init_test!(matrix, node_exec, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("busrecv", "sig")
+        .node_io("amp", "inp", "sig")
+        .node_inp("bussend", "inp")
+        .place(matrix, 0, 0)
+        .unwrap();
+    assert_eq!(matrix.sync(), Err(MatrixError::CycleDetected));
+
+    pset_s(matrix, NodeId::BusSend(0), "bus", 1);
+    matrix.sync().unwrap();
+    run_for_ms(node_exec, 10.0);
+}
+
+#[test]
+fn check_node_bus_named() {
+    init_test!(matrix, node_exec, 5);
+
+    // The unnamed nodes use the buses 0 and 1, so "fx" gets bus 2:
+    place_recv_out(matrix, 0, 0);
+    matrix.set_param(NodeId::BusRecv(0).inp_param("name").unwrap(), SAtom::str("fx"));
+    place_send(matrix, 2, 0.2, 0);
+    matrix.set_param(NodeId::BusSend(0).inp_param("name").unwrap(), SAtom::str("fx"));
+    place_send(matrix, 3, 0.3, 0);
+    place_send(matrix, 4, 0.4, 1);
+    matrix.sync().unwrap();
+
+    let bus = NodeId::BusRecv(0).inp_param("bus").unwrap();
+    assert_eq!(matrix.get_param(&bus), Some(SAtom::setting(2)));
+    let bus = NodeId::BusSend(0).inp_param("bus").unwrap();
+    assert_eq!(matrix.get_param(&bus), Some(SAtom::setting(2)));
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert_float_eq!(out_l[0], 0.2);
+    assert_float_eq!(out_l[out_l.len() - 1], 0.2);
+
+    // Renaming the receiver leaves it alone on its own bus:
+    matrix.set_param(NodeId::BusRecv(0).inp_param("name").unwrap(), SAtom::str("dry"));
+    matrix.sync().unwrap();
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert_float_eq!(out_l[out_l.len() - 1], 0.0);
+}
+
+#[test]
+fn check_node_bus_named_graph() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut graph = Graph::new(node_conf);
+
+    graph.connect(NodeId::BusRecv(0), "sig", NodeId::Out(0), "ch1").unwrap();
+    graph.set_atom(NodeId::BusRecv(0), "name", SAtom::str("fx")).unwrap();
+    graph.connect(NodeId::Amp(0), "sig", NodeId::BusSend(0), "inp").unwrap();
+    graph.set_denorm(NodeId::Amp(0), "inp", 0.3).unwrap();
+    graph.set_atom(NodeId::BusSend(0), "name", SAtom::str("fx")).unwrap();
+    graph.set_atom(NodeId::BusSend(1), "bus", SAtom::setting(0)).unwrap();
+    graph.add_node(NodeId::BusSend(1));
+    assert!(graph.sync().unwrap());
+    assert_eq!(graph.get_atom(NodeId::BusRecv(0), "bus"), Some(SAtom::setting(1)));
+
+    let (out_l, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_float_eq!(out_l[out_l.len() - 1], 0.3);
+
+    // All buses are used by unnamed nodes:
+    for i in 1..16 {
+        graph.set_atom(NodeId::BusSend(i + 1), "bus", SAtom::setting(i)).unwrap();
+        graph.add_node(NodeId::BusSend(i + 1));
+    }
+    assert!(graph.is_dirty());
+    assert_eq!(graph.sync(), Err(GraphError::TooManyBuses));
+}
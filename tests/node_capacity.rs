@@ -0,0 +1,55 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+#[test]
+fn check_node_capacity_grows() {
+    let (node_conf, mut node_exec) = NodeEngineBuilder::new().nodes(4).build();
+    let mut matrix = Matrix::new(node_conf, 20, 20);
+
+    // 300 unconnected nodes, so that the nodes placed below
+    // get indices beyond the default capacity:
+    let mut ids: Vec<NodeId> = (0..200).map(NodeId::Amp).collect();
+    ids.extend((0..100).map(NodeId::Sin));
+    for (i, id) in ids.iter().enumerate() {
+        matrix.place(i / 20, i % 20, Cell::empty(*id));
+    }
+
+    let sin = NodeId::Sin(100);
+    let out = NodeId::Out(0);
+    matrix.place(19, 18, Cell::empty(sin).out(None, None, sin.out("sig")));
+    matrix.place(19, 19, Cell::empty(out).input(out.inp("ch1"), None, None));
+    matrix.sync().unwrap();
+
+    matrix.set_dsp_profiling(true);
+    let (out_l, _) = run_for_ms(&mut node_exec, 50.0);
+    let max = out_l.iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+    assert!(max > 0.9, "max={}", max);
+
+    let load = matrix.dsp_load_for(&sin).unwrap();
+    assert!(load.avg_ns > 0.0, "{:?}", load);
+}
+
+#[test]
+fn check_node_capacity_feedback_delays_grow() {
+    let (node_conf, mut node_exec) = NodeEngineBuilder::new().feedback_delays(1).build();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let sin = NodeId::Sin(0);
+    let wr = NodeId::FbWr(5);
+    let rd = NodeId::FbRd(5);
+    let out = NodeId::Out(0);
+
+    matrix.place(0, 0, Cell::empty(sin).out(None, None, sin.out("sig")));
+    matrix.place(0, 1, Cell::empty(wr).input(wr.inp("inp"), None, None));
+    matrix.place(1, 0, Cell::empty(rd).out(None, None, rd.out("sig")));
+    matrix.place(1, 1, Cell::empty(out).input(out.inp("ch1"), None, None));
+    matrix.sync().unwrap();
+
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    let max = out_l.iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+    assert!(max > 0.9, "max={}", max);
+}
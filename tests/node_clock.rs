@@ -0,0 +1,125 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_clock(matrix: &mut Matrix, out: &str) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("clock", out).node_inp("out", "ch1").place(matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+}
+
+fn trigger_starts(buf: &[f32]) -> Vec<usize> {
+    collect_gates(buf).iter().map(|(start, _)| *start).collect()
+}
+
+fn assert_frames_near(frames: Vec<usize>, expected: Vec<usize>) {
+    assert_eq!(frames.len(), expected.len(), "frames={:?}", frames);
+    for (f, e) in frames.iter().zip(expected.iter()) {
+        assert!((*f as i64 - *e as i64).abs() <= 1, "frames={:?} expected={:?}", frames, expected);
+    }
+}
+
+#[test]
+fn check_node_clock_beats() {
+    init_test!(matrix, node_exec, 3);
+    setup_clock(matrix, "beat");
+
+    matrix.set_tempo(120.0);
+    matrix.set_playing(true);
+
+    // 120 BPM => one quarter note every 0.5 seconds:
+    let (out_l, _) = run_for_ms(node_exec, 1100.0);
+    assert_frames_near(trigger_starts(&out_l[..]), vec![0, 22050, 44100]);
+}
+
+#[test]
+fn check_node_clock_stopped() {
+    init_test!(matrix, node_exec, 3);
+    setup_clock(matrix, "beat");
+
+    let (out_l, _) = run_for_ms(node_exec, 1100.0);
+    assert_eq!(trigger_starts(&out_l[..]), Vec::<usize>::new());
+
+    matrix.set_playing(true);
+    let (out_l, _) = run_for_ms(node_exec, 600.0);
+    assert_frames_near(trigger_starts(&out_l[..]), vec![0, 22050]);
+
+    matrix.set_playing(false);
+    let (out_l, _) = run_for_ms(node_exec, 1100.0);
+    assert_eq!(trigger_starts(&out_l[..]), Vec::<usize>::new());
+}
+
+#[test]
+fn check_node_clock_bars() {
+    init_test!(matrix, node_exec, 3);
+    setup_clock(matrix, "bar");
+
+    matrix.set_tempo(120.0);
+    matrix.set_time_signature(3, 4);
+    matrix.set_playing(true);
+
+    let (out_l, _) = run_for_ms(node_exec, 2000.0);
+    assert_frames_near(trigger_starts(&out_l[..]), vec![0, 66150]);
+}
+
+#[test]
+fn check_node_clock_phase_res() {
+    init_test!(matrix, node_exec, 3);
+    setup_clock(matrix, "phase");
+
+    pset_s(matrix, NodeId::Clock(0), "res", 3);
+    matrix.set_tempo(120.0);
+    matrix.set_playing(true);
+
+    // 1/8 notes are 11025 samples long at 120 BPM:
+    let (out_l, _) = run_for_ms(node_exec, 500.0);
+    assert_float_eq!(out_l[0], 0.0);
+    assert!((out_l[5512] - 0.5).abs() < 0.001, "phase={}", out_l[5512]);
+    assert!((out_l[11025 + 2756] - 0.25).abs() < 0.001, "phase={}", out_l[11025 + 2756]);
+}
+
+#[test]
+fn check_node_clock_locate() {
+    init_test!(matrix, node_exec, 3);
+    setup_clock(matrix, "bphase");
+
+    matrix.locate(2.0);
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert_float_eq!(out_l[0], 0.5);
+}
+
+#[test]
+fn check_node_clock_host_transport() {
+    init_test!(matrix, node_exec, 3);
+    setup_clock(matrix, "phase");
+
+    node_exec.set_sample_rate(44100.0);
+    node_exec.process_graph_updates();
+
+    let transport = Transport {
+        bpm: 60.0,
+        sig_num: 4,
+        sig_denom: 4,
+        playing: true,
+        sample_pos: 22050,
+        beat_pos: 0.5,
+    };
+
+    let input = vec![0.0; 128];
+    let mut out_l = vec![0.0; 128];
+    let mut out_r = vec![0.0; 128];
+    let mut context = hexodsp::Context {
+        nframes: 128,
+        output: &mut [&mut out_l[..], &mut out_r[..]],
+        input: &[&input[..]],
+        events: &[],
+        transport: Some(transport),
+    };
+    node_exec.process(&mut context);
+
+    assert_float_eq!(out_l[0], 0.5);
+    assert!((out_l[127] - (0.5 + 127.0 / 44100.0)).abs() < 0.0001, "phase={}", out_l[127]);
+}
@@ -364,3 +364,73 @@ fn check_node_delay_fb_pos() {
         ]
     );
 }
+
+fn run_tempo_synced_delay(bpm: f64, time: f32, ms: f32) -> Vec<(usize, i64)> {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 4, 4);
+
+    let test = NodeId::Test(0);
+    let dly = NodeId::Delay(0);
+    let out = NodeId::Out(0);
+    matrix.place(1, 1, Cell::empty(test).out(None, None, test.out("tsig")));
+    matrix.place(
+        1,
+        2,
+        Cell::empty(dly).input(dly.inp("inp"), None, None).out(None, None, dly.out("sig")),
+    );
+    matrix.place(1, 3, Cell::empty(out).input(out.inp("ch1"), None, None).out(None, None, None));
+
+    matrix.set_tempo(bpm);
+    pset_n(&mut matrix, dly, "mix", 1.0);
+    pset_s(&mut matrix, dly, "sync", 1);
+    pset_n(&mut matrix, dly, "time", time);
+
+    matrix.sync().unwrap();
+
+    pset_n(&mut matrix, test, "trig", 1.0);
+    let res = run_for_ms(&mut node_exec, ms);
+
+    collect_signal_changes(&res.0[..], 70)
+}
+
+#[test]
+fn check_node_delay_tempo_sync() {
+    // 1/8 at 120 BPM => 250ms
+    let idxs_big = run_tempo_synced_delay(120.0, 0.5, 300.0);
+    assert_eq!(idxs_big.len(), 1);
+    assert!((idxs_big[0].0 as i64 - 11026).abs() <= 2, "{:?}", idxs_big);
+
+    // 1/8. at 60 BPM => 750ms
+    let idxs_big = run_tempo_synced_delay(60.0, 0.6, 800.0);
+    assert_eq!(idxs_big.len(), 1);
+    assert!((idxs_big[0].0 as i64 - 33076).abs() <= 2, "{:?}", idxs_big);
+
+    // 1/4T at 60 BPM => 666.67ms
+    let idxs_big = run_tempo_synced_delay(60.0, 0.55, 800.0);
+    assert_eq!(idxs_big.len(), 1);
+    assert!((idxs_big[0].0 as i64 - 29401).abs() <= 2, "{:?}", idxs_big);
+}
+
+#[test]
+fn check_node_delay_tempo_sync_format() {
+    let (node_conf, _node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 4, 4);
+
+    let dly = NodeId::Delay(0);
+    matrix.place(0, 0, Cell::empty(dly).out(None, None, dly.out("sig")));
+    matrix.sync().unwrap();
+
+    let time = dly.inp_param("time").unwrap();
+    assert_eq!(time.sync_atom(), dly.inp_param("sync"));
+    assert_eq!(NodeId::Delay(0).inp_param("fb").unwrap().sync_atom(), None);
+
+    let mut buf: Vec<u8> = vec![];
+    matrix.format_param(&mut buf, &time, time.norm(250.0)).unwrap().unwrap();
+    assert_eq!(std::str::from_utf8(&buf).unwrap(), "250.0ms");
+
+    pset_s(&mut matrix, dly, "sync", 1);
+
+    let mut buf: Vec<u8> = vec![];
+    matrix.format_param(&mut buf, &time, 0.6).unwrap().unwrap();
+    assert_eq!(std::str::from_utf8(&buf).unwrap(), "1/8.");
+}
@@ -0,0 +1,116 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_fb_step(matrix: &mut Matrix, mode: i64, time_ms: f32) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("amp", "sig")
+        .set_denorm("inp", 1.0)
+        .node_inp("fbwr", "inp")
+        .place(matrix, 0, 0)
+        .unwrap();
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("fbrd", "sig")
+        .set_denorm("time", time_ms)
+        .set_atom("mode", SAtom::setting(mode))
+        .node_inp("out", "ch1")
+        .place(matrix, 1, 0)
+        .unwrap();
+
+    matrix.sync().unwrap();
+}
+
+fn first_non_zero(buf: &[f32]) -> Option<usize> {
+    buf.iter().position(|s| *s != 0.0)
+}
+
+#[test]
+fn check_node_fbrd_fixed() {
+    init_test!(matrix, node_exec, 3);
+    // The time is ignored in the fixed mode:
+    setup_fb_step(matrix, 0, 10.0);
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    // 3.14ms at 44.1kHz:
+    assert_eq!(first_non_zero(&out_l), Some(138));
+    assert_float_eq!(out_l[138], 1.0);
+}
+
+#[test]
+fn check_node_fbrd_time() {
+    init_test!(matrix, node_exec, 3);
+    setup_fb_step(matrix, 1, 10.0);
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert_eq!(first_non_zero(&out_l), Some(441));
+    assert_float_eq!(out_l[441], 1.0);
+}
+
+#[test]
+fn check_node_fbrd_time_one_block() {
+    init_test!(matrix, node_exec, 3);
+    // The delay can't be shorter than one block of 128 samples:
+    setup_fb_step(matrix, 1, 0.0);
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert_eq!(first_non_zero(&out_l), Some(128));
+    assert_float_eq!(out_l[128], 1.0);
+}
+
+#[test]
+fn check_node_fbrd_time_interpolated() {
+    init_test!(matrix, node_exec, 3);
+    setup_fb_step(matrix, 1, 200.5 / 44.1);
+
+    let (out_l, _) = run_for_ms(node_exec, 10.0);
+    assert_eq!(first_non_zero(&out_l), Some(200));
+    assert_float_eq!(out_l[200], 0.5);
+    assert_float_eq!(out_l[201], 1.0);
+}
+
+fn run_blocks(node_exec: &mut NodeExecutor, srate: f32, block: usize, len: usize) -> Vec<f32> {
+    node_exec.set_sample_rate(srate);
+    node_exec.process_graph_updates();
+
+    let input = vec![0.0; block];
+    let mut out_l = vec![0.0; len];
+    let mut out_r = vec![0.0; len];
+
+    let mut offs = 0;
+    while offs < len {
+        let nframes = block.min(len - offs);
+        let mut context = hexodsp::Context {
+            nframes,
+            output: &mut [&mut out_l[offs..(offs + nframes)], &mut out_r[offs..(offs + nframes)]],
+            input: &[&input[..nframes]],
+            events: &[],
+            transport: None,
+        };
+        node_exec.process(&mut context);
+        offs += nframes;
+    }
+
+    out_l
+}
+
+#[test]
+fn check_node_fbrd_fixed_other_srates() {
+    // The fixed delay is 3.14ms at any sample rate and
+    // does not depend on the block size:
+    for (srate, block, delay) in
+        [(22050.0, 64, 69), (22050.0, 32, 69), (48000.0, 128, 150), (48000.0, 100, 150)]
+    {
+        init_test!(matrix, node_exec, 3);
+        setup_fb_step(matrix, 0, 10.0);
+
+        let out_l = run_blocks(node_exec, srate, block, 1024);
+        assert_eq!(first_non_zero(&out_l), Some(delay), "srate={} block={}", srate, block);
+        assert_float_eq!(out_l[delay], 1.0);
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_inp(matrix: &mut Matrix) {
+    let inp = NodeId::Inp(0);
+    let out = NodeId::Out(0);
+    matrix.place(0, 0, Cell::empty(inp).out(None, None, inp.out("sig1")));
+    matrix.place(0, 1, Cell::empty(out).input(out.inp("ch1"), None, None));
+    matrix.place(1, 0, Cell::empty(inp).out(None, None, inp.out("sig2")));
+    matrix.place(1, 1, Cell::empty(out).input(out.inp("ch2"), None, None));
+    matrix.sync().unwrap();
+}
+
+#[test]
+fn check_node_inp_passthrough() {
+    init_test!(matrix, node_exec, 3);
+    setup_inp(matrix);
+
+    let in1: Vec<f32> = (0..1000).map(|i| i as f32 / 1000.0).collect();
+    let in2: Vec<f32> = (0..1000).map(|i| -(i as f32) / 1000.0).collect();
+
+    let (out_l, out_r) = run_with_input(node_exec, &[&in1[..], &in2[..]]);
+    assert_eq!(out_l.len(), 1000);
+    assert_float_eq!(out_l[0], 0.0);
+    assert_float_eq!(out_l[500], 0.5);
+    assert_float_eq!(out_l[999], 0.999);
+    assert_float_eq!(out_r[500], -0.5);
+    assert_float_eq!(out_r[999], -0.999);
+}
+
+#[test]
+fn check_node_inp_gain() {
+    init_test!(matrix, node_exec, 3);
+    setup_inp(matrix);
+
+    pset_d_wait(matrix, node_exec, NodeId::Inp(0), "gain", 0.5);
+
+    let input = vec![0.8; 1000];
+    let (out_l, out_r) = run_with_input(node_exec, &[&input[..], &input[..]]);
+    assert_float_eq!(out_l[500], 0.4);
+    assert_float_eq!(out_r[500], 0.4);
+}
+
+#[test]
+fn check_node_inp_channel_pair() {
+    init_test!(matrix, node_exec, 3);
+    setup_inp(matrix);
+
+    pset_s(matrix, NodeId::Inp(0), "chans", 1);
+
+    let in1 = vec![0.1; 1000];
+    let in2 = vec![0.2; 1000];
+    let in3 = vec![0.3; 1000];
+    // The 4th channel is missing and delivers silence:
+    let (out_l, out_r) = run_with_input(node_exec, &[&in1[..], &in2[..], &in3[..]]);
+    assert_float_eq!(out_l[500], 0.3);
+    assert_float_eq!(out_r[500], 0.0);
+}
@@ -0,0 +1,84 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_midiin(matrix: &mut Matrix, out: &str) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midiin", out).node_inp("out", "ch1").place(matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+}
+
+fn note_on(frame: usize, note: u8) -> MidiEvent {
+    MidiEvent::new(frame, 0, MidiEventKind::NoteOn { note, vel: 1.0 })
+}
+
+fn note_off(frame: usize, note: u8) -> MidiEvent {
+    MidiEvent::new(frame, 0, MidiEventKind::NoteOff { note })
+}
+
+#[test]
+fn check_node_midiin_gate() {
+    init_test!(matrix, node_exec, 3);
+    setup_midiin(matrix, "gate");
+
+    let res = run_for_ms_with_events(node_exec, 50.0, &[note_on(100, 60), note_off(1000, 60)]);
+    assert_eq!(collect_gates(&res.0[..]), vec![(100, 900)]);
+}
+
+#[test]
+fn check_node_midiin_retrigger() {
+    init_test!(matrix, node_exec, 3);
+    setup_midiin(matrix, "gate");
+
+    let res = run_for_ms_with_events(
+        node_exec,
+        50.0,
+        &[note_on(100, 60), note_on(200, 64), note_off(300, 60), note_off(400, 64)],
+    );
+    // The second note sends a one sample low gate, the release of the
+    // first note does not end the gate:
+    assert_eq!(collect_gates(&res.0[..]), vec![(100, 100), (201, 199)]);
+}
+
+#[test]
+fn check_node_midiin_freq() {
+    init_test!(matrix, node_exec, 3);
+    setup_midiin(matrix, "freq");
+
+    let res = run_for_ms_with_events(
+        node_exec,
+        50.0,
+        &[note_on(10, 81), note_on(200, 57), note_off(400, 57), note_off(600, 81)],
+    );
+    assert_float_eq!(res.0[0], 0.0);
+    assert_float_eq!(res.0[100], 0.1);
+    assert_float_eq!(res.0[300], -0.1);
+    // Falls back to the still held note:
+    assert_float_eq!(res.0[500], 0.1);
+    // Keeps the last note after the release:
+    assert_float_eq!(res.0[700], 0.1);
+}
+
+#[test]
+fn check_node_midiin_channel() {
+    init_test!(matrix, node_exec, 3);
+    setup_midiin(matrix, "gate");
+
+    pset_s(matrix, NodeId::MidiIn(0), "chan", 2);
+    run_for_ms(node_exec, 10.0);
+
+    let res = run_for_ms_with_events(
+        node_exec,
+        50.0,
+        &[
+            note_on(100, 60),
+            note_off(200, 60),
+            MidiEvent::new(300, 1, MidiEventKind::NoteOn { note: 60, vel: 1.0 }),
+            MidiEvent::new(400, 1, MidiEventKind::NoteOff { note: 60 }),
+        ],
+    );
+    assert_eq!(collect_gates(&res.0[..]), vec![(300, 100)]);
+}
@@ -0,0 +1,122 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::matrix_repr::MatrixRepr;
+
+fn setup_sin_amp_out(matrix: &mut Matrix) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        // Silences the Amp, so that only a bypass lets the sine through:
+        .set_denorm("gain", 0.0)
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+}
+
+#[test]
+fn check_node_state_bypass() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix);
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert_eq!(max_abs(&out_l), 0.0);
+
+    matrix.set_node_state(NodeId::Amp(0), NodeState::Bypass);
+    assert_eq!(matrix.node_state(&NodeId::Amp(0)), NodeState::Bypass);
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+
+    // The state is kept for new programs:
+    matrix.sync().unwrap();
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+
+    matrix.set_node_state(NodeId::Amp(0), NodeState::Active);
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert_eq!(max_abs(&out_l), 0.0);
+}
+
+#[test]
+fn check_node_state_mute() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix);
+    pset_d(matrix, NodeId::Amp(0), "gain", 1.0);
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+
+    matrix.set_node_state(NodeId::Amp(0), NodeState::Mute);
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert_eq!(max_abs(&out_l), 0.0);
+
+    matrix.set_node_state(NodeId::Amp(0), NodeState::Active);
+    matrix.set_node_state(NodeId::Out(0), NodeState::Mute);
+    let (out_l, out_r) = run_for_ms(node_exec, 20.0);
+    assert_eq!(max_abs(&out_l), 0.0);
+    assert_eq!(max_abs(&out_r), 0.0);
+}
+
+#[test]
+fn check_node_state_bypass_without_input() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix);
+    pset_d(matrix, NodeId::Amp(0), "gain", 1.0);
+
+    assert_eq!(NodeId::Sin(0).bypass_inp(), None);
+    assert_eq!(NodeId::Amp(0).bypass_inp(), NodeId::Amp(0).inp("inp"));
+    assert_eq!(NodeId::PVerb(0).bypass_inp(), NodeId::PVerb(0).inp("in_l"));
+
+    // Nodes without a bypass input are muted:
+    matrix.set_node_state(NodeId::Sin(0), NodeState::Bypass);
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert_eq!(max_abs(&out_l), 0.0);
+}
+
+#[test]
+fn check_node_state_with_workers() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_amp_out(matrix);
+    matrix.set_worker_threads(2);
+
+    matrix.set_node_state(NodeId::Amp(0), NodeState::Bypass);
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+}
+
+#[test]
+fn check_node_state_repr() {
+    let repr = {
+        init_test!(matrix, node_exec, 3);
+        setup_sin_amp_out(matrix);
+        matrix.set_node_state(NodeId::Amp(0), NodeState::Bypass);
+        run_for_ms(node_exec, 10.0);
+
+        let mut repr = matrix.to_repr();
+        MatrixRepr::deserialize(&repr.serialize()).unwrap()
+    };
+
+    assert_eq!(repr.node_states, vec![(NodeId::Amp(0), NodeState::Bypass)]);
+
+    init_test!(matrix, node_exec, 3);
+    matrix.from_repr(&repr).unwrap();
+    assert_eq!(matrix.node_state(&NodeId::Amp(0)), NodeState::Bypass);
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.9, "max={}", max);
+
+    matrix.clear();
+    assert_eq!(matrix.node_state(&NodeId::Amp(0)), NodeState::Active);
+}
@@ -0,0 +1,201 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::offline_render::*;
+
+#[test]
+fn check_render_length_and_rate() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+
+    let settings = RenderSettings {
+        sample_rate: 48000.0,
+        length: RenderLength::Seconds(0.5),
+        tail: 0.25,
+        ..RenderSettings::default()
+    };
+
+    let mut calls = vec![];
+    let audio = node_exec.render(&settings, |frame, _time| calls.push(frame)).unwrap();
+
+    assert_eq!(audio.sample_rate, 48000.0);
+    assert_eq!(audio.channels.len(), 2);
+    assert_eq!(audio.len(), 36000);
+    assert_eq!(calls[0], 0);
+    assert_eq!(calls[1], hexodsp::dsp::MAX_BLOCK_SIZE);
+
+    // 440Hz sine at 48kHz:
+    let zero_crossings = audio.channels[0].windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+    assert!((zero_crossings as i64 - 330).abs() <= 1, "crossings={}", zero_crossings);
+    assert_float_eq!(audio.channels[1].iter().map(|s| s.abs()).sum::<f32>(), 0.0);
+}
+
+#[test]
+fn check_render_automation() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+
+    let out_gain = NodeId::Out(0).inp_param("gain").unwrap();
+
+    let settings = RenderSettings {
+        length: RenderLength::Seconds(0.2),
+        channels: 1,
+        block_size: 441,
+        ..RenderSettings::default()
+    };
+
+    let mut muted = false;
+    let audio = node_exec
+        .render(&settings, |_frame, time| {
+            if time >= 0.1 && !muted {
+                matrix.set_param(out_gain, SAtom::param(0.0));
+                muted = true;
+            }
+        })
+        .unwrap();
+
+    let rms = calc_rms_mimax_each_ms(&audio.channels[0][..], 50.0);
+    assert!(rms[0].2 > 0.9, "rms={:?}", rms);
+    assert!(rms[3].2 < 0.001, "rms={:?}", rms);
+}
+
+#[test]
+fn check_render_until_silence() {
+    init_test!(matrix, node_exec, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    let amp_att = NodeId::Amp(0).inp_param("att").unwrap();
+
+    let settings = RenderSettings {
+        length: RenderLength::UntilSilence { threshold: 0.001, hold: 0.1, max: 10.0 },
+        tail: 0.05,
+        ..RenderSettings::default()
+    };
+
+    let mut released = false;
+    let audio = node_exec
+        .render(&settings, |_frame, time| {
+            if time >= 0.3 && !released {
+                matrix.set_param(amp_att, SAtom::param(0.0));
+                released = true;
+            }
+        })
+        .unwrap();
+
+    // 0.3s sound + (smoothing) + 0.1s silence + 0.05s tail:
+    let secs = audio.len() as f32 / 44100.0;
+    assert!(secs > 0.45 && secs < 0.5, "secs={}", secs);
+}
+
+#[test]
+fn check_render_until_silence_attack() {
+    init_test!(matrix, node_exec, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        .set_denorm("att", 0.0)
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    let amp_att = NodeId::Amp(0).inp_param("att").unwrap();
+
+    let settings = RenderSettings {
+        length: RenderLength::UntilSilence { threshold: 0.001, hold: 0.1, max: 10.0 },
+        ..RenderSettings::default()
+    };
+
+    // The leading silence is longer than the hold time:
+    let audio = node_exec
+        .render(&settings, |_frame, time| {
+            if time >= 0.4 {
+                matrix.set_param(amp_att, SAtom::param(0.0));
+            } else if time >= 0.2 {
+                matrix.set_param(amp_att, SAtom::param(1.0));
+            }
+        })
+        .unwrap();
+
+    // 0.2s silence + 0.2s sound + (smoothing) + 0.1s silence:
+    let secs = audio.len() as f32 / 44100.0;
+    assert!(secs > 0.5 && secs < 0.55, "secs={}", secs);
+}
+
+#[test]
+fn check_render_channels() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+
+    for channels in [0, 3].iter() {
+        let settings = RenderSettings { channels: *channels, ..RenderSettings::default() };
+        let err = node_exec.render(&settings, |_, _| ()).unwrap_err();
+        assert!(matches!(err, RenderError::UnsupportedChannels(c) if c == *channels));
+    }
+}
+
+#[test]
+fn check_render_wav() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+
+    let settings = RenderSettings {
+        sample_rate: 22050.0,
+        length: RenderLength::Seconds(0.1),
+        ..RenderSettings::default()
+    };
+
+    for (format, bits) in
+        [(WavFormat::Int16, 16), (WavFormat::Int24, 24), (WavFormat::Float32, 32)].iter()
+    {
+        let mut path = std::env::temp_dir();
+        path.push(format!("hexodsp_render_test_{}.wav", bits));
+        let path = path.to_str().unwrap().to_string();
+
+        let audio = node_exec.render_to_wav(&path, *format, &settings, |_, _| ()).unwrap();
+
+        let mut rd = hound::WavReader::open(&path).unwrap();
+        let spec = rd.spec();
+        assert_eq!(spec.sample_rate, 22050);
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.bits_per_sample, *bits);
+        assert_eq!(rd.len() as usize, audio.len() * 2);
+
+        let first_peak: f32 = if *format == WavFormat::Float32 {
+            rd.samples::<f32>().step_by(2).take(100).map(|s| s.unwrap().abs()).fold(0.0, f32::max)
+        } else {
+            let max = (1 << (*bits - 1)) as f32;
+            rd.samples::<i32>()
+                .step_by(2)
+                .take(100)
+                .map(|s| s.unwrap().abs() as f32 / max)
+                .fold(0.0, f32::max)
+        };
+        assert!(first_peak > 0.95, "peak={}", first_peak);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    let mut path = std::env::temp_dir();
+    path.push("hexodsp_no_such_dir");
+    path.push("render.wav");
+    let err = node_exec
+        .render_to_wav(path.to_str().unwrap(), WavFormat::Int16, &settings, |_, _| ())
+        .unwrap_err();
+    assert!(err.to_string().starts_with("can't write WAV file: "), "err={}", err);
+    assert!(std::error::Error::source(&err).is_some());
+}
@@ -0,0 +1,205 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_branches(matrix: &mut Matrix) {
+    let sin_1 = NodeId::Sin(0);
+    let sin_2 = NodeId::Sin(1);
+    let bosc_1 = NodeId::BOsc(0);
+    let mix3_1 = NodeId::Mix3(0);
+    let out_1 = NodeId::Out(0);
+    matrix.place(
+        1,
+        1,
+        Cell::empty(sin_1).input(None, None, None).out(None, sin_1.out("sig"), None),
+    );
+    matrix.place(
+        1,
+        2,
+        Cell::empty(bosc_1).input(None, None, None).out(bosc_1.out("sig"), None, None),
+    );
+    matrix.place(
+        2,
+        1,
+        Cell::empty(sin_2).input(None, None, None).out(None, None, sin_2.out("sig")),
+    );
+    matrix.place(
+        2,
+        2,
+        Cell::empty(mix3_1).input(mix3_1.inp("ch1"), mix3_1.inp("ch2"), mix3_1.inp("ch3")).out(
+            None,
+            mix3_1.out("sig"),
+            None,
+        ),
+    );
+    matrix.place(
+        3,
+        2,
+        Cell::empty(out_1).input(None, out_1.inp("ch1"), None).out(None, None, None),
+    );
+
+    pset_d(matrix, sin_1, "freq", 110.0);
+    pset_d(matrix, sin_2, "freq", 331.0);
+    pset_d(matrix, bosc_1, "freq", 523.0);
+    pset_d(matrix, mix3_1, "gain1", 0.3);
+    pset_d(matrix, mix3_1, "gain2", 0.3);
+    pset_d(matrix, mix3_1, "gain3", 0.3);
+    matrix.sync().unwrap();
+}
+
+fn render_branches(threads: usize) -> Vec<f32> {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 5, 5);
+    setup_branches(&mut matrix);
+
+    matrix.set_worker_threads(threads);
+
+    let (out_l, _) = run_for_ms(&mut node_exec, 200.0);
+
+    // Changing the parameters while the workers are active:
+    pset_d(&mut matrix, NodeId::Sin(1), "freq", 880.0);
+    let (out_l_2, _) = run_for_ms(&mut node_exec, 50.0);
+
+    out_l.iter().chain(out_l_2.iter()).copied().collect()
+}
+
+#[test]
+fn check_parallel_exec_identical_output() {
+    let sequential = render_branches(0);
+    let parallel = render_branches(3);
+
+    let rms = calc_rms_mimax_each_ms(&sequential[..], 50.0);
+    assert!(rms[0].0 > 0.01, "{:?}", rms);
+
+    assert_eq!(sequential.len(), parallel.len());
+    for (i, (s, p)) in sequential.iter().zip(parallel.iter()).enumerate() {
+        assert_eq!(s.to_bits(), p.to_bits(), "sample {} differs: {} != {}", i, s, p);
+    }
+}
+
+#[test]
+fn check_parallel_exec_back_to_sequential() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 5, 5);
+    setup_branches(&mut matrix);
+
+    matrix.set_worker_threads(2);
+    run_for_ms(&mut node_exec, 20.0);
+    matrix.set_worker_threads(0);
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+
+    assert!(out_l.iter().any(|s| s.abs() > 0.1));
+}
+
+#[test]
+fn check_parallel_exec_levels_shared_state() {
+    let (mut node_conf, _node_exec) = new_node_engine();
+
+    let nodes = [
+        NodeId::Sin(0),
+        NodeId::FbWr(0),
+        NodeId::FbRd(0),
+        NodeId::Amp(0),
+        NodeId::BusSend(0),
+        NodeId::BusRecv(0),
+        NodeId::Sin(1),
+        NodeId::Out(0),
+    ];
+    for node_id in nodes.iter() {
+        node_conf.create_node(*node_id);
+    }
+
+    let mut prog = node_conf.rebuild_node_ports();
+    for node_id in nodes.iter() {
+        node_conf.add_prog_node(&mut prog, node_id);
+    }
+
+    let edges = [
+        (NodeId::Sin(0), "sig", NodeId::FbWr(0), "inp"),
+        (NodeId::FbRd(0), "sig", NodeId::Amp(0), "inp"),
+        (NodeId::Amp(0), "sig", NodeId::BusSend(0), "inp"),
+        (NodeId::BusRecv(0), "sig", NodeId::Out(0), "ch1"),
+        (NodeId::Sin(1), "sig", NodeId::Out(0), "ch2"),
+    ];
+    for (from, out, to, inp) in edges.iter() {
+        node_conf.set_prog_node_exec_connection(
+            &mut prog,
+            (*to, to.inp(inp).unwrap()),
+            (*from, from.out(out).unwrap()),
+        );
+    }
+
+    prog.calculate_levels();
+
+    // The feedback and bus nodes access the NodeExecContext of the audio
+    // thread, so they are never put on the workers:
+    let mut parallel = vec![];
+    let mut sequential = vec![];
+    for level in prog.levels.iter() {
+        if let hexodsp::nodes::ProgLevel::Ops { start, par_end, end } = level {
+            for op_i in prog.level_ops[*start..*par_end].iter() {
+                parallel.push(prog.prog[*op_i].node_id);
+            }
+            for op_i in prog.level_ops[*par_end..*end].iter() {
+                sequential.push(prog.prog[*op_i].node_id);
+            }
+        }
+    }
+
+    assert_eq!(parallel, vec![NodeId::Sin(0), NodeId::Sin(1), NodeId::Amp(0)]);
+    assert_eq!(
+        sequential,
+        vec![
+            NodeId::FbWr(0),
+            NodeId::FbRd(0),
+            NodeId::BusSend(0),
+            NodeId::BusRecv(0),
+            NodeId::Out(0)
+        ]
+    );
+}
+
+fn render_feedback(threads: usize) -> Vec<f32> {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 5, 5);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sin", "sig").node_inp("fbwr", "inp").place(&mut matrix, 0, 0).unwrap();
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("fbrd", "sig")
+        .node_io("amp", "inp", "sig")
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 2, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    matrix.set_worker_threads(threads);
+
+    // Uploads a new program every few blocks while the workers run:
+    let freq = NodeId::Sin(0).inp_param("freq").unwrap();
+    let mut out = vec![];
+    for i in 0..40 {
+        let modamt = if i % 2 == 0 { Some(0.1) } else { None };
+        matrix.set_param_modamt(freq, modamt).unwrap();
+        let (out_l, _) = run_for_ms(&mut node_exec, 5.0);
+        out.extend_from_slice(&out_l[..]);
+    }
+
+    out
+}
+
+#[test]
+fn check_parallel_exec_feedback_stress() {
+    let sequential = render_feedback(0);
+    let parallel = render_feedback(3);
+
+    assert!(sequential.iter().any(|s| s.abs() > 0.5));
+    assert_eq!(sequential.len(), parallel.len());
+    for (i, (s, p)) in sequential.iter().zip(parallel.iter()).enumerate() {
+        assert_eq!(s.to_bits(), p.to_bits(), "sample {} differs: {} != {}", i, s, p);
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+#[test]
+fn check_param_event_step() {
+    let (mut matrix, mut node_exec) = setup_out_matrix();
+    let out = NodeId::Out(0);
+
+    pset_n_at(&mut matrix, out, "ch1", 1000, 0.5, ParamEventMode::Step);
+    pset_n_at(&mut matrix, out, "ch1", 1500, -0.25, ParamEventMode::Step);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 50.0);
+    assert_float_eq!(ch1[0], 0.0);
+    assert_float_eq!(ch1[999], 0.0);
+    assert_float_eq!(ch1[1000], 0.5);
+    assert_float_eq!(ch1[1499], 0.5);
+    assert_float_eq!(ch1[1500], -0.25);
+    assert_float_eq!(ch1[2000], -0.25);
+
+    // The value persists in the following runs:
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_float_eq!(ch1[0], -0.25);
+    assert_float_eq!(ch1[400], -0.25);
+}
+
+#[test]
+fn check_param_event_ramp() {
+    let (mut matrix, mut node_exec) = setup_out_matrix();
+    let out = NodeId::Out(0);
+
+    pset_n_at(&mut matrix, out, "ch1", 300, 1.0, ParamEventMode::Ramp);
+
+    // The ramp takes 10ms => 441 samples:
+    let (ch1, _) = run_for_ms(&mut node_exec, 50.0);
+    assert_float_eq!(ch1[299], 0.0);
+    assert_float_eq!(ch1[300], 1.0 / 441.0);
+    assert_float_eq!(ch1[300 + 220], 221.0 / 441.0);
+    assert_float_eq!(ch1[300 + 440], 1.0);
+    assert_float_eq!(ch1[2000], 1.0);
+}
+
+#[test]
+fn check_param_event_step_stops_ramp() {
+    let (mut matrix, mut node_exec) = setup_out_matrix();
+    let out = NodeId::Out(0);
+
+    pset_n_at(&mut matrix, out, "ch1", 0, 1.0, ParamEventMode::Ramp);
+    pset_n_at(&mut matrix, out, "ch1", 100, 0.0, ParamEventMode::Step);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 50.0);
+    assert_float_eq!(ch1[99], 100.0 / 441.0);
+    assert_float_eq!(ch1[100], 0.0);
+    assert_float_eq!(ch1[441], 0.0);
+    assert_float_eq!(ch1[2000], 0.0);
+}
+
+#[test]
+fn check_param_event_later_block() {
+    let (mut matrix, mut node_exec) = setup_out_matrix();
+    let out = NodeId::Out(0);
+
+    // Events are kept until the block they belong to is processed:
+    pset_n_at(&mut matrix, out, "ch1", 10000, 0.5, ParamEventMode::Step);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 100.0);
+    assert_float_eq!(ch1[3000], 0.0);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 200.0);
+    let ofs = 10000 - 4410;
+    assert_float_eq!(ch1[ofs - 1], 0.0);
+    assert_float_eq!(ch1[ofs], 0.5);
+    assert_float_eq!(ch1[8000], 0.5);
+}
+
+#[test]
+fn check_param_event_dropped_by_new_prog() {
+    let (mut matrix, mut node_exec) = setup_out_matrix();
+    let out = NodeId::Out(0);
+
+    pset_n_at(&mut matrix, out, "ch1", 10000, 0.5, ParamEventMode::Step);
+    let (ch1, _) = run_for_ms(&mut node_exec, 100.0);
+    assert_float_eq!(ch1[3000], 0.0);
+
+    // The new program starts with the value of the pending event,
+    // which must not be applied later anymore:
+    matrix.place(2, 2, Cell::empty(NodeId::Sin(0)));
+    matrix.sync().unwrap();
+    pset_n(&mut matrix, out, "ch1", 0.25);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 200.0);
+    let ofs = 10000 - 4410;
+    assert_float_eq!(ch1[ofs - 1], 0.25);
+    assert_float_eq!(ch1[ofs], 0.25);
+    assert_float_eq!(ch1[8000], 0.25);
+}
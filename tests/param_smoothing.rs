@@ -0,0 +1,101 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+#[test]
+fn check_param_smoothing_declared() {
+    for node_id in [
+        NodeId::TSeq(0),
+        NodeId::Sampl(0),
+        NodeId::Scope(0),
+        NodeId::Ad(0),
+        NodeId::TsLFO(0),
+        NodeId::RndWk(0),
+        NodeId::Delay(0),
+    ] {
+        let trig = node_id.inp_param("trig").unwrap();
+        assert_eq!(trig.smoothing(), Smoothing::None, "{:?}", node_id);
+    }
+
+    let freq = NodeId::BiqFilt(0).inp_param("freq").unwrap();
+    assert_eq!(freq.smoothing(), Smoothing::Exp(50.0));
+
+    let freq = NodeId::Sin(0).inp_param("freq").unwrap();
+    assert_eq!(freq.smoothing(), DEFAULT_SMOOTHING);
+    assert_eq!(freq.smoothing(), Smoothing::Linear(10.0));
+}
+
+#[test]
+fn check_param_smoothing_linear() {
+    let (mut matrix, mut node_exec) = setup_out_matrix();
+    let out = NodeId::Out(0);
+
+    pset_n(&mut matrix, out, "ch1", 1.0);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 20.0);
+    assert_float_eq!(ch1[0], 1.0 / 441.0);
+    assert_float_eq!(ch1[220], 221.0 / 441.0);
+    assert_float_eq!(ch1[440], 1.0);
+    assert_float_eq!(ch1[800], 1.0);
+}
+
+#[test]
+fn check_param_smoothing_override() {
+    let (mut matrix, mut node_exec) = setup_out_matrix();
+    let out = NodeId::Out(0);
+    let ch1_p = out.inp_param("ch1").unwrap();
+
+    matrix.set_param_smoothing(ch1_p, Some(Smoothing::None));
+    assert_eq!(matrix.get_param_smoothing(&ch1_p), Smoothing::None);
+
+    pset_n(&mut matrix, out, "ch1", 1.0);
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_float_eq!(ch1[0], 1.0);
+    assert_float_eq!(ch1[400], 1.0);
+
+    // 20ms => 882 samples:
+    matrix.set_param_smoothing(ch1_p, Some(Smoothing::Exp(20.0)));
+    pset_n(&mut matrix, out, "ch1", 0.0);
+    let (ch1, _) = run_for_ms(&mut node_exec, 30.0);
+    assert!(ch1[0] < 1.0 && ch1[0] > 0.99);
+    assert_float_eq!(ch1[440], (-2.3_f32).exp());
+    assert_float_eq!(ch1[882], 0.0);
+    for w in ch1[0..882].windows(2) {
+        assert!(w[0] > w[1]);
+    }
+
+    // Restore the default:
+    matrix.set_param_smoothing(ch1_p, None);
+    assert_eq!(matrix.get_param_smoothing(&ch1_p), Smoothing::Linear(10.0));
+
+    pset_n(&mut matrix, out, "ch1", 1.0);
+    let (ch1, _) = run_for_ms(&mut node_exec, 20.0);
+    assert_float_eq!(ch1[220], 221.0 / 441.0);
+}
+
+#[test]
+fn check_param_smoothing_many_params() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 4, 4);
+
+    for i in 0..16 {
+        matrix.place(i % 4, i / 4, Cell::empty(NodeId::Amp(i as u8)));
+    }
+    matrix.sync().unwrap();
+
+    // 48 parameters change at once, more than the initial smoother pool has:
+    for i in 0..16 {
+        for p in ["inp", "gain", "att"] {
+            pset_n(&mut matrix, NodeId::Amp(i), p, 0.25);
+        }
+    }
+
+    run_for_ms(&mut node_exec, 50.0);
+
+    let updated =
+        node_exec.get_prog().params.iter().filter(|v| (**v - 0.25).abs() < 0.0001).count();
+    assert_eq!(updated, 48);
+}
@@ -0,0 +1,102 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::{Graph, PatchExport};
+use serde_json::{json, Value};
+
+fn setup_feedback_patch(matrix: &mut Matrix) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        .set_denorm("att", 0.5)
+        .node_inp("fbwr", "inp")
+        .place(matrix, 0, 0)
+        .unwrap();
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("fbrd", "sig").node_inp("out", "ch1").place(matrix, 2, 0).unwrap();
+
+    let freq = NodeId::Sin(0).inp_param("freq").unwrap();
+    matrix.set_param_modamt(freq, Some(0.25)).unwrap();
+    matrix.sync().unwrap();
+}
+
+#[test]
+fn check_patch_export_dot() {
+    init_test!(matrix, node_exec, 4);
+    setup_feedback_patch(matrix);
+
+    let export = PatchExport::from_matrix(matrix);
+    let node_ids: Vec<NodeId> = export.nodes().iter().map(|n| n.node_id).collect();
+    assert_eq!(
+        node_ids,
+        vec![NodeId::Sin(0), NodeId::Amp(0), NodeId::FbWr(0), NodeId::FbRd(0), NodeId::Out(0)]
+    );
+    assert_eq!(export.feedback_pairs(), vec![(NodeId::FbWr(0), NodeId::FbRd(0))]);
+
+    // Only the changed parameters are exported:
+    let amp = &export.nodes()[1];
+    assert_eq!(amp.params.len(), 1);
+    assert_eq!(amp.params[0].param.name(), "att");
+    assert_float_eq!(amp.params[0].value.f(), 0.5);
+    assert!(export.nodes()[4].params.is_empty());
+
+    let dot = export.to_dot();
+    assert!(dot.starts_with("digraph patch {\n"));
+    assert!(dot.contains("    \"amp_0\" [label=\"Amp 0\\natt = "));
+    assert!(dot.contains("(mod 0.25)\"];\n"));
+    assert!(dot.contains("    \"sin_0\" -> \"amp_0\" [taillabel=\"sig\", headlabel=\"inp\"];\n"));
+    assert!(dot.contains("    \"fbrd_0\" -> \"out_0\" [taillabel=\"sig\", headlabel=\"ch1\"];\n"));
+    assert!(dot.contains("    \"fbwr_0\" -> \"fbrd_0\" [style=dashed];\n"));
+    assert!(dot.ends_with("}\n"));
+
+    run_for_ms(node_exec, 10.0);
+}
+
+#[test]
+fn check_patch_export_json() {
+    init_test!(matrix, node_exec, 4);
+    setup_feedback_patch(matrix);
+
+    let v: Value = serde_json::from_str(&PatchExport::from_matrix(matrix).to_json()).unwrap();
+
+    assert_eq!(v["nodes"].as_array().unwrap().len(), 5);
+    assert_eq!(v["nodes"][0]["id"], json!("sin_0"));
+    assert_eq!(v["nodes"][0]["params"][0]["name"], json!("freq"));
+    assert_eq!(v["nodes"][0]["params"][0]["modamt"], json!(0.25));
+    assert_eq!(v["nodes"][1]["params"][0]["name"], json!("att"));
+    assert_eq!(
+        v["edges"],
+        json!([
+            { "from": "sin_0", "out": "sig", "to": "amp_0", "inp": "inp" },
+            { "from": "amp_0", "out": "sig", "to": "fbwr_0", "inp": "inp" },
+            { "from": "fbrd_0", "out": "sig", "to": "out_0", "inp": "ch1" },
+        ])
+    );
+    assert_eq!(v["feedback"], json!([{ "from": "fbwr_0", "to": "fbrd_0" }]));
+
+    run_for_ms(node_exec, 10.0);
+}
+
+#[test]
+fn check_patch_export_graph() {
+    let (node_conf, _node_exec) = new_node_engine();
+    let mut graph = Graph::new(node_conf);
+    graph.connect(NodeId::BOsc(0), "sig", NodeId::Out(0), "ch2").unwrap();
+    graph.set_atom(NodeId::BOsc(0), "wtype", SAtom::setting(2)).unwrap();
+    graph.sync().unwrap();
+
+    let export = PatchExport::from_graph(&graph);
+    let bosc = &export.nodes()[0];
+    assert_eq!(bosc.params[0].value, SAtom::setting(2));
+    assert!(!bosc.params[0].text.is_empty());
+
+    let dot = export.to_dot();
+    assert!(dot.contains("    \"bosc_0\" -> \"out_0\" [taillabel=\"sig\", headlabel=\"ch2\"];\n"));
+    assert!(export.feedback_pairs().is_empty());
+}
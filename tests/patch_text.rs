@@ -0,0 +1,69 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::graph::GraphError;
+use hexodsp::{Graph, PatchText};
+
+#[test]
+fn check_patch_text_graph() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut graph = Graph::new(node_conf);
+
+    let patch = PatchText::parse(
+        "sin(freq=220) -> amp(att=0.5) -> out\n\
+         sin:1(freq=110, det=0.1~0.25) -> out.ch2\n\
+         bosc(wtype=2)\n",
+    )
+    .unwrap();
+    patch.apply(&mut graph).unwrap();
+    assert!(graph.sync().unwrap());
+
+    assert_float_eq!(graph.get_denorm(NodeId::Sin(0), "freq").unwrap(), 220.0);
+    assert_eq!(graph.get_modamt(NodeId::Sin(1), "det"), Some(0.25));
+    assert_eq!(graph.get_atom(NodeId::BOsc(0), "wtype").unwrap().i(), 2);
+
+    let (out_l, out_r) = run_for_ms(&mut node_exec, 20.0);
+    let max = max_abs(&out_l);
+    assert!(max > 0.4 && max < 0.6, "max={}", max);
+    assert!(max_abs(&out_r) > 0.9);
+
+    // Only the parameters that differ from the defaults are written:
+    let text = PatchText::from_graph(&graph).to_string();
+    assert_eq!(
+        text,
+        "sin(freq=220)\n\
+         amp(att=0.5)\n\
+         out\n\
+         sin:1(freq=110, det=0.1~0.25)\n\
+         bosc(wtype=2)\n\
+         \n\
+         sin -> amp\n\
+         amp -> out\n\
+         sin:1 -> out.ch2\n"
+    );
+    assert_eq!(PatchText::parse(&text).unwrap().to_string(), text);
+}
+
+#[test]
+fn check_patch_text_graph_errors() {
+    let (node_conf, _node_exec) = new_node_engine();
+    let mut graph = Graph::new(node_conf);
+
+    // Cycles are only found when the patch is applied:
+    let patch = PatchText::parse("sin -> amp -> sin.freq").unwrap();
+    assert_eq!(patch.apply(&mut graph), Err(GraphError::CycleDetected));
+
+    let error = PatchText::parse("sin -> amp\n  amp.sig -> out.ch9").unwrap_err();
+    assert_eq!((error.line, error.col), (2, 18));
+    assert_eq!(error.msg, "unknown input 'ch9' of out");
+
+    // Node instances are limited to 8 bits:
+    let error = PatchText::parse("sin:255 -> out\nsin:256 -> out.ch2").unwrap_err();
+    assert_eq!((error.line, error.col), (2, 5));
+    assert_eq!(error.msg, "invalid node instance 256, the maximum is 255");
+    assert_eq!(error.to_string(), "2:5: invalid node instance 256, the maximum is 255");
+}
@@ -0,0 +1,165 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+// 10ms at 44.1kHz:
+const FADE_LEN: usize = 441;
+
+fn remove_out(matrix: &mut Matrix) {
+    matrix.place(0, 1, Cell::empty(NodeId::Nop));
+    matrix.sync().unwrap();
+}
+
+#[test]
+fn check_prog_crossfade_disabled() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    run_for_ms(node_exec, 20.0);
+
+    remove_out(matrix);
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert!(out_l.iter().all(|s| *s == 0.0));
+}
+
+#[test]
+fn check_prog_crossfade_fade_out() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    run_for_ms(node_exec, 20.0);
+
+    matrix.set_prog_crossfade(Some(10.0));
+    remove_out(matrix);
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+
+    for (i, s) in out_l.iter().enumerate() {
+        let old_gain = 1.0 - (i as f32 / FADE_LEN as f32).min(1.0);
+        assert!(s.abs() <= old_gain + 0.0001, "frame {}: {} > {}", i, s, old_gain);
+    }
+
+    let max = out_l[0..FADE_LEN / 4].iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+    assert!(max > 0.5, "max={}", max);
+}
+
+#[test]
+fn check_prog_crossfade_fade_in() {
+    init_test!(matrix, node_exec, 3);
+    matrix.set_prog_crossfade(Some(10.0));
+
+    setup_sin_out(matrix);
+    remove_out(matrix);
+    run_for_ms(node_exec, 20.0);
+
+    setup_sin_out(matrix);
+    let (out_l, _) = run_for_ms(node_exec, 50.0);
+
+    for (i, s) in out_l.iter().take(FADE_LEN).enumerate() {
+        let new_gain = i as f32 / FADE_LEN as f32;
+        assert!(s.abs() <= new_gain + 0.0001, "frame {}: {} > {}", i, s, new_gain);
+    }
+
+    let max = out_l[FADE_LEN..].iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+    assert!(max > 0.9, "max={}", max);
+}
+
+#[test]
+fn check_prog_crossfade_fallback() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    assert!(!matrix.is_prog_crossfaded());
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sin", "sig").node_inp("fbwr", "inp").place(matrix, 2, 0).unwrap();
+    matrix.sync().unwrap();
+    run_for_ms(node_exec, 20.0);
+
+    // The FbWr is not changed and keeps running on its instance:
+    matrix.set_prog_crossfade(Some(10.0));
+    remove_out(matrix);
+    assert!(matrix.is_prog_crossfaded());
+    run_for_ms(node_exec, 20.0);
+
+    // The FbWr can't be duplicated when its input changes:
+    matrix.place(2, 0, Cell::empty(NodeId::Nop));
+    matrix.sync().unwrap();
+    assert!(!matrix.is_prog_crossfaded());
+    run_for_ms(node_exec, 20.0);
+}
+
+#[test]
+fn check_prog_crossfade_shared_nodes() {
+    init_test!(matrix_a, node_exec_a, 3);
+    setup_sin_out(matrix_a);
+    run_for_ms(node_exec_a, 20.0);
+    let (expected, _) = run_for_ms(node_exec_a, 20.0);
+
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+    run_for_ms(node_exec, 20.0);
+
+    // The Sin is shared by both programs and does not restart its phase:
+    matrix.set_prog_crossfade(Some(10.0));
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sin", "sig").node_inp("amp", "inp").place(matrix, 2, 0).unwrap();
+    matrix.sync().unwrap();
+    assert!(matrix.is_prog_crossfaded());
+
+    let (out_l, _) = run_for_ms(node_exec, 20.0);
+    assert_eq!(out_l.len(), expected.len());
+    for (i, (s, e)) in out_l.iter().zip(expected.iter()).enumerate() {
+        assert!((s - e).abs() < 0.0001, "frame {}: {} != {}", i, s, e);
+    }
+}
+
+// A feedback path on ch1 and the bar phase of the transport on ch2:
+fn setup_feedback_clock(matrix: &mut Matrix) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sin", "sig").node_inp("fbwr", "inp").place(matrix, 0, 0).unwrap();
+
+    let (fbrd, clock, out) = (NodeId::FbRd(0), NodeId::Clock(0), NodeId::Out(0));
+    matrix.place(2, 0, Cell::empty(fbrd).out(None, None, fbrd.out("sig")));
+    matrix.place(1, 1, Cell::empty(clock).out(clock.out("bphase"), None, None));
+    matrix.place(2, 1, Cell::empty(out).input(out.inp("ch1"), None, out.inp("ch2")));
+    matrix.sync().unwrap();
+
+    matrix.set_tempo(120.0);
+    matrix.set_playing(true);
+}
+
+#[test]
+fn check_prog_crossfade_feedback_transport() {
+    init_test!(matrix_a, node_exec_a, 5);
+    setup_feedback_clock(matrix_a);
+    run_for_ms(node_exec_a, 20.0);
+    let (expected_l, expected_r) = run_for_ms(node_exec_a, 20.0);
+
+    init_test!(matrix, node_exec, 5);
+    setup_feedback_clock(matrix);
+    run_for_ms(node_exec, 20.0);
+
+    // The silent Amp changes the inputs of the Clock, which keeps running
+    // in the old program. Both programs have to see the same transport
+    // position, and the feedback buffers are only advanced once:
+    matrix.set_prog_crossfade(Some(10.0));
+    let (amp, clock) = (NodeId::Amp(0), NodeId::Clock(0));
+    matrix.place(1, 0, Cell::empty(amp).out(None, None, amp.out("sig")));
+    matrix.place(
+        1,
+        1,
+        Cell::empty(clock).input(clock.inp("ofs"), None, None).out(clock.out("bphase"), None, None),
+    );
+    matrix.sync().unwrap();
+    assert!(matrix.is_prog_crossfaded());
+
+    let (out_l, out_r) = run_for_ms(node_exec, 20.0);
+    assert!(max_abs(&out_l) > 0.5);
+    assert!(max_abs(&out_r) > 0.0);
+    for (i, (s, e)) in out_l.iter().zip(expected_l.iter()).enumerate() {
+        assert!((s - e).abs() < 0.00001, "ch1 frame {}: {} != {}", i, s, e);
+    }
+    for (i, (s, e)) in out_r.iter().zip(expected_r.iter()).enumerate() {
+        assert!((s - e).abs() < 0.00001, "ch2 frame {}: {} != {}", i, s, e);
+    }
+}
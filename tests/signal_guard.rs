@@ -0,0 +1,75 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+#[test]
+fn check_signal_guard_disabled() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+
+    let (out_l, _) = run_for_ms(node_exec, 50.0);
+    let max = out_l.iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+    assert!(max > 0.9, "max={}", max);
+    assert_eq!(matrix.pop_signal_fault(), None);
+}
+
+#[test]
+fn check_signal_guard_out_of_bounds() {
+    init_test!(matrix, node_exec, 3);
+    setup_sin_out(matrix);
+
+    matrix.set_signal_guard(Some(0.5));
+    let (out_l, _) = run_for_ms(node_exec, 50.0);
+
+    // The sine exceeds 0.5 in every block, so the guard silences all of them:
+    assert!(out_l.iter().all(|s| *s == 0.0));
+    assert_eq!(matrix.pop_signal_fault(), Some((NodeId::Sin(0), SignalFault::OutOfBounds)));
+
+    while matrix.pop_signal_fault().is_some() {}
+
+    matrix.set_signal_guard(Some(f32::INFINITY));
+    let (out_l, _) = run_for_ms(node_exec, 50.0);
+    let max = out_l.iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+    assert!(max > 0.9, "max={}", max);
+    assert_eq!(matrix.pop_signal_fault(), None);
+
+    matrix.set_signal_guard(None);
+    let (out_l, _) = run_for_ms(node_exec, 50.0);
+    let max = out_l.iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+    assert!(max > 0.9, "max={}", max);
+    assert_eq!(matrix.pop_signal_fault(), None);
+}
+
+#[test]
+fn check_signal_guard_non_finite() {
+    init_test!(matrix, node_exec, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sfilter", "sig").node_inp("out", "ch1").place(matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    // Only NaN and infinite samples trip the guard with this limit:
+    matrix.set_signal_guard(Some(f32::INFINITY));
+
+    // A NaN input spoils the state of the filter:
+    let inp = NodeId::SFilter(0).inp_param("inp").unwrap();
+    matrix.set_param(inp, SAtom::param(f32::NAN));
+    let (out_l, _) = run_for_ms(node_exec, 50.0);
+    assert!(out_l.iter().all(|s| *s == 0.0));
+    assert_eq!(matrix.pop_signal_fault(), Some((NodeId::SFilter(0), SignalFault::NonFinite)));
+
+    while matrix.pop_signal_fault().is_some() {}
+
+    // The filter was reset, so it recovers once the input is finite again:
+    matrix.set_param(inp, SAtom::param(0.5));
+    run_for_ms(node_exec, 50.0);
+    while matrix.pop_signal_fault().is_some() {}
+
+    let (out_l, _) = run_for_ms(node_exec, 50.0);
+    assert!(out_l.iter().all(|s| s.is_finite()));
+    assert_float_eq!(out_l[out_l.len() - 1], 0.5);
+    assert_eq!(matrix.pop_signal_fault(), None);
+}
@@ -0,0 +1,196 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::nodes::VoiceStealMode;
+
+fn setup_voice_sin_patch(node_conf: &mut hexodsp::NodeConfigurator, voices: usize) {
+    let voice = NodeId::Voice(0);
+    let sin = NodeId::Sin(0);
+    let amp = NodeId::Amp(0);
+    let out = NodeId::Out(0);
+
+    node_conf.create_node(voice);
+    node_conf.create_node(sin);
+    node_conf.create_node(amp);
+    node_conf.create_node(out);
+
+    node_conf.set_voice_region(&[voice, sin, amp], voices, VoiceStealMode::Oldest);
+
+    let mut prog = node_conf.rebuild_node_ports();
+
+    node_conf.add_prog_node(&mut prog, &voice);
+    node_conf.add_prog_node(&mut prog, &sin);
+    node_conf.add_prog_node(&mut prog, &amp);
+    node_conf.add_prog_node(&mut prog, &out);
+
+    node_conf.set_prog_node_exec_connection(
+        &mut prog,
+        (sin, sin.inp("freq").unwrap()),
+        (voice, voice.out("freq").unwrap()),
+    );
+    node_conf.set_prog_node_exec_connection(
+        &mut prog,
+        (amp, amp.inp("inp").unwrap()),
+        (sin, sin.out("sig").unwrap()),
+    );
+    node_conf.set_prog_node_exec_connection(
+        &mut prog,
+        (amp, amp.inp("att").unwrap()),
+        (voice, voice.out("gate").unwrap()),
+    );
+    node_conf.set_prog_node_exec_connection(
+        &mut prog,
+        (out, out.inp("ch1").unwrap()),
+        (amp, amp.out("sig").unwrap()),
+    );
+
+    node_conf.upload_prog(prog, false);
+}
+
+#[test]
+fn check_voice_region_sum() {
+    let (mut node_conf, mut node_exec) = new_node_engine();
+    setup_voice_sin_patch(&mut node_conf, 4);
+
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    let rms = calc_rms_mimax_each_ms(&out_l[..], 10.0);
+    assert_float_eq!(rms[1].0, 0.0);
+
+    node_conf.note_on(69, 1.0);
+    let (out_l, _) = run_for_ms(&mut node_exec, 50.0);
+    let rms = calc_rms_mimax_each_ms(&out_l[..], 20.0);
+    // One sine wave with an amplitude of 1.0:
+    assert!((rms[1].0 - 0.5).abs() < 0.02, "rms={:?}", rms[1]);
+    assert!((rms[1].2 - 1.0).abs() < 0.01, "rms={:?}", rms[1]);
+
+    node_conf.note_on(81, 1.0);
+    let (out_l, _) = run_for_ms(&mut node_exec, 50.0);
+    let rms = calc_rms_mimax_each_ms(&out_l[..], 20.0);
+    // Two sine waves of different frequencies are summed up:
+    assert!((rms[1].0 - 1.0).abs() < 0.05, "rms={:?}", rms[1]);
+    assert!(rms[1].2 > 1.2, "rms={:?}", rms[1]);
+
+    node_conf.note_off(69);
+    node_conf.note_off(81);
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    let rms = calc_rms_mimax_each_ms(&out_l[..], 10.0);
+    assert_float_eq!(rms[1].0, 0.0);
+}
+
+#[test]
+fn check_voice_region_steal() {
+    let (mut node_conf, mut node_exec) = new_node_engine();
+    setup_voice_sin_patch(&mut node_conf, 2);
+
+    node_conf.note_on(57, 1.0);
+    node_conf.note_on(69, 1.0);
+    node_conf.note_on(81, 1.0);
+    run_for_ms(&mut node_exec, 10.0);
+
+    // The oldest note (57) was stolen, only 2 voices are audible:
+    let (out_l, _) = run_for_ms(&mut node_exec, 50.0);
+    let rms = calc_rms_mimax_each_ms(&out_l[..], 20.0);
+    assert!((rms[1].0 - 1.0).abs() < 0.05, "rms={:?}", rms[1]);
+
+    node_conf.note_off(69);
+    node_conf.note_off(81);
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    let rms = calc_rms_mimax_each_ms(&out_l[..], 10.0);
+    assert_float_eq!(rms[1].0, 0.0);
+}
+
+#[test]
+fn check_voice_region_outside_dependency() {
+    init_test!(matrix, node_exec, 4);
+
+    // The region node Amp(1) reads from Amp(0), which is outside of the
+    // region and reads from the region node Sin(0):
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        .node_io("amp", "inp", "sig")
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    assert_eq!(
+        matrix.set_voice_region(&[NodeId::Sin(0), NodeId::Amp(1)], 2, VoiceStealMode::Oldest),
+        Err(MatrixError::VoiceRegionDependency)
+    );
+    assert!(matrix.voice_region().is_none());
+    matrix.sync().unwrap();
+
+    let region = [NodeId::Sin(0), NodeId::Amp(0), NodeId::Amp(1)];
+    matrix.set_voice_region(&region, 2, VoiceStealMode::Oldest).unwrap();
+    assert_eq!(matrix.voice_region().unwrap().0, region.to_vec());
+
+    run_for_ms(node_exec, 10.0);
+}
+
+#[test]
+fn check_voice_region_upload_dependency() {
+    let (mut node_conf, mut node_exec) = new_node_engine();
+    let sin = NodeId::Sin(0);
+    let amp0 = NodeId::Amp(0);
+    let amp1 = NodeId::Amp(1);
+    let out = NodeId::Out(0);
+
+    for node_id in [sin, amp0, amp1, out].iter() {
+        node_conf.create_node(*node_id);
+    }
+
+    // The region node Amp(1) reads from Amp(0), which reads from the region:
+    node_conf.set_voice_region(&[sin, amp1], 2, VoiceStealMode::Oldest);
+
+    let mut prog = node_conf.rebuild_node_ports();
+    for node_id in [sin, amp0, amp1, out].iter() {
+        node_conf.add_prog_node(&mut prog, node_id);
+    }
+    for (to, from) in [(amp0, sin), (amp1, amp0), (out, amp1)].iter() {
+        let inp = if *to == out { "ch1" } else { "inp" };
+        node_conf.set_prog_node_exec_connection(
+            &mut prog,
+            (*to, to.inp(inp).unwrap()),
+            (*from, from.out("sig").unwrap()),
+        );
+    }
+
+    node_conf.upload_prog(prog, false);
+    assert!(node_conf.voice_region().is_none());
+    assert!(node_conf.pop_error().unwrap().starts_with("Voice Region Error"));
+
+    // The patch still plays without the region:
+    let (out_l, _) = run_for_ms(&mut node_exec, 20.0);
+    assert!(max_abs(&out_l) > 0.9);
+}
+
+#[test]
+fn check_voice_region_grows_storage() {
+    let (mut node_conf, mut node_exec) = NodeEngineBuilder::new().nodes(2).build();
+    setup_voice_sin_patch(&mut node_conf, 2);
+
+    node_conf.note_on(69, 1.0);
+    node_conf.note_on(81, 1.0);
+    let (out_l, _) = run_for_ms(&mut node_exec, 50.0);
+    let rms = calc_rms_mimax_each_ms(&out_l[..], 20.0);
+    assert!((rms[1].0 - 1.0).abs() < 0.05, "rms={:?}", rms[1]);
+
+    // Grow the node slots while the voices have their copies,
+    // and then the number of voices:
+    for i in 1..=10 {
+        node_conf.create_node(NodeId::Amp(i));
+    }
+    setup_voice_sin_patch(&mut node_conf, 4);
+
+    node_conf.note_on(93, 1.0);
+    let (out_l, _) = run_for_ms(&mut node_exec, 50.0);
+    let rms = calc_rms_mimax_each_ms(&out_l[..], 20.0);
+    // The two playing voices kept their state, three sine waves are summed up:
+    assert!((rms[1].0 - 1.5).abs() < 0.1, "rms={:?}", rms[1]);
+}